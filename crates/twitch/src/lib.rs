@@ -7,7 +7,6 @@ pub use twitch_api2;
 
 use anyhow::Result;
 use models::GameId;
-use std::time::Duration;
 use twitch_api2::helix::clips::GetClipsRequest;
 use twitch_api2::helix::search::search_categories;
 use twitch_api2::helix::Paginated;
@@ -69,6 +68,38 @@ impl Client {
         Ok(resp.data.into_iter().next().map(From::from))
     }
 
+    /// At most 100 broadcasters can be asked for at once.
+    ///
+    /// <https://dev.twitch.tv/docs/api/reference/#get-users>
+    pub async fn get_broadcasters(
+        &self,
+        broadcaster_ids: Vec<String>,
+    ) -> Result<Vec<models::Broadcaster>> {
+        let req =
+            twitch_api2::helix::users::get_users::GetUsersRequest::builder()
+                .id(broadcaster_ids.into_iter().map(Into::into).collect())
+                .build();
+
+        let resp = self.inner.helix.req_get(req, &self.token).await?;
+
+        Ok(resp.data.into_iter().map(From::from).collect())
+    }
+
+    /// At most 100 clips can be asked for at once. Clips which Twitch
+    /// doesn't know are left out.
+    ///
+    /// <https://dev.twitch.tv/docs/api/reference/#get-clips>
+    pub async fn get_clips_by_ids(
+        &self,
+        clip_ids: Vec<String>,
+    ) -> Result<Vec<models::Clip>> {
+        let req = GetClipsRequest::builder().id(clip_ids).build();
+
+        let resp = self.inner.helix.req_get(req, &self.token).await?;
+
+        Ok(resp.data.into_iter().map(From::from).collect())
+    }
+
     /// Performs given request, returning the clips and optionally another
     /// request which contains a cursor for the next page.
     ///
@@ -90,26 +121,7 @@ impl Client {
             None
         };
 
-        let clips = resp
-            .data
-            .into_iter()
-            .map(|c| models::Clip {
-                url: c.thumbnail_url.split("-preview-").collect::<Vec<_>>()[0]
-                    .to_string()
-                    + ".mp4",
-                id: c.id,
-                broadcaster_id: c.broadcaster_id.to_string(),
-                broadcaster_name: c.broadcaster_name.to_string(),
-                creator_name: c.creator_name.to_string(),
-                recorded_at: c.created_at.into_string(),
-                duration: Duration::from_secs_f64(c.duration),
-                title: c.title,
-                view_count: c.view_count as usize,
-                lang: c.language,
-                game_id: c.game_id.to_string(),
-                thumbnail_url: c.thumbnail_url,
-            })
-            .collect::<Vec<_>>();
+        let clips = resp.data.into_iter().map(From::from).collect();
 
         Ok((clips, next_req))
     }
@@ -1,6 +1,8 @@
+mod broadcaster;
 mod clip;
 mod game;
 
+pub use broadcaster::*;
 pub use clip::*;
 pub use game::*;
 
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use twitch_api2::helix::users::User;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Broadcaster {
+    pub id: String,
+    pub name: String,
+    /// None if the broadcaster has no avatar
+    pub profile_image_url: Option<String>,
+}
+
+impl From<User> for Broadcaster {
+    fn from(u: User) -> Self {
+        Self {
+            id: u.id.to_string(),
+            name: u.display_name.to_string(),
+            profile_image_url: u.profile_image_url,
+        }
+    }
+}
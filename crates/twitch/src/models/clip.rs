@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use anyhow::anyhow;
+use twitch_api2::helix::clips::get_clips;
 
 #[derive(Debug, Clone)]
 pub struct Clip {
@@ -38,6 +39,27 @@ impl Clip {
     }
 }
 
+impl From<get_clips::Clip> for Clip {
+    fn from(c: get_clips::Clip) -> Self {
+        Self {
+            url: c.thumbnail_url.split("-preview-").collect::<Vec<_>>()[0]
+                .to_string()
+                + ".mp4",
+            id: c.id,
+            broadcaster_id: c.broadcaster_id.to_string(),
+            broadcaster_name: c.broadcaster_name.to_string(),
+            creator_name: c.creator_name.to_string(),
+            recorded_at: c.created_at.into_string(),
+            duration: Duration::from_secs_f64(c.duration),
+            title: c.title,
+            view_count: c.view_count as usize,
+            lang: c.language,
+            game_id: c.game_id.to_string(),
+            thumbnail_url: c.thumbnail_url,
+        }
+    }
+}
+
 /// Bind clips to it and execute all inserts at once.
 #[cfg(feature = "sqlite")]
 pub struct InsertClipStatement<'a>(rusqlite::Statement<'a>);
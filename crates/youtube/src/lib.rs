@@ -0,0 +1,98 @@
+//! Uploads videos to a YouTube channel via the Data API.
+//!
+//! Endpoints are configurable so that a local stand-in can be used instead
+//! of Google in tests and development.
+
+/// Title, description and tags of a compilation
+pub mod metadata;
+/// Access to the user's channel
+pub mod oauth;
+/// Resumable upload protocol
+pub mod upload;
+
+pub use metadata::VideoMetadata;
+pub use oauth::OAuthToken;
+pub use upload::{UploadSession, Video};
+
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    /// Where the user grants access to their channel
+    pub auth_url: String,
+    /// Exchanges authorization codes and refresh tokens for access tokens
+    pub token_url: String,
+    /// Where resumable uploads of videos are started
+    pub upload_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            auth_url: "https://accounts.google.com/o/oauth2/v2/auth".into(),
+            token_url: "https://oauth2.googleapis.com/token".into(),
+            upload_url: "https://www.googleapis.com/upload/youtube/v3/videos"
+                .into(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// All endpoints under one base url, with the same paths as Google's
+    /// apart from the hosts.
+    pub fn at(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            auth_url: format!("{base_url}/o/oauth2/v2/auth"),
+            token_url: format!("{base_url}/token"),
+            upload_url: format!("{base_url}/upload/youtube/v3/videos"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    endpoints: Endpoints,
+    client_id: String,
+    client_secret: String,
+    chunk_size: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl Client {
+    /// Go to <https://console.cloud.google.com/apis/credentials> to obtain
+    /// the OAuth client.
+    pub fn new(
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        endpoints: Endpoints,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoints,
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            chunk_size: upload::DEFAULT_CHUNK_SIZE,
+            max_retries: 5,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Bytes sent per request, rounded down to a multiple of 256 KiB as
+    /// the protocol requires.
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = (bytes / upload::CHUNK_GRANULARITY).max(1)
+            * upload::CHUNK_GRANULARITY;
+        self
+    }
+
+    /// How many times in a row a chunk is retried, waiting twice as long
+    /// before each retry starting with given delay.
+    pub fn with_retries(mut self, max_retries: u32, delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = delay;
+        self
+    }
+}
@@ -0,0 +1,252 @@
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// YouTube rejects longer titles.
+const MAX_TITLE_CHARS: usize = 100;
+/// YouTube rejects longer descriptions.
+const MAX_DESCRIPTION_BYTES: usize = 5000;
+/// YouTube rejects tags which are longer in total.
+const MAX_TAGS_CHARS: usize = 500;
+/// <https://developers.google.com/youtube/v3/docs/videoCategories/list>
+const GAMING_CATEGORY_ID: &str = "20";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VideoMetadata {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub category_id: String,
+    pub privacy_status: PrivacyStatus,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyStatus {
+    /// Uploads are reviewed before they go public
+    #[default]
+    Private,
+    Unlisted,
+    Public,
+}
+
+/// Clip as it plays in the compilation.
+#[derive(Debug, Clone)]
+pub struct CompilationClip {
+    pub title: String,
+    pub broadcaster_name: String,
+    pub duration: Duration,
+}
+
+/// Title and description of a compilation with a chapter per clip and
+/// credits for each broadcaster.
+///
+/// Clips are expected in the order they play in.
+/// Everything is cut to fit YouTube's limits.
+pub fn generate(
+    title: &str,
+    game_name: Option<&str>,
+    clips: &[CompilationClip],
+) -> VideoMetadata {
+    let broadcasters = broadcasters(clips);
+
+    let mut chapters = vec![];
+    let mut starts_at = Duration::ZERO;
+    for clip in clips {
+        chapters.push(format!(
+            "{} {} ({})",
+            timestamp(starts_at),
+            sanitize(&clip.title),
+            sanitize(&clip.broadcaster_name),
+        ));
+        starts_at += clip.duration;
+    }
+
+    let mut tags = vec![];
+    let mut tags_chars = 0;
+    for tag in game_name.into_iter().chain(broadcasters.iter().copied()) {
+        let tag = sanitize(tag);
+        // YouTube counts a separating comma per tag
+        if tags_chars + tag.chars().count() + 1 > MAX_TAGS_CHARS {
+            break;
+        }
+        tags_chars += tag.chars().count() + 1;
+        tags.push(tag);
+    }
+
+    VideoMetadata {
+        title: sanitize(title).chars().take(MAX_TITLE_CHARS).collect(),
+        description: description(&chapters, &credits(clips)),
+        tags,
+        category_id: GAMING_CATEGORY_ID.to_string(),
+        privacy_status: PrivacyStatus::default(),
+    }
+}
+
+impl VideoMetadata {
+    /// The video resource as the upload expects it.
+    ///
+    /// <https://developers.google.com/youtube/v3/docs/videos#resource>
+    pub fn to_resource(&self) -> serde_json::Value {
+        json!({
+            "snippet": {
+                "title": self.title,
+                "description": self.description,
+                "tags": self.tags,
+                "categoryId": self.category_id,
+            },
+            "status": {
+                "privacyStatus": self.privacy_status,
+                "selfDeclaredMadeForKids": false,
+            },
+        })
+    }
+}
+
+/// A line per broadcaster, in the order they first appear, with a link to
+/// their channel where we can tell it.
+pub fn credits(clips: &[CompilationClip]) -> Vec<String> {
+    broadcasters(clips)
+        .into_iter()
+        .map(|name| match channel_url(name) {
+            Some(url) => format!("{} {url}", sanitize(name)),
+            None => sanitize(name),
+        })
+        .collect()
+}
+
+fn broadcasters(clips: &[CompilationClip]) -> Vec<&str> {
+    let mut broadcasters: Vec<&str> = vec![];
+    for clip in clips {
+        if !broadcasters.contains(&clip.broadcaster_name.as_str()) {
+            broadcasters.push(&clip.broadcaster_name);
+        }
+    }
+
+    broadcasters
+}
+
+/// Drops the last chapter or credit, whichever there are more of, until it
+/// fits.
+fn description(chapters: &[String], credits: &[String]) -> String {
+    let render = |chapters: &[String], credits: &[String]| {
+        let mut description = String::new();
+        if !chapters.is_empty() {
+            description += "Chapters\n";
+            description += &chapters.join("\n");
+        }
+        if !credits.is_empty() {
+            if !description.is_empty() {
+                description += "\n\n";
+            }
+            description += "Clipped from\n";
+            description += &credits.join("\n");
+        }
+        description
+    };
+
+    let (mut chapters, mut credits) = (chapters, credits);
+    loop {
+        let description = render(chapters, credits);
+        if description.len() <= MAX_DESCRIPTION_BYTES {
+            return description;
+        }
+
+        if chapters.is_empty() && credits.is_empty() {
+            return description;
+        } else if chapters.len() >= credits.len() {
+            chapters = &chapters[..chapters.len() - 1];
+        } else {
+            credits = &credits[..credits.len() - 1];
+        }
+    }
+}
+
+/// E.g. "0:00", "4:05" or "1:02:03", as YouTube recognizes chapters.
+fn timestamp(at: Duration) -> String {
+    let secs = at.as_secs();
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
+/// Display names are the login in different case, except for names in
+/// other scripts, which we cannot link.
+fn channel_url(broadcaster_name: &str) -> Option<String> {
+    broadcaster_name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        .then(|| {
+            format!("https://www.twitch.tv/{}", broadcaster_name.to_lowercase())
+        })
+}
+
+/// YouTube rejects titles and descriptions with angle brackets, and a new
+/// line in a clip title would break the chapter.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(c, '<' | '>'))
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(title: &str, broadcaster_name: &str, secs: u64) -> CompilationClip {
+        CompilationClip {
+            title: title.to_string(),
+            broadcaster_name: broadcaster_name.to_string(),
+            duration: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn it_generates_chapters_and_credits() {
+        let clips = vec![
+            clip("Insane <clutch>", "Shroud", 35),
+            clip("what\nwas that", "이상호", 3590),
+            clip("GG", "Shroud", 20),
+        ];
+
+        let metadata = generate("Best of the week", Some("Valorant"), &clips);
+
+        assert_eq!(metadata.title, "Best of the week");
+        assert_eq!(
+            metadata.description,
+            "Chapters\n\
+            0:00 Insane clutch (Shroud)\n\
+            0:35 what was that (이상호)\n\
+            1:00:25 GG (Shroud)\n\
+            \n\
+            Clipped from\n\
+            Shroud https://www.twitch.tv/shroud\n\
+            이상호"
+        );
+        assert_eq!(metadata.tags, vec!["Valorant", "Shroud", "이상호"]);
+        assert_eq!(metadata.privacy_status, PrivacyStatus::Private);
+    }
+
+    #[test]
+    fn it_fits_youtube_limits() {
+        let clips: Vec<_> = (0..500)
+            .map(|i| {
+                clip(&"long title ".repeat(5), &format!("streamer{i}"), 10)
+            })
+            .collect();
+
+        let metadata = generate(&"title ".repeat(50), None, &clips);
+
+        assert_eq!(metadata.title.chars().count(), MAX_TITLE_CHARS);
+        assert!(metadata.description.len() <= MAX_DESCRIPTION_BYTES);
+        assert!(metadata.description.starts_with("Chapters\n0:00 "));
+        assert!(metadata.description.contains("Clipped from\nstreamer0 "));
+        assert!(metadata.tags.join(",").chars().count() <= MAX_TAGS_CHARS);
+    }
+}
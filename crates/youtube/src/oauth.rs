@@ -0,0 +1,184 @@
+use crate::Client;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Lets the app upload videos, but not delete or edit them.
+pub const UPLOAD_SCOPE: &str = "https://www.googleapis.com/auth/youtube.upload";
+
+/// Refreshed a little before it actually expires so that it doesn't expire
+/// mid-request.
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Access to the user's channel, to be stored by the app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthToken {
+    pub access_token: String,
+    /// Only given the first time the user grants access, see
+    /// [`Client::refresh`]
+    pub refresh_token: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+    refresh_token: Option<String>,
+}
+
+impl OAuthToken {
+    pub fn needs_refresh(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at - now < Duration::seconds(EXPIRY_MARGIN_SECS)
+    }
+}
+
+impl Client {
+    /// Where to send the user to grant access to their channel.
+    /// They are sent back to the redirect url with a `code` to exchange and
+    /// the `state` as given.
+    ///
+    /// <https://developers.google.com/identity/protocols/oauth2/web-server#creatingclient>
+    pub fn authorize_url(
+        &self,
+        redirect_url: &str,
+        state: &str,
+    ) -> Result<String> {
+        let url = reqwest::Url::parse_with_params(
+            &self.endpoints.auth_url,
+            [
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", redirect_url),
+                ("response_type", "code"),
+                ("scope", UPLOAD_SCOPE),
+                // for a refresh token
+                ("access_type", "offline"),
+                // or there is no refresh token if the user granted access
+                // before
+                ("prompt", "consent"),
+                ("state", state),
+            ],
+        )?;
+
+        Ok(url.into())
+    }
+
+    /// <https://developers.google.com/identity/protocols/oauth2/web-server#exchange-authorization-code>
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_url: &str,
+    ) -> Result<OAuthToken> {
+        self.request_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_url),
+        ])
+        .await
+    }
+
+    /// The refresh token is carried over to the new token.
+    ///
+    /// <https://developers.google.com/identity/protocols/oauth2/web-server#offline>
+    pub async fn refresh(&self, token: &OAuthToken) -> Result<OAuthToken> {
+        let Some(refresh_token) = &token.refresh_token else {
+            bail!("Token cannot be refreshed, grant access again");
+        };
+
+        let refreshed = self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await?;
+
+        Ok(OAuthToken {
+            refresh_token: refreshed
+                .refresh_token
+                .or_else(|| token.refresh_token.clone()),
+            ..refreshed
+        })
+    }
+
+    async fn request_token(
+        &self,
+        params: &[(&str, &str)],
+    ) -> Result<OAuthToken> {
+        let resp = self
+            .http
+            .post(&self.endpoints.token_url)
+            .form(
+                &[
+                    ("client_id", self.client_id.as_str()),
+                    ("client_secret", self.client_secret.as_str()),
+                ]
+                .iter()
+                .chain(params)
+                .collect::<Vec<_>>(),
+            )
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("Token request failed with {status}: {}", resp.text().await?);
+        }
+
+        let TokenResponse {
+            access_token,
+            expires_in,
+            refresh_token,
+        } = resp.json().await?;
+
+        Ok(OAuthToken {
+            access_token,
+            refresh_token,
+            expires_at: Utc::now() + Duration::seconds(expires_in),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endpoints;
+
+    #[test]
+    fn it_builds_authorize_url() -> Result<()> {
+        let client = Client::new("id", "secret", Endpoints::default());
+
+        let url = reqwest::Url::parse(
+            &client.authorize_url("http://localhost/callback", "xyz")?,
+        )?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.into_owned())
+        };
+
+        assert_eq!(url.host_str(), Some("accounts.google.com"));
+        assert_eq!(param("client_id").as_deref(), Some("id"));
+        assert_eq!(
+            param("redirect_uri").as_deref(),
+            Some("http://localhost/callback")
+        );
+        assert_eq!(param("scope").as_deref(), Some(UPLOAD_SCOPE));
+        assert_eq!(param("state").as_deref(), Some("xyz"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_refreshes_before_expiry() {
+        let now = Utc::now();
+        let token = |expires_in| OAuthToken {
+            access_token: "a".into(),
+            refresh_token: None,
+            expires_at: now + Duration::seconds(expires_in),
+        };
+
+        assert!(token(-10).needs_refresh(now));
+        assert!(token(30).needs_refresh(now));
+        assert!(!token(3600).needs_refresh(now));
+    }
+}
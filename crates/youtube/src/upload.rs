@@ -0,0 +1,545 @@
+use crate::{Client, OAuthToken, VideoMetadata};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, warn};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Chunks must be multiples of this, except for the last one.
+pub(crate) const CHUNK_GRANULARITY: usize = 256 * 1024;
+/// Large enough to not spend the upload on request overhead, small enough
+/// to not resend much after a failure.
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 32 * CHUNK_GRANULARITY;
+
+/// Can be stored to resume the upload later, for about a week.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub url: String,
+    pub total_bytes: u64,
+}
+
+/// The uploaded video.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Video {
+    pub id: String,
+}
+
+enum Progress {
+    /// The server has the first that many bytes
+    Received(u64),
+    Done(Video),
+}
+
+enum Failure {
+    /// Network errors and server errors, worth trying again
+    Transient(anyhow::Error),
+    /// The access token expired or was revoked, worth trying again with a
+    /// refreshed one
+    Unauthorized,
+    Fatal(anyhow::Error),
+}
+
+impl Client {
+    /// Starts a session and uploads the whole file.
+    ///
+    /// The token is refreshed if it expires or is rejected along the way,
+    /// store it afterwards if it changed.
+    pub async fn upload(
+        &self,
+        token: &mut OAuthToken,
+        metadata: &VideoMetadata,
+        path: &Path,
+    ) -> Result<Video> {
+        let total_bytes = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Cannot read {path:?}"))?
+            .len();
+
+        if token.needs_refresh(chrono::Utc::now()) {
+            *token = self.refresh(token).await?;
+        }
+        let session =
+            match self.start_upload(token, metadata, total_bytes).await {
+                Err(e) if is_unauthorized(&e) => {
+                    info!("Access token rejected, refreshing it");
+                    *token = self.refresh(token).await?;
+                    self.start_upload(token, metadata, total_bytes).await?
+                }
+                res => res?,
+            };
+        self.resume_upload(token, &session, path).await
+    }
+
+    /// <https://developers.google.com/youtube/v3/guides/using_resumable_upload_protocol#Start_Resumable_Session>
+    pub async fn start_upload(
+        &self,
+        token: &OAuthToken,
+        metadata: &VideoMetadata,
+        total_bytes: u64,
+    ) -> Result<UploadSession> {
+        if total_bytes == 0 {
+            bail!("Cannot upload an empty video");
+        }
+
+        let resp = self
+            .http
+            .post(&self.endpoints.upload_url)
+            .query(&[("uploadType", "resumable"), ("part", "snippet,status")])
+            .bearer_auth(&token.access_token)
+            .header("X-Upload-Content-Length", total_bytes)
+            .header("X-Upload-Content-Type", "video/*")
+            .json(&metadata.to_resource())
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(Unauthorized.into());
+        }
+        if !status.is_success() {
+            bail!("Cannot start upload, {status}: {}", resp.text().await?);
+        }
+
+        let url = resp
+            .headers()
+            .get(header::LOCATION)
+            .ok_or_else(|| anyhow!("Upload session url missing"))?
+            .to_str()?
+            .to_string();
+        debug!("Started upload session {url}");
+
+        Ok(UploadSession { url, total_bytes })
+    }
+
+    /// Uploads the file in chunks from wherever the server says the session
+    /// left off.
+    ///
+    /// A chunk which fails for reasons other than the request itself is
+    /// retried with exponential backoff.
+    /// The token is refreshed once if it is rejected.
+    /// Gives up if the server keeps taking chunks without storing them.
+    ///
+    /// <https://developers.google.com/youtube/v3/guides/using_resumable_upload_protocol#Resume_Interrupted_Upload>
+    pub async fn resume_upload(
+        &self,
+        token: &mut OAuthToken,
+        session: &UploadSession,
+        path: &Path,
+    ) -> Result<Video> {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Cannot open {path:?}"))?;
+        if file.metadata().await?.len() != session.total_bytes {
+            bail!(
+                "{path:?} is not the file the upload session was started for"
+            );
+        }
+
+        // unknown until the server tells us
+        let mut offset = None;
+        let mut failures = 0;
+        // chunks in a row which the server didn't store
+        let mut stalls = 0;
+        let mut is_refreshed = false;
+        loop {
+            let attempt = match offset {
+                None => self.query_progress(token, session).await,
+                Some(offset) => {
+                    self.put_chunk(token, session, &mut file, offset).await
+                }
+            };
+
+            match attempt {
+                Ok(Progress::Done(video)) => {
+                    info!("Uploaded video {}", video.id);
+                    return Ok(video);
+                }
+                Ok(Progress::Received(received)) => {
+                    debug!("Uploaded {received}/{} bytes", session.total_bytes);
+                    match offset {
+                        Some(sent_from) if received <= sent_from => {
+                            stalls += 1;
+                            if stalls > self.max_retries {
+                                bail!(
+                                    "Upload stopped advancing at {received} \
+                                    of {} bytes",
+                                    session.total_bytes
+                                );
+                            }
+                        }
+                        _ => stalls = 0,
+                    }
+                    offset = Some(received);
+                    failures = 0;
+                }
+                Err(Failure::Unauthorized) if !is_refreshed => {
+                    info!("Access token rejected, refreshing it");
+                    *token = self.refresh(token).await?;
+                    is_refreshed = true;
+                    offset = None;
+                }
+                Err(Failure::Unauthorized) => {
+                    return Err(Unauthorized.into());
+                }
+                Err(Failure::Transient(e)) if failures < self.max_retries => {
+                    let delay = self.retry_delay * 2u32.pow(failures);
+                    failures += 1;
+                    warn!("Upload failed, retrying in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+                    offset = None;
+                }
+                Err(Failure::Transient(e) | Failure::Fatal(e)) => {
+                    return Err(e)
+                }
+            }
+        }
+    }
+
+    async fn query_progress(
+        &self,
+        token: &OAuthToken,
+        session: &UploadSession,
+    ) -> StdResult<Progress, Failure> {
+        let resp = self
+            .http
+            .put(&session.url)
+            .bearer_auth(&token.access_token)
+            .header(header::CONTENT_LENGTH, 0)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes */{}", session.total_bytes),
+            )
+            .send()
+            .await;
+
+        progress(resp).await
+    }
+
+    async fn put_chunk(
+        &self,
+        token: &OAuthToken,
+        session: &UploadSession,
+        file: &mut tokio::fs::File,
+        offset: u64,
+    ) -> StdResult<Progress, Failure> {
+        let len = (session.total_bytes - offset).min(self.chunk_size as u64);
+        let mut chunk = vec![0; len as usize];
+        let read = async {
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            file.read_exact(&mut chunk).await
+        };
+        read.await.map_err(|e| Failure::Fatal(e.into()))?;
+
+        let resp = self
+            .http
+            .put(&session.url)
+            .bearer_auth(&token.access_token)
+            .header(
+                header::CONTENT_RANGE,
+                format!(
+                    "bytes {offset}-{}/{}",
+                    offset + len - 1,
+                    session.total_bytes
+                ),
+            )
+            .body(chunk)
+            .send()
+            .await;
+
+        progress(resp).await
+    }
+}
+
+type StdResult<T, E> = std::result::Result<T, E>;
+
+/// YouTube rejected the access token even though it was refreshed.
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl std::fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Access to the channel was rejected, grant access again")
+    }
+}
+
+impl std::error::Error for Unauthorized {}
+
+fn is_unauthorized(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<Unauthorized>().is_some()
+}
+
+async fn progress(
+    resp: reqwest::Result<reqwest::Response>,
+) -> StdResult<Progress, Failure> {
+    let resp = resp.map_err(|e| Failure::Transient(e.into()))?;
+
+    match resp.status() {
+        StatusCode::OK | StatusCode::CREATED => resp
+            .json()
+            .await
+            .map(Progress::Done)
+            .map_err(|e| Failure::Fatal(e.into())),
+        StatusCode::PERMANENT_REDIRECT => {
+            // e.g. "bytes=0-524287", missing if nothing was received
+            let received = match resp.headers().get(header::RANGE) {
+                Some(range) => range
+                    .to_str()
+                    .ok()
+                    .and_then(|range| range.rsplit_once('-'))
+                    .and_then(|(_, last)| last.parse::<u64>().ok())
+                    .map(|last| last + 1)
+                    .ok_or_else(|| {
+                        Failure::Fatal(anyhow!("Invalid range {range:?}"))
+                    })?,
+                None => 0,
+            };
+            Ok(Progress::Received(received))
+        }
+        status @ (StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT) => {
+            Err(Failure::Transient(anyhow!("Upload failed with {status}")))
+        }
+        StatusCode::UNAUTHORIZED => Err(Failure::Unauthorized),
+        status => {
+            let body = resp.text().await.unwrap_or_default();
+            Err(Failure::Fatal(anyhow!(
+                "Upload failed with {status}: {body}"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metadata, Endpoints};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response};
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+    use std::{net::SocketAddr, time::Duration};
+
+    /// What the stand-in YouTube received.
+    #[derive(Default)]
+    struct Received {
+        resource: Option<serde_json::Value>,
+        bytes: Vec<u8>,
+        chunks: usize,
+        /// Fails the next chunk after this many were stored
+        fail_after_chunks: Option<usize>,
+        /// Takes chunks without storing them
+        is_stalled: bool,
+    }
+
+    async fn stand_in(
+        received: Arc<Mutex<Received>>,
+        addr: SocketAddr,
+        req: Request<Body>,
+    ) -> Response<Body> {
+        let response = |status: u16| Response::builder().status(status);
+        let authorized = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .is_some_and(|auth| auth == "Bearer access");
+        if req.uri().path() == "/token" {
+            return response(200)
+                .body(Body::from(
+                    r#"{"access_token":"access","expires_in":3600}"#,
+                ))
+                .unwrap();
+        }
+        if !authorized {
+            return response(401).body(Body::empty()).unwrap();
+        }
+
+        match (req.method(), req.uri().path()) {
+            (&Method::POST, "/upload/youtube/v3/videos") => {
+                let body =
+                    hyper::body::to_bytes(req.into_body()).await.unwrap();
+                received.lock().unwrap().resource =
+                    Some(serde_json::from_slice(&body).unwrap());
+
+                response(200)
+                    .header(header::LOCATION, format!("http://{addr}/session"))
+                    .body(Body::empty())
+                    .unwrap()
+            }
+            (&Method::PUT, "/session") => {
+                let range = req.headers()[header::CONTENT_RANGE]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let total: usize =
+                    range.rsplit_once('/').unwrap().1.parse().unwrap();
+                let body =
+                    hyper::body::to_bytes(req.into_body()).await.unwrap();
+
+                let mut received = received.lock().unwrap();
+                if !range.starts_with("bytes */") && !received.is_stalled {
+                    if received.fail_after_chunks == Some(received.chunks) {
+                        received.fail_after_chunks = None;
+                        return response(503).body(Body::empty()).unwrap();
+                    }
+
+                    let start: usize = range["bytes ".len()..]
+                        .split_once('-')
+                        .unwrap()
+                        .0
+                        .parse()
+                        .unwrap();
+                    assert_eq!(start, received.bytes.len(), "Chunk skipped");
+                    received.bytes.extend_from_slice(&body);
+                    received.chunks += 1;
+                }
+
+                if received.bytes.len() == total {
+                    response(201).body(Body::from(r#"{"id":"v1"}"#)).unwrap()
+                } else if received.bytes.is_empty() {
+                    response(308).body(Body::empty()).unwrap()
+                } else {
+                    response(308)
+                        .header(
+                            header::RANGE,
+                            format!("bytes=0-{}", received.bytes.len() - 1),
+                        )
+                        .body(Body::empty())
+                        .unwrap()
+                }
+            }
+            _ => response(404).body(Body::empty()).unwrap(),
+        }
+    }
+
+    /// Serves the stand-in at a random port, the client sends chunks of
+    /// the smallest size and barely waits before retrying.
+    fn serve(received: &Arc<Mutex<Received>>) -> Result<Client> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server =
+            hyper::Server::from_tcp(listener)?.serve(make_service_fn({
+                let received = Arc::clone(received);
+                move |_| {
+                    let received = Arc::clone(&received);
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |req| {
+                            let received = Arc::clone(&received);
+                            async move {
+                                Ok::<_, Infallible>(
+                                    stand_in(received, addr, req).await,
+                                )
+                            }
+                        }))
+                    }
+                }
+            }));
+        tokio::spawn(server);
+
+        Ok(Client::new(
+            "id",
+            "secret",
+            Endpoints::at(&format!("http://{addr}")),
+        )
+        .with_chunk_size(CHUNK_GRANULARITY)
+        .with_retries(3, Duration::from_millis(1)))
+    }
+
+    /// A bit over two chunks, removed when the returned dir is.
+    async fn write_video(name: &str) -> Result<(std::path::PathBuf, Vec<u8>)> {
+        let video: Vec<u8> = (0..(2 * CHUNK_GRANULARITY + 1000))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let dir = std::env::temp_dir()
+            .join(format!("youtube-upload-test-{name}-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join("video.mp4"), &video).await?;
+
+        Ok((dir, video))
+    }
+
+    fn token(access_token: &str) -> OAuthToken {
+        OAuthToken {
+            access_token: access_token.into(),
+            refresh_token: Some("refresh".into()),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_uploads_in_chunks_and_retries() -> Result<()> {
+        let received = Arc::new(Mutex::new(Received {
+            fail_after_chunks: Some(1),
+            ..Default::default()
+        }));
+        let client = serve(&received)?;
+        let (dir, video) = write_video("retries").await?;
+
+        let mut token = token("access");
+        let metadata = metadata::generate("Best of", None, &[]);
+
+        let uploaded = client
+            .upload(&mut token, &metadata, &dir.join("video.mp4"))
+            .await;
+        tokio::fs::remove_dir_all(&dir).await?;
+
+        assert_eq!(uploaded?, Video { id: "v1".into() });
+        let received = received.lock().unwrap();
+        assert_eq!(received.chunks, 3);
+        assert!(received.bytes == video, "Uploaded bytes differ");
+        assert_eq!(
+            received.resource.as_ref().unwrap()["snippet"]["title"],
+            "Best of"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_refreshes_rejected_tokens() -> Result<()> {
+        let received = Arc::new(Mutex::new(Received::default()));
+        let client = serve(&received)?;
+        let (dir, _) = write_video("refresh").await?;
+
+        // not expired yet as far as we know, but revoked
+        let mut token = token("revoked");
+        let metadata = metadata::generate("Best of", None, &[]);
+
+        let uploaded = client
+            .upload(&mut token, &metadata, &dir.join("video.mp4"))
+            .await;
+        tokio::fs::remove_dir_all(&dir).await?;
+
+        assert_eq!(uploaded?, Video { id: "v1".into() });
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_gives_up_when_upload_stalls() -> Result<()> {
+        let received = Arc::new(Mutex::new(Received {
+            is_stalled: true,
+            ..Default::default()
+        }));
+        let client = serve(&received)?;
+        let (dir, _) = write_video("stall").await?;
+
+        let mut token = token("access");
+        let metadata = metadata::generate("Best of", None, &[]);
+
+        let uploaded = tokio::time::timeout(
+            Duration::from_secs(10),
+            client.upload(&mut token, &metadata, &dir.join("video.mp4")),
+        )
+        .await;
+        tokio::fs::remove_dir_all(&dir).await?;
+
+        let e = uploaded?.unwrap_err();
+        assert!(e.to_string().contains("stopped advancing"), "{e}");
+
+        Ok(())
+    }
+}
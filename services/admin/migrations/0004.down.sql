@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS moment_clips;
+DROP TABLE IF EXISTS moments;
+DROP TABLE IF EXISTS clip_frame_hashes;
@@ -0,0 +1,30 @@
+-- perceptual hashes of frames sampled from downloaded clips
+CREATE TABLE IF NOT EXISTS clip_frame_hashes (
+    -- can be joined with clips table using this
+    clip_id TEXT NOT NULL,
+    -- which second of the clip was the frame sampled at
+    position INTEGER NOT NULL,
+    -- 64bit difference hash of the frame, stored as signed integer
+    hash INTEGER NOT NULL,
+    UNIQUE (clip_id, position)
+);
+
+-- popular moments get clipped by many viewers, such clips are grouped into
+-- a moment and only the canonical clip is listed by default
+CREATE TABLE IF NOT EXISTS moments (
+    id INTEGER PRIMARY KEY,
+    -- not a foreign key, but can be joined with games table using this
+    game_id TEXT NOT NULL,
+    -- all clips of a moment have the same broadcaster
+    broadcaster_id TEXT NOT NULL,
+    -- the clip which represents the moment, the most viewed one
+    canonical_clip_id TEXT NOT NULL UNIQUE,
+    -- when was the moment detected
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS moment_clips (
+    moment_id INTEGER NOT NULL,
+    -- a clip belongs to at most one moment, including the canonical clip
+    clip_id TEXT NOT NULL UNIQUE
+);
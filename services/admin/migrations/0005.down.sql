@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS clip_music_matches;
+DROP TABLE IF EXISTS clip_music_scans;
@@ -0,0 +1,25 @@
+-- clips whose audio was checked against reference tracks by the worker
+CREATE TABLE IF NOT EXISTS clip_music_scans (
+    -- can be joined with clips table using this
+    clip_id TEXT NOT NULL UNIQUE,
+    -- when was the latest scan
+    scanned_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+-- parts of clips which play a known, possibly copyrighted, track
+CREATE TABLE IF NOT EXISTS clip_music_matches (
+    id INTEGER PRIMARY KEY,
+    -- can be joined with clips table using this
+    clip_id TEXT NOT NULL,
+    -- id of the reference track in the worker db
+    track_id INTEGER NOT NULL,
+    -- copied from the worker at the time of the scan
+    track_title TEXT NOT NULL,
+    -- where in the clip does the track start and stop playing, in seconds
+    start_secs REAL NOT NULL,
+    end_secs REAL NOT NULL,
+    -- how many fingerprints aligned, the higher the more certain the match
+    score INTEGER NOT NULL,
+    -- what to do with the segment on export: 'none', 'mute' or 'exclude'
+    action TEXT NOT NULL DEFAULT 'none'
+);
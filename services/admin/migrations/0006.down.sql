@@ -0,0 +1,2 @@
+DELETE FROM settings WHERE name = 'collect_media_garbage_cron';
+DROP TABLE IF EXISTS kept_clips;
@@ -0,0 +1,13 @@
+-- clips whose downloaded media the worker must not garbage collect
+CREATE TABLE IF NOT EXISTS kept_clips (
+    -- can be joined with clips table using this
+    clip_id TEXT NOT NULL UNIQUE,
+    -- when was the clip marked as kept
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+INSERT INTO settings (name, value) VALUES (
+    'collect_media_garbage_cron',
+  -- sec   min   hour   day of month   month   day of week   year
+    '0      0     4      *              *       *             *'
+);
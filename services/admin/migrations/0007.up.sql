@@ -0,0 +1,11 @@
+-- access to the YouTube channel compilations are uploaded to, there is at
+-- most one channel
+CREATE TABLE IF NOT EXISTS youtube_tokens (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    access_token TEXT NOT NULL,
+    -- exchanged for a new access token when it expires
+    refresh_token TEXT,
+    expires_at TEXT NOT NULL,
+    -- when was the channel connected
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
@@ -0,0 +1,16 @@
+-- how clips of a broadcaster are arranged when rendered as vertical shorts
+CREATE TABLE IF NOT EXISTS broadcaster_layouts (
+    broadcaster_id TEXT PRIMARY KEY,
+    -- 'center_crop', 'blurred_fit' or 'split'
+    layout TEXT NOT NULL,
+    -- crop rectangles of the split layout as fractions of the source frame
+    facecam_x REAL NOT NULL,
+    facecam_y REAL NOT NULL,
+    facecam_width REAL NOT NULL,
+    facecam_height REAL NOT NULL,
+    gameplay_x REAL NOT NULL,
+    gameplay_y REAL NOT NULL,
+    gameplay_width REAL NOT NULL,
+    gameplay_height REAL NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
@@ -0,0 +1,3 @@
+DELETE FROM settings WHERE name = 'loudness_tolerance_lu';
+DELETE FROM settings WHERE name = 'loudness_target_lufs';
+DROP TABLE IF EXISTS clip_loudness;
@@ -0,0 +1,17 @@
+-- EBU R128 loudness of clips as measured by the worker
+CREATE TABLE IF NOT EXISTS clip_loudness (
+    -- can be joined with clips table using this
+    clip_id TEXT PRIMARY KEY,
+    -- NULL if the clip is silent
+    integrated_lufs REAL,
+    -- highest peak including those between samples, NULL if the clip is
+    -- silent
+    true_peak_dbtp REAL,
+    measured_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+-- what clips are normalized to when rendered, YouTube plays videos at this
+-- loudness
+INSERT INTO settings (name, value) VALUES ('loudness_target_lufs', '-14');
+-- clips further from the target than this are flagged
+INSERT INTO settings (name, value) VALUES ('loudness_tolerance_lu', '6');
@@ -0,0 +1,12 @@
+-- storyboards, waveforms and animations of clips which the worker generated
+CREATE TABLE IF NOT EXISTS clip_previews (
+    -- can be joined with clips table using this
+    clip_id TEXT PRIMARY KEY,
+    -- previews are artifacts only on the worker which generated them
+    worker_addr TEXT NOT NULL,
+    storyboard_key TEXT NOT NULL,
+    -- NULL if the clip has no audio
+    waveform_key TEXT,
+    animation_key TEXT NOT NULL,
+    generated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
@@ -0,0 +1,21 @@
+-- accounts which can log in to the admin
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    -- argon2 in the PHC string format, which includes the salt
+    password_hash TEXT NOT NULL,
+    -- viewer, editor or admin
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+-- browsers which are logged in, the token is in their cookie
+CREATE TABLE IF NOT EXISTS sessions (
+    token TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- must be sent along with every request which changes something
+    csrf_token TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sessions_user_id ON sessions (user_id);
@@ -0,0 +1,24 @@
+-- every run of a job, whether triggered by cron or by a user
+CREATE TABLE IF NOT EXISTS job_runs (
+    id INTEGER PRIMARY KEY,
+    -- name of the job as it appears in the logs
+    job TEXT NOT NULL,
+    -- cron or manual
+    triggered_by TEXT NOT NULL,
+    -- json array of ids of games the job ran for
+    game_ids TEXT NOT NULL DEFAULT '[]',
+    -- json object with how the job was configured
+    conf TEXT NOT NULL DEFAULT '{}',
+    -- running, succeeded or failed
+    status TEXT NOT NULL DEFAULT 'running',
+    started_at TEXT NOT NULL,
+    -- null while running
+    ended_at TEXT,
+    -- only fetching clips counts these
+    pages_fetched INTEGER NOT NULL DEFAULT 0,
+    clips_inserted INTEGER NOT NULL DEFAULT 0,
+    clips_updated INTEGER NOT NULL DEFAULT 0,
+    retries INTEGER NOT NULL DEFAULT 0,
+    -- json array of error messages, the run failed if there are any
+    errors TEXT NOT NULL DEFAULT '[]'
+);
@@ -0,0 +1,11 @@
+-- who changed which setting from what to what
+CREATE TABLE IF NOT EXISTS setting_changes (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- the default if the setting was never changed before
+    old_value TEXT NOT NULL,
+    new_value TEXT NOT NULL,
+    -- username, kept even if the user is deleted
+    changed_by TEXT NOT NULL,
+    changed_at TEXT NOT NULL
+);
@@ -0,0 +1,16 @@
+-- per game overrides of how its clips are fetched, NULL falls back to the
+-- global settings
+CREATE TABLE IF NOT EXISTS game_fetch_policies (
+    -- twitch id of the game
+    game_id TEXT PRIMARY KEY,
+    -- the scheduled job skips the game until this many hours passed since
+    -- its last successful run which included the game
+    fetch_every_hours INTEGER,
+    recorded_at_least_hours_ago INTEGER,
+    recorded_at_most_hours_ago INTEGER,
+    -- clips with fewer views are not stored
+    min_view_count INTEGER,
+    -- json array of language codes, any language if empty
+    langs TEXT NOT NULL DEFAULT '[]',
+    max_pages_per_run INTEGER
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS clip_review_transitions_clip_id;
+DROP TABLE IF EXISTS clip_review_transitions;
+DROP TABLE IF EXISTS clip_reviews;
@@ -0,0 +1,22 @@
+-- where each clip is in curation, clips which are not here are new
+CREATE TABLE IF NOT EXISTS clip_reviews (
+    clip_id TEXT PRIMARY KEY,
+    -- new, shortlisted, rejected, downloaded or used_in_video
+    state TEXT NOT NULL,
+    -- username of who moved the clip to this state
+    reviewed_by TEXT NOT NULL,
+    reviewed_at TEXT NOT NULL
+);
+
+-- every move of a clip from one state to another
+CREATE TABLE IF NOT EXISTS clip_review_transitions (
+    id INTEGER PRIMARY KEY,
+    clip_id TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    -- username, kept even if the user is deleted
+    reviewed_by TEXT NOT NULL,
+    reviewed_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS clip_review_transitions_clip_id
+    ON clip_review_transitions (clip_id);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS project_entries_clip_id;
+DROP TABLE IF EXISTS project_entries;
+DROP TABLE IF EXISTS projects;
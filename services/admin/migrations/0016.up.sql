@@ -0,0 +1,31 @@
+-- compilation videos such as weekly best ofs
+CREATE TABLE IF NOT EXISTS projects (
+    id INTEGER PRIMARY KEY,
+    title TEXT NOT NULL,
+    -- how long the video should be
+    target_duration_secs INTEGER NOT NULL,
+    -- draft, editing, ready or published
+    status TEXT NOT NULL DEFAULT 'draft',
+    notes TEXT NOT NULL DEFAULT '',
+    -- username, kept even if the user is deleted
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    published_at TEXT
+);
+
+-- clips in a project in the order they play
+CREATE TABLE IF NOT EXISTS project_entries (
+    id INTEGER PRIMARY KEY,
+    project_id INTEGER NOT NULL,
+    clip_id TEXT NOT NULL,
+    -- starts at 0 in each project
+    position INTEGER NOT NULL,
+    -- where in the clip the entry starts and ends, the whole clip if null
+    trim_in_secs REAL,
+    trim_out_secs REAL,
+    notes TEXT NOT NULL DEFAULT '',
+    UNIQUE (project_id, clip_id)
+);
+CREATE INDEX IF NOT EXISTS project_entries_clip_id
+    ON project_entries (clip_id);
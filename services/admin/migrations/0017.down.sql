@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS saved_searches;
+DROP INDEX IF EXISTS clip_tags_tag_id;
+DROP TABLE IF EXISTS clip_tags;
+DROP TABLE IF EXISTS tags;
@@ -0,0 +1,26 @@
+-- labels editors put on clips, such as "funny" or "sponsor-safe"
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY,
+    -- lowercase letters, digits and dashes
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS clip_tags (
+    clip_id TEXT NOT NULL,
+    tag_id INTEGER NOT NULL,
+    -- username, kept even if the user is deleted
+    tagged_by TEXT NOT NULL,
+    tagged_at TEXT NOT NULL,
+    PRIMARY KEY (clip_id, tag_id)
+);
+CREATE INDEX IF NOT EXISTS clip_tags_tag_id ON clip_tags (tag_id);
+
+-- named filters of the clips page, shared by everyone
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    -- query string of the clips page without the leading question mark
+    query TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
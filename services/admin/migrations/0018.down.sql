@@ -0,0 +1,10 @@
+DROP TRIGGER IF EXISTS clip_tags_search_delete;
+DROP TRIGGER IF EXISTS clip_tags_search_insert;
+DROP TRIGGER IF EXISTS clips_search_delete;
+DROP TRIGGER IF EXISTS clips_search_update;
+DROP TRIGGER IF EXISTS clips_search_insert;
+DROP TRIGGER IF EXISTS clip_search_update;
+DROP TRIGGER IF EXISTS clip_search_delete;
+DROP TRIGGER IF EXISTS clip_search_insert;
+DROP TABLE IF EXISTS clip_search_index;
+DROP TABLE IF EXISTS clip_search;
@@ -0,0 +1,109 @@
+-- what full-text search looks at, one row per clip
+--
+-- kept in sync with clips and clip_tags by the triggers below, the integer id
+-- stays the same when a refetch replaces the clip
+CREATE TABLE IF NOT EXISTS clip_search (
+    id INTEGER PRIMARY KEY,
+    clip_id TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    -- names of the tags of the clip separated by spaces
+    tags TEXT NOT NULL
+);
+
+-- porter stems english words, so "fails" matches "failed"
+CREATE VIRTUAL TABLE IF NOT EXISTS clip_search_index USING fts5(
+    title,
+    tags,
+    content = 'clip_search',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS clip_search_insert
+AFTER INSERT ON clip_search
+BEGIN
+    INSERT INTO clip_search_index (rowid, title, tags)
+    VALUES (new.id, new.title, new.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_search_delete
+AFTER DELETE ON clip_search
+BEGIN
+    INSERT INTO clip_search_index (clip_search_index, rowid, title, tags)
+    VALUES ('delete', old.id, old.title, old.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_search_update
+AFTER UPDATE ON clip_search
+BEGIN
+    INSERT INTO clip_search_index (clip_search_index, rowid, title, tags)
+    VALUES ('delete', old.id, old.title, old.tags);
+    INSERT INTO clip_search_index (rowid, title, tags)
+    VALUES (new.id, new.title, new.tags);
+END;
+
+-- fetching replaces clips, which doesn't fire delete triggers
+CREATE TRIGGER IF NOT EXISTS clips_search_insert
+AFTER INSERT ON clips
+BEGIN
+    INSERT INTO clip_search (clip_id, title, tags)
+    VALUES (
+        new.id,
+        COALESCE(new.title, ''),
+        COALESCE((
+            SELECT group_concat(tags.name, ' ') FROM clip_tags
+            JOIN tags ON tags.id = clip_tags.tag_id
+            WHERE clip_tags.clip_id = new.id
+        ), '')
+    )
+    ON CONFLICT (clip_id) DO UPDATE SET title = excluded.title
+    WHERE title != excluded.title;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_search_update
+AFTER UPDATE OF title ON clips
+BEGIN
+    UPDATE clip_search SET title = COALESCE(new.title, '')
+    WHERE clip_id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_search_delete
+AFTER DELETE ON clips
+BEGIN
+    DELETE FROM clip_search WHERE clip_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_tags_search_insert
+AFTER INSERT ON clip_tags
+BEGIN
+    UPDATE clip_search SET tags = COALESCE((
+        SELECT group_concat(tags.name, ' ') FROM clip_tags
+        JOIN tags ON tags.id = clip_tags.tag_id
+        WHERE clip_tags.clip_id = new.clip_id
+    ), '')
+    WHERE clip_id = new.clip_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clip_tags_search_delete
+AFTER DELETE ON clip_tags
+BEGIN
+    UPDATE clip_search SET tags = COALESCE((
+        SELECT group_concat(tags.name, ' ') FROM clip_tags
+        JOIN tags ON tags.id = clip_tags.tag_id
+        WHERE clip_tags.clip_id = old.clip_id
+    ), '')
+    WHERE clip_id = old.clip_id;
+END;
+
+INSERT INTO clip_search (clip_id, title, tags)
+SELECT
+    id,
+    COALESCE(title, ''),
+    COALESCE((
+        SELECT group_concat(tags.name, ' ') FROM clip_tags
+        JOIN tags ON tags.id = clip_tags.tag_id
+        WHERE clip_tags.clip_id = clips.id
+    ), '')
+FROM clips
+WHERE true
+ON CONFLICT (clip_id) DO NOTHING;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS clips_broadcaster_id_recorded_at;
+DROP INDEX IF EXISTS clips_game_id_recorded_at;
+DROP INDEX IF EXISTS clips_game_id_view_count;
@@ -0,0 +1,10 @@
+-- the clips listing filters by game and sorts by one of these, the id keeps
+-- the order stable when paging with a cursor
+CREATE INDEX IF NOT EXISTS clips_game_id_view_count
+ON clips (game_id, view_count, id);
+CREATE INDEX IF NOT EXISTS clips_game_id_recorded_at
+ON clips (game_id, recorded_at, id);
+
+-- latest clip of a broadcaster
+CREATE INDEX IF NOT EXISTS clips_broadcaster_id_recorded_at
+ON clips (broadcaster_id, recorded_at);
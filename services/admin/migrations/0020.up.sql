@@ -0,0 +1,8 @@
+-- clips stored by hand rather than by the fetch job, they don't tell how far
+-- the fetch job got
+CREATE TABLE IF NOT EXISTS imported_clips (
+    clip_id TEXT PRIMARY KEY,
+    -- username, kept even if the user is deleted
+    imported_by TEXT NOT NULL,
+    imported_at TEXT NOT NULL
+);
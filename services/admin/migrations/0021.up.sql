@@ -0,0 +1,9 @@
+-- compilations uploaded to the connected YouTube channel
+CREATE TABLE IF NOT EXISTS project_uploads (
+    project_id INTEGER PRIMARY KEY,
+    -- YouTube assigned id, the video is at https://youtu.be/{video_id}
+    video_id TEXT NOT NULL,
+    -- username, kept even if the user is deleted
+    uploaded_by TEXT NOT NULL,
+    uploaded_at TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- when the fetch job last fetched every page of a game without errors, a
+-- run which failed for another game still counts for this one
+CREATE TABLE IF NOT EXISTS game_fetches (
+    game_id TEXT PRIMARY KEY,
+    -- when the run started
+    fetched_at TEXT NOT NULL
+);
@@ -0,0 +1,8 @@
+-- uploads of projects which are running, so that a project is not uploaded
+-- twice at once, rows left over by a restart are cleared on start
+CREATE TABLE IF NOT EXISTS project_uploads_in_flight (
+    project_id INTEGER PRIMARY KEY,
+    -- username, kept even if the user is deleted
+    started_by TEXT NOT NULL,
+    started_at TEXT NOT NULL
+);
@@ -0,0 +1,92 @@
+use anyhow::{bail, Context};
+use std::io::BufRead;
+
+use crate::models::user::{self, Role};
+use crate::prelude::*;
+
+/// Runs the command given on the command line, if any.
+/// Returns false if the admin should start serving.
+pub fn run(conf: &Conf) -> AnyResult<bool> {
+    let mut args = std::env::args().skip(1);
+    let Some(command) = args.next() else {
+        return Ok(false);
+    };
+
+    match (command.as_str(), args.next()) {
+        ("bootstrap-admin", Some(username)) => {
+            let password = match std::env::var("ADMIN_PASSWORD") {
+                Ok(password) => password,
+                Err(_) => read_password()?,
+            };
+            let db = db::open(conf.db_path())?;
+            bootstrap_admin(&db, &username, &password)?;
+        }
+        _ => bail!("Usage: admin [bootstrap-admin <username>]"),
+    }
+
+    Ok(true)
+}
+
+/// Creates an admin, or makes an existing user admin with given password.
+/// This is how the first admin gets in, and how a locked out one gets back.
+pub fn bootstrap_admin(
+    db: &DbConn,
+    username: &str,
+    password: &str,
+) -> AnyResult<()> {
+    user::validate_username(username)?;
+    let password_hash = user::hash_password(password)?;
+
+    match db::user::select_by_username(db, username)? {
+        Some((existing, _)) => {
+            db::user::set_password_hash(db, existing.id, &password_hash)?;
+            db::user::set_role(db, existing.id, Role::Admin)?;
+            info!("User {username} is admin now, with the new password");
+        }
+        None => {
+            db::user::insert(db, username, &password_hash, Role::Admin)?;
+            info!("Added admin {username}");
+        }
+    }
+
+    Ok(())
+}
+
+/// The password is not hidden as it's typed, pipe it in or set
+/// `ADMIN_PASSWORD` to keep it off the screen.
+fn read_password() -> AnyResult<String> {
+    eprintln!("Password, at least 12 characters:");
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Cannot read password")?;
+
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_bootstraps_or_restores_admins() -> AnyResult<()> {
+        let db = db::open(":memory:")?;
+
+        bootstrap_admin(&db, "root", "correct horse battery")?;
+        let (admin, hash) = db::user::select_by_username(&db, "root")?.unwrap();
+        assert_eq!(admin.role, Role::Admin);
+        assert!(user::verify_password("correct horse battery", &hash));
+
+        db::user::set_role(&db, admin.id, Role::Viewer)?;
+        bootstrap_admin(&db, "root", "staple horse battery")?;
+        let (admin, hash) = db::user::select_by_username(&db, "root")?.unwrap();
+        assert_eq!(admin.role, Role::Admin);
+        assert!(user::verify_password("staple horse battery", &hash));
+        assert_eq!(db::user::select_all(&db)?.len(), 1);
+
+        assert!(bootstrap_admin(&db, "root", "short").is_err());
+
+        Ok(())
+    }
+}
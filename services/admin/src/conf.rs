@@ -1,5 +1,5 @@
-use crate::prelude::*;
-use anyhow::Context;
+use crate::{prelude::*, worker_pool::GameRouting};
+use anyhow::{bail, Context};
 use std::{
     env,
     net::SocketAddr,
@@ -10,9 +10,40 @@ pub struct Conf {
     /// For example 0.0.0.0:8080
     pub http_addr: SocketAddr,
     pub sqlite_db_path: PathBuf,
-    pub worker_addr: SocketAddr,
+    /// Jobs are spread over all of these workers.
+    pub worker_addrs: Vec<SocketAddr>,
+    pub worker_routing: GameRouting,
+    /// Must match the worker's RPC_TOKEN.
+    pub worker_token: Option<String>,
+    /// The worker is reached over TLS if set.
+    pub worker_tls: Option<WorkerTlsConf>,
     pub twitch_client_id: String,
     pub twitch_secret: String,
+    /// Compilations can be uploaded to YouTube if set.
+    pub youtube: Option<YoutubeConf>,
+}
+
+pub struct YoutubeConf {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Where Google sends the user back to after they grant access to their
+    /// channel, must be registered with the OAuth client.
+    pub redirect_url: String,
+    /// Google's unless a stand-in is configured
+    pub endpoints: youtube::Endpoints,
+    /// Where the edited videos of compilations are put to be uploaded
+    pub videos_dir: PathBuf,
+}
+
+pub struct WorkerTlsConf {
+    /// PEM CA certificate which the worker's certificate is signed by
+    pub ca_path: PathBuf,
+    /// PEM certificate and key the admin presents to the worker if it
+    /// requires client certificates
+    pub identity: Option<(PathBuf, PathBuf)>,
+    /// Name in the workers' certificates if it is not the host of their
+    /// address
+    pub domain: Option<String>,
 }
 
 impl Conf {
@@ -22,8 +53,39 @@ impl Conf {
         let http_addr = env::var("HTTP_ADDR").context("HTTP_ADDR")?;
         debug!("HTTP_ADDR: {http_addr}");
 
-        let worker_addr = env::var("WORKER_ADDR").context("WORKER_ADDR")?;
-        debug!("WORKER_ADDR: {worker_addr}");
+        // WORKER_ADDR is what a single worker used to be configured with
+        let worker_addrs = env::var("WORKER_ADDRS")
+            .or_else(|_| env::var("WORKER_ADDR"))
+            .context("WORKER_ADDRS")?;
+        debug!("WORKER_ADDRS: {worker_addrs}");
+        let worker_addrs = worker_addrs
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(|addr| addr.parse().with_context(|| format!("Worker {addr}")))
+            .collect::<AnyResult<Vec<SocketAddr>>>()?;
+        if worker_addrs.is_empty() {
+            bail!("WORKER_ADDRS must list at least one worker");
+        }
+
+        let worker_routing =
+            env::var("WORKER_ROUTING").unwrap_or_else(|_| "game".to_string());
+        debug!("WORKER_ROUTING: {worker_routing}");
+        let worker_routing = match worker_routing.as_str() {
+            "game" => GameRouting::Affinity,
+            "load" => GameRouting::LeastLoaded,
+            other => {
+                bail!("WORKER_ROUTING must be 'game' or 'load', got '{other}'")
+            }
+        };
+
+        let worker_token = env::var("WORKER_TOKEN").ok();
+        debug!(
+            "WORKER_TOKEN: {}",
+            if worker_token.is_some() { "***" } else { "-" }
+        );
+
+        let worker_tls = WorkerTlsConf::from_env()?;
 
         let sqlite_db_path =
             env::var("SQLITE_DB_PATH").context("SQLITE_DB_PATH")?;
@@ -45,12 +107,19 @@ impl Conf {
             "*".repeat(twitch_secret.len() - 3)
         );
 
+        let http_addr = http_addr.parse()?;
+        let youtube = YoutubeConf::from_env(&http_addr)?;
+
         Ok(Self {
-            http_addr: http_addr.parse()?,
-            worker_addr: worker_addr.parse()?,
+            http_addr,
+            worker_addrs,
+            worker_routing,
+            worker_token,
+            worker_tls,
             sqlite_db_path: sqlite_db_path.into(),
             twitch_client_id,
             twitch_secret,
+            youtube,
         })
     }
 
@@ -58,13 +127,48 @@ impl Conf {
         self.sqlite_db_path.as_ref()
     }
 
-    /// The worker does not have to be running for this to succeed.
-    pub async fn connect_lazy_worker_client(
+    /// The workers do not have to be running for this to succeed.
+    pub fn connect_worker_pool(&self) -> AnyResult<WorkerPool> {
+        let workers = self
+            .worker_addrs
+            .iter()
+            .map(|addr| Ok((*addr, self.connect_lazy_worker_client(addr)?)))
+            .collect::<AnyResult<_>>()?;
+
+        Ok(WorkerPool::new(workers, self.worker_routing))
+    }
+
+    fn connect_lazy_worker_client(
         &self,
+        addr: &SocketAddr,
     ) -> AnyResult<worker::Client> {
-        let dst = format!("http://{}", self.worker_addr);
-        let conn = tonic::transport::Endpoint::new(dst)?.connect_lazy();
-        Ok(worker::Client::new(conn))
+        let conn = match &self.worker_tls {
+            Some(tls) => {
+                let dst = format!("https://{addr}");
+                tonic::transport::Endpoint::new(dst)?
+                    .tls_config(tls.client_config()?)?
+                    .connect_lazy()
+            }
+            None => {
+                let dst = format!("http://{addr}");
+                tonic::transport::Endpoint::new(dst)?.connect_lazy()
+            }
+        };
+        let token = worker::BearerToken::new(self.worker_token.as_deref())
+            .context("WORKER_TOKEN")?;
+        Ok(worker::rpc::worker_client::WorkerClient::with_interceptor(
+            conn, token,
+        ))
+    }
+
+    pub fn construct_youtube_client(&self) -> Option<youtube::Client> {
+        self.youtube.as_ref().map(|conf| {
+            youtube::Client::new(
+                &conf.client_id,
+                &conf.client_secret,
+                conf.endpoints.clone(),
+            )
+        })
     }
 
     pub async fn construct_twitch_client(&self) -> AnyResult<twitch::Client> {
@@ -75,3 +179,103 @@ impl Conf {
         .await
     }
 }
+
+impl WorkerTlsConf {
+    fn from_env() -> AnyResult<Option<Self>> {
+        let ca_path = env::var("WORKER_TLS_CA").ok();
+        debug!("WORKER_TLS_CA: {ca_path:?}");
+        let cert_path = env::var("WORKER_TLS_CERT").ok();
+        debug!("WORKER_TLS_CERT: {cert_path:?}");
+        let key_path = env::var("WORKER_TLS_KEY").ok();
+        debug!("WORKER_TLS_KEY: {key_path:?}");
+        let domain = env::var("WORKER_TLS_DOMAIN").ok();
+        debug!("WORKER_TLS_DOMAIN: {domain:?}");
+
+        let identity = match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                Some((cert_path.into(), key_path.into()))
+            }
+            (None, None) => None,
+            _ => {
+                bail!("WORKER_TLS_CERT and WORKER_TLS_KEY must be set together")
+            }
+        };
+
+        match ca_path {
+            Some(ca_path) => Ok(Some(Self {
+                ca_path: ca_path.into(),
+                identity,
+                domain,
+            })),
+            None if identity.is_some() || domain.is_some() => {
+                bail!("WORKER_TLS_CA is required to connect over TLS")
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn client_config(&self) -> AnyResult<tonic::transport::ClientTlsConfig> {
+        use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+        let read = |path: &PathBuf, what: &str| {
+            std::fs::read(path)
+                .with_context(|| format!("Cannot read {what} from {path:?}"))
+        };
+
+        let mut config = ClientTlsConfig::new().ca_certificate(
+            Certificate::from_pem(read(&self.ca_path, "WORKER_TLS_CA")?),
+        );
+        if let Some((cert_path, key_path)) = &self.identity {
+            config = config.identity(Identity::from_pem(
+                read(cert_path, "WORKER_TLS_CERT")?,
+                read(key_path, "WORKER_TLS_KEY")?,
+            ));
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain);
+        }
+
+        Ok(config)
+    }
+}
+
+impl YoutubeConf {
+    fn from_env(http_addr: &SocketAddr) -> AnyResult<Option<Self>> {
+        let client_id = env::var("YOUTUBE_CLIENT_ID").ok();
+        debug!("YOUTUBE_CLIENT_ID: {client_id:?}");
+        let client_secret = env::var("YOUTUBE_CLIENT_SECRET").ok();
+        let (client_id, client_secret) = match (client_id, client_secret) {
+            (Some(id), Some(secret)) => (id, secret),
+            (None, None) => return Ok(None),
+            _ => bail!(
+                "YOUTUBE_CLIENT_ID and YOUTUBE_CLIENT_SECRET must be set together"
+            ),
+        };
+
+        let redirect_url =
+            env::var("YOUTUBE_REDIRECT_URL").unwrap_or_else(|_| {
+                format!(
+                    "http://localhost:{}/youtube/oauth/callback",
+                    http_addr.port()
+                )
+            });
+        debug!("YOUTUBE_REDIRECT_URL: {redirect_url}");
+
+        let api_url = env::var("YOUTUBE_API_URL").ok();
+        debug!("YOUTUBE_API_URL: {api_url:?}");
+
+        let videos_dir = env::var("YOUTUBE_VIDEOS_DIR")
+            .unwrap_or_else(|_| "videos".to_string());
+        debug!("YOUTUBE_VIDEOS_DIR: {videos_dir}");
+
+        Ok(Some(Self {
+            client_id,
+            client_secret,
+            redirect_url,
+            endpoints: api_url
+                .map(|url| youtube::Endpoints::at(&url))
+                .unwrap_or_default(),
+            videos_dir: videos_dir.into(),
+        }))
+    }
+}
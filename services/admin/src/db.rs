@@ -1,8 +1,36 @@
 pub mod clip;
+/// How clips of each game are fetched
+pub mod fetch_policy;
 pub mod game;
+/// History of job runs
+pub mod job_run;
+/// How clips of each broadcaster are rendered as vertical shorts
+pub mod layout;
+/// Loudness of clips as measured by the worker
+pub mod loudness;
+/// Groups of clips which captured the same moment of a stream
+pub mod moment;
+/// Known tracks found in clips audio
+pub mod music;
+/// Storyboards, waveforms and animations of clips generated by the worker
+pub mod preview;
+/// Compilation videos and the clips in them
+pub mod project;
+/// Clips whose media the worker must keep
+pub mod retention;
+/// Decisions about clips and who made them
+pub mod review;
+/// Named filters of the clips page
+pub mod saved_search;
 /// Stores various settings in db instead of constants so that they can be
-/// changed via dashboard
+/// changed via dashboard, see `models::setting` for which there are
 pub mod setting;
+/// Labels on clips
+pub mod tag;
+/// Accounts which can log in and their sessions
+pub mod user;
+/// Access to the YouTube channel
+pub mod youtube;
 
 use crate::prelude::*;
 use rusqlite_migration::{Migrations, M};
@@ -41,5 +69,45 @@ fn migrations() -> Migrations<'static> {
             .down(include_str!("../migrations/0002.down.sql")),
         M::up(include_str!("../migrations/0003.up.sql"))
             .down(include_str!("../migrations/0003.down.sql")),
+        M::up(include_str!("../migrations/0004.up.sql"))
+            .down(include_str!("../migrations/0004.down.sql")),
+        M::up(include_str!("../migrations/0005.up.sql"))
+            .down(include_str!("../migrations/0005.down.sql")),
+        M::up(include_str!("../migrations/0006.up.sql"))
+            .down(include_str!("../migrations/0006.down.sql")),
+        M::up(include_str!("../migrations/0007.up.sql"))
+            .down(include_str!("../migrations/0007.down.sql")),
+        M::up(include_str!("../migrations/0008.up.sql"))
+            .down(include_str!("../migrations/0008.down.sql")),
+        M::up(include_str!("../migrations/0009.up.sql"))
+            .down(include_str!("../migrations/0009.down.sql")),
+        M::up(include_str!("../migrations/0010.up.sql"))
+            .down(include_str!("../migrations/0010.down.sql")),
+        M::up(include_str!("../migrations/0011.up.sql"))
+            .down(include_str!("../migrations/0011.down.sql")),
+        M::up(include_str!("../migrations/0012.up.sql"))
+            .down(include_str!("../migrations/0012.down.sql")),
+        M::up(include_str!("../migrations/0013.up.sql"))
+            .down(include_str!("../migrations/0013.down.sql")),
+        M::up(include_str!("../migrations/0014.up.sql"))
+            .down(include_str!("../migrations/0014.down.sql")),
+        M::up(include_str!("../migrations/0015.up.sql"))
+            .down(include_str!("../migrations/0015.down.sql")),
+        M::up(include_str!("../migrations/0016.up.sql"))
+            .down(include_str!("../migrations/0016.down.sql")),
+        M::up(include_str!("../migrations/0017.up.sql"))
+            .down(include_str!("../migrations/0017.down.sql")),
+        M::up(include_str!("../migrations/0018.up.sql"))
+            .down(include_str!("../migrations/0018.down.sql")),
+        M::up(include_str!("../migrations/0019.up.sql"))
+            .down(include_str!("../migrations/0019.down.sql")),
+        M::up(include_str!("../migrations/0020.up.sql"))
+            .down(include_str!("../migrations/0020.down.sql")),
+        M::up(include_str!("../migrations/0021.up.sql"))
+            .down(include_str!("../migrations/0021.down.sql")),
+        M::up(include_str!("../migrations/0022.up.sql"))
+            .down(include_str!("../migrations/0022.down.sql")),
+        M::up(include_str!("../migrations/0023.up.sql"))
+            .down(include_str!("../migrations/0023.down.sql")),
     ])
 }
@@ -1,28 +1,164 @@
 use itertools::Itertools;
-use rusqlite::named_params;
+use rusqlite::{named_params, OptionalExtension};
 use std::{rc::Rc, time::Duration};
 use twitch::models::GameId;
 
-use crate::models::clip::{Clip, ShowParams};
+use crate::models::clip::{Clip, ClipPage, ShowParams, ShowSortBy};
+use crate::models::loudness::Loudness;
+use crate::models::review::ReviewState;
+use crate::models::user::User;
 use crate::prelude::*;
 
+/// Listing counts the matching clips up to this many.
+pub const COUNT_LIMIT: usize = 10_000;
+
+/// Selects all columns needed to construct [`Clip`].
+pub const SELECT_CLIPS: &str = "
+    SELECT
+        broadcaster_id,
+        broadcaster_name,
+        created_at,
+        creator_name,
+        duration,
+        game_id,
+        id,
+        lang,
+        recorded_at,
+        thumbnail_url,
+        title,
+        updated_at,
+        url,
+        view_count,
+        (
+            SELECT moment_id FROM moment_clips WHERE clip_id = clips.id
+        ) AS moment_id,
+        (
+            SELECT COUNT(*)
+            FROM moment_clips
+            JOIN moments ON moments.id = moment_clips.moment_id
+            WHERE moments.canonical_clip_id = clips.id
+            AND moment_clips.clip_id != clips.id
+        ) AS duplicate_count,
+        (
+            SELECT COUNT(*) FROM clip_music_matches
+            WHERE clip_id = clips.id
+        ) AS music_match_count,
+        EXISTS (
+            SELECT 1 FROM kept_clips WHERE clip_id = clips.id
+        ) AS is_kept,
+        EXISTS (
+            SELECT 1 FROM clip_previews WHERE clip_id = clips.id
+        ) AS has_preview,
+        EXISTS (
+            SELECT 1 FROM clip_loudness WHERE clip_id = clips.id
+        ) AS is_loudness_measured,
+        (
+            SELECT integrated_lufs FROM clip_loudness
+            WHERE clip_id = clips.id
+        ) AS integrated_lufs,
+        (
+            SELECT true_peak_dbtp FROM clip_loudness
+            WHERE clip_id = clips.id
+        ) AS true_peak_dbtp,
+        COALESCE(
+            (SELECT state FROM clip_reviews WHERE clip_id = clips.id),
+            'new'
+        ) AS review_state,
+        (
+            SELECT reviewed_by FROM clip_reviews WHERE clip_id = clips.id
+        ) AS reviewed_by,
+        (
+            SELECT reviewed_at FROM clip_reviews WHERE clip_id = clips.id
+        ) AS reviewed_at,
+        (
+            SELECT json_group_array(name) FROM (
+                SELECT tags.name FROM clip_tags
+                JOIN tags ON tags.id = clip_tags.tag_id
+                WHERE clip_tags.clip_id = clips.id
+                ORDER BY tags.name
+            )
+        ) AS tags
+    FROM clips";
+
+pub fn select_by_id(db: &DbConn, clip_id: &str) -> Result<Clip> {
+    let clip = db
+        .prepare(&format!("{SELECT_CLIPS} WHERE id = :id"))?
+        .query_row(named_params! { ":id": clip_id }, |row| {
+            Clip::try_from(row)
+        })?;
+
+    Ok(clip)
+}
+
+/// Most recently recorded clip of the broadcaster, if any was fetched.
+pub fn select_latest_by_broadcaster(
+    db: &DbConn,
+    broadcaster_id: &str,
+) -> Result<Option<Clip>> {
+    db.prepare(&format!(
+        "{SELECT_CLIPS} WHERE broadcaster_id = :broadcaster_id
+        ORDER BY recorded_at DESC LIMIT 1"
+    ))?
+    .query_row(named_params! { ":broadcaster_id": broadcaster_id }, |row| {
+        Clip::try_from(row)
+    })
+    .optional()
+    .map_err(AppError::from)
+}
+
 pub fn list(
     db: &DbConn,
     game_id: &GameId,
     request: &ShowParams,
-) -> Result<(usize, Vec<Clip>)> {
+) -> Result<ClipPage> {
+    let filter = Filter::new(db, game_id, request)?;
+    let (clips, next_cursor) = page(db, &filter, request)?;
+
+    // counting every match is what slows down large listings
+    let total_count_sql = format!(
+        "SELECT COUNT(*) FROM (
+            SELECT 1 FROM clips {} LIMIT :count_limit
+        )",
+        Filter::WHERE_CLAUSE
+    );
+    let mut params = filter.params();
+    params.extend(named_params! { ":count_limit": COUNT_LIMIT + 1 });
+    let total_count: usize = db
+        .prepare(&total_count_sql)?
+        .query_row(params.as_slice(), |row| row.get(0))?;
+
+    Ok(ClipPage {
+        total_count: total_count.min(COUNT_LIMIT),
+        is_total_count_exact: total_count <= COUNT_LIMIT,
+        clips,
+        next_cursor,
+    })
+}
+
+/// Like [`list`] without counting the matching clips, for going through
+/// all of them page after page.
+/// Returns the clips of the page and the cursor of the next one, if any.
+pub fn select_page(
+    db: &DbConn,
+    game_id: &GameId,
+    request: &ShowParams,
+) -> Result<(Vec<Clip>, Option<String>)> {
+    let filter = Filter::new(db, game_id, request)?;
+
+    page(db, &filter, request)
+}
+
+fn page(
+    db: &DbConn,
+    filter: &Filter,
+    request: &ShowParams,
+) -> Result<(Vec<Clip>, Option<String>)> {
     let ShowParams {
-        broadcaster_name,
-        langs,
-        max_recorded_at,
-        min_recorded_at,
         page_offset,
         page_size,
         sort_by,
         sort_direction_asc,
-        title_like,
-        view_count_max,
-        view_count_min,
+        ..
     } = request;
 
     if *page_size == 0 {
@@ -31,84 +167,393 @@ pub fn list(
         ));
     }
 
-    let sort_by: &str = From::from(*sort_by);
-    let sort_direction = if *sort_direction_asc { "ASC" } else { "DESC" };
-    // array feature of sqlite
-    let langs = Rc::new(
-        langs
-            .into_iter()
-            .cloned()
-            .map(rusqlite::types::Value::from)
-            .collect_vec(),
+    let where_clause = Filter::WHERE_CLAUSE;
+    // bm25 is only known for clips which match a search
+    let (sort_by, search_join) = match (sort_by, &request.search) {
+        (ShowSortBy::Relevance, None) => ("view_count", ""),
+        (ShowSortBy::Relevance, Some(_)) => ("relevance", Filter::SEARCH_JOIN),
+        (sort_by, _) => (From::from(*sort_by), ""),
+    };
+    let (sort_direction, after) = if *sort_direction_asc {
+        ("ASC", ">")
+    } else {
+        ("DESC", "<")
+    };
+    let cursor = request
+        .cursor
+        .as_deref()
+        .map(|cursor| Cursor::parse(cursor, sort_by))
+        .transpose()?;
+    let keyset_clause = if cursor.is_some() {
+        format!("AND ({sort_by}, clips.id) {after} (:cursor_value, :cursor_id)")
+    } else {
+        String::new()
+    };
+    let paginated_sql = format!(
+        "{SELECT_CLIPS}
+        {search_join}
+        {where_clause}
+        {keyset_clause}
+        ORDER BY {sort_by} {sort_direction}, clips.id {sort_direction}
+        LIMIT :limit
+        OFFSET :page_offset"
     );
-    let where_clause = "WHERE
+    let mut params = filter.params();
+    // one more than asked for tells whether there is a next page
+    let limit = page_size + 1;
+    let page_offset = if cursor.is_some() { &0 } else { page_offset };
+    params.extend(named_params! {
+        ":page_offset": page_offset,
+        ":limit": limit,
+    });
+    if let Some(cursor) = &cursor {
+        params.extend(named_params! {
+            ":cursor_value": cursor.value,
+            ":cursor_id": cursor.clip_id,
+        });
+    }
+    let mut clips: Vec<Clip> = db
+        .prepare(&paginated_sql)?
+        .query_map(params.as_slice(), |row| Clip::try_from(row))?
+        .map(|row| row.map_err(AppError::from))
+        .try_collect()?;
+
+    let next_cursor = if clips.len() > *page_size {
+        clips.truncate(*page_size);
+        let last_clip_id = &clips[clips.len() - 1].id;
+        let mut params = named_params! { ":clip_id": last_clip_id }.to_vec();
+        if !search_join.is_empty() {
+            params.push((":search", &filter.search));
+        }
+        let value = db.query_row(
+            &format!(
+                "SELECT {sort_by} FROM clips {search_join}
+                WHERE clips.id = :clip_id"
+            ),
+            params.as_slice(),
+            |row| row.get(0),
+        )?;
+
+        Some(
+            Cursor {
+                sort_by,
+                value,
+                clip_id: last_clip_id.clone(),
+            }
+            .to_string(),
+        )
+    } else {
+        None
+    };
+
+    Ok((clips, next_cursor))
+}
+
+/// Ids of all clips which match the filters, on any page.
+pub fn select_ids(
+    db: &DbConn,
+    game_id: &GameId,
+    request: &ShowParams,
+) -> Result<Vec<String>> {
+    let filter = Filter::new(db, game_id, request)?;
+
+    db.prepare(&format!(
+        "SELECT id FROM clips {} ORDER BY id",
+        Filter::WHERE_CLAUSE
+    ))?
+    .query_map(filter.params().as_slice(), |row| row.get("id"))?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+/// Stores clips imported by hand, returning the ids of those which were not
+/// stored before.
+/// Those which were get their view count and title refreshed.
+///
+/// They are marked as imported so that they don't count as fetched, the
+/// fetch job carries on from its latest fetched clip.
+pub fn upsert_imported(
+    db: &DbConn,
+    clips: &[twitch::models::Clip],
+    imported_by: &User,
+) -> Result<Vec<String>> {
+    let mut is_stored =
+        db.prepare("SELECT EXISTS(SELECT 1 FROM clips WHERE id = ?)")?;
+    let mut stmt = twitch::models::InsertClipStatement::new(db)?;
+    let mut mark_imported = db.prepare(
+        "INSERT OR REPLACE INTO imported_clips
+            (clip_id, imported_by, imported_at)
+        VALUES
+            (:clip_id, :imported_by, :imported_at)",
+    )?;
+
+    let mut inserted = vec![];
+    for clip in clips {
+        if !is_stored.query_row([&clip.id], |row| row.get(0))? {
+            inserted.push(clip.id.clone());
+        }
+        stmt.execute(clip)?;
+        mark_imported.execute(named_params! {
+            ":clip_id": clip.id,
+            ":imported_by": imported_by.username,
+            ":imported_at": chrono::Utc::now(),
+        })?;
+    }
+
+    Ok(inserted)
+}
+
+/// Which clips match the filters of [`ShowParams`], regardless of paging.
+struct Filter<'a> {
+    game_id: &'a GameId,
+    request: &'a ShowParams,
+    /// array feature of sqlite
+    langs: Rc<Vec<rusqlite::types::Value>>,
+    skip_langs: bool,
+    review_states: Rc<Vec<rusqlite::types::Value>>,
+    tags: Rc<Vec<rusqlite::types::Value>>,
+    tag_count: usize,
+    /// Formatted like Twitch timestamps so that they compare as text
+    recorded_after: Option<String>,
+    /// As given, with dashed terms quoted
+    search: Option<String>,
+}
+
+impl<'a> Filter<'a> {
+    const WHERE_CLAUSE: &'static str = "WHERE
         game_id = :game_id
         AND (:broadcaster_name IS NULL OR broadcaster_name = :broadcaster_name)
         AND (:title_like IS NULL OR title LIKE '%' || :title_like || '%')
+        AND (:search IS NULL OR id IN (
+            SELECT clip_search.clip_id FROM clip_search_index
+            JOIN clip_search ON clip_search.id = clip_search_index.rowid
+            WHERE clip_search_index MATCH :search
+        ))
         AND (:skip_langs OR lang IN rarray(:langs))
         AND (:view_count_max IS NULL OR view_count <= :view_count_max)
         AND view_count >= :view_count_min
         AND (:min_recorded_at IS NULL OR recorded_at >= :min_recorded_at)
-        AND (:max_recorded_at IS NULL OR recorded_at <= :max_recorded_at)";
-
-    let total_count_sql = format!("SELECT COUNT(*) FROM clips {where_clause}");
-    let params = named_params! {
-        ":broadcaster_name": broadcaster_name,
-        ":game_id": game_id,
-        ":langs": langs,
-        ":max_recorded_at": max_recorded_at,
-        ":min_recorded_at": min_recorded_at,
-        ":skip_langs": langs.is_empty(),
-        ":title_like": title_like,
-        ":view_count_max": view_count_max,
-        ":view_count_min": view_count_min,
-    };
-    let total_count: i64 = db
-        .prepare(&total_count_sql)?
-        .query_row(params, |row| row.get(0))?;
+        AND (:max_recorded_at IS NULL OR recorded_at <= :max_recorded_at)
+        AND (:recorded_after IS NULL OR recorded_at >= :recorded_after)
+        AND (:moment_id IS NULL OR id IN (
+            SELECT clip_id FROM moment_clips WHERE moment_id = :moment_id
+        ))
+        AND (:show_duplicates OR :moment_id IS NOT NULL OR id NOT IN (
+            SELECT moment_clips.clip_id
+            FROM moment_clips
+            JOIN moments ON moments.id = moment_clips.moment_id
+            WHERE moment_clips.clip_id != moments.canonical_clip_id
+        ))
+        AND COALESCE(
+            (SELECT state FROM clip_reviews WHERE clip_id = clips.id),
+            'new'
+        ) IN rarray(:review_states)
+        AND (:tag_count = 0 OR id IN (
+            SELECT clip_tags.clip_id FROM clip_tags
+            JOIN tags ON tags.id = clip_tags.tag_id
+            WHERE tags.name IN rarray(:tags)
+            GROUP BY clip_tags.clip_id
+            HAVING COUNT(*) = :tag_count
+        ))";
 
-    let paginated_sql = format!(
-        "SELECT
-            broadcaster_id,
-            broadcaster_name,
-            created_at,
-            creator_name,
-            duration,
+    /// Adds the `relevance` column, bm25 is negative and lower for better
+    /// matches.
+    const SEARCH_JOIN: &'static str = "
+        JOIN (
+            SELECT
+                clip_search.clip_id AS search_clip_id,
+                -bm25(clip_search_index) AS relevance
+            FROM clip_search_index
+            JOIN clip_search ON clip_search.id = clip_search_index.rowid
+            WHERE clip_search_index MATCH :search
+        ) ON search_clip_id = clips.id";
+
+    fn new(
+        db: &DbConn,
+        game_id: &'a GameId,
+        request: &'a ShowParams,
+    ) -> Result<Self> {
+        // syntax errors of the search query would only come up half way
+        // through listing
+        let search = request.search.as_deref().map(quote_dashed_terms);
+        if let Some(search) = &search {
+            db.query_row(
+                "SELECT 1 FROM clip_search_index
+                WHERE clip_search_index MATCH :search LIMIT 1",
+                named_params! { ":search": search },
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| {
+                AppError::bad_request(format!("Cannot search '{search}': {e}"))
+            })?;
+        }
+
+        let review_states = ReviewState::parse_listed(&request.review_states)?;
+        let tags: Vec<String> = request
+            .tags
+            .iter()
+            .map(|tag| models::tag::normalize_name(tag))
+            .try_collect()?;
+        let tags = tags.into_iter().unique().collect_vec();
+        let recorded_after = request.recorded_within_days.map(|days| {
+            (chrono::Utc::now() - chrono::Duration::days(days.into()))
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string()
+        });
+
+        Ok(Self {
             game_id,
-            id,
-            lang,
-            recorded_at,
-            thumbnail_url,
-            title,
-            updated_at,
-            url,
-            view_count
-        FROM clips
-        {where_clause}
-        ORDER BY {sort_by} {sort_direction}
-        LIMIT :page_size
-        OFFSET :page_offset"
-    );
-    let params = named_params! {
-        ":broadcaster_name": broadcaster_name,
-        ":game_id": game_id,
-        ":langs": langs,
-        ":max_recorded_at": max_recorded_at,
-        ":min_recorded_at": min_recorded_at,
-        ":page_offset": page_offset,
-        ":page_size": page_size,
-        ":skip_langs": langs.is_empty(),
-        ":title_like": title_like,
-        ":view_count_max": view_count_max,
-        ":view_count_min": view_count_min,
-    };
-    let clips = db
-        .prepare(&paginated_sql)?
-        .query_map(params, |row| Clip::try_from(row))?
-        .map(|row| row.map_err(AppError::from))
-        .try_collect()?;
+            request,
+            langs: Rc::new(
+                request
+                    .langs
+                    .iter()
+                    .cloned()
+                    .map(rusqlite::types::Value::from)
+                    .collect_vec(),
+            ),
+            skip_langs: request.langs.is_empty(),
+            review_states: Rc::new(
+                review_states
+                    .into_iter()
+                    .map(|state| <&str>::from(state).to_string().into())
+                    .collect_vec(),
+            ),
+            tag_count: tags.len(),
+            tags: Rc::new(
+                tags.into_iter()
+                    .map(rusqlite::types::Value::from)
+                    .collect_vec(),
+            ),
+            recorded_after,
+            search,
+        })
+    }
+
+    fn params(&self) -> Vec<(&str, &dyn rusqlite::ToSql)> {
+        let request = self.request;
 
-    Ok((total_count as usize, clips))
+        vec![
+            (":broadcaster_name", &request.broadcaster_name),
+            (":game_id", self.game_id),
+            (":langs", &self.langs),
+            (":max_recorded_at", &request.max_recorded_at),
+            (":min_recorded_at", &request.min_recorded_at),
+            (":moment_id", &request.moment_id),
+            (":recorded_after", &self.recorded_after),
+            (":review_states", &self.review_states),
+            (":search", &self.search),
+            (":show_duplicates", &request.show_duplicates),
+            (":skip_langs", &self.skip_langs),
+            (":tag_count", &self.tag_count),
+            (":tags", &self.tags),
+            (":title_like", &request.title_like),
+            (":view_count_max", &request.view_count_max),
+            (":view_count_min", &request.view_count_min),
+        ]
+    }
+}
+
+/// FTS5 reads a dash as a syntax error, tags such as `sponsor-safe` are
+/// searched for as the phrase they are tokenized to instead.
+///
+/// E.g. `tags:sponsor-safe` becomes `tags:"sponsor-safe"`, quoted phrases,
+/// operators and prefixes are left as they are.
+fn quote_dashed_terms(search: &str) -> String {
+    let mut terms = vec![];
+    let mut term = String::new();
+    let mut is_quoted = false;
+    for c in search.chars() {
+        if c == '"' {
+            is_quoted = !is_quoted;
+        }
+        if c.is_whitespace() && !is_quoted {
+            terms.push(std::mem::take(&mut term));
+        } else {
+            term.push(c);
+        }
+    }
+    terms.push(term);
+
+    terms
+        .into_iter()
+        .filter(|term| !term.is_empty())
+        .map(|term| {
+            if term.contains('"') || !term.contains('-') {
+                return term;
+            }
+
+            let opening = term.len() - term.trim_start_matches('(').len();
+            let closing = term.len() - term.trim_end_matches([')', '*']).len();
+            let (prefix, rest) = term.split_at(opening);
+            let (word, suffix) = rest.split_at(rest.len() - closing);
+            let (column, word) = match word.split_once(':') {
+                Some((column, word)) => (format!("{column}:"), word),
+                None => (String::new(), word),
+            };
+
+            format!("{prefix}{column}\"{word}\"{suffix}")
+        })
+        .join(" ")
+}
+
+/// Where a page starts when listing with keyset pagination: after the clip
+/// which sorts by given value and has given id.
+///
+/// Shown as `{sort_by},{value},{clip_id}`, the id goes last as it's the
+/// only part which could contain a comma.
+struct Cursor {
+    /// Column the listing is sorted by
+    sort_by: &'static str,
+    value: rusqlite::types::Value,
+    clip_id: String,
+}
+
+impl Cursor {
+    fn parse(cursor: &str, sort_by: &'static str) -> Result<Self> {
+        let invalid =
+            || AppError::bad_request(format!("Invalid cursor '{cursor}'"));
+
+        let mut parts = cursor.splitn(3, ',');
+        let (Some(cursor_sort_by), Some(value), Some(clip_id)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if cursor_sort_by != sort_by {
+            return Err(AppError::bad_request(format!(
+                "Cursor '{cursor}' is for sorting by {cursor_sort_by}, \
+                not {sort_by}"
+            )));
+        }
+        let value = match sort_by {
+            "view_count" => value.parse::<i64>().map_err(|_| invalid())?.into(),
+            "relevance" => value.parse::<f64>().map_err(|_| invalid())?.into(),
+            _ => value.to_string().into(),
+        };
+
+        Ok(Self {
+            sort_by,
+            value,
+            clip_id: clip_id.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use rusqlite::types::Value;
+
+        write!(f, "{},", self.sort_by)?;
+        match &self.value {
+            Value::Integer(value) => write!(f, "{value}")?,
+            Value::Real(value) => write!(f, "{value}")?,
+            Value::Text(value) => write!(f, "{value}")?,
+            Value::Null | Value::Blob(_) => {}
+        }
+        write!(f, ",{}", self.clip_id)
+    }
 }
 
 impl TryFrom<&rusqlite::Row<'_>> for Clip {
@@ -120,12 +565,45 @@ impl TryFrom<&rusqlite::Row<'_>> for Clip {
             broadcaster_name: row.get("broadcaster_name")?,
             created_at: row.get("created_at")?,
             creator_name: row.get("creator_name")?,
+            duplicate_count: row.get("duplicate_count")?,
             duration: Duration::from_secs(row.get::<_, i64>("duration")? as u64),
             thumbnail_url: row.get("thumbnail_url")?,
             game_id: row.get("game_id")?,
+            has_preview: row.get("has_preview")?,
             id: row.get("id")?,
+            is_kept: row.get("is_kept")?,
             lang: row.get("lang")?,
+            loudness: if row.get("is_loudness_measured")? {
+                Some(Loudness {
+                    integrated_lufs: row.get("integrated_lufs")?,
+                    true_peak_dbtp: row.get("true_peak_dbtp")?,
+                })
+            } else {
+                None
+            },
+            moment_id: row.get("moment_id")?,
+            music_match_count: row.get("music_match_count")?,
             recorded_at: row.get("recorded_at")?,
+            review_state: ReviewState::try_from(
+                row.get::<_, String>("review_state")?.as_str(),
+            )
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+            reviewed_by: row.get("reviewed_by")?,
+            reviewed_at: row.get("reviewed_at")?,
+            tags: serde_json::from_str(&row.get::<_, String>("tags")?)
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        e.into(),
+                    )
+                })?,
             title: row.get("title")?,
             updated_at: row.get("updated_at")?,
             url: row.get("url")?,
@@ -139,7 +617,8 @@ mod tests {
     //! We have an SQL with some sample data in tests/assets/clips.sql.
     //! Load that data, insert it into in-memory sqlite.
 
-    use crate::models::clip::ShowSortBy;
+    use crate::models::moment::Moment;
+    use crate::models::user::{Role, User};
 
     use super::*;
 
@@ -147,7 +626,9 @@ mod tests {
     fn it_lists_clips_sorted_asc() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -169,7 +650,9 @@ mod tests {
     fn it_lists_clips_sorted_desc() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -190,7 +673,9 @@ mod tests {
     fn it_lists_clips_by_views() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -212,7 +697,9 @@ mod tests {
     fn it_lists_clips_in_views_range() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -236,7 +723,9 @@ mod tests {
     fn it_lists_clips_with_title_like() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -256,7 +745,9 @@ mod tests {
     fn it_lists_clips_with_offset() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -278,7 +769,9 @@ mod tests {
     fn it_lists_clips_based_on_langs() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -290,7 +783,9 @@ mod tests {
         assert_eq!(clips.len(), 7);
         assert_eq!(total_count, 7);
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -302,7 +797,9 @@ mod tests {
         assert_eq!(clips.len(), 1);
         assert_eq!(total_count, 1);
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -321,7 +818,9 @@ mod tests {
     fn it_lists_clips_based_on_broadcaster() -> Result<()> {
         let db = prepare_db()?;
 
-        let (total_count, clips) = list(
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
             &db,
             &GameId::from("55"),
             &ShowParams {
@@ -340,6 +839,521 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn it_collapses_clips_of_the_same_moment() -> Result<()> {
+        let mut db = prepare_db()?;
+        db::moment::replace_for_game(
+            &mut db,
+            &GameId::from("55"),
+            &[Moment {
+                broadcaster_id: "145218456".to_string(),
+                canonical_clip_id: "KnottyLaconicSparrowMau5".to_string(),
+                clip_ids: vec![
+                    "KnottyLaconicSparrowMau5".to_string(),
+                    "EsteemedShinyAsteriskBibleThump".to_string(),
+                    "MoistUnsightlyBatteryTwitchRPG".to_string(),
+                ],
+            }],
+        )?;
+
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
+            &db,
+            &GameId::from("55"),
+            &ShowParams {
+                page_size: 10,
+                broadcaster_name: Some("Davaeorn".to_string()),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(total_count, 3);
+        assert_eq!(clips[0].id, "KnottyLaconicSparrowMau5");
+        assert_eq!(clips[0].duplicate_count, 2);
+
+        let ClipPage { total_count, .. } = list(
+            &db,
+            &GameId::from("55"),
+            &ShowParams {
+                page_size: 10,
+                broadcaster_name: Some("Davaeorn".to_string()),
+                show_duplicates: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(total_count, 5);
+
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
+            &db,
+            &GameId::from("55"),
+            &ShowParams {
+                page_size: 10,
+                moment_id: clips[0].moment_id,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(total_count, 3);
+        assert_eq!(clips[0].id, "KnottyLaconicSparrowMau5");
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_hides_rejected_and_used_clips_by_default() -> Result<()> {
+        let db = prepare_db()?;
+        let jane = crate::models::user::User {
+            id: 1,
+            username: "jane".to_string(),
+            role: crate::models::user::Role::Editor,
+        };
+        let request = ShowParams {
+            page_size: 100,
+            broadcaster_name: Some("Davaeorn".to_string()),
+            show_duplicates: true,
+            ..Default::default()
+        };
+        let ids = select_ids(&db, &GameId::from("55"), &request)?;
+        assert_eq!(ids.len(), 5);
+
+        db::review::transition(&db, &ids[0], ReviewState::Rejected, &jane)?;
+        for state in [
+            ReviewState::Shortlisted,
+            ReviewState::Downloaded,
+            ReviewState::UsedInVideo,
+        ] {
+            db::review::transition(&db, &ids[1], state, &jane)?;
+        }
+        db::review::transition(&db, &ids[2], ReviewState::Shortlisted, &jane)?;
+
+        let ClipPage {
+            total_count, clips, ..
+        } = list(&db, &GameId::from("55"), &request)?;
+        assert_eq!(total_count, 3);
+        assert!(clips
+            .iter()
+            .all(|clip| clip.id != ids[0] && clip.id != ids[1]));
+
+        let ClipPage {
+            total_count, clips, ..
+        } = list(
+            &db,
+            &GameId::from("55"),
+            &ShowParams {
+                review_states: vec![
+                    "rejected".to_string(),
+                    "used-in-video".to_string(),
+                ],
+                ..request
+            },
+        )?;
+        assert_eq!(total_count, 2);
+        let used = clips.iter().find(|clip| clip.id == ids[1]).unwrap();
+        assert_eq!(used.review_state, ReviewState::UsedInVideo);
+        assert_eq!(used.reviewed_by.as_deref(), Some("jane"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_lists_clips_recorded_within_days() -> Result<()> {
+        let db = prepare_db()?;
+        // sample clips were recorded in the summer of 2023
+        db.execute(
+            "UPDATE clips SET recorded_at = :recorded_at
+            WHERE id = 'SuaveHonestWeaselJKanStyle'",
+            named_params! {
+                ":recorded_at": (chrono::Utc::now()
+                    - chrono::Duration::days(2))
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+            },
+        )?;
+
+        let list_within = |days| {
+            list(
+                &db,
+                &GameId::from("55"),
+                &ShowParams {
+                    page_size: 50,
+                    recorded_within_days: Some(days),
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!(list_within(1)?.total_count, 0);
+        let ClipPage {
+            total_count, clips, ..
+        } = list_within(7)?;
+        assert_eq!(total_count, 1);
+        assert_eq!(clips[0].id, "SuaveHonestWeaselJKanStyle");
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_searches_clips() -> Result<()> {
+        let db = prepare_db()?;
+        for (id, title) in [
+            ("SuaveHonestWeaselJKanStyle", "He failed the jump again"),
+            ("EsteemedShinyAsteriskBibleThump", "Jump fail compilation"),
+            (
+                "MoistUnsightlyBatteryTwitchRPG",
+                "So close to the world record",
+            ),
+        ] {
+            db.execute(
+                "UPDATE clips SET title = :title WHERE id = :id",
+                named_params! { ":id": id, ":title": title },
+            )?;
+        }
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        db::tag::tag(&db, "MoistUnsightlyBatteryTwitchRPG", "clutch", &jane)?;
+
+        let search = |search: &str| -> Result<Vec<String>> {
+            let ClipPage { clips, .. } = list(
+                &db,
+                &GameId::from("55"),
+                &ShowParams {
+                    page_size: 50,
+                    search: Some(search.to_string()),
+                    sort_by: ShowSortBy::Relevance,
+                    ..Default::default()
+                },
+            )?;
+            Ok(clips.into_iter().map(|clip| clip.id).collect())
+        };
+
+        // stemmed, so both fail and failed match
+        let failed = search("fails jump")?;
+        assert_eq!(failed.len(), 2);
+        // shorter title, better match
+        assert_eq!(failed[0], "EsteemedShinyAsteriskBibleThump");
+        assert_eq!(search("\"jump again\"")?, ["SuaveHonestWeaselJKanStyle"]);
+        assert_eq!(search("jump NOT again")?.len(), 1);
+        assert_eq!(search("wor* OR again")?.len(), 2);
+        assert_eq!(search("tags:clutch")?, ["MoistUnsightlyBatteryTwitchRPG"]);
+        db::tag::tag(&db, "SuaveHonestWeaselJKanStyle", "Sponsor safe", &jane)?;
+        assert_eq!(
+            search("tags:sponsor-safe")?,
+            ["SuaveHonestWeaselJKanStyle"]
+        );
+        assert_eq!(
+            search("(sponsor-safe OR clutch) NOT tags:clutch")?,
+            ["SuaveHonestWeaselJKanStyle"]
+        );
+        assert_eq!(search("sponsor-sa*")?, ["SuaveHonestWeaselJKanStyle"]);
+        db::tag::untag(&db, "SuaveHonestWeaselJKanStyle", "sponsor-safe")?;
+
+        // fetching again replaces the clip
+        db.execute(
+            "INSERT OR REPLACE INTO clips
+            SELECT * FROM clips WHERE id = 'MoistUnsightlyBatteryTwitchRPG'",
+            [],
+        )?;
+        assert_eq!(search("record")?, ["MoistUnsightlyBatteryTwitchRPG"]);
+        assert_eq!(search("clutch")?, ["MoistUnsightlyBatteryTwitchRPG"]);
+
+        db::tag::untag(&db, "MoistUnsightlyBatteryTwitchRPG", "clutch")?;
+        assert!(search("clutch")?.is_empty());
+        db.execute(
+            "DELETE FROM clips WHERE id = 'SuaveHonestWeaselJKanStyle'",
+            [],
+        )?;
+        assert_eq!(search("jump")?, ["EsteemedShinyAsteriskBibleThump"]);
+
+        let err = search("\"jump").unwrap_err();
+        assert_eq!(err.status, hyper::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_pages_clips_with_cursor() -> Result<()> {
+        let db = prepare_db()?;
+        // ties are broken by id
+        db.execute(
+            "UPDATE clips SET title = 'Clip of the week', view_count = 100
+            WHERE id IN (
+                'SuaveHonestWeaselJKanStyle',
+                'EsteemedShinyAsteriskBibleThump',
+                'MoistUnsightlyBatteryTwitchRPG'
+            )",
+            [],
+        )?;
+        let game_id = GameId::from("55");
+
+        for sort_by in [
+            ShowSortBy::RecordedAt,
+            ShowSortBy::ViewCount,
+            ShowSortBy::Relevance,
+        ] {
+            for sort_direction_asc in [false, true] {
+                let search = matches!(sort_by, ShowSortBy::Relevance)
+                    .then(|| "week OR to OR a OR the OR in OR of".to_string());
+                let all = list(
+                    &db,
+                    &game_id,
+                    &ShowParams {
+                        page_size: 50,
+                        sort_by,
+                        sort_direction_asc,
+                        search: search.clone(),
+                        ..Default::default()
+                    },
+                )?;
+                assert!(all.next_cursor.is_none());
+
+                let mut paged = vec![];
+                let mut cursor = None;
+                loop {
+                    let page = list(
+                        &db,
+                        &game_id,
+                        &ShowParams {
+                            page_size: 2,
+                            cursor: cursor.clone(),
+                            sort_by,
+                            sort_direction_asc,
+                            search: search.clone(),
+                            ..Default::default()
+                        },
+                    )?;
+                    assert_eq!(page.total_count, all.total_count);
+                    let (clips, next_cursor) = select_page(
+                        &db,
+                        &game_id,
+                        &ShowParams {
+                            page_size: 2,
+                            cursor: cursor.clone(),
+                            sort_by,
+                            sort_direction_asc,
+                            search: search.clone(),
+                            ..Default::default()
+                        },
+                    )?;
+                    assert_eq!(
+                        clips.iter().map(|clip| &clip.id).collect_vec(),
+                        page.clips.iter().map(|clip| &clip.id).collect_vec()
+                    );
+                    assert_eq!(next_cursor, page.next_cursor);
+                    paged.extend(page.clips.into_iter().map(|clip| clip.id));
+                    match page.next_cursor {
+                        Some(next_cursor) => cursor = Some(next_cursor),
+                        None => break,
+                    }
+                }
+                assert_eq!(
+                    paged,
+                    all.clips.into_iter().map(|clip| clip.id).collect_vec(),
+                    "{sort_by:?} asc {sort_direction_asc}"
+                );
+            }
+        }
+
+        // a cursor only fits the sort order it came from
+        for cursor in ["recorded_at,2023-07-11T08:05:11Z,a", "view_count,x,a"] {
+            assert!(list(
+                &db,
+                &game_id,
+                &ShowParams {
+                    page_size: 2,
+                    cursor: Some(cursor.to_string()),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+        }
+
+        Ok(())
+    }
+
+    /// Generates a large game and times listing a page deep into it, with
+    /// and without the cursor and the indexes. Run it with
+    ///
+    /// ```sh
+    /// cargo test --release -p admin -- --ignored --nocapture \
+    ///     it_lists_large_games_quickly
+    /// ```
+    ///
+    /// `BENCH_CLIP_COUNT` sets how many clips are generated, more than
+    /// 10 000 so that counting them is cut short.
+    #[test]
+    #[ignore]
+    fn it_lists_large_games_quickly() -> Result<()> {
+        let clip_count: usize = std::env::var("BENCH_CLIP_COUNT")
+            .ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(200_000);
+        // fewer clips are counted exactly and listed about as fast either
+        // way, there would be nothing to compare
+        assert!(
+            clip_count > COUNT_LIMIT,
+            "BENCH_CLIP_COUNT must be more than {COUNT_LIMIT}, is {clip_count}"
+        );
+        let db = db::open(":memory:")?;
+        let game_id = GameId::from("1");
+
+        let started_at = std::time::Instant::now();
+        db.execute(
+            "WITH RECURSIVE n(i) AS (
+                SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < :clip_count
+            )
+            INSERT INTO clips (
+                id, broadcaster_id, broadcaster_name, creator_name,
+                recorded_at, duration, title, thumbnail_url, url,
+                view_count, lang, game_id
+            )
+            SELECT
+                'Clip' || i,
+                i % 500,
+                'Broadcaster' || (i % 500),
+                'Creator' || (i % 5000),
+                strftime(
+                    '%Y-%m-%dT%H:%M:%SZ',
+                    '2023-01-01',
+                    '+' || (i * 7919 % 31536000) || ' seconds'
+                ),
+                30,
+                'Clip number ' || i,
+                '',
+                '',
+                i * 104729 % 100000,
+                'en',
+                '1'
+            FROM n",
+            named_params! { ":clip_count": clip_count },
+        )?;
+        println!("generated {clip_count} clips in {:?}", started_at.elapsed());
+
+        let deep_page =
+            |cursor: Option<String>| -> Result<(Duration, ClipPage)> {
+                let started_at = std::time::Instant::now();
+                let page = list(
+                    &db,
+                    &game_id,
+                    &ShowParams {
+                        page_size: 50,
+                        page_offset: if cursor.is_some() {
+                            0
+                        } else {
+                            clip_count / 2
+                        },
+                        cursor,
+                        ..Default::default()
+                    },
+                )?;
+                Ok((started_at.elapsed(), page))
+            };
+        let count = |limit_clause: &str| -> Result<Duration> {
+            let started_at = std::time::Instant::now();
+            let request = ShowParams::default();
+            let filter = Filter::new(&db, &game_id, &request)?;
+            db.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM (
+                        SELECT 1 FROM clips {} {limit_clause}
+                    )",
+                    Filter::WHERE_CLAUSE
+                ),
+                filter.params().as_slice(),
+                |row| row.get::<_, i64>(0),
+            )?;
+            Ok(started_at.elapsed())
+        };
+
+        // the cursor of the page right before the one halfway through
+        let cursor = list(
+            &db,
+            &game_id,
+            &ShowParams {
+                page_size: 50,
+                page_offset: clip_count / 2 - 50,
+                ..Default::default()
+            },
+        )?
+        .next_cursor;
+
+        let (by_offset, offset_page) = deep_page(None)?;
+        let (by_cursor, cursor_page) = deep_page(cursor.clone())?;
+        assert_eq!(
+            offset_page.clips.iter().map(|clip| &clip.id).collect_vec(),
+            cursor_page.clips.iter().map(|clip| &clip.id).collect_vec(),
+        );
+        assert!(!cursor_page.is_total_count_exact);
+        let exact_count = count("")?;
+        let limited_count = count(&format!("LIMIT {}", COUNT_LIMIT + 1))?;
+
+        db.execute_batch(include_str!("../../migrations/0019.down.sql"))?;
+        let (by_offset_unindexed, _) = deep_page(None)?;
+        let (by_cursor_unindexed, _) = deep_page(cursor)?;
+
+        println!("page halfway through by offset  {by_offset:>12.2?}");
+        println!("page halfway through by cursor  {by_cursor:>12.2?}");
+        println!(
+            "  without indexes, by offset    {by_offset_unindexed:>12.2?}"
+        );
+        println!(
+            "  without indexes, by cursor    {by_cursor_unindexed:>12.2?}"
+        );
+        println!("count of all matches            {exact_count:>12.2?}");
+        println!(
+            "count up to {COUNT_LIMIT}             {limited_count:>12.2?}"
+        );
+        assert!(by_cursor < by_offset);
+        assert!(by_cursor < by_cursor_unindexed);
+        assert!(limited_count < exact_count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_upserts_imported_clips() -> Result<()> {
+        let db = prepare_db()?;
+
+        let stored = select_by_id(&db, "FlirtyTenuousCasetteHoneyBadger")?;
+        let clip = |id: &str, view_count| twitch::models::Clip {
+            id: id.to_string(),
+            broadcaster_id: stored.broadcaster_id.clone(),
+            broadcaster_name: stored.broadcaster_name.clone(),
+            creator_name: stored.creator_name.clone(),
+            recorded_at: "2021-05-01T12:00:00Z".to_string(),
+            duration: Duration::from_secs(30),
+            title: "Imported".to_string(),
+            url: stored.url.clone(),
+            thumbnail_url: stored.thumbnail_url.clone(),
+            view_count,
+            lang: "en".to_string(),
+            game_id: "1234".to_string(),
+        };
+
+        let inserted = upsert_imported(
+            &db,
+            &[
+                clip(&stored.id, stored.view_count + 1),
+                clip("MissedClip", 5),
+            ],
+            &User {
+                id: 1,
+                username: "jane".to_string(),
+                role: Role::Editor,
+            },
+        )?;
+        assert_eq!(inserted, ["MissedClip"]);
+        assert_eq!(select_by_id(&db, "MissedClip")?.game_id, "1234");
+        assert_eq!(
+            select_by_id(&db, &stored.id)?.view_count,
+            stored.view_count + 1
+        );
+
+        Ok(())
+    }
+
     fn prepare_db() -> Result<DbConn> {
         pretty_env_logger::try_init_timed().ok();
 
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{named_params, OptionalExtension};
+
+use crate::models::fetch_policy::FetchPolicy;
+use crate::prelude::*;
+
+/// The default policy if none was saved for the game.
+pub fn select_by_game(
+    db: &DbConn,
+    game_id: &twitch::models::GameId,
+) -> Result<FetchPolicy> {
+    let policy = db
+        .query_row(
+            "SELECT * FROM game_fetch_policies WHERE game_id = :game_id",
+            named_params! { ":game_id": game_id },
+            |row| FetchPolicy::try_from(row),
+        )
+        .optional()?;
+
+    Ok(policy.unwrap_or_default())
+}
+
+/// Replaces the game's previously saved policy, a default policy is not
+/// stored at all.
+pub fn save(
+    db: &DbConn,
+    game_id: &twitch::models::GameId,
+    policy: &FetchPolicy,
+) -> Result<()> {
+    if policy.is_default() {
+        return delete(db, game_id);
+    }
+
+    db.execute(
+        "INSERT OR REPLACE INTO game_fetch_policies (
+            game_id, fetch_every_hours,
+            recorded_at_least_hours_ago, recorded_at_most_hours_ago,
+            min_view_count, langs, max_pages_per_run
+        ) VALUES (
+            :game_id, :fetch_every_hours,
+            :recorded_at_least_hours_ago, :recorded_at_most_hours_ago,
+            :min_view_count, :langs, :max_pages_per_run
+        )",
+        named_params! {
+            ":game_id": game_id,
+            ":fetch_every_hours": policy.fetch_every_hours,
+            ":recorded_at_least_hours_ago": policy.recorded_at_least_hours_ago,
+            ":recorded_at_most_hours_ago": policy.recorded_at_most_hours_ago,
+            ":min_view_count": policy.min_view_count,
+            ":langs": serde_json::to_string(&policy.langs)
+                .map_err(AnyError::from)?,
+            ":max_pages_per_run": policy.max_pages_per_run,
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn delete(db: &DbConn, game_id: &twitch::models::GameId) -> Result<()> {
+    db.execute(
+        "DELETE FROM game_fetch_policies WHERE game_id = :game_id",
+        named_params! { ":game_id": game_id },
+    )?;
+
+    Ok(())
+}
+
+/// When a run of the fetch job which fetched the game without errors
+/// started, if any did.
+pub fn select_last_fetched_at(
+    db: &DbConn,
+    game_id: &twitch::models::GameId,
+) -> Result<Option<DateTime<Utc>>> {
+    db.query_row(
+        "SELECT fetched_at FROM game_fetches WHERE game_id = :game_id",
+        named_params! { ":game_id": game_id },
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+pub fn set_last_fetched_at(
+    db: &DbConn,
+    game_ids: &[twitch::models::GameId],
+    fetched_at: DateTime<Utc>,
+) -> Result<()> {
+    let mut stmt = db.prepare(
+        "INSERT OR REPLACE INTO game_fetches (game_id, fetched_at)
+        VALUES (:game_id, :fetched_at)",
+    )?;
+    for game_id in game_ids {
+        stmt.execute(named_params! {
+            ":game_id": game_id,
+            ":fetched_at": fetched_at,
+        })?;
+    }
+
+    Ok(())
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for FetchPolicy {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let langs: String = row.get("langs")?;
+
+        Ok(Self {
+            fetch_every_hours: row.get("fetch_every_hours")?,
+            recorded_at_least_hours_ago: row
+                .get("recorded_at_least_hours_ago")?,
+            recorded_at_most_hours_ago: row
+                .get("recorded_at_most_hours_ago")?,
+            min_view_count: row.get("min_view_count")?,
+            langs: serde_json::from_str(&langs).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+            max_pages_per_run: row.get("max_pages_per_run")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_saves_policy_per_game() -> Result<()> {
+        let db = db::open(":memory:")?;
+        let (niche, chatting) = ("55".into(), "509658".into());
+
+        assert_eq!(select_by_game(&db, &niche)?, FetchPolicy::default());
+
+        let policy = FetchPolicy {
+            recorded_at_least_hours_ago: Some(24),
+            recorded_at_most_hours_ago: Some(72),
+            langs: vec!["de".to_string(), "en".to_string()],
+            ..Default::default()
+        };
+        save(&db, &niche, &policy)?;
+        assert_eq!(select_by_game(&db, &niche)?, policy);
+        assert_eq!(select_by_game(&db, &chatting)?, FetchPolicy::default());
+
+        let policy = FetchPolicy {
+            fetch_every_hours: Some(1),
+            min_view_count: Some(100),
+            max_pages_per_run: Some(5),
+            ..Default::default()
+        };
+        save(&db, &chatting, &policy)?;
+        assert_eq!(select_by_game(&db, &chatting)?, policy);
+
+        save(&db, &niche, &FetchPolicy::default())?;
+        let count: i64 = db.query_row(
+            "SELECT COUNT(*) FROM game_fetch_policies",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(count, 1, "default policy is not stored");
+
+        assert_eq!(select_last_fetched_at(&db, &niche)?, None);
+        let fetched_at = Utc::now();
+        set_last_fetched_at(&db, std::slice::from_ref(&niche), fetched_at)?;
+        assert_eq!(select_last_fetched_at(&db, &niche)?, Some(fetched_at));
+        assert_eq!(select_last_fetched_at(&db, &chatting)?, None);
+        db::game::delete(&db, &niche)?;
+        assert_eq!(select_last_fetched_at(&db, &niche)?, None);
+
+        Ok(())
+    }
+}
@@ -2,9 +2,11 @@ use crate::prelude::*;
 use chrono::Utc;
 use rusqlite::{named_params, ErrorCode};
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Game {
+    #[schema(value_type = String)]
     pub id: twitch::models::GameId,
     pub name: String,
     /// Note that this _might_ contain a placeholder for the size.
@@ -36,13 +38,17 @@ pub fn insert(db: &DbConn, game: &twitch::models::Game) -> Result<()> {
     }
 }
 
+/// Also forgets how its clips are fetched.
 pub fn delete(db: &DbConn, game_id: &twitch::models::GameId) -> Result<()> {
     db.execute(
         "DELETE FROM games WHERE id = :id;",
         named_params! { ":id": game_id },
-    )
-    .map(drop)
-    .map_err(From::from)
+    )?;
+    db.execute(
+        "DELETE FROM game_fetches WHERE game_id = :id;",
+        named_params! { ":id": game_id },
+    )?;
+    db::fetch_policy::delete(db, game_id)
 }
 
 pub fn select_all(db: &DbConn) -> Result<Vec<Game>> {
@@ -90,9 +96,10 @@ pub fn set_is_paused(
     }
 }
 
-pub fn select_all_active_that_have_last_clip_older_than(
+/// Games which are not paused along with when their latest fetched clip was
+/// recorded, imported clips don't count.
+pub fn select_all_active_with_latest_clip_recorded_at(
     db: &DbConn,
-    duration: chrono::Duration,
 ) -> Result<Vec<(twitch::models::GameId, Option<chrono::DateTime<Utc>>)>> {
     db.prepare(
         "
@@ -101,23 +108,20 @@ pub fn select_all_active_that_have_last_clip_older_than(
                 MAX(clips.recorded_at) as latest_clip_recorded_at
             FROM games
             LEFT JOIN clips ON clips.game_id = games.id
+                AND clips.id NOT IN (SELECT clip_id FROM imported_clips)
             WHERE games.is_paused = FALSE
-            AND games.id NOT IN (
-                SELECT game_id
-                FROM clips
-                WHERE recorded_at > :last_queried
-            )
             GROUP BY games.id
         ",
     )?
-    .query_map(
-        named_params! { ":last_queried": chrono::Utc::now() - duration },
-        |row| Ok((row.get("game_id")?, row.get("latest_clip_recorded_at")?)),
-    )?
+    .query_map((), |row| {
+        Ok((row.get("game_id")?, row.get("latest_clip_recorded_at")?))
+    })?
     .map(|res| res.map_err(AppError::from))
     .collect()
 }
 
+/// Imported clips don't count, they can be newer than what the fetch job got
+/// to.
 pub fn select_latest_clip_recorded_at(
     db: &DbConn,
     game_id: &twitch::models::GameId,
@@ -128,6 +132,7 @@ pub fn select_latest_clip_recorded_at(
                 MAX(clips.recorded_at) as latest_clip_recorded_at
             FROM clips
             WHERE clips.game_id = :game_id
+            AND clips.id NOT IN (SELECT clip_id FROM imported_clips)
         ",
     )?
     .query_row(named_params! { ":game_id": game_id }, |row| {
@@ -0,0 +1,257 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::named_params;
+
+use crate::models::job_run::{JobRun, Stats, Status, Trigger};
+use crate::prelude::*;
+
+/// Runs which ended before this are forgotten.
+const KEEP_RUNS_FOR_DAYS: i64 = 30;
+
+/// Also forgets old runs.
+pub fn insert(
+    db: &DbConn,
+    job: &str,
+    trigger: Trigger,
+    game_ids: &[String],
+    conf: &serde_json::Value,
+) -> Result<i64> {
+    db.execute(
+        "DELETE FROM job_runs
+        WHERE ended_at IS NOT NULL AND ended_at < :forget_before",
+        named_params! {
+            ":forget_before":
+                Utc::now() - chrono::Duration::days(KEEP_RUNS_FOR_DAYS),
+        },
+    )?;
+    db.execute(
+        "INSERT INTO job_runs
+            (job, triggered_by, game_ids, conf, status, started_at)
+        VALUES
+            (:job, :triggered_by, :game_ids, :conf, :status, :started_at)",
+        named_params! {
+            ":job": job,
+            ":triggered_by": <&str>::from(trigger),
+            ":game_ids": serde_json::to_string(game_ids).map_err(AnyError::from)?,
+            ":conf": conf.to_string(),
+            ":status": <&str>::from(Status::Running),
+            ":started_at": Utc::now(),
+        },
+    )?;
+
+    Ok(db.last_insert_rowid())
+}
+
+/// For jobs which find out what games to run for only once they started.
+pub fn set_game_ids(
+    db: &DbConn,
+    run_id: i64,
+    game_ids: &[String],
+) -> Result<()> {
+    db.execute(
+        "UPDATE job_runs SET game_ids = :game_ids WHERE id = :id",
+        named_params! {
+            ":id": run_id,
+            ":game_ids": serde_json::to_string(game_ids).map_err(AnyError::from)?,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Saves how far a running job got.
+pub fn update_progress(
+    db: &DbConn,
+    run_id: i64,
+    stats: &Stats,
+    errors: &[String],
+) -> Result<()> {
+    db.execute(
+        "UPDATE job_runs SET
+            pages_fetched = :pages_fetched,
+            clips_inserted = :clips_inserted,
+            clips_updated = :clips_updated,
+            retries = :retries,
+            errors = :errors
+        WHERE id = :id",
+        named_params! {
+            ":id": run_id,
+            ":pages_fetched": stats.pages_fetched,
+            ":clips_inserted": stats.clips_inserted,
+            ":clips_updated": stats.clips_updated,
+            ":retries": stats.retries,
+            ":errors": serde_json::to_string(errors).map_err(AnyError::from)?,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// The run failed if there are any errors.
+pub fn finish(
+    db: &DbConn,
+    run_id: i64,
+    stats: &Stats,
+    errors: &[String],
+) -> Result<()> {
+    update_progress(db, run_id, stats, errors)?;
+
+    let status = if errors.is_empty() {
+        Status::Succeeded
+    } else {
+        Status::Failed
+    };
+    db.execute(
+        "UPDATE job_runs SET status = :status, ended_at = :ended_at
+        WHERE id = :id",
+        named_params! {
+            ":id": run_id,
+            ":status": <&str>::from(status),
+            ":ended_at": Utc::now(),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Runs which were still running when the admin stopped never finish.
+/// Returns how many there were.
+pub fn abandon_running(db: &DbConn) -> Result<usize> {
+    db.execute(
+        "UPDATE job_runs SET
+            status = :failed,
+            ended_at = :ended_at,
+            errors = json_insert(errors, '$[#]', :error)
+        WHERE status = :running",
+        named_params! {
+            ":failed": <&str>::from(Status::Failed),
+            ":running": <&str>::from(Status::Running),
+            ":ended_at": Utc::now(),
+            ":error": "Interrupted by a restart of the admin",
+        },
+    )
+    .map_err(AppError::from)
+}
+
+/// Newest first.
+pub fn select_latest(db: &DbConn, limit: usize) -> Result<Vec<JobRun>> {
+    db.prepare(
+        "SELECT * FROM job_runs ORDER BY started_at DESC, id DESC LIMIT :limit",
+    )?
+    .query_map(named_params! { ":limit": limit }, |row| {
+        JobRun::try_from(row)
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for JobRun {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        fn conversion_failure(
+            e: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+        ) -> rusqlite::Error {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                e.into(),
+            )
+        }
+
+        let trigger: String = row.get("triggered_by")?;
+        let status: String = row.get("status")?;
+        let game_ids: String = row.get("game_ids")?;
+        let conf: String = row.get("conf")?;
+        let errors: String = row.get("errors")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            job: row.get("job")?,
+            trigger: Trigger::try_from(trigger.as_str())
+                .map_err(conversion_failure)?,
+            game_ids: serde_json::from_str(&game_ids)
+                .map_err(conversion_failure)?,
+            conf: serde_json::from_str(&conf).map_err(conversion_failure)?,
+            status: Status::try_from(status.as_str())
+                .map_err(conversion_failure)?,
+            started_at: row.get::<_, DateTime<Utc>>("started_at")?,
+            ended_at: row.get::<_, Option<DateTime<Utc>>>("ended_at")?,
+            stats: Stats {
+                pages_fetched: row.get("pages_fetched")?,
+                clips_inserted: row.get("clips_inserted")?,
+                clips_updated: row.get("clips_updated")?,
+                retries: row.get("retries")?,
+            },
+            errors: serde_json::from_str(&errors)
+                .map_err(conversion_failure)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_keeps_history_of_runs() -> Result<()> {
+        let db = db::open(":memory:")?;
+
+        let fetched = insert(
+            &db,
+            "fetch_new_game_clips",
+            Trigger::Cron,
+            &[],
+            &json!({ "recorded_at_least_hours_ago": 8 }),
+        )?;
+        set_game_ids(&db, fetched, &["1".to_string(), "2".to_string()])?;
+        let stats = Stats {
+            pages_fetched: 3,
+            clips_inserted: 250,
+            clips_updated: 50,
+            retries: 1,
+        };
+        update_progress(&db, fetched, &stats, &[])?;
+
+        let [run] = select_latest(&db, 10)?.try_into().unwrap();
+        assert_eq!(run.status, Status::Running);
+        assert_eq!(run.trigger, Trigger::Cron);
+        assert_eq!(run.game_ids, vec!["1", "2"]);
+        assert_eq!(run.conf["recorded_at_least_hours_ago"], 8);
+        assert_eq!(run.stats, stats);
+        assert!(run.ended_at.is_none());
+
+        finish(&db, fetched, &stats, &[])?;
+        let scanned = insert(
+            &db,
+            "scan_music",
+            Trigger::Manual,
+            &["1".into()],
+            &json!({}),
+        )?;
+        finish(&db, scanned, &Stats::default(), &["Worker down".into()])?;
+        let interrupted =
+            insert(&db, "detect_moments", Trigger::Manual, &[], &json!({}))?;
+        assert_eq!(abandon_running(&db)?, 1);
+
+        let runs = select_latest(&db, 10)?;
+        assert_eq!(
+            runs.iter().map(|run| (run.id, run.status)).collect_vec(),
+            vec![
+                (interrupted, Status::Failed),
+                (scanned, Status::Failed),
+                (fetched, Status::Succeeded),
+            ]
+        );
+        assert_eq!(
+            runs[0].errors,
+            vec!["Interrupted by a restart of the admin"]
+        );
+        assert_eq!(runs[1].errors, vec!["Worker down"]);
+        assert!(runs.iter().all(|run| run.ended_at.is_some()));
+        assert_eq!(select_latest(&db, 1)?.len(), 1);
+
+        Ok(())
+    }
+}
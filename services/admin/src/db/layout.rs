@@ -0,0 +1,135 @@
+use rusqlite::{named_params, OptionalExtension};
+
+use crate::models::layout::{BroadcasterLayout, CropRect, LayoutKind};
+use crate::prelude::*;
+
+/// The default layout if none was saved for the broadcaster.
+pub fn select_by_broadcaster(
+    db: &DbConn,
+    broadcaster_id: &str,
+) -> Result<BroadcasterLayout> {
+    let layout = db
+        .query_row(
+            "SELECT * FROM broadcaster_layouts
+            WHERE broadcaster_id = :broadcaster_id",
+            named_params! { ":broadcaster_id": broadcaster_id },
+            |row| BroadcasterLayout::try_from(row),
+        )
+        .optional()?;
+
+    Ok(
+        layout
+            .unwrap_or_else(|| BroadcasterLayout::default_for(broadcaster_id)),
+    )
+}
+
+/// Replaces the broadcaster's previously saved layout.
+pub fn save(db: &DbConn, layout: &BroadcasterLayout) -> Result<()> {
+    let BroadcasterLayout {
+        broadcaster_id,
+        layout,
+        facecam,
+        gameplay,
+    } = layout;
+
+    db.execute(
+        "INSERT OR REPLACE INTO broadcaster_layouts (
+            broadcaster_id, layout,
+            facecam_x, facecam_y, facecam_width, facecam_height,
+            gameplay_x, gameplay_y, gameplay_width, gameplay_height
+        ) VALUES (
+            :broadcaster_id, :layout,
+            :facecam_x, :facecam_y, :facecam_width, :facecam_height,
+            :gameplay_x, :gameplay_y, :gameplay_width, :gameplay_height
+        )",
+        named_params! {
+            ":broadcaster_id": broadcaster_id,
+            ":layout": <&str>::from(*layout),
+            ":facecam_x": facecam.x,
+            ":facecam_y": facecam.y,
+            ":facecam_width": facecam.width,
+            ":facecam_height": facecam.height,
+            ":gameplay_x": gameplay.x,
+            ":gameplay_y": gameplay.y,
+            ":gameplay_width": gameplay.width,
+            ":gameplay_height": gameplay.height,
+        },
+    )?;
+
+    Ok(())
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for BroadcasterLayout {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let layout: String = row.get("layout")?;
+        let rect = |prefix: &str| -> StdResult<CropRect, Self::Error> {
+            Ok(CropRect {
+                x: row.get(format!("{prefix}_x").as_str())?,
+                y: row.get(format!("{prefix}_y").as_str())?,
+                width: row.get(format!("{prefix}_width").as_str())?,
+                height: row.get(format!("{prefix}_height").as_str())?,
+            })
+        };
+
+        Ok(Self {
+            broadcaster_id: row.get("broadcaster_id")?,
+            layout: LayoutKind::try_from(layout.as_str()).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+            facecam: rect("facecam")?,
+            gameplay: rect("gameplay")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_saves_layout_per_broadcaster() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+
+        assert_eq!(
+            select_by_broadcaster(&db, "160504245")?,
+            BroadcasterLayout::default_for("160504245")
+        );
+
+        let mut layout = BroadcasterLayout {
+            broadcaster_id: "160504245".to_string(),
+            layout: LayoutKind::Split,
+            facecam: CropRect {
+                x: 0.7,
+                y: 0.05,
+                width: 0.25,
+                height: 0.3,
+            },
+            gameplay: CropRect {
+                x: 0.1,
+                y: 0.0,
+                width: 0.6,
+                height: 1.0,
+            },
+        };
+        save(&db, &layout)?;
+        assert_eq!(select_by_broadcaster(&db, "160504245")?, layout);
+
+        layout.layout = LayoutKind::BlurredFit;
+        save(&db, &layout)?;
+        assert_eq!(select_by_broadcaster(&db, "160504245")?, layout);
+
+        assert_eq!(
+            select_by_broadcaster(&db, "536097518")?.layout,
+            LayoutKind::CenterCrop
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use itertools::Itertools;
+use rusqlite::named_params;
+use twitch::models::GameId;
+
+use crate::models::loudness::Loudness;
+use crate::prelude::*;
+
+/// Replaces any earlier measurement of the clip.
+pub fn insert(db: &DbConn, clip_id: &str, loudness: &Loudness) -> Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO clip_loudness (
+            clip_id, integrated_lufs, true_peak_dbtp
+        ) VALUES (
+            :clip_id, :integrated_lufs, :true_peak_dbtp
+        )",
+        named_params! {
+            ":clip_id": clip_id,
+            ":integrated_lufs": loudness.integrated_lufs,
+            ":true_peak_dbtp": loudness.true_peak_dbtp,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Ids and urls of the most viewed clips of the game whose loudness wasn't
+/// measured yet, leaving out rejected and used clips.
+pub fn select_unmeasured_clips(
+    db: &DbConn,
+    game_id: &GameId,
+    limit: usize,
+) -> Result<Vec<(String, String)>> {
+    db.prepare(&format!(
+        "SELECT id, url FROM clips
+        WHERE game_id = :game_id
+        AND id NOT IN (SELECT clip_id FROM clip_loudness)
+        AND id NOT IN ({})
+        ORDER BY view_count DESC
+        LIMIT :limit",
+        db::review::SETTLED_CLIP_IDS
+    ))?
+    .query_map(
+        named_params! { ":game_id": game_id, ":limit": limit },
+        |row| Ok((row.get("id")?, row.get("url")?)),
+    )?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stores_loudness_per_clip() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let clip_id = "EntertainingCheerfulPartridgeWholeWheat";
+
+        let unmeasured = select_unmeasured_clips(&db, &"55".into(), 1000)?;
+        assert!(unmeasured.iter().any(|(id, _)| id == clip_id));
+        assert_eq!(db::clip::select_by_id(&db, clip_id)?.loudness, None);
+
+        let loudness = Loudness {
+            integrated_lufs: Some(-23.5),
+            true_peak_dbtp: Some(-4.25),
+        };
+        insert(&db, clip_id, &loudness)?;
+
+        let unmeasured = select_unmeasured_clips(&db, &"55".into(), 1000)?;
+        assert!(!unmeasured.iter().any(|(id, _)| id == clip_id));
+        assert_eq!(
+            db::clip::select_by_id(&db, clip_id)?.loudness,
+            Some(loudness)
+        );
+
+        let silent = Loudness {
+            integrated_lufs: None,
+            true_peak_dbtp: None,
+        };
+        insert(&db, clip_id, &silent)?;
+        assert_eq!(
+            db::clip::select_by_id(&db, clip_id)?.loudness,
+            Some(silent)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use itertools::Itertools;
+use rusqlite::named_params;
+use twitch::models::GameId;
+
+use crate::models::moment::{Moment, MomentCandidate};
+use crate::prelude::*;
+
+/// Clips of given game which have at least one other clip of the same
+/// broadcaster recorded within given proximity.
+///
+/// Frame hashes are loaded for clips which have already been hashed.
+pub fn select_candidates(
+    db: &DbConn,
+    game_id: &GameId,
+    proximity: chrono::Duration,
+) -> Result<Vec<MomentCandidate>> {
+    let mut candidates: Vec<MomentCandidate> = db
+        .prepare(
+            "
+            SELECT DISTINCT
+                a.id,
+                a.broadcaster_id,
+                a.recorded_at,
+                a.view_count,
+                a.url
+            FROM clips a
+            JOIN clips b
+                ON b.broadcaster_id = a.broadcaster_id
+                AND b.game_id = a.game_id
+                AND b.id != a.id
+                AND ABS(
+                    strftime('%s', a.recorded_at)
+                    - strftime('%s', b.recorded_at)
+                ) <= :proximity_secs
+            WHERE a.game_id = :game_id
+            ",
+        )?
+        .query_map(
+            named_params! {
+                ":game_id": game_id,
+                ":proximity_secs": proximity.num_seconds(),
+            },
+            |row| {
+                Ok(MomentCandidate {
+                    clip_id: row.get("id")?,
+                    broadcaster_id: row.get("broadcaster_id")?,
+                    recorded_at: row.get("recorded_at")?,
+                    view_count: row.get("view_count")?,
+                    url: row.get("url")?,
+                    frame_hashes: vec![],
+                })
+            },
+        )?
+        .try_collect()?;
+
+    for candidate in &mut candidates {
+        candidate.frame_hashes = select_frame_hashes(db, &candidate.clip_id)?;
+    }
+
+    Ok(candidates)
+}
+
+pub fn select_frame_hashes(db: &DbConn, clip_id: &str) -> Result<Vec<u64>> {
+    db.prepare_cached(
+        "SELECT hash FROM clip_frame_hashes
+        WHERE clip_id = :clip_id ORDER BY position ASC",
+    )?
+    .query_map(named_params! { ":clip_id": clip_id }, |row| {
+        // sqlite only has signed integers
+        row.get::<_, i64>("hash").map(|hash| hash as u64)
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+/// Replaces any previously stored hashes of the clip.
+pub fn insert_frame_hashes(
+    db: &mut DbConn,
+    clip_id: &str,
+    hashes: &[u64],
+) -> Result<()> {
+    let tx = db.transaction()?;
+    tx.execute(
+        "DELETE FROM clip_frame_hashes WHERE clip_id = :clip_id",
+        named_params! { ":clip_id": clip_id },
+    )?;
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO clip_frame_hashes (clip_id, position, hash)
+            VALUES (:clip_id, :position, :hash)",
+        )?;
+        for (position, hash) in hashes.iter().enumerate() {
+            stmt.execute(named_params! {
+                ":clip_id": clip_id,
+                ":position": position,
+                ":hash": *hash as i64,
+            })?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Moments are always detected for all clips of a game at once, hence we
+/// drop whatever was detected previously.
+pub fn replace_for_game(
+    db: &mut DbConn,
+    game_id: &GameId,
+    moments: &[Moment],
+) -> Result<()> {
+    let tx = db.transaction()?;
+    tx.execute(
+        "DELETE FROM moment_clips WHERE moment_id IN (
+            SELECT id FROM moments WHERE game_id = :game_id
+        )",
+        named_params! { ":game_id": game_id },
+    )?;
+    tx.execute(
+        "DELETE FROM moments WHERE game_id = :game_id",
+        named_params! { ":game_id": game_id },
+    )?;
+
+    {
+        let mut insert_moment = tx.prepare(
+            "INSERT INTO moments (game_id, broadcaster_id, canonical_clip_id)
+            VALUES (:game_id, :broadcaster_id, :canonical_clip_id)",
+        )?;
+        let mut insert_clip = tx.prepare(
+            "INSERT INTO moment_clips (moment_id, clip_id)
+            VALUES (:moment_id, :clip_id)",
+        )?;
+
+        for moment in moments {
+            insert_moment.execute(named_params! {
+                ":game_id": game_id,
+                ":broadcaster_id": moment.broadcaster_id,
+                ":canonical_clip_id": moment.canonical_clip_id,
+            })?;
+            let moment_id = tx.last_insert_rowid();
+
+            for clip_id in &moment.clip_ids {
+                insert_clip.execute(named_params! {
+                    ":moment_id": moment_id,
+                    ":clip_id": clip_id,
+                })?;
+            }
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_selects_candidates_with_hashes() -> Result<()> {
+        let mut db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        db.execute(
+            "UPDATE clips SET recorded_at = '2018-06-08T17:53:03Z'
+            WHERE id = 'SuaveHonestWeaselJKanStyle'",
+            (),
+        )?;
+        insert_frame_hashes(&mut db, "SuaveHonestWeaselJKanStyle", &[1, 2])?;
+
+        let candidates = select_candidates(
+            &db,
+            &GameId::from("55"),
+            chrono::Duration::minutes(3),
+        )?
+        .into_iter()
+        .sorted_by(|a, b| a.clip_id.cmp(&b.clip_id))
+        .collect_vec();
+
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].clip_id, "MoistUnsightlyBatteryTwitchRPG");
+        assert!(candidates[0].frame_hashes.is_empty());
+        assert_eq!(candidates[1].clip_id, "SuaveHonestWeaselJKanStyle");
+        assert_eq!(candidates[1].frame_hashes, vec![1, 2]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use itertools::Itertools;
+use rusqlite::named_params;
+use twitch::models::GameId;
+
+use crate::models::music::{MusicAction, MusicMatch};
+use crate::prelude::*;
+
+/// Replaces matches of any previous scan of the clip.
+pub fn insert_scan(
+    db: &mut DbConn,
+    clip_id: &str,
+    matches: &[worker::rpc::MusicMatch],
+) -> Result<()> {
+    let tx = db.transaction()?;
+    tx.execute(
+        "INSERT OR REPLACE INTO clip_music_scans (clip_id) VALUES (:clip_id)",
+        named_params! { ":clip_id": clip_id },
+    )?;
+    tx.execute(
+        "DELETE FROM clip_music_matches WHERE clip_id = :clip_id",
+        named_params! { ":clip_id": clip_id },
+    )?;
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO clip_music_matches (
+                clip_id, track_id, track_title, start_secs, end_secs, score
+            ) VALUES (
+                :clip_id, :track_id, :track_title, :start_secs, :end_secs, :score
+            )",
+        )?;
+        for m in matches {
+            stmt.execute(named_params! {
+                ":clip_id": clip_id,
+                ":track_id": m.track_id,
+                ":track_title": m.track_title,
+                // sub-second precision is noise
+                ":start_secs": (m.start_secs * 10.0).floor() / 10.0,
+                ":end_secs": (m.end_secs * 10.0).ceil() / 10.0,
+                ":score": m.score as i64,
+            })?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+pub fn select_matches(db: &DbConn, clip_id: &str) -> Result<Vec<MusicMatch>> {
+    db.prepare(
+        "SELECT
+            id,
+            clip_id,
+            track_id,
+            track_title,
+            start_secs,
+            end_secs,
+            score,
+            action
+        FROM clip_music_matches
+        WHERE clip_id = :clip_id
+        ORDER BY start_secs ASC",
+    )?
+    .query_map(named_params! { ":clip_id": clip_id }, |row| {
+        MusicMatch::try_from(row)
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+pub fn set_action(
+    db: &DbConn,
+    clip_id: &str,
+    match_id: i64,
+    action: MusicAction,
+) -> Result<()> {
+    let updated = db.execute(
+        "UPDATE clip_music_matches SET action = :action
+        WHERE id = :match_id AND clip_id = :clip_id",
+        named_params! {
+            ":action": <&str>::from(action),
+            ":clip_id": clip_id,
+            ":match_id": match_id,
+        },
+    )?;
+
+    if updated == 0 {
+        return Err(AppError::bad_request(format!(
+            "Clip {clip_id} has no music match {match_id}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns (id, url) of the most viewed clips of the game which haven't
+/// been scanned yet, leaving out rejected and used clips.
+pub fn select_unscanned_clips(
+    db: &DbConn,
+    game_id: &GameId,
+    limit: usize,
+) -> Result<Vec<(String, String)>> {
+    db.prepare(&format!(
+        "SELECT id, url FROM clips
+        WHERE game_id = :game_id
+        AND id NOT IN (SELECT clip_id FROM clip_music_scans)
+        AND id NOT IN ({})
+        ORDER BY view_count DESC
+        LIMIT :limit",
+        db::review::SETTLED_CLIP_IDS
+    ))?
+    .query_map(
+        named_params! { ":game_id": game_id, ":limit": limit },
+        |row| Ok((row.get("id")?, row.get("url")?)),
+    )?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for MusicMatch {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let action: String = row.get("action")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            clip_id: row.get("clip_id")?,
+            track_id: row.get("track_id")?,
+            track_title: row.get("track_title")?,
+            start_secs: row.get("start_secs")?,
+            end_secs: row.get("end_secs")?,
+            score: row.get("score")?,
+            action: MusicAction::try_from(action.as_str()).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_replaces_matches_on_rescan() -> Result<()> {
+        let mut db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let clip_id = "KnottyLaconicSparrowMau5";
+        let track = |start_secs| worker::rpc::MusicMatch {
+            track_id: 1,
+            track_title: "artist - song".to_string(),
+            start_secs,
+            end_secs: start_secs + 10.0,
+            score: 20,
+        };
+
+        insert_scan(&mut db, clip_id, &[track(1.0), track(30.04)])?;
+        let matches = select_matches(&db, clip_id)?;
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[1].start_secs, 30.0);
+        assert_eq!(matches[1].end_secs, 40.1);
+
+        set_action(&db, clip_id, matches[0].id, MusicAction::Mute)?;
+        assert_eq!(select_matches(&db, clip_id)?[0].action, MusicAction::Mute);
+
+        insert_scan(&mut db, clip_id, &[track(5.0)])?;
+        let matches = select_matches(&db, clip_id)?;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].action, MusicAction::None);
+
+        let unscanned = select_unscanned_clips(&db, &GameId::from("55"), 100)?;
+        assert_eq!(unscanned.len(), 8);
+        assert!(unscanned.iter().all(|(id, _)| id != clip_id));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use itertools::Itertools;
+use rusqlite::{named_params, OptionalExtension};
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::Duration;
+use twitch::models::GameId;
+
+use crate::models::preview::ClipPreview;
+use crate::prelude::*;
+
+/// Replaces previews which were generated earlier.
+pub fn insert(db: &DbConn, preview: &ClipPreview) -> Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO clip_previews (
+            clip_id, worker_addr, storyboard_key, waveform_key, animation_key
+        ) VALUES (
+            :clip_id, :worker_addr, :storyboard_key, :waveform_key,
+            :animation_key
+        )",
+        named_params! {
+            ":clip_id": preview.clip_id,
+            ":worker_addr": preview.worker_addr.to_string(),
+            ":storyboard_key": preview.storyboard_key,
+            ":waveform_key": preview.waveform_key,
+            ":animation_key": preview.animation_key,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Forgets previews any of whose media the worker evicted, so that they are
+/// generated again.
+pub fn delete_evicted(
+    db: &DbConn,
+    worker_addr: SocketAddr,
+    evicted_keys: &[String],
+) -> Result<usize> {
+    let keys = Rc::new(
+        evicted_keys
+            .iter()
+            .cloned()
+            .map(rusqlite::types::Value::from)
+            .collect_vec(),
+    );
+    let deleted = db.execute(
+        "DELETE FROM clip_previews
+        WHERE worker_addr = :worker_addr
+        AND (
+            storyboard_key IN rarray(:keys)
+            OR waveform_key IN rarray(:keys)
+            OR animation_key IN rarray(:keys)
+        )",
+        named_params! {
+            ":worker_addr": worker_addr.to_string(),
+            ":keys": keys,
+        },
+    )?;
+
+    Ok(deleted)
+}
+
+pub fn select_by_clip(
+    db: &DbConn,
+    clip_id: &str,
+) -> Result<Option<ClipPreview>> {
+    db.query_row(
+        "SELECT * FROM clip_previews WHERE clip_id = :clip_id",
+        named_params! { ":clip_id": clip_id },
+        |row| ClipPreview::try_from(row),
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+/// Ids, urls and durations of the most viewed clips of the game which have
+/// no previews yet, leaving out rejected and used clips.
+pub fn select_unpreviewed_clips(
+    db: &DbConn,
+    game_id: &GameId,
+    limit: usize,
+) -> Result<Vec<(String, String, Duration)>> {
+    db.prepare(&format!(
+        "SELECT id, url, duration FROM clips
+        WHERE game_id = :game_id
+        AND id NOT IN (SELECT clip_id FROM clip_previews)
+        AND id NOT IN ({})
+        ORDER BY view_count DESC
+        LIMIT :limit",
+        db::review::SETTLED_CLIP_IDS
+    ))?
+    .query_map(
+        named_params! { ":game_id": game_id, ":limit": limit },
+        |row| {
+            Ok((
+                row.get("id")?,
+                row.get("url")?,
+                Duration::from_secs(row.get::<_, i64>("duration")? as u64),
+            ))
+        },
+    )?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for ClipPreview {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let worker_addr: String = row.get("worker_addr")?;
+
+        Ok(Self {
+            clip_id: row.get("clip_id")?,
+            worker_addr: worker_addr.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?,
+            storyboard_key: row.get("storyboard_key")?,
+            waveform_key: row.get("waveform_key")?,
+            animation_key: row.get("animation_key")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stores_previews_per_clip() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let clip_id = "EntertainingCheerfulPartridgeWholeWheat";
+
+        let unpreviewed = select_unpreviewed_clips(&db, &"55".into(), 1000)?;
+        let (_, _, duration) = unpreviewed
+            .iter()
+            .find(|(id, _, _)| id == clip_id)
+            .expect("Clip has no previews");
+        assert_eq!(duration.as_secs(), 31);
+        assert_eq!(select_by_clip(&db, clip_id)?, None);
+        assert!(!db::clip::select_by_id(&db, clip_id)?.has_preview);
+
+        let preview = ClipPreview {
+            clip_id: clip_id.to_string(),
+            worker_addr: "127.0.0.1:50051".parse().unwrap(),
+            storyboard_key: format!("previews/{clip_id}/storyboard.jpg"),
+            waveform_key: None,
+            animation_key: format!("previews/{clip_id}/animation.webp"),
+        };
+        insert(&db, &preview)?;
+
+        let unpreviewed = select_unpreviewed_clips(&db, &"55".into(), 1000)?;
+        assert!(!unpreviewed.iter().any(|(id, _, _)| id == clip_id));
+        assert_eq!(select_by_clip(&db, clip_id)?, Some(preview.clone()));
+        assert!(db::clip::select_by_id(&db, clip_id)?.has_preview);
+
+        let regenerated = ClipPreview {
+            worker_addr: "127.0.0.1:50052".parse().unwrap(),
+            waveform_key: Some(format!("previews/{clip_id}/waveform.png")),
+            ..preview
+        };
+        insert(&db, &regenerated)?;
+        assert_eq!(select_by_clip(&db, clip_id)?, Some(regenerated.clone()));
+
+        let evicted = vec![regenerated.animation_key.clone()];
+        assert_eq!(
+            delete_evicted(&db, "127.0.0.1:50051".parse().unwrap(), &evicted)?,
+            0,
+            "media of the same key on another worker is still there"
+        );
+        assert_eq!(delete_evicted(&db, regenerated.worker_addr, &evicted)?, 1);
+        assert_eq!(select_by_clip(&db, clip_id)?, None);
+        let unpreviewed = select_unpreviewed_clips(&db, &"55".into(), 1000)?;
+        assert!(unpreviewed.iter().any(|(id, _, _)| id == clip_id));
+
+        Ok(())
+    }
+}
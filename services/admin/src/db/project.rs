@@ -0,0 +1,714 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::{named_params, ErrorCode, OptionalExtension};
+use std::collections::HashMap;
+
+use crate::models::clip::Clip;
+use crate::models::project::{
+    Entry, EntryDetails, Project, ProjectDetails, ProjectStatus,
+};
+use crate::models::user::User;
+use crate::prelude::*;
+
+/// Selects all columns needed to construct [`Project`].
+const SELECT_PROJECTS: &str = "
+    SELECT
+        *,
+        (
+            SELECT COALESCE(SUM(
+                COALESCE(trim_out_secs, clips.duration)
+                - COALESCE(trim_in_secs, 0)
+            ), 0)
+            FROM project_entries
+            JOIN clips ON clips.id = project_entries.clip_id
+            WHERE project_id = projects.id
+        ) AS total_duration_secs,
+        (
+            SELECT COUNT(*) FROM project_entries
+            WHERE project_id = projects.id
+        ) AS entry_count,
+        (
+            SELECT video_id FROM project_uploads
+            WHERE project_id = projects.id
+        ) AS youtube_video_id
+    FROM projects";
+
+/// Ids of clips in projects which are not published yet, the worker must
+/// keep their media until the video is done.
+pub const UNPUBLISHED_CLIP_IDS: &str = "
+    SELECT clip_id FROM project_entries
+    WHERE project_id IN (
+        SELECT id FROM projects WHERE status != 'published'
+    )";
+
+pub fn insert(
+    db: &DbConn,
+    details: &ProjectDetails,
+    created_by: &User,
+) -> Result<i64> {
+    let now = Utc::now();
+    db.execute(
+        "INSERT INTO projects (
+            title, target_duration_secs, status, notes,
+            created_by, created_at, updated_at
+        ) VALUES (
+            :title, :target_duration_secs, :status, :notes,
+            :created_by, :created_at, :updated_at
+        )",
+        named_params! {
+            ":title": details.title,
+            ":target_duration_secs": details.target_duration_secs,
+            ":status": <&str>::from(ProjectStatus::Draft),
+            ":notes": details.notes,
+            ":created_by": created_by.username,
+            ":created_at": now,
+            ":updated_at": now,
+        },
+    )?;
+
+    Ok(db.last_insert_rowid())
+}
+
+/// Unpublished projects first, most recently changed first.
+pub fn select_all(db: &DbConn) -> Result<Vec<Project>> {
+    db.prepare(&format!(
+        "{SELECT_PROJECTS}
+        ORDER BY status = 'published' ASC, updated_at DESC"
+    ))?
+    .query_map((), |row| Project::try_from(row))?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+/// Projects clips can still be added to, most recently changed first.
+pub fn select_all_unpublished(db: &DbConn) -> Result<Vec<Project>> {
+    db.prepare(&format!(
+        "{SELECT_PROJECTS} WHERE status != 'published'
+        ORDER BY updated_at DESC"
+    ))?
+    .query_map((), |row| Project::try_from(row))?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+pub fn select_by_id(db: &DbConn, project_id: i64) -> Result<Project> {
+    db.prepare(&format!("{SELECT_PROJECTS} WHERE id = :id"))?
+        .query_row(named_params! { ":id": project_id }, |row| {
+            Project::try_from(row)
+        })
+        .optional()?
+        .ok_or_else(|| {
+            AppError::not_found(format!("Project {project_id} not found"))
+        })
+}
+
+/// Fails if the project is published.
+pub fn update(
+    db: &DbConn,
+    project_id: i64,
+    details: &ProjectDetails,
+) -> Result<()> {
+    select_by_id(db, project_id)?.ensure_is_editable()?;
+
+    db.execute(
+        "UPDATE projects SET
+            title = :title,
+            target_duration_secs = :target_duration_secs,
+            notes = :notes,
+            updated_at = :updated_at
+        WHERE id = :id",
+        named_params! {
+            ":id": project_id,
+            ":title": details.title,
+            ":target_duration_secs": details.target_duration_secs,
+            ":notes": details.notes,
+            ":updated_at": Utc::now(),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Fails if the project cannot become given status.
+/// Publishing moves all of the clips in the project to used in video.
+pub fn set_status(
+    db: &DbConn,
+    project_id: i64,
+    to: ProjectStatus,
+    user: &User,
+) -> Result<()> {
+    let project = select_by_id(db, project_id)?;
+    if !project.status.can_become(to) {
+        return Err(AppError::bad_request(format!(
+            "Project {project_id} is {} and cannot become {}",
+            <&str>::from(project.status),
+            <&str>::from(to)
+        )));
+    }
+
+    let now = Utc::now();
+    let tx = db.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE projects SET
+            status = :status,
+            updated_at = :updated_at,
+            published_at = :published_at
+        WHERE id = :id",
+        named_params! {
+            ":id": project_id,
+            ":status": <&str>::from(to),
+            ":updated_at": now,
+            ":published_at": (to == ProjectStatus::Published).then_some(now),
+        },
+    )?;
+    if to == ProjectStatus::Published {
+        let clip_ids = select_clip_ids(&tx, project_id)?;
+        db::review::use_all_in_video(&tx, &clip_ids, user)?;
+    }
+    tx.commit()?;
+
+    info!(
+        "{} moved project {project_id} to {}",
+        user.username,
+        <&str>::from(to)
+    );
+
+    Ok(())
+}
+
+pub fn delete(db: &DbConn, project_id: i64) -> Result<()> {
+    let tx = db.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM project_entries WHERE project_id = :project_id",
+        named_params! { ":project_id": project_id },
+    )?;
+    tx.execute(
+        "DELETE FROM project_uploads WHERE project_id = :project_id",
+        named_params! { ":project_id": project_id },
+    )?;
+    tx.execute(
+        "DELETE FROM project_uploads_in_flight WHERE project_id = :project_id",
+        named_params! { ":project_id": project_id },
+    )?;
+    tx.execute(
+        "DELETE FROM projects WHERE id = :project_id",
+        named_params! { ":project_id": project_id },
+    )?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Marks the project as being uploaded, fails if it already is.
+pub fn start_upload(db: &DbConn, project_id: i64, user: &User) -> Result<()> {
+    if is_uploading(db, project_id)? {
+        return Err(AppError::bad_request(format!(
+            "Project {project_id} is already being uploaded"
+        )));
+    }
+
+    db.execute(
+        "INSERT INTO project_uploads_in_flight
+            (project_id, started_by, started_at)
+        VALUES
+            (:project_id, :started_by, :started_at)",
+        named_params! {
+            ":project_id": project_id,
+            ":started_by": user.username,
+            ":started_at": Utc::now(),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Whether it worked or not, the project can be uploaded again.
+pub fn finish_upload(db: &DbConn, project_id: i64) -> Result<()> {
+    db.execute(
+        "DELETE FROM project_uploads_in_flight WHERE project_id = :project_id",
+        named_params! { ":project_id": project_id },
+    )?;
+
+    Ok(())
+}
+
+pub fn is_uploading(db: &DbConn, project_id: i64) -> Result<bool> {
+    let is_uploading = db.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM project_uploads_in_flight
+            WHERE project_id = :project_id
+        )",
+        named_params! { ":project_id": project_id },
+        |row| row.get(0),
+    )?;
+
+    Ok(is_uploading)
+}
+
+/// Uploads don't survive a restart, they must be started again.
+/// Returns how many were interrupted.
+pub fn abandon_uploads(db: &DbConn) -> Result<usize> {
+    Ok(db.execute("DELETE FROM project_uploads_in_flight", [])?)
+}
+
+/// Records the video the project was uploaded as.
+pub fn insert_upload(
+    db: &DbConn,
+    project_id: i64,
+    video_id: &str,
+    user: &User,
+) -> Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO project_uploads
+            (project_id, video_id, uploaded_by, uploaded_at)
+        VALUES
+            (:project_id, :video_id, :uploaded_by, :uploaded_at)",
+        named_params! {
+            ":project_id": project_id,
+            ":video_id": video_id,
+            ":uploaded_by": user.username,
+            ":uploaded_at": Utc::now(),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// In the order they play, entries whose clip was deleted with its game are
+/// left out.
+pub fn select_entries(db: &DbConn, project_id: i64) -> Result<Vec<Entry>> {
+    let mut clips: HashMap<String, Clip> = db
+        .prepare(&format!(
+            "{} WHERE id IN (
+                SELECT clip_id FROM project_entries
+                WHERE project_id = :project_id
+            )",
+            db::clip::SELECT_CLIPS
+        ))?
+        .query_map(named_params! { ":project_id": project_id }, |row| {
+            Clip::try_from(row)
+        })?
+        .map_ok(|clip| (clip.id.clone(), clip))
+        .try_collect()?;
+
+    let rows: Vec<(i64, String, usize, EntryDetails)> = db
+        .prepare(
+            "SELECT * FROM project_entries
+            WHERE project_id = :project_id
+            AND clip_id IN (SELECT id FROM clips)
+            ORDER BY position ASC",
+        )?
+        .query_map(named_params! { ":project_id": project_id }, |row| {
+            Ok((
+                row.get("id")?,
+                row.get("clip_id")?,
+                row.get("position")?,
+                EntryDetails {
+                    trim_in_secs: row.get("trim_in_secs")?,
+                    trim_out_secs: row.get("trim_out_secs")?,
+                    notes: row.get("notes")?,
+                },
+            ))
+        })?
+        .try_collect()?;
+
+    rows.into_iter()
+        .map(|(id, clip_id, position, details)| {
+            let clip = clips.remove(&clip_id).ok_or_else(|| {
+                AppError::internal(format!("Clip {clip_id} not found"))
+            })?;
+
+            Ok(Entry {
+                id,
+                position,
+                duration_secs: details.duration_secs(&clip),
+                trim_in_secs: details.trim_in_secs,
+                trim_out_secs: details.trim_out_secs,
+                notes: details.notes,
+                clip,
+            })
+        })
+        .collect()
+}
+
+/// Appends the clip to the project, fails if it's there already.
+pub fn insert_entry(
+    db: &DbConn,
+    project_id: i64,
+    clip_id: &str,
+    details: EntryDetails,
+) -> Result<i64> {
+    select_by_id(db, project_id)?.ensure_is_editable()?;
+    let clip = db::clip::select_by_id(db, clip_id)?;
+    let details = details.validate(&clip)?;
+
+    let res = db.execute(
+        "INSERT INTO project_entries (
+            project_id, clip_id, position,
+            trim_in_secs, trim_out_secs, notes
+        ) VALUES (
+            :project_id, :clip_id,
+            (
+                SELECT COUNT(*) FROM project_entries
+                WHERE project_id = :project_id
+            ),
+            :trim_in_secs, :trim_out_secs, :notes
+        )",
+        named_params! {
+            ":project_id": project_id,
+            ":clip_id": clip_id,
+            ":trim_in_secs": details.trim_in_secs,
+            ":trim_out_secs": details.trim_out_secs,
+            ":notes": details.notes,
+        },
+    );
+
+    match res {
+        Ok(_) => {
+            let entry_id = db.last_insert_rowid();
+            touch(db, project_id)?;
+            Ok(entry_id)
+        }
+        Err(e)
+            if e.sqlite_error_code()
+                == Some(ErrorCode::ConstraintViolation) =>
+        {
+            Err(AppError::already_exists(format!(
+                "Clip {clip_id} is already in project {project_id}"
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn update_entry(
+    db: &DbConn,
+    project_id: i64,
+    entry_id: i64,
+    details: EntryDetails,
+) -> Result<()> {
+    select_by_id(db, project_id)?.ensure_is_editable()?;
+    let clip_id = select_entry_clip_id(db, project_id, entry_id)?;
+    let clip = db::clip::select_by_id(db, &clip_id)?;
+    let details = details.validate(&clip)?;
+
+    db.execute(
+        "UPDATE project_entries SET
+            trim_in_secs = :trim_in_secs,
+            trim_out_secs = :trim_out_secs,
+            notes = :notes
+        WHERE id = :id",
+        named_params! {
+            ":id": entry_id,
+            ":trim_in_secs": details.trim_in_secs,
+            ":trim_out_secs": details.trim_out_secs,
+            ":notes": details.notes,
+        },
+    )?;
+    touch(db, project_id)?;
+
+    Ok(())
+}
+
+/// Positions past the end move the entry last, the entries in between shift
+/// by one.
+pub fn move_entry(
+    db: &DbConn,
+    project_id: i64,
+    entry_id: i64,
+    to_position: usize,
+) -> Result<()> {
+    select_by_id(db, project_id)?.ensure_is_editable()?;
+    select_entry_clip_id(db, project_id, entry_id)?;
+
+    let mut entry_ids = select_entry_ids(db, project_id)?;
+    entry_ids.retain(|id| *id != entry_id);
+    entry_ids.insert(to_position.min(entry_ids.len()), entry_id);
+
+    let tx = db.unchecked_transaction()?;
+    renumber(&tx, &entry_ids)?;
+    touch(&tx, project_id)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// The entries after it move up.
+pub fn delete_entry(db: &DbConn, project_id: i64, entry_id: i64) -> Result<()> {
+    select_by_id(db, project_id)?.ensure_is_editable()?;
+    select_entry_clip_id(db, project_id, entry_id)?;
+
+    let tx = db.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM project_entries WHERE id = :id",
+        named_params! { ":id": entry_id },
+    )?;
+    renumber(&tx, &select_entry_ids(&tx, project_id)?)?;
+    touch(&tx, project_id)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// In the order they play.
+pub fn select_clip_ids(db: &DbConn, project_id: i64) -> Result<Vec<String>> {
+    db.prepare(
+        "SELECT clip_id FROM project_entries
+        WHERE project_id = :project_id ORDER BY position ASC",
+    )?
+    .query_map(named_params! { ":project_id": project_id }, |row| {
+        row.get("clip_id")
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+fn select_entry_ids(db: &DbConn, project_id: i64) -> Result<Vec<i64>> {
+    db.prepare(
+        "SELECT id FROM project_entries
+        WHERE project_id = :project_id ORDER BY position ASC",
+    )?
+    .query_map(named_params! { ":project_id": project_id }, |row| {
+        row.get("id")
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+fn select_entry_clip_id(
+    db: &DbConn,
+    project_id: i64,
+    entry_id: i64,
+) -> Result<String> {
+    db.query_row(
+        "SELECT clip_id FROM project_entries
+        WHERE id = :id AND project_id = :project_id",
+        named_params! { ":id": entry_id, ":project_id": project_id },
+        |row| row.get("clip_id"),
+    )
+    .optional()?
+    .ok_or_else(|| {
+        AppError::not_found(format!(
+            "Entry {entry_id} not found in project {project_id}"
+        ))
+    })
+}
+
+/// Positions follow the order of the ids.
+fn renumber(db: &DbConn, entry_ids: &[i64]) -> Result<()> {
+    let mut stmt = db.prepare(
+        "UPDATE project_entries SET position = :position WHERE id = :id",
+    )?;
+    for (position, entry_id) in entry_ids.iter().enumerate() {
+        stmt.execute(named_params! { ":position": position, ":id": entry_id })?;
+    }
+
+    Ok(())
+}
+
+fn touch(db: &DbConn, project_id: i64) -> Result<()> {
+    db.execute(
+        "UPDATE projects SET updated_at = :updated_at WHERE id = :id",
+        named_params! { ":id": project_id, ":updated_at": Utc::now() },
+    )?;
+
+    Ok(())
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for Project {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let status: String = row.get("status")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            title: row.get("title")?,
+            target_duration_secs: row.get("target_duration_secs")?,
+            status: ProjectStatus::try_from(status.as_str()).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+            notes: row.get("notes")?,
+            created_by: row.get("created_by")?,
+            created_at: row.get::<_, DateTime<Utc>>("created_at")?,
+            updated_at: row.get::<_, DateTime<Utc>>("updated_at")?,
+            published_at: row
+                .get::<_, Option<DateTime<Utc>>>("published_at")?,
+            youtube_video_id: row.get("youtube_video_id")?,
+            total_duration_secs: row.get("total_duration_secs")?,
+            entry_count: row.get("entry_count")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project::{credits, credits_text};
+    use crate::models::review::ReviewState;
+    use crate::models::user::Role;
+
+    #[test]
+    fn it_assembles_project() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        let details = ProjectDetails {
+            title: "Best of week 42".to_string(),
+            target_duration_secs: 120,
+            notes: String::new(),
+        };
+
+        let project_id = insert(&db, &details, &jane)?;
+        let project = select_by_id(&db, project_id)?;
+        assert_eq!(project.status, ProjectStatus::Draft);
+        assert_eq!(project.total_duration_secs, 0.0);
+
+        // 31s by 39daph, 25s and 60s by Davaeorn
+        let partridge = insert_entry(
+            &db,
+            project_id,
+            "EntertainingCheerfulPartridgeWholeWheat",
+            EntryDetails::default(),
+        )?;
+        let weasel = insert_entry(
+            &db,
+            project_id,
+            "SuaveHonestWeaselJKanStyle",
+            EntryDetails {
+                trim_in_secs: Some(5.0),
+                ..Default::default()
+            },
+        )?;
+        let asterisk = insert_entry(
+            &db,
+            project_id,
+            "EsteemedShinyAsteriskBibleThump",
+            EntryDetails {
+                trim_in_secs: Some(10.0),
+                trim_out_secs: Some(40.0),
+                notes: "cut before the ad".to_string(),
+            },
+        )?;
+        assert!(insert_entry(
+            &db,
+            project_id,
+            "SuaveHonestWeaselJKanStyle",
+            EntryDetails::default()
+        )
+        .is_err());
+        assert!(
+            update_entry(
+                &db,
+                project_id,
+                weasel,
+                EntryDetails {
+                    trim_out_secs: Some(26.0),
+                    ..Default::default()
+                }
+            )
+            .is_err(),
+            "trim past the end of the clip"
+        );
+
+        let project = select_by_id(&db, project_id)?;
+        assert_eq!(project.entry_count, 3);
+        assert_eq!(project.total_duration_secs, 31.0 + 20.0 + 30.0);
+        assert_eq!(project.overrun_secs(), -39.0);
+
+        move_entry(&db, project_id, asterisk, 0)?;
+        move_entry(&db, project_id, partridge, 10)?;
+        let entries = select_entries(&db, project_id)?;
+        assert_eq!(
+            entries.iter().map(|e| (e.id, e.position)).collect_vec(),
+            vec![(asterisk, 0), (weasel, 1), (partridge, 2)]
+        );
+        assert_eq!(entries[0].duration_secs, 30.0);
+        assert_eq!(entries[0].notes, "cut before the ad");
+
+        let credits = credits(&entries);
+        assert_eq!(
+            credits
+                .iter()
+                .map(|c| (c.broadcaster_name.as_str(), c.clip_count))
+                .collect_vec(),
+            vec![("Davaeorn", 2), ("39daph", 1)]
+        );
+        assert_eq!(
+            credits_text(&entries),
+            "Davaeorn https://www.twitch.tv/davaeorn\n\
+            39daph https://www.twitch.tv/39daph"
+        );
+
+        delete_entry(&db, project_id, weasel)?;
+        assert_eq!(
+            select_clip_ids(&db, project_id)?,
+            vec![
+                "EsteemedShinyAsteriskBibleThump",
+                "EntertainingCheerfulPartridgeWholeWheat"
+            ]
+        );
+
+        assert!(
+            set_status(&db, project_id, ProjectStatus::Published, &jane)
+                .is_err(),
+            "draft must be edited first"
+        );
+        db::review::transition(
+            &db,
+            "EsteemedShinyAsteriskBibleThump",
+            ReviewState::Shortlisted,
+            &jane,
+        )?;
+        db::review::transition(
+            &db,
+            "EsteemedShinyAsteriskBibleThump",
+            ReviewState::Downloaded,
+            &jane,
+        )?;
+        set_status(&db, project_id, ProjectStatus::Editing, &jane)?;
+        set_status(&db, project_id, ProjectStatus::Ready, &jane)?;
+        start_upload(&db, project_id, &jane)?;
+        assert!(is_uploading(&db, project_id)?);
+        assert!(
+            start_upload(&db, project_id, &jane).is_err(),
+            "project must not be uploaded twice at once"
+        );
+        finish_upload(&db, project_id)?;
+        start_upload(&db, project_id, &jane)?;
+        assert_eq!(abandon_uploads(&db)?, 1);
+        assert!(!is_uploading(&db, project_id)?);
+        set_status(&db, project_id, ProjectStatus::Published, &jane)?;
+
+        let project = select_by_id(&db, project_id)?;
+        assert!(project.published_at.is_some());
+        assert!(update(&db, project_id, &details).is_err());
+        assert!(move_entry(&db, project_id, partridge, 0).is_err());
+        assert_eq!(
+            db::review::select_state(&db, "EsteemedShinyAsteriskBibleThump")?,
+            ReviewState::UsedInVideo
+        );
+        assert_eq!(
+            db::review::select_state(
+                &db,
+                "EntertainingCheerfulPartridgeWholeWheat"
+            )?,
+            ReviewState::UsedInVideo,
+            "clip which was not downloaded is used all the same"
+        );
+
+        delete(&db, project_id)?;
+        assert!(select_by_id(&db, project_id).is_err());
+        assert!(select_entries(&db, project_id)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use rusqlite::named_params;
+
+use crate::models::clip::Clip;
+use crate::prelude::*;
+
+/// Marking a clip which is already kept is a no-op.
+pub fn keep(db: &DbConn, clip_id: &str) -> Result<()> {
+    db.execute(
+        "INSERT OR IGNORE INTO kept_clips (clip_id) VALUES (:clip_id)",
+        named_params! { ":clip_id": clip_id },
+    )?;
+
+    Ok(())
+}
+
+/// Lets the worker garbage collect the clip's media again.
+pub fn release(db: &DbConn, clip_id: &str) -> Result<()> {
+    db.execute(
+        "DELETE FROM kept_clips WHERE clip_id = :clip_id",
+        named_params! { ":clip_id": clip_id },
+    )?;
+
+    Ok(())
+}
+
+/// Ids of all clips whose media the worker must not delete, that is those
+/// marked as kept and those in projects which are not published yet.
+pub fn select_kept_clip_ids(db: &DbConn) -> Result<Vec<String>> {
+    db.prepare(&format!(
+        "SELECT clip_id FROM kept_clips
+        UNION {}
+        ORDER BY clip_id ASC",
+        db::project::UNPUBLISHED_CLIP_IDS
+    ))?
+    .query_map((), |row| row.get("clip_id"))?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+/// Clips marked as kept, most recently marked first.
+pub fn select_kept_clips(db: &DbConn) -> Result<Vec<Clip>> {
+    db.prepare(&format!(
+        "{} WHERE id IN (SELECT clip_id FROM kept_clips)
+        ORDER BY (
+            SELECT created_at FROM kept_clips WHERE clip_id = clips.id
+        ) DESC",
+        db::clip::SELECT_CLIPS
+    ))?
+    .query_map((), |row| Clip::try_from(row))?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project::ProjectDetails;
+    use crate::models::user::{Role, User};
+
+    #[test]
+    fn it_keeps_and_releases_clips() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+
+        keep(&db, "SuaveHonestWeaselJKanStyle")?;
+        keep(&db, "SuaveHonestWeaselJKanStyle")?;
+        keep(&db, "MoistUnsightlyBatteryTwitchRPG")?;
+        release(&db, "MoistUnsightlyBatteryTwitchRPG")?;
+
+        assert_eq!(
+            select_kept_clip_ids(&db)?,
+            vec!["SuaveHonestWeaselJKanStyle".to_string()]
+        );
+
+        let clips = select_kept_clips(&db)?;
+        assert_eq!(clips.len(), 1);
+        assert!(clips[0].is_kept);
+        assert!(
+            !db::clip::select_by_id(&db, "MoistUnsightlyBatteryTwitchRPG")?
+                .is_kept
+        );
+
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        let details = ProjectDetails {
+            title: "Best of".to_string(),
+            target_duration_secs: 600,
+            notes: String::new(),
+        };
+        let project_id = db::project::insert(&db, &details, &jane)?;
+        db::project::insert_entry(
+            &db,
+            project_id,
+            "MoistUnsightlyBatteryTwitchRPG",
+            Default::default(),
+        )?;
+        assert_eq!(
+            select_kept_clip_ids(&db)?,
+            vec![
+                "MoistUnsightlyBatteryTwitchRPG".to_string(),
+                "SuaveHonestWeaselJKanStyle".to_string()
+            ],
+            "clips of unpublished projects are kept"
+        );
+
+        Ok(())
+    }
+}
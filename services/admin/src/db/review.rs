@@ -0,0 +1,260 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::{named_params, OptionalExtension};
+
+use crate::models::review::{ReviewState, Transition};
+use crate::models::user::User;
+use crate::prelude::*;
+
+/// Ids of clips which were rejected or used, they are never listed nor
+/// processed by jobs again unless asked for.
+pub const SETTLED_CLIP_IDS: &str = "
+    SELECT clip_id FROM clip_reviews
+    WHERE state IN ('rejected', 'used_in_video')";
+
+pub fn select_state(db: &DbConn, clip_id: &str) -> Result<ReviewState> {
+    let state: Option<String> = db
+        .query_row(
+            "SELECT state FROM clip_reviews WHERE clip_id = :clip_id",
+            named_params! { ":clip_id": clip_id },
+            |row| row.get("state"),
+        )
+        .optional()?;
+
+    match state {
+        Some(state) => {
+            ReviewState::try_from(state.as_str()).map_err(AppError::internal)
+        }
+        None => Ok(ReviewState::New),
+    }
+}
+
+/// Fails if the clip cannot become given state.
+pub fn transition(
+    db: &DbConn,
+    clip_id: &str,
+    to: ReviewState,
+    reviewer: &User,
+) -> Result<Transition> {
+    let from = select_state(db, clip_id)?;
+    if !from.can_become(to) {
+        return Err(AppError::bad_request(format!(
+            "Clip {clip_id} is {} and cannot become {}",
+            <&str>::from(from),
+            <&str>::from(to)
+        )));
+    }
+
+    let [transition] =
+        transition_all(db, &[clip_id.to_string()], to, reviewer)?
+            .try_into()
+            .map_err(|_| AppError::internal("Clip was not moved"))?;
+
+    Ok(transition)
+}
+
+/// Moves those of the clips which can become given state, the rest are left
+/// as they are.
+pub fn transition_all(
+    db: &DbConn,
+    clip_ids: &[String],
+    to: ReviewState,
+    reviewer: &User,
+) -> Result<Vec<Transition>> {
+    let tx = db.unchecked_transaction()?;
+    let mut transitions = vec![];
+    for clip_id in clip_ids {
+        let from = select_state(&tx, clip_id)?;
+        if !from.can_become(to) {
+            continue;
+        }
+
+        transitions.push(insert_transition(&tx, clip_id, from, to, reviewer)?);
+    }
+    tx.commit()?;
+
+    info!(
+        "{} moved {} of {} clips to {}",
+        reviewer.username,
+        transitions.len(),
+        clip_ids.len(),
+        <&str>::from(to)
+    );
+
+    Ok(transitions)
+}
+
+/// Moves the clips to used in video whatever state they are in, they made it
+/// into a published video.
+/// Runs in the caller's transaction, if any.
+pub fn use_all_in_video(
+    db: &DbConn,
+    clip_ids: &[String],
+    reviewer: &User,
+) -> Result<Vec<Transition>> {
+    let to = ReviewState::UsedInVideo;
+    let mut transitions = vec![];
+    for clip_id in clip_ids {
+        let from = select_state(db, clip_id)?;
+        if from == to {
+            continue;
+        }
+
+        transitions.push(insert_transition(db, clip_id, from, to, reviewer)?);
+    }
+
+    info!(
+        "{} used {} of {} clips in video",
+        reviewer.username,
+        transitions.len(),
+        clip_ids.len()
+    );
+
+    Ok(transitions)
+}
+
+fn insert_transition(
+    db: &DbConn,
+    clip_id: &str,
+    from: ReviewState,
+    to: ReviewState,
+    reviewer: &User,
+) -> Result<Transition> {
+    let transition = Transition {
+        clip_id: clip_id.to_string(),
+        from_state: from,
+        to_state: to,
+        reviewed_by: reviewer.username.clone(),
+        reviewed_at: Utc::now(),
+    };
+    db.execute(
+        "INSERT OR REPLACE INTO clip_reviews
+            (clip_id, state, reviewed_by, reviewed_at)
+        VALUES
+            (:clip_id, :state, :reviewed_by, :reviewed_at)",
+        named_params! {
+            ":clip_id": transition.clip_id,
+            ":state": <&str>::from(to),
+            ":reviewed_by": transition.reviewed_by,
+            ":reviewed_at": transition.reviewed_at,
+        },
+    )?;
+    db.execute(
+        "INSERT INTO clip_review_transitions
+            (clip_id, from_state, to_state, reviewed_by, reviewed_at)
+        VALUES
+            (:clip_id, :from_state, :to_state, :reviewed_by, :reviewed_at)",
+        named_params! {
+            ":clip_id": transition.clip_id,
+            ":from_state": <&str>::from(from),
+            ":to_state": <&str>::from(to),
+            ":reviewed_by": transition.reviewed_by,
+            ":reviewed_at": transition.reviewed_at,
+        },
+    )?;
+
+    Ok(transition)
+}
+
+/// Oldest first.
+pub fn select_transitions(
+    db: &DbConn,
+    clip_id: &str,
+) -> Result<Vec<Transition>> {
+    db.prepare(
+        "SELECT * FROM clip_review_transitions
+        WHERE clip_id = :clip_id ORDER BY id ASC",
+    )?
+    .query_map(named_params! { ":clip_id": clip_id }, |row| {
+        Transition::try_from(row)
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for Transition {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let state = |column: &str| -> StdResult<ReviewState, Self::Error> {
+            let state: String = row.get(column)?;
+            ReviewState::try_from(state.as_str()).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })
+        };
+
+        Ok(Self {
+            clip_id: row.get("clip_id")?,
+            from_state: state("from_state")?,
+            to_state: state("to_state")?,
+            reviewed_by: row.get("reviewed_by")?,
+            reviewed_at: row.get::<_, DateTime<Utc>>("reviewed_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+
+    #[test]
+    fn it_records_who_moved_clips() -> Result<()> {
+        let db = db::open(":memory:")?;
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        let clip_ids = ["a".to_string(), "b".to_string(), "c".to_string()];
+
+        assert_eq!(select_state(&db, "a")?, ReviewState::New);
+
+        transition(&db, "a", ReviewState::Rejected, &jane)?;
+        assert!(
+            transition(&db, "b", ReviewState::Downloaded, &jane).is_err(),
+            "new clip must be shortlisted first"
+        );
+
+        let moved =
+            transition_all(&db, &clip_ids, ReviewState::Shortlisted, &jane)?;
+        assert_eq!(moved.len(), 3, "rejected clip can be reconsidered");
+
+        transition(&db, "b", ReviewState::Downloaded, &jane)?;
+        transition(&db, "b", ReviewState::UsedInVideo, &jane)?;
+        let moved =
+            transition_all(&db, &clip_ids, ReviewState::Rejected, &jane)?;
+        assert_eq!(
+            moved.iter().map(|t| t.clip_id.as_str()).collect_vec(),
+            vec!["a", "c"],
+            "used clip stays used"
+        );
+        assert_eq!(select_state(&db, "b")?, ReviewState::UsedInVideo);
+
+        let transitions = select_transitions(&db, "b")?;
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|t| (t.from_state, t.to_state))
+                .collect_vec(),
+            vec![
+                (ReviewState::New, ReviewState::Shortlisted),
+                (ReviewState::Shortlisted, ReviewState::Downloaded),
+                (ReviewState::Downloaded, ReviewState::UsedInVideo),
+            ]
+        );
+        assert!(transitions.iter().all(|t| t.reviewed_by == "jane"));
+
+        let settled: Vec<String> = db
+            .prepare(&format!("{SETTLED_CLIP_IDS} ORDER BY clip_id"))?
+            .query_map([], |row| row.get(0))?
+            .try_collect()?;
+        assert_eq!(settled, vec!["a", "b", "c"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::{named_params, ErrorCode};
+
+use crate::models::saved_search::{NewSavedSearch, SavedSearch};
+use crate::models::user::User;
+use crate::prelude::*;
+
+/// Names must be unique.
+pub fn insert(
+    db: &DbConn,
+    search: &NewSavedSearch,
+    created_by: &User,
+) -> Result<i64> {
+    let res = db.execute(
+        "INSERT INTO saved_searches (name, query, created_by, created_at)
+        VALUES (:name, :query, :created_by, :created_at)",
+        named_params! {
+            ":name": search.name,
+            ":query": search.query,
+            ":created_by": created_by.username,
+            ":created_at": Utc::now(),
+        },
+    );
+
+    match res {
+        Ok(_) => Ok(db.last_insert_rowid()),
+        Err(e)
+            if e.sqlite_error_code()
+                == Some(ErrorCode::ConstraintViolation) =>
+        {
+            Err(AppError::already_exists(format!(
+                "Search {} already exists",
+                search.name
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// By name.
+pub fn select_all(db: &DbConn) -> Result<Vec<SavedSearch>> {
+    db.prepare("SELECT * FROM saved_searches ORDER BY name ASC")?
+        .query_map((), |row| SavedSearch::try_from(row))?
+        .map(|res| res.map_err(AppError::from))
+        .try_collect()
+}
+
+pub fn select_by_id(db: &DbConn, search_id: i64) -> Result<SavedSearch> {
+    db.query_row(
+        "SELECT * FROM saved_searches WHERE id = :id",
+        named_params! { ":id": search_id },
+        |row| SavedSearch::try_from(row),
+    )
+    .map_err(AppError::from)
+}
+
+pub fn delete(db: &DbConn, search_id: i64) -> Result<()> {
+    db.execute(
+        "DELETE FROM saved_searches WHERE id = :id",
+        named_params! { ":id": search_id },
+    )?;
+
+    Ok(())
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for SavedSearch {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            query: row.get("query")?,
+            created_by: row.get("created_by")?,
+            created_at: row.get::<_, DateTime<Utc>>("created_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+
+    #[test]
+    fn it_saves_searches() -> Result<()> {
+        let db = db::open(":memory:")?;
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        let weekly = NewSavedSearch {
+            name: "weekly".to_string(),
+            query: "langs=en&view-count-min=5000&recorded-within-days=7"
+                .to_string(),
+        };
+
+        let weekly_id = insert(&db, &weekly, &jane)?;
+        insert(
+            &db,
+            &NewSavedSearch {
+                name: "funny".to_string(),
+                query: "tags=funny".to_string(),
+            },
+            &jane,
+        )?;
+        assert!(insert(&db, &weekly, &jane).is_err(), "name is taken");
+
+        let searches = select_all(&db)?;
+        assert_eq!(
+            searches.iter().map(|s| s.name.as_str()).collect_vec(),
+            vec!["funny", "weekly"]
+        );
+        assert_eq!(searches[1].query, weekly.query);
+        assert_eq!(searches[1].created_by, "jane");
+
+        delete(&db, weekly_id)?;
+        assert_eq!(select_all(&db)?.len(), 1);
+
+        Ok(())
+    }
+}
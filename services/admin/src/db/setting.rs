@@ -1,53 +1,241 @@
-use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::{named_params, OptionalExtension};
 
+use crate::models::setting::{
+    self, Change, Definition, FromValue, Setting, Value, Values,
+};
+use crate::models::user::User;
 use crate::prelude::*;
 
-pub fn update(db: &DbConn, name: &str, value: &str) -> Result<()> {
-    let mut stmt = db.prepare(
-        "INSERT OR REPLACE INTO settings (name, value) VALUES (:name, :value)",
-    )?;
-    stmt.execute(rusqlite::named_params! {
-        ":name": name,
-        ":value": value,
-    })?;
+/// Settings which were never changed have their default value.
+pub fn get<T: FromValue>(db: &DbConn, setting: &Setting<T>) -> Result<T> {
+    setting.read(&select(db, &setting.definition)?)
+}
 
-    Ok(())
+/// Every setting there is.
+pub fn select_all(db: &DbConn) -> Result<Values> {
+    setting::REGISTRY
+        .iter()
+        .map(|definition| {
+            Ok((definition.name.to_string(), select(db, definition)?))
+        })
+        .collect()
 }
 
-pub fn fetch_new_game_clips_cron(db: &DbConn) -> Result<String> {
-    fetch(db, "fetch_new_game_clips_cron")
+/// Validates all given settings before changing any of them.
+/// Changes are recorded along with who made them, settings which already
+/// have given value are left out.
+/// Runs in the caller's transaction, if any.
+pub fn update_all(
+    db: &DbConn,
+    values: Values,
+    changed_by: &User,
+) -> Result<Vec<Change>> {
+    let values = setting::validate_all(values)?;
+
+    let mut changes = vec![];
+    for (name, new_value) in values {
+        let old_value = select(db, Definition::find(&name)?)?;
+        if old_value == new_value {
+            continue;
+        }
+
+        let change = Change {
+            name,
+            old_value: old_value.to_string(),
+            new_value: new_value.to_string(),
+            changed_by: changed_by.username.clone(),
+            changed_at: Utc::now(),
+        };
+        db.execute(
+            "INSERT OR REPLACE INTO settings (name, value)
+            VALUES (:name, :value)",
+            named_params! {
+                ":name": change.name,
+                ":value": change.new_value,
+            },
+        )?;
+        db.execute(
+            "INSERT INTO setting_changes
+                (name, old_value, new_value, changed_by, changed_at)
+            VALUES
+                (:name, :old_value, :new_value, :changed_by, :changed_at)",
+            named_params! {
+                ":name": change.name,
+                ":old_value": change.old_value,
+                ":new_value": change.new_value,
+                ":changed_by": change.changed_by,
+                ":changed_at": change.changed_at,
+            },
+        )?;
+        changes.push(change);
+    }
+
+    Ok(changes)
 }
 
-pub fn recorded_at_least_hours_ago(db: &DbConn) -> Result<i64> {
-    Ok(fetch(db, "recorded_at_least_hours_ago")?
-        .parse()
-        .map_err(|e| {
-            anyhow!("Cannot parse recorded_at_least_hours_ago as i64: {e}")
-        })?)
+/// Newest first.
+pub fn select_changes(db: &DbConn, limit: usize) -> Result<Vec<Change>> {
+    db.prepare(
+        "SELECT name, old_value, new_value, changed_by, changed_at
+        FROM setting_changes ORDER BY id DESC LIMIT :limit",
+    )?
+    .query_map(named_params! { ":limit": limit }, |row| {
+        Ok(Change {
+            name: row.get("name")?,
+            old_value: row.get("old_value")?,
+            new_value: row.get("new_value")?,
+            changed_by: row.get("changed_by")?,
+            changed_at: row.get::<_, DateTime<Utc>>("changed_at")?,
+        })
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
 }
 
-fn fetch(db: &DbConn, name: &str) -> Result<String> {
-    let mut stmt =
-        db.prepare("SELECT value FROM settings WHERE name = :name")?;
-    let value = stmt
-        .query_row(rusqlite::named_params! { ":name": name }, |row| {
-            row.get(0)
-        })?;
+fn select(db: &DbConn, definition: &Definition) -> Result<Value> {
+    let stored: Option<String> = db
+        .query_row(
+            "SELECT value FROM settings WHERE name = :name",
+            named_params! { ":name": definition.name },
+            |row| row.get(0),
+        )
+        .optional()?;
 
-    Ok(value)
+    match stored {
+        Some(text) => definition.parse(&text),
+        None => Ok(definition.default.clone()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::setting::*;
+    use crate::models::user::Role;
+    use std::borrow::Cow;
+
+    fn jane() -> User {
+        User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Admin,
+        }
+    }
 
     #[test]
     fn it_loads_defaults_ok() -> Result<()> {
-        let mut db = DbConn::open_in_memory()?;
-        db::up(&mut db)?;
+        let db = db::open(":memory:")?;
+
+        assert!(!get(&db, &FETCH_NEW_GAME_CLIPS_CRON)?.is_empty());
+        assert!(!get(&db, &COLLECT_MEDIA_GARBAGE_CRON)?.is_empty());
+        assert_eq!(get(&db, &RECORDED_AT_LEAST_HOURS_AGO)?, 8);
+        assert_eq!(get(&db, &LOUDNESS_TARGET_LUFS)?, -14.0);
+        assert_eq!(get(&db, &LOUDNESS_TOLERANCE_LU)?, 6.0);
+        assert_eq!(select_all(&db)?.len(), REGISTRY.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_falls_back_to_defaults() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute("DELETE FROM settings", [])?;
+
+        for definition in REGISTRY {
+            assert_eq!(select(&db, definition)?, definition.default);
+            assert_eq!(
+                definition.validate(definition.default.clone())?,
+                definition.default,
+                "default of {} is valid",
+                definition.name
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_only_given_settings() -> Result<()> {
+        let db = db::open(":memory:")?;
+        let defaults = select_all(&db)?;
+
+        let changes = update_all(
+            &db,
+            Values::from([
+                ("loudness_target_lufs".to_string(), Value::Integer(-16)),
+                ("recorded_at_least_hours_ago".to_string(), Value::Integer(8)),
+            ]),
+            &jane(),
+        )?;
+        assert_eq!(changes.len(), 1, "unchanged settings are left out");
+        assert_eq!(get(&db, &LOUDNESS_TARGET_LUFS)?, -16.0);
+        let mut expected = defaults.clone();
+        expected
+            .insert("loudness_target_lufs".to_string(), Value::Float(-16.0));
+        assert_eq!(select_all(&db)?, expected);
+
+        let [change] = select_changes(&db, 10)?.try_into().unwrap();
+        assert_eq!(change.name, "loudness_target_lufs");
+        assert_eq!(change.old_value, "-14");
+        assert_eq!(change.new_value, "-16");
+        assert_eq!(change.changed_by, "jane");
+
+        let invalid = Values::from([
+            (
+                "fetch_new_game_clips_cron".to_string(),
+                Value::Text(Cow::Borrowed("0 0 * * * *")),
+            ),
+            ("loudness_tolerance_lu".to_string(), Value::Float(-1.0)),
+        ]);
+        assert!(update_all(&db, invalid, &jane()).is_err());
+        assert_eq!(
+            select_all(&db)?,
+            expected,
+            "nothing is changed if any setting is invalid"
+        );
+
+        let unknown =
+            Values::from([("no_such_setting".to_string(), Value::Integer(1))]);
+        assert!(update_all(&db, unknown, &jane()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_invalid_values() -> Result<()> {
+        let db = db::open(":memory:")?;
+
+        for (name, value) in [
+            (
+                "collect_media_garbage_cron",
+                Value::Text("every hour".into()),
+            ),
+            (
+                "collect_media_garbage_cron",
+                Value::Text("0 0 * * *".into()),
+            ),
+            (
+                "collect_media_garbage_cron",
+                Value::Text("0 0 0 1 1 * 2000".into()),
+            ),
+            ("collect_media_garbage_cron", Value::Integer(4)),
+            ("recorded_at_least_hours_ago", Value::Integer(-1)),
+            ("recorded_at_least_hours_ago", Value::Float(1.5)),
+            ("recorded_at_least_hours_ago", Value::Text("8".into())),
+            ("loudness_target_lufs", Value::Float(3.0)),
+        ] {
+            let values = Values::from([(name.to_string(), value.clone())]);
+            assert!(update_all(&db, values, &jane()).is_err(), "{value}");
+        }
 
-        assert!(!fetch_new_game_clips_cron(&db).unwrap().is_empty());
-        assert!(recorded_at_least_hours_ago(&db).is_ok());
+        let values = Values::from([(
+            "collect_media_garbage_cron".to_string(),
+            Value::Text("0 30 4 * * *".into()),
+        )]);
+        update_all(&db, values, &jane())?;
+        assert_eq!(get(&db, &COLLECT_MEDIA_GARBAGE_CRON)?, "0 30 4 * * *");
 
         Ok(())
     }
@@ -0,0 +1,169 @@
+use chrono::Utc;
+use itertools::Itertools;
+use rusqlite::named_params;
+
+use crate::models::tag::Tag;
+use crate::models::user::User;
+use crate::prelude::*;
+
+/// Tags that are in use, most used first.
+pub fn select_all(db: &DbConn) -> Result<Vec<Tag>> {
+    db.prepare(
+        "SELECT
+            name,
+            (
+                SELECT COUNT(*) FROM clip_tags WHERE tag_id = tags.id
+            ) AS clip_count
+        FROM tags
+        ORDER BY clip_count DESC, name ASC",
+    )?
+    .query_map((), |row| {
+        Ok(Tag {
+            name: row.get("name")?,
+            clip_count: row.get("clip_count")?,
+        })
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .try_collect()
+}
+
+/// Creates the tag if nobody used it yet, tagging a clip twice is a no-op.
+/// Returns the normalized name of the tag.
+pub fn tag(
+    db: &DbConn,
+    clip_id: &str,
+    name: &str,
+    tagged_by: &User,
+) -> Result<String> {
+    let name = models::tag::normalize_name(name)?;
+
+    let tx = db.unchecked_transaction()?;
+    tx.execute(
+        "INSERT OR IGNORE INTO tags (name) VALUES (:name)",
+        named_params! { ":name": name },
+    )?;
+    tx.execute(
+        "INSERT OR IGNORE INTO clip_tags (clip_id, tag_id, tagged_by, tagged_at)
+        VALUES (
+            :clip_id,
+            (SELECT id FROM tags WHERE name = :name),
+            :tagged_by,
+            :tagged_at
+        )",
+        named_params! {
+            ":clip_id": clip_id,
+            ":name": name,
+            ":tagged_by": tagged_by.username,
+            ":tagged_at": Utc::now(),
+        },
+    )?;
+    tx.commit()?;
+
+    Ok(name)
+}
+
+/// The tag is deleted once no clip has it, so that unused tags are not
+/// offered.
+pub fn untag(db: &DbConn, clip_id: &str, name: &str) -> Result<()> {
+    let tx = db.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM clip_tags
+        WHERE clip_id = :clip_id
+        AND tag_id = (SELECT id FROM tags WHERE name = :name)",
+        named_params! { ":clip_id": clip_id, ":name": name },
+    )?;
+    tx.execute(
+        "DELETE FROM tags
+        WHERE name = :name
+        AND id NOT IN (SELECT tag_id FROM clip_tags)",
+        named_params! { ":name": name },
+    )?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::clip::ShowParams;
+    use crate::models::user::Role;
+    use twitch::models::GameId;
+
+    #[test]
+    fn it_tags_clips() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let jane = User {
+            id: 1,
+            username: "jane".to_string(),
+            role: Role::Editor,
+        };
+        let game_id = GameId::from("55");
+
+        assert_eq!(
+            tag(&db, "SuaveHonestWeaselJKanStyle", "Sponsor safe", &jane)?,
+            "sponsor-safe"
+        );
+        tag(&db, "SuaveHonestWeaselJKanStyle", "funny", &jane)?;
+        tag(&db, "SuaveHonestWeaselJKanStyle", "funny", &jane)?;
+        tag(&db, "MoistUnsightlyBatteryTwitchRPG", "funny", &jane)?;
+        assert!(
+            tag(&db, "MoistUnsightlyBatteryTwitchRPG", "a,b", &jane).is_err()
+        );
+
+        assert_eq!(
+            select_all(&db)?,
+            vec![
+                Tag {
+                    name: "funny".to_string(),
+                    clip_count: 2
+                },
+                Tag {
+                    name: "sponsor-safe".to_string(),
+                    clip_count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            db::clip::select_by_id(&db, "SuaveHonestWeaselJKanStyle")?.tags,
+            vec!["funny", "sponsor-safe"]
+        );
+
+        let tagged = |tags: &[&str]| -> Result<Vec<String>> {
+            let request = ShowParams {
+                page_size: 50,
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            };
+            let clips = db::clip::list(&db, &game_id, &request)?.clips;
+            Ok(clips.into_iter().map(|clip| clip.id).sorted().collect())
+        };
+        assert_eq!(
+            tagged(&["funny"])?,
+            vec![
+                "MoistUnsightlyBatteryTwitchRPG",
+                "SuaveHonestWeaselJKanStyle"
+            ]
+        );
+        assert_eq!(
+            tagged(&["funny", "Sponsor-Safe"])?,
+            vec!["SuaveHonestWeaselJKanStyle"],
+            "clips must have all of the tags"
+        );
+        assert_eq!(tagged(&[])?.len(), 9);
+
+        untag(&db, "SuaveHonestWeaselJKanStyle", "sponsor-safe")?;
+        untag(&db, "SuaveHonestWeaselJKanStyle", "funny")?;
+        assert_eq!(
+            select_all(&db)?,
+            vec![Tag {
+                name: "funny".to_string(),
+                clip_count: 1
+            }],
+            "unused tag is deleted"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::{named_params, ErrorCode, OptionalExtension};
+
+use crate::models::user::{Role, Session, User};
+use crate::prelude::*;
+
+pub fn insert(
+    db: &DbConn,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+) -> Result<i64> {
+    let res = db.execute(
+        "INSERT INTO users (username, password_hash, role)
+        VALUES (:username, :password_hash, :role)",
+        named_params! {
+            ":username": username,
+            ":password_hash": password_hash,
+            ":role": <&str>::from(role),
+        },
+    );
+
+    match res {
+        Ok(_) => Ok(db.last_insert_rowid()),
+        Err(e)
+            if e.sqlite_error_code()
+                == Some(ErrorCode::ConstraintViolation) =>
+        {
+            Err(AppError::already_exists(format!(
+                "User {username} already exists"
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn select_all(db: &DbConn) -> Result<Vec<User>> {
+    db.prepare("SELECT id, username, role FROM users ORDER BY username")?
+        .query_map((), |row| User::try_from(row))?
+        .map(|res| res.map_err(AppError::from))
+        .try_collect()
+}
+
+pub fn select_by_id(db: &DbConn, user_id: i64) -> Result<User> {
+    db.query_row(
+        "SELECT id, username, role FROM users WHERE id = :id",
+        named_params! { ":id": user_id },
+        |row| User::try_from(row),
+    )
+    .map_err(AppError::from)
+}
+
+/// The user and their password hash, None if there's no such user.
+pub fn select_by_username(
+    db: &DbConn,
+    username: &str,
+) -> Result<Option<(User, String)>> {
+    db.query_row(
+        "SELECT id, username, role, password_hash
+        FROM users WHERE username = :username",
+        named_params! { ":username": username },
+        |row| Ok((User::try_from(row)?, row.get("password_hash")?)),
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+pub fn set_role(db: &DbConn, user_id: i64, role: Role) -> Result<()> {
+    db.execute(
+        "UPDATE users SET role = :role WHERE id = :id",
+        named_params! { ":id": user_id, ":role": <&str>::from(role) },
+    )?;
+
+    Ok(())
+}
+
+/// Logs the user out everywhere.
+pub fn set_password_hash(
+    db: &DbConn,
+    user_id: i64,
+    password_hash: &str,
+) -> Result<()> {
+    db.execute(
+        "UPDATE users SET password_hash = :password_hash WHERE id = :id",
+        named_params! { ":id": user_id, ":password_hash": password_hash },
+    )?;
+    db.execute(
+        "DELETE FROM sessions WHERE user_id = :user_id",
+        named_params! { ":user_id": user_id },
+    )?;
+
+    Ok(())
+}
+
+pub fn delete(db: &DbConn, user_id: i64) -> Result<()> {
+    db.execute(
+        "DELETE FROM sessions WHERE user_id = :user_id",
+        named_params! { ":user_id": user_id },
+    )?;
+    db.execute(
+        "DELETE FROM users WHERE id = :id",
+        named_params! { ":id": user_id },
+    )?;
+
+    Ok(())
+}
+
+/// Nobody could manage users if the last admin was demoted or deleted.
+pub fn count_admins(db: &DbConn) -> Result<usize> {
+    db.query_row(
+        "SELECT COUNT(*) FROM users WHERE role = :role",
+        named_params! { ":role": <&str>::from(Role::Admin) },
+        |row| row.get(0),
+    )
+    .map_err(AppError::from)
+}
+
+/// Also forgets sessions which expired.
+pub fn insert_session(db: &DbConn, session: &Session) -> Result<()> {
+    db.execute(
+        "DELETE FROM sessions WHERE expires_at <= :now",
+        named_params! { ":now": Utc::now() },
+    )?;
+    db.execute(
+        "INSERT INTO sessions (token, user_id, csrf_token, expires_at)
+        VALUES (:token, :user_id, :csrf_token, :expires_at)",
+        named_params! {
+            ":token": session.token,
+            ":user_id": session.user.id,
+            ":csrf_token": session.csrf_token,
+            ":expires_at": session.expires_at,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// None if there's no such session or it expired.
+pub fn select_session(db: &DbConn, token: &str) -> Result<Option<Session>> {
+    db.query_row(
+        "SELECT
+            sessions.token, sessions.csrf_token, sessions.expires_at,
+            users.id, users.username, users.role
+        FROM sessions
+        INNER JOIN users ON users.id = sessions.user_id
+        WHERE sessions.token = :token AND sessions.expires_at > :now",
+        named_params! { ":token": token, ":now": Utc::now() },
+        |row| {
+            Ok(Session {
+                token: row.get("token")?,
+                csrf_token: row.get("csrf_token")?,
+                expires_at: row.get::<_, DateTime<Utc>>("expires_at")?,
+                user: User::try_from(row)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+pub fn delete_session(db: &DbConn, token: &str) -> Result<()> {
+    db.execute(
+        "DELETE FROM sessions WHERE token = :token",
+        named_params! { ":token": token },
+    )?;
+
+    Ok(())
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for User {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> StdResult<Self, Self::Error> {
+        let role: String = row.get("role")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            username: row.get("username")?,
+            role: Role::try_from(role.as_str()).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    e.into(),
+                )
+            })?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::new_token;
+
+    #[test]
+    fn it_keeps_sessions_until_they_expire() -> Result<()> {
+        let db = db::open(":memory:")?;
+
+        let user_id = insert(&db, "jane", "hash", Role::Editor)?;
+        assert!(insert(&db, "jane", "hash", Role::Viewer).is_err());
+        let (user, password_hash) = select_by_username(&db, "jane")?.unwrap();
+        assert_eq!(user.id, user_id);
+        assert_eq!(user.role, Role::Editor);
+        assert_eq!(password_hash, "hash");
+        assert_eq!(count_admins(&db)?, 0);
+
+        let session = Session {
+            token: new_token(),
+            csrf_token: new_token(),
+            user: user.clone(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        insert_session(&db, &session)?;
+        let expired = Session {
+            token: new_token(),
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+            ..session.clone()
+        };
+        insert_session(&db, &expired)?;
+
+        let found = select_session(&db, &session.token)?.unwrap();
+        assert_eq!(found.csrf_token, session.csrf_token);
+        assert_eq!(found.user, user);
+        assert!(select_session(&db, &expired.token)?.is_none());
+
+        set_role(&db, user_id, Role::Admin)?;
+        assert_eq!(count_admins(&db)?, 1);
+        assert_eq!(
+            select_session(&db, &session.token)?.unwrap().user.role,
+            Role::Admin,
+            "roles apply to sessions right away"
+        );
+
+        set_password_hash(&db, user_id, "new hash")?;
+        assert!(select_session(&db, &session.token)?.is_none());
+
+        insert_session(&db, &session)?;
+        delete(&db, user_id)?;
+        assert!(select_session(&db, &session.token)?.is_none());
+        assert!(select_all(&db)?.is_empty());
+
+        Ok(())
+    }
+}
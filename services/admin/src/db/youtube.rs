@@ -0,0 +1,77 @@
+use rusqlite::{named_params, OptionalExtension};
+use youtube::OAuthToken;
+
+use crate::prelude::*;
+
+/// None if no channel is connected.
+pub fn select_token(db: &DbConn) -> Result<Option<OAuthToken>> {
+    db.query_row(
+        "SELECT access_token, refresh_token, expires_at
+        FROM youtube_tokens WHERE id = 1",
+        (),
+        |row| {
+            Ok(OAuthToken {
+                access_token: row.get("access_token")?,
+                refresh_token: row.get("refresh_token")?,
+                expires_at: row.get("expires_at")?,
+            })
+        },
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+/// Replaces the token of any previously connected channel.
+pub fn save_token(db: &DbConn, token: &OAuthToken) -> Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO youtube_tokens (
+            id, access_token, refresh_token, expires_at
+        ) VALUES (
+            1, :access_token, :refresh_token, :expires_at
+        )",
+        named_params! {
+            ":access_token": token.access_token,
+            ":refresh_token": token.refresh_token,
+            ":expires_at": token.expires_at,
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn delete_token(db: &DbConn) -> Result<()> {
+    db.execute("DELETE FROM youtube_tokens", ())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stores_one_token() -> Result<()> {
+        let db = db::open(":memory:")?;
+        assert_eq!(select_token(&db)?, None);
+
+        let token = OAuthToken {
+            access_token: "access".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            expires_at: "2023-07-10T12:00:00Z".parse().unwrap(),
+        };
+        save_token(&db, &token)?;
+        assert_eq!(select_token(&db)?, Some(token.clone()));
+
+        let refreshed = OAuthToken {
+            access_token: "access2".to_string(),
+            ..token
+        };
+        save_token(&db, &refreshed)?;
+        assert_eq!(select_token(&db)?, Some(refreshed));
+
+        delete_token(&db)?;
+        assert_eq!(select_token(&db)?, None);
+
+        Ok(())
+    }
+}
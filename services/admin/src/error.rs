@@ -1,10 +1,11 @@
 use crate::prelude::*;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
 use axum::response::{IntoResponse, Response};
 use hyper::StatusCode;
 use serde::Serialize;
-use serde_json::json;
 use std::borrow::Cow;
 use std::error::Error as StdError;
+use utoipa::ToSchema;
 
 #[derive(Debug)]
 pub struct AppError {
@@ -13,12 +14,16 @@ pub struct AppError {
     pub status: StatusCode,
 }
 
-#[derive(Default, Debug, Serialize)]
+#[derive(Default, Debug, Serialize, ToSchema)]
 pub enum AppErrorKind {
     /// Something wrong with the user request, see message for more info.
     BadRequest,
     AlreadyExists,
     NotFound,
+    /// Not logged in or the session expired.
+    Unauthorized,
+    /// The role of the user doesn't allow it, or the CSRF token is missing.
+    Forbidden,
     /// Internal server error.
     ///
     /// We don't track the error kind for that error as it's too specific.
@@ -27,6 +32,14 @@ pub enum AppErrorKind {
     Other,
 }
 
+/// What every error response is made of, HTML endpoints included.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub kind: AppErrorKind,
+    #[schema(value_type = String)]
+    pub error: Cow<'static, str>,
+}
+
 impl AppError {
     pub fn bad_request(message: impl Into<String>) -> Self {
         Self {
@@ -51,6 +64,30 @@ impl AppError {
             status: StatusCode::CONFLICT,
         }
     }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into().into(),
+            kind: AppErrorKind::NotFound,
+            status: StatusCode::NOT_FOUND,
+        }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into().into(),
+            kind: AppErrorKind::Unauthorized,
+            status: StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into().into(),
+            kind: AppErrorKind::Forbidden,
+            status: StatusCode::FORBIDDEN,
+        }
+    }
 }
 
 impl IntoResponse for AppError {
@@ -65,10 +102,10 @@ impl IntoResponse for AppError {
             error!("500: {message}");
         }
 
-        let body = Json(json!({
-            "kind": kind,
-            "error": message,
-        }));
+        let body = Json(ErrorBody {
+            kind,
+            error: message,
+        });
 
         (status, body).into_response()
     }
@@ -116,6 +153,10 @@ impl From<tonic::Status> for AppError {
 
 impl From<rusqlite::Error> for AppError {
     fn from(err: rusqlite::Error) -> Self {
+        if matches!(err, rusqlite::Error::QueryReturnedNoRows) {
+            return Self::not_found("No such record");
+        }
+
         Self {
             message: err.to_string().into(),
             kind: AppErrorKind::Other,
@@ -124,6 +165,18 @@ impl From<rusqlite::Error> for AppError {
     }
 }
 
+impl From<JsonRejection> for AppError {
+    fn from(err: JsonRejection) -> Self {
+        Self::bad_request(err.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(err: QueryRejection) -> Self {
+        Self::bad_request(err.body_text())
+    }
+}
+
 impl std::fmt::Display for AppError {
     fn fmt(
         &self,
@@ -11,9 +11,11 @@ pub struct HttpState {
     pub db: Arc<Mutex<DbConn>>,
     pub conf: Arc<Conf>,
     pub views: Views,
-    pub worker: Arc<Mutex<worker::Client>>,
+    pub workers: WorkerPool,
     pub twitch: Arc<twitch::Client>,
-    pub _jobs: Jobs,
+    /// None if uploading to YouTube is not configured
+    pub youtube: Option<youtube::Client>,
+    pub jobs: Jobs,
 }
 
 pub fn empty_string_is_none<'de, D, T: FromStr>(
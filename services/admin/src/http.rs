@@ -1,3 +1,8 @@
+/// versioned JSON API, which unlike the HTML endpoints uses the HTTP verbs
+/// and always answers with JSON
+mod api;
+/// login, sessions and who can do what
+mod auth;
 /// endpoints for clips management
 mod clips;
 /// endpoints which ease development
@@ -6,23 +11,41 @@ mod dev;
 mod game;
 /// homepage
 mod home;
+/// history of job runs and when scheduled jobs run next
+mod jobs;
+/// endpoints for worker media retention
+mod media;
+/// endpoints for reference tracks and music found in clips
+mod music;
+/// compilation videos and the clips in them
+mod projects;
 /// endpoints for global settings
 mod settings;
+/// vertical shorts and the layouts they are rendered with
+mod shorts;
+/// thumbnails for compilation videos
+mod thumbnail;
+/// accounts and their roles
+mod users;
+/// worker pool status
+mod workers;
+/// connecting the YouTube channel compilations are uploaded to
+mod youtube;
 
+use crate::models::user::Role;
 use crate::prelude::*;
 use axum::{
+    middleware,
     response::IntoResponse,
     routing::{get, post},
     Router,
 };
-use hyper::StatusCode;
-use serde_json::json;
 use std::net::SocketAddr;
 
 pub async fn start(g: g::HttpState) -> AnyResult<()> {
     let http_addr = g.conf.http_addr;
 
-    let routes = routes().with_state(g);
+    let routes = routes(g.clone()).with_state(g);
 
     info!("http://{http_addr}");
     axum::Server::bind(&http_addr)
@@ -32,14 +55,9 @@ pub async fn start(g: g::HttpState) -> AnyResult<()> {
     Ok(())
 }
 
-fn routes() -> Router<g::HttpState> {
+fn routes(g: g::HttpState) -> Router<g::HttpState> {
     async fn handler_404() -> impl IntoResponse {
-        (
-            StatusCode::NOT_FOUND,
-            Json(json!({
-                "error": "Invalid path",
-            })),
-        )
+        AppError::not_found("Invalid path")
     }
 
     // since data is being sent with <form> which only supports GET and POST,
@@ -47,22 +65,139 @@ fn routes() -> Router<g::HttpState> {
     //
     // this is a trade-off between ~dogma~ conventions and no-js life
     // i'll take the latter any day of the week
-    let app = Router::new()
+    let viewer = Router::new()
         .route("/", get(home::page))
         .route("/search/game", get(game::search))
         .route("/game/:game_id", get(game::show))
+        .route("/game/:game_id/clips", get(clips::show))
+        .route("/game/:game_id/clips/export", get(clips::export))
+        .route("/clips/import", get(clips::import_page))
+        .route("/game/:game_id/thumbnail", get(thumbnail::show))
+        .route("/clip/:clip_id/music", get(music::clip))
+        .route("/clip/:clip_id/vertical", get(shorts::clip))
+        .route("/broadcaster/:broadcaster_id/layout", get(shorts::layout))
+        .route("/media", get(media::show))
+        .route("/media/artifact", get(media::download_artifact))
+        .route("/clip/:clip_id/preview/:kind", get(media::preview))
+        .route("/music", get(music::tracks))
+        .route("/workers", get(workers::show))
+        .route("/jobs", get(jobs::show))
+        .route("/projects", get(projects::list))
+        .route("/project/:project_id", get(projects::show))
+        .route("/youtube", get(youtube::show))
+        .route("/settings", get(settings::show))
+        .route("/settings/export", get(settings::export))
+        .route("/logout/post", post(auth::logout));
+
+    let editor = Router::new()
         .route("/game/:game_id/post", post(game::add))
-        .route("/game/:game_id/delete", post(game::delete))
         .route("/game/:game_id/pause/post", post(game::pause))
         .route("/game/:game_id/pause/delete", post(game::resume))
-        .route("/game/:game_id/clips", get(clips::show))
+        .route(
+            "/game/:game_id/fetch-policy/put",
+            post(game::save_fetch_policy),
+        )
         .route(
             "/game/:game_id/clips/fetch/post",
             post(clips::trigger_fetch),
         )
-        .route("/settings", get(settings::show))
+        .route(
+            "/game/:game_id/moments/detect/post",
+            post(clips::trigger_detect_moments),
+        )
+        .route(
+            "/game/:game_id/loudness/measure/post",
+            post(clips::trigger_measure_loudness),
+        )
+        .route(
+            "/game/:game_id/previews/generate/post",
+            post(clips::trigger_generate_previews),
+        )
+        .route("/game/:game_id/thumbnail/post", post(thumbnail::compose))
+        .route("/game/:game_id/music/scan/post", post(music::trigger_scan))
+        .route(
+            "/clip/:clip_id/music/:match_id/put",
+            post(music::set_action),
+        )
+        .route("/clip/:clip_id/vertical/post", post(shorts::render))
+        .route(
+            "/broadcaster/:broadcaster_id/layout/put",
+            post(shorts::save_layout),
+        )
+        .route("/clips/import/post", post(clips::import))
+        .route("/clip/:clip_id/review/put", post(clips::review))
+        .route("/game/:game_id/clips/review/put", post(clips::review_all))
+        .route("/clip/:clip_id/tags/post", post(clips::tag))
+        .route("/clip/:clip_id/tag/:tag/delete", post(clips::untag))
+        .route("/game/:game_id/searches/post", post(clips::save_search))
+        .route("/search/:search_id/delete", post(clips::delete_search))
+        .route("/clip/:clip_id/projects/post", post(projects::add_clip))
+        .route("/projects/post", post(projects::add))
+        .route("/project/:project_id/put", post(projects::edit))
+        .route(
+            "/project/:project_id/status/put",
+            post(projects::set_status),
+        )
+        .route("/project/:project_id/upload/post", post(projects::upload))
+        .route(
+            "/project/:project_id/entries/post",
+            post(projects::add_entry),
+        )
+        .route(
+            "/project/:project_id/entry/:entry_id/put",
+            post(projects::edit_entry),
+        )
+        .route(
+            "/project/:project_id/entry/:entry_id/position/put",
+            post(projects::move_entry),
+        )
+        .route(
+            "/project/:project_id/entry/:entry_id/delete",
+            post(projects::delete_entry),
+        )
+        .route("/clip/:clip_id/keep/put", post(media::keep))
+        .route("/clip/:clip_id/keep/delete", post(media::release))
+        .route("/music/post", post(music::add_track))
+        .route_layer(middleware::from_fn_with_state(
+            Role::Editor,
+            auth::require_role,
+        ));
+
+    // deletes data, changes how the app runs or who can use it
+    let admin = Router::new()
+        .route("/game/:game_id/delete", post(game::delete))
+        .route("/project/:project_id/delete", post(projects::delete))
+        .route("/media/gc/post", post(media::trigger_gc))
+        .route("/music/:track_id/delete", post(music::delete_track))
+        .route("/youtube/connect/post", post(youtube::connect))
+        .route("/youtube/oauth/callback", get(youtube::callback))
+        .route("/youtube/token/delete", post(youtube::disconnect))
         .route("/settings/put", post(settings::edit))
+        .route("/settings/import/post", post(settings::import))
+        .route("/users", get(users::show))
+        .route("/users/post", post(users::add))
+        .route("/user/:user_id/role/put", post(users::set_role))
+        .route("/user/:user_id/password/put", post(users::set_password))
+        .route("/user/:user_id/delete", post(users::delete))
         .route("/dev/reset/post", post(dev::reset))
+        .route_layer(middleware::from_fn_with_state(
+            Role::Admin,
+            auth::require_role,
+        ));
+
+    let public = Router::new()
+        .route("/login", get(auth::login_page))
+        .route("/login/post", post(auth::login));
+
+    let app = viewer
+        .merge(editor)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            g.clone(),
+            auth::authenticate,
+        ))
+        .merge(public)
+        .nest("/api/v1", api::routes(g))
         .fallback(handler_404);
 
     debug!("Http routes constructed");
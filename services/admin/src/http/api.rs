@@ -0,0 +1,315 @@
+/// clips of games and their filters
+mod clips;
+/// games which clips are fetched for
+mod games;
+/// triggering jobs out of their schedule
+mod jobs;
+/// compilation videos and the clips in them
+mod projects;
+/// logging in from scripts
+mod session;
+/// global settings
+mod settings;
+
+use super::auth;
+use crate::error::{AppErrorKind, ErrorBody};
+use crate::models::user::Role;
+use crate::prelude::*;
+use axum::{
+    middleware,
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+use utoipa::OpenApi;
+
+/// Generated from the handlers, served at `/api/v1/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Admin API", version = "1"),
+    paths(
+        session::create,
+        session::show,
+        session::delete,
+        games::list,
+        games::add,
+        games::show,
+        games::edit,
+        games::delete,
+        games::show_fetch_policy,
+        games::edit_fetch_policy,
+        clips::list,
+        clips::export,
+        clips::import,
+        clips::show,
+        clips::review,
+        clips::review_all,
+        clips::reviews,
+        clips::tags,
+        clips::tag,
+        clips::untag,
+        clips::searches,
+        clips::save_search,
+        clips::delete_search,
+        projects::list,
+        projects::create,
+        projects::show,
+        projects::edit,
+        projects::delete,
+        projects::add_entry,
+        projects::edit_entry,
+        projects::delete_entry,
+        settings::show,
+        settings::edit,
+        settings::definitions,
+        settings::changes,
+        jobs::fetch_clips,
+        jobs::detect_moments,
+        jobs::measure_loudness,
+        jobs::generate_previews,
+        jobs::scan_music,
+        jobs::upload_project,
+        jobs::collect_media_garbage,
+    ),
+    components(schemas(
+        AppErrorKind,
+        ErrorBody,
+        auth::Login,
+        models::user::Session,
+        models::user::User,
+        models::user::Role,
+        db::game::Game,
+        games::AddGame,
+        games::EditGame,
+        models::fetch_policy::FetchPolicy,
+        models::clip::Clip,
+        models::clip::SerializedDuration,
+        models::clip::ClipPage,
+        models::export::ExportFormat,
+        models::clip_import::ImportClips,
+        models::clip_import::ImportReport,
+        models::clip_import::ImportedClip,
+        models::loudness::Loudness,
+        clips::Review,
+        clips::Reviewed,
+        models::review::ReviewState,
+        models::review::Transition,
+        models::tag::Tag,
+        models::saved_search::SavedSearch,
+        models::saved_search::NewSavedSearch,
+        models::project::Project,
+        models::project::ProjectStatus,
+        models::project::ProjectDetails,
+        models::project::Entry,
+        models::project::Credit,
+        projects::ProjectWithEntries,
+        projects::EditProject,
+        projects::AddEntry,
+        projects::EditEntry,
+        models::setting::Value,
+        models::setting::Definition,
+        models::setting::Kind,
+        models::setting::Change,
+        jobs::FetchClips,
+        jobs::LimitClips,
+        jobs::UploadProject,
+        jobs::JobTriggered,
+    )),
+    tags(
+        (name = "session", description = "Requests which change something \
+            must send the CSRF token of the session in the X-CSRF-Token \
+            header"),
+        (name = "games"),
+        (name = "clips"),
+        (name = "projects", description = "Compilation videos and the \
+            clips in them"),
+        (name = "settings"),
+        (name = "jobs", description = "Jobs run in the background, \
+            their runs are listed at /jobs"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Uses the same sessions and roles as the HTML endpoints, scripts log in
+/// at `/api/v1/session`.
+pub fn routes(g: g::HttpState) -> Router<g::HttpState> {
+    let viewer = Router::new()
+        .route("/session", get(session::show).delete(session::delete))
+        .route("/games", get(games::list))
+        .route("/games/:game_id", get(games::show))
+        .route("/games/:game_id/clips", get(clips::list))
+        .route("/games/:game_id/clips/export", get(clips::export))
+        .route(
+            "/games/:game_id/fetch-policy",
+            get(games::show_fetch_policy),
+        )
+        .route("/clips/:clip_id", get(clips::show))
+        .route("/clips/:clip_id/reviews", get(clips::reviews))
+        .route("/tags", get(clips::tags))
+        .route("/searches", get(clips::searches))
+        .route("/projects", get(projects::list))
+        .route("/projects/:project_id", get(projects::show))
+        .route("/settings", get(settings::show))
+        .route("/settings/definitions", get(settings::definitions))
+        .route("/settings/changes", get(settings::changes));
+
+    let editor = Router::new()
+        .route("/games", post(games::add))
+        .route("/games/:game_id", patch(games::edit))
+        .route(
+            "/games/:game_id/fetch-policy",
+            put(games::edit_fetch_policy),
+        )
+        .route("/games/:game_id/clips/review", post(clips::review_all))
+        .route("/clips/import", post(clips::import))
+        .route("/clips/:clip_id/review", put(clips::review))
+        .route(
+            "/clips/:clip_id/tags/:tag",
+            put(clips::tag).delete(clips::untag),
+        )
+        .route("/searches", post(clips::save_search))
+        .route("/searches/:search_id", delete(clips::delete_search))
+        .route("/projects", post(projects::create))
+        .route("/projects/:project_id", patch(projects::edit))
+        .route("/projects/:project_id/entries", post(projects::add_entry))
+        .route(
+            "/projects/:project_id/entries/:entry_id",
+            put(projects::edit_entry).delete(projects::delete_entry),
+        )
+        .route("/games/:game_id/jobs/fetch-clips", post(jobs::fetch_clips))
+        .route(
+            "/games/:game_id/jobs/detect-moments",
+            post(jobs::detect_moments),
+        )
+        .route(
+            "/games/:game_id/jobs/measure-loudness",
+            post(jobs::measure_loudness),
+        )
+        .route(
+            "/games/:game_id/jobs/generate-previews",
+            post(jobs::generate_previews),
+        )
+        .route("/games/:game_id/jobs/scan-music", post(jobs::scan_music))
+        .route(
+            "/projects/:project_id/jobs/upload",
+            post(jobs::upload_project),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Role::Editor,
+            auth::require_role,
+        ));
+
+    let admin = Router::new()
+        .route("/games/:game_id", delete(games::delete))
+        .route("/projects/:project_id", delete(projects::delete))
+        .route("/settings", patch(settings::edit))
+        .route(
+            "/jobs/collect-media-garbage",
+            post(jobs::collect_media_garbage),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Role::Admin,
+            auth::require_role,
+        ));
+
+    let public = Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/session", post(session::create));
+
+    viewer
+        .merge(editor)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(g, auth::authenticate))
+        .merge(public)
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn it_documents_every_route() -> Result<()> {
+        let doc =
+            serde_json::to_value(ApiDoc::openapi()).map_err(AnyError::from)?;
+
+        let paths = doc["paths"].as_object().unwrap();
+        for (path, methods) in [
+            ("/api/v1/session", &["get", "post", "delete"][..]),
+            ("/api/v1/games", &["get", "post"]),
+            ("/api/v1/games/{game_id}", &["get", "patch", "delete"]),
+            ("/api/v1/games/{game_id}/clips", &["get"]),
+            ("/api/v1/games/{game_id}/clips/export", &["get"]),
+            ("/api/v1/games/{game_id}/clips/review", &["post"]),
+            ("/api/v1/clips/import", &["post"]),
+            ("/api/v1/clips/{clip_id}/review", &["put"]),
+            ("/api/v1/clips/{clip_id}/reviews", &["get"]),
+            ("/api/v1/clips/{clip_id}/tags/{tag}", &["put", "delete"]),
+            ("/api/v1/tags", &["get"]),
+            ("/api/v1/searches", &["get", "post"]),
+            ("/api/v1/searches/{search_id}", &["delete"]),
+            ("/api/v1/games/{game_id}/fetch-policy", &["get", "put"]),
+            ("/api/v1/games/{game_id}/jobs/fetch-clips", &["post"]),
+            ("/api/v1/games/{game_id}/jobs/detect-moments", &["post"]),
+            ("/api/v1/games/{game_id}/jobs/measure-loudness", &["post"]),
+            ("/api/v1/games/{game_id}/jobs/generate-previews", &["post"]),
+            ("/api/v1/games/{game_id}/jobs/scan-music", &["post"]),
+            ("/api/v1/clips/{clip_id}", &["get"]),
+            ("/api/v1/projects", &["get", "post"]),
+            ("/api/v1/projects/{project_id}", &["get", "patch", "delete"]),
+            ("/api/v1/projects/{project_id}/entries", &["post"]),
+            (
+                "/api/v1/projects/{project_id}/entries/{entry_id}",
+                &["put", "delete"],
+            ),
+            ("/api/v1/projects/{project_id}/jobs/upload", &["post"]),
+            ("/api/v1/settings", &["get", "patch"]),
+            ("/api/v1/settings/definitions", &["get"]),
+            ("/api/v1/settings/changes", &["get"]),
+            ("/api/v1/jobs/collect-media-garbage", &["post"]),
+        ] {
+            for method in methods {
+                assert!(
+                    paths[path].get(method).is_some(),
+                    "{method} {path} is not documented"
+                );
+            }
+        }
+        assert_eq!(paths.len(), 29);
+
+        let operation_ids: Vec<_> = paths
+            .values()
+            .flat_map(|methods| methods.as_object().unwrap().values())
+            .map(|operation| operation["operationId"].as_str().unwrap())
+            .collect();
+        assert!(operation_ids.iter().all_unique(), "{operation_ids:?}");
+
+        // every filter of the clips page is a query parameter
+        let params = doc["paths"]["/api/v1/games/{game_id}/clips"]["get"]
+            ["parameters"]
+            .as_array()
+            .unwrap();
+        for name in [
+            "game_id",
+            "page-size",
+            "cursor",
+            "title-like",
+            "search",
+            "langs",
+            "sort-by",
+            "review-states",
+            "recorded-within-days",
+            "tags",
+        ] {
+            assert!(
+                params.iter().any(|param| param["name"] == name),
+                "{name} is not a parameter"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query,
+    },
+    response::Response,
+};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::clip::{Clip, ClipPage, ShowParams};
+use crate::models::clip_import::{ImportClips, ImportReport};
+use crate::models::export::ExportParams;
+use crate::models::review::{ReviewState, Transition};
+use crate::models::saved_search::{NewSavedSearch, SavedSearch};
+use crate::models::tag::Tag;
+use crate::models::user::Session;
+use crate::prelude::*;
+
+#[derive(Deserialize, ToSchema)]
+pub struct Review {
+    state: ReviewState,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Reviewed {
+    /// Clips which match the filters, on any page
+    matched: usize,
+    /// Those which could become given state, the rest are left as they are
+    moved: Vec<Transition>,
+}
+
+/// Filters and pages clips the same way the clips page does, pass the
+/// `next_cursor` of a page as `cursor` to get the next one.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{game_id}/clips",
+    tag = "clips",
+    operation_id = "list_clips",
+    params(("game_id" = String, Path, description = "Twitch id of the game"), ShowParams),
+    responses(
+        (status = 200, body = ClipPage),
+        (status = 400, body = ErrorBody),
+    ),
+)]
+pub async fn list(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    query: StdResult<Query<ShowParams>, QueryRejection>,
+) -> Result<Json<ClipPage>> {
+    let Query(query) = query?;
+
+    let db = s.db.lock().await;
+    Ok(Json(db::clip::list(&db, &game_id, &query)?))
+}
+
+/// Streams all clips which match the filters, not only a page of them, with
+/// fields computed on export such as the views per hour.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{game_id}/clips/export",
+    tag = "clips",
+    operation_id = "export_clips",
+    params(("game_id" = String, Path, description = "Twitch id of the game"), ShowParams, ExportParams),
+    responses(
+        (
+            status = 200,
+            description = "CSV with a header row, JSON array or NDJSON",
+            content_type = "text/csv",
+            body = String,
+        ),
+        (status = 400, body = ErrorBody),
+    ),
+)]
+pub async fn export(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    query: StdResult<Query<ShowParams>, QueryRejection>,
+    params: StdResult<Query<ExportParams>, QueryRejection>,
+) -> Result<Response> {
+    let Query(query) = query?;
+    let Query(params) = params?;
+
+    crate::http::clips::export_clips(&s, game_id, query, params).await
+}
+
+/// Stores clips the fetch job missed, looked up on Twitch by their links or
+/// ids. Games which are not tracked yet are added paused.
+#[utoipa::path(
+    post,
+    path = "/api/v1/clips/import",
+    tag = "clips",
+    operation_id = "import_clips",
+    request_body = ImportClips,
+    responses(
+        (status = 200, body = ImportReport),
+        (status = 400, body = ErrorBody, description = "Not clip links"),
+    ),
+)]
+pub async fn import(
+    State(s): State<g::HttpState>,
+    session: Session,
+    body: StdResult<Json<ImportClips>, JsonRejection>,
+) -> Result<Json<ImportReport>> {
+    let Json(ImportClips { clips }) = body?;
+
+    Ok(Json(
+        crate::http::clips::import_clips(&s, &session, &clips).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/clips/{clip_id}",
+    tag = "clips",
+    operation_id = "show_clip",
+    params(("clip_id" = String, Path, description = "Twitch id of the clip")),
+    responses(
+        (status = 200, body = Clip),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn show(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+) -> Result<Json<Clip>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::clip::select_by_id(&db, &clip_id)?))
+}
+
+/// Moves the clip to given state, which must follow its current one.
+#[utoipa::path(
+    put,
+    path = "/api/v1/clips/{clip_id}/review",
+    tag = "clips",
+    operation_id = "review_clip",
+    params(("clip_id" = String, Path, description = "Twitch id of the clip")),
+    request_body = Review,
+    responses(
+        (status = 200, body = Clip),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn review(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(clip_id): Path<String>,
+    body: StdResult<Json<Review>, JsonRejection>,
+) -> Result<Json<Clip>> {
+    let Json(Review { state }) = body?;
+
+    let db = s.db.lock().await;
+    db::clip::select_by_id(&db, &clip_id)?;
+    db::review::transition(&db, &clip_id, state, &session.user)?;
+
+    Ok(Json(db::clip::select_by_id(&db, &clip_id)?))
+}
+
+/// Moves all clips which match the same filters as when listing them, on
+/// any page, which can become given state.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/clips/review",
+    tag = "clips",
+    operation_id = "review_clips",
+    params(("game_id" = String, Path, description = "Twitch id of the game"), ShowParams),
+    request_body = Review,
+    responses(
+        (status = 200, body = Reviewed),
+        (status = 400, body = ErrorBody),
+    ),
+)]
+pub async fn review_all(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(game_id): Path<twitch::models::GameId>,
+    query: StdResult<Query<ShowParams>, QueryRejection>,
+    body: StdResult<Json<Review>, JsonRejection>,
+) -> Result<Json<Reviewed>> {
+    let Query(query) = query?;
+    let Json(Review { state }) = body?;
+
+    let db = s.db.lock().await;
+    let clip_ids = db::clip::select_ids(&db, &game_id, &query)?;
+    let moved =
+        db::review::transition_all(&db, &clip_ids, state, &session.user)?;
+
+    Ok(Json(Reviewed {
+        matched: clip_ids.len(),
+        moved,
+    }))
+}
+
+/// Who moved the clip from which state to which, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/clips/{clip_id}/reviews",
+    tag = "clips",
+    operation_id = "list_clip_reviews",
+    params(("clip_id" = String, Path, description = "Twitch id of the clip")),
+    responses((status = 200, body = [Transition])),
+)]
+pub async fn reviews(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+) -> Result<Json<Vec<Transition>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::review::select_transitions(&db, &clip_id)?))
+}
+
+/// Tags in use, most used first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags",
+    tag = "clips",
+    operation_id = "list_tags",
+    responses((status = 200, body = [Tag])),
+)]
+pub async fn tags(State(s): State<g::HttpState>) -> Result<Json<Vec<Tag>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::tag::select_all(&db)?))
+}
+
+/// Tags the clip, the tag is created if nobody used it yet.
+#[utoipa::path(
+    put,
+    path = "/api/v1/clips/{clip_id}/tags/{tag}",
+    tag = "clips",
+    operation_id = "tag_clip",
+    params(
+        ("clip_id" = String, Path, description = "Twitch id of the clip"),
+        ("tag" = String, Path, description = "Letters, digits and dashes"),
+    ),
+    responses(
+        (status = 200, body = Clip),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn tag(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path((clip_id, tag)): Path<(String, String)>,
+) -> Result<Json<Clip>> {
+    let db = s.db.lock().await;
+    db::clip::select_by_id(&db, &clip_id)?;
+    db::tag::tag(&db, &clip_id, &tag, &session.user)?;
+
+    Ok(Json(db::clip::select_by_id(&db, &clip_id)?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/clips/{clip_id}/tags/{tag}",
+    tag = "clips",
+    operation_id = "untag_clip",
+    params(
+        ("clip_id" = String, Path, description = "Twitch id of the clip"),
+        ("tag" = String, Path, description = "Letters, digits and dashes"),
+    ),
+    responses(
+        (status = 200, body = Clip),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn untag(
+    State(s): State<g::HttpState>,
+    Path((clip_id, tag)): Path<(String, String)>,
+) -> Result<Json<Clip>> {
+    let db = s.db.lock().await;
+    db::clip::select_by_id(&db, &clip_id)?;
+    db::tag::untag(&db, &clip_id, &tag)?;
+
+    Ok(Json(db::clip::select_by_id(&db, &clip_id)?))
+}
+
+/// Saved searches of all users by name, their query applies to clips of any
+/// game.
+#[utoipa::path(
+    get,
+    path = "/api/v1/searches",
+    tag = "clips",
+    operation_id = "list_saved_searches",
+    responses((status = 200, body = [SavedSearch])),
+)]
+pub async fn searches(
+    State(s): State<g::HttpState>,
+) -> Result<Json<Vec<SavedSearch>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::saved_search::select_all(&db)?))
+}
+
+/// The query takes the same parameters as listing clips.
+#[utoipa::path(
+    post,
+    path = "/api/v1/searches",
+    tag = "clips",
+    operation_id = "save_search",
+    request_body = NewSavedSearch,
+    responses(
+        (status = 201, body = SavedSearch),
+        (status = 400, body = ErrorBody),
+        (status = 409, body = ErrorBody, description = "Name is taken"),
+    ),
+)]
+pub async fn save_search(
+    State(s): State<g::HttpState>,
+    session: Session,
+    body: StdResult<Json<NewSavedSearch>, JsonRejection>,
+) -> Result<(StatusCode, Json<SavedSearch>)> {
+    let Json(search) = body?;
+    let search = search.validate()?;
+
+    let db = s.db.lock().await;
+    let search_id = db::saved_search::insert(&db, &search, &session.user)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(db::saved_search::select_by_id(&db, search_id)?),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/searches/{search_id}",
+    tag = "clips",
+    operation_id = "delete_saved_search",
+    params(("search_id" = i64, Path, description = "Id of the search")),
+    responses((status = 204)),
+)]
+pub async fn delete_search(
+    State(s): State<g::HttpState>,
+    Path(search_id): Path<i64>,
+) -> Result<StatusCode> {
+    let db = s.db.lock().await;
+    db::saved_search::delete(&db, search_id)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,174 @@
+use axum::{extract::rejection::JsonRejection, extract::Path};
+use hyper::StatusCode;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::db::game::Game;
+use crate::http::game;
+use crate::models::fetch_policy::FetchPolicy;
+use crate::prelude::*;
+
+/// Paused games come last.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games",
+    tag = "games",
+    operation_id = "list_games",
+    responses((status = 200, body = [Game])),
+)]
+pub async fn list(State(s): State<g::HttpState>) -> Result<Json<Vec<Game>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::game::select_all(&db)?))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddGame {
+    /// Twitch id of the game
+    #[schema(value_type = String)]
+    id: twitch::models::GameId,
+}
+
+/// Starts fetching clips of a game found on Twitch.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games",
+    tag = "games",
+    operation_id = "add_game",
+    request_body = AddGame,
+    responses(
+        (status = 201, body = Game),
+        (status = 404, body = ErrorBody, description = "Not on Twitch"),
+        (status = 409, body = ErrorBody, description = "Already added"),
+    ),
+)]
+pub async fn add(
+    State(s): State<g::HttpState>,
+    body: StdResult<Json<AddGame>, JsonRejection>,
+) -> Result<(StatusCode, Json<Game>)> {
+    let Json(AddGame { id }) = body?;
+
+    let Some(game) = s.twitch.get_game(id.clone()).await? else {
+        return Err(AppError::not_found(format!(
+            "Game with id {id} not found"
+        )));
+    };
+
+    let db = s.db.lock().await;
+    db::game::insert(&db, &game)?;
+
+    Ok((StatusCode::CREATED, Json(db::game::select_by_id(&db, &id)?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{game_id}",
+    tag = "games",
+    operation_id = "show_game",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    responses(
+        (status = 200, body = Game),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn show(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+) -> Result<Json<Game>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::game::select_by_id(&db, &game_id)?))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditGame {
+    /// Clips of paused games are not fetched on schedule
+    is_paused: Option<bool>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/games/{game_id}",
+    tag = "games",
+    operation_id = "edit_game",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = EditGame,
+    responses(
+        (status = 200, body = Game),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn edit(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<EditGame>, JsonRejection>,
+) -> Result<Json<Game>> {
+    let Json(EditGame { is_paused }) = body?;
+
+    let db = s.db.lock().await;
+    if let Some(is_paused) = is_paused {
+        db::game::set_is_paused(&db, &game_id, is_paused)?;
+    }
+
+    Ok(Json(db::game::select_by_id(&db, &game_id)?))
+}
+
+/// How clips of the game are fetched, unset fields fall back to the global
+/// settings.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{game_id}/fetch-policy",
+    tag = "games",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    responses(
+        (status = 200, body = FetchPolicy),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn show_fetch_policy(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+) -> Result<Json<FetchPolicy>> {
+    let db = s.db.lock().await;
+    db::game::select_by_id(&db, &game_id)?;
+    Ok(Json(db::fetch_policy::select_by_game(&db, &game_id)?))
+}
+
+/// Replaces the policy, an empty object resets it.
+#[utoipa::path(
+    put,
+    path = "/api/v1/games/{game_id}/fetch-policy",
+    tag = "games",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = FetchPolicy,
+    responses(
+        (status = 200, body = FetchPolicy),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn edit_fetch_policy(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<FetchPolicy>, JsonRejection>,
+) -> Result<Json<FetchPolicy>> {
+    let Json(policy) = body?;
+    Ok(Json(game::save_fetch_policy_(&s, &game_id, policy).await?))
+}
+
+/// Stops fetching clips of the game.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/games/{game_id}",
+    tag = "games",
+    operation_id = "delete_game",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    responses((status = 204)),
+)]
+pub async fn delete(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+) -> Result<StatusCode> {
+    let db = s.db.lock().await;
+    db::game::delete(&db, &game_id)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,223 @@
+use axum::extract::{rejection::JsonRejection, Path};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::http::{clips, media, music, projects};
+use crate::models::user::Session;
+use crate::prelude::*;
+
+#[derive(Serialize, ToSchema)]
+pub struct JobTriggered {
+    /// Name of the job as it appears in the logs
+    job: &'static str,
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+#[serde(default)]
+pub struct FetchClips {
+    /// The game's fetch policy applies if missing or zero
+    recorded_at_most_hours_ago: usize,
+    recorded_at_least_hours_ago: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LimitClips {
+    /// How many of the most viewed clips the job goes through
+    limit: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UploadProject {
+    /// Name of the edited video in the videos dir, see YOUTUBE_VIDEOS_DIR
+    #[schema(example = "best-of-october.mp4")]
+    video_file_name: String,
+}
+
+type Triggered = (StatusCode, Json<JobTriggered>);
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/jobs/fetch-clips",
+    tag = "jobs",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = FetchClips,
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn fetch_clips(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<FetchClips>, JsonRejection>,
+) -> Result<Triggered> {
+    let Json(FetchClips {
+        recorded_at_most_hours_ago,
+        recorded_at_least_hours_ago,
+    }) = body?;
+    ensure_game_exists(&s, &game_id).await?;
+
+    clips::spawn_fetch(
+        &s,
+        game_id,
+        recorded_at_most_hours_ago,
+        recorded_at_least_hours_ago,
+    )?;
+
+    Ok(triggered("fetch_new_game_clips"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/jobs/detect-moments",
+    tag = "jobs",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn detect_moments(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+) -> Result<Triggered> {
+    ensure_game_exists(&s, &game_id).await?;
+
+    clips::spawn_detect_moments(&s, game_id);
+
+    Ok(triggered("detect_moments"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/jobs/measure-loudness",
+    tag = "jobs",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = LimitClips,
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn measure_loudness(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<LimitClips>, JsonRejection>,
+) -> Result<Triggered> {
+    let Json(LimitClips { limit }) = body?;
+    ensure_game_exists(&s, &game_id).await?;
+
+    clips::spawn_measure_loudness(&s, game_id, limit);
+
+    Ok(triggered("measure_loudness"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/jobs/generate-previews",
+    tag = "jobs",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = LimitClips,
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn generate_previews(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<LimitClips>, JsonRejection>,
+) -> Result<Triggered> {
+    let Json(LimitClips { limit }) = body?;
+    ensure_game_exists(&s, &game_id).await?;
+
+    clips::spawn_generate_previews(&s, game_id, limit);
+
+    Ok(triggered("generate_previews"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{game_id}/jobs/scan-music",
+    tag = "jobs",
+    params(("game_id" = String, Path, description = "Twitch id of the game")),
+    request_body = LimitClips,
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn scan_music(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    body: StdResult<Json<LimitClips>, JsonRejection>,
+) -> Result<Triggered> {
+    let Json(LimitClips { limit }) = body?;
+    ensure_game_exists(&s, &game_id).await?;
+
+    music::spawn_scan(&s, game_id, limit);
+
+    Ok(triggered("scan_music"))
+}
+
+/// Uploads the edited video of a ready project to the connected YouTube
+/// channel, with generated title, chapters and credits, and publishes the
+/// project once it's uploaded.
+#[utoipa::path(
+    post,
+    path = "/api/v1/projects/{project_id}/jobs/upload",
+    tag = "jobs",
+    params(("project_id" = i64, Path, description = "Id of the project")),
+    request_body = UploadProject,
+    responses(
+        (status = 202, body = JobTriggered),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn upload_project(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+    body: StdResult<Json<UploadProject>, JsonRejection>,
+) -> Result<Triggered> {
+    let Json(UploadProject { video_file_name }) = body?;
+
+    projects::spawn_upload(&s, &session, project_id, &video_file_name).await?;
+
+    Ok(triggered("upload_project"))
+}
+
+/// Removes media of clips which are not kept from all workers.
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs/collect-media-garbage",
+    tag = "jobs",
+    responses((status = 202, body = JobTriggered)),
+)]
+pub async fn collect_media_garbage(
+    State(s): State<g::HttpState>,
+) -> Result<Triggered> {
+    media::spawn_gc(&s);
+
+    Ok(triggered("collect_media_garbage"))
+}
+
+fn triggered(job: &'static str) -> Triggered {
+    (StatusCode::ACCEPTED, Json(JobTriggered { job }))
+}
+
+/// Unlike the HTML pages, which only link to existing games, the API is
+/// told the game id by the client.
+async fn ensure_game_exists(
+    s: &g::HttpState,
+    game_id: &twitch::models::GameId,
+) -> Result<()> {
+    let db = s.db.lock().await;
+    db::game::select_by_id(&db, game_id).map(drop)
+}
@@ -0,0 +1,264 @@
+use axum::{extract::rejection::JsonRejection, extract::Path};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::project::{
+    self, Credit, Entry, EntryDetails, Project, ProjectDetails, ProjectStatus,
+};
+use crate::models::user::Session;
+use crate::prelude::*;
+
+#[derive(Serialize, ToSchema)]
+pub struct ProjectWithEntries {
+    project: Project,
+    /// In the order they play
+    entries: Vec<Entry>,
+    credits: Vec<Credit>,
+    /// One line per broadcaster, ready to paste into the video description
+    credits_text: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditProject {
+    title: Option<String>,
+    target_duration_secs: Option<u32>,
+    notes: Option<String>,
+    /// Publishing moves all of the clips to used in video
+    status: Option<ProjectStatus>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddEntry {
+    clip_id: String,
+    #[serde(flatten)]
+    #[schema(inline)]
+    details: EntryDetails,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditEntry {
+    #[serde(flatten)]
+    #[schema(inline)]
+    details: EntryDetails,
+    /// Starts at 0, the entries in between shift by one
+    position: Option<usize>,
+}
+
+/// Unpublished projects first, most recently changed first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects",
+    tag = "projects",
+    operation_id = "list_projects",
+    responses((status = 200, body = [Project])),
+)]
+pub async fn list(State(s): State<g::HttpState>) -> Result<Json<Vec<Project>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::project::select_all(&db)?))
+}
+
+/// Starts a project as a draft.
+#[utoipa::path(
+    post,
+    path = "/api/v1/projects",
+    tag = "projects",
+    operation_id = "create_project",
+    request_body = ProjectDetails,
+    responses(
+        (status = 201, body = ProjectWithEntries),
+        (status = 400, body = ErrorBody),
+    ),
+)]
+pub async fn create(
+    State(s): State<g::HttpState>,
+    session: Session,
+    body: StdResult<Json<ProjectDetails>, JsonRejection>,
+) -> Result<(StatusCode, Json<ProjectWithEntries>)> {
+    let Json(details) = body?;
+    let details = details.validate()?;
+
+    let db = s.db.lock().await;
+    let project_id = db::project::insert(&db, &details, &session.user)?;
+
+    Ok((StatusCode::CREATED, Json(with_entries(&db, project_id)?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects/{project_id}",
+    tag = "projects",
+    operation_id = "show_project",
+    params(("project_id" = i64, Path, description = "Id of the project")),
+    responses(
+        (status = 200, body = ProjectWithEntries),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn show(
+    State(s): State<g::HttpState>,
+    Path(project_id): Path<i64>,
+) -> Result<Json<ProjectWithEntries>> {
+    let db = s.db.lock().await;
+    Ok(Json(with_entries(&db, project_id)?))
+}
+
+/// Unset fields are left as they are, details of published projects cannot
+/// be changed.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/projects/{project_id}",
+    tag = "projects",
+    operation_id = "edit_project",
+    params(("project_id" = i64, Path, description = "Id of the project")),
+    request_body = EditProject,
+    responses(
+        (status = 200, body = ProjectWithEntries),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn edit(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+    body: StdResult<Json<EditProject>, JsonRejection>,
+) -> Result<Json<ProjectWithEntries>> {
+    let Json(EditProject {
+        title,
+        target_duration_secs,
+        notes,
+        status,
+    }) = body?;
+
+    let db = s.db.lock().await;
+    let project = db::project::select_by_id(&db, project_id)?;
+    if title.is_some() || target_duration_secs.is_some() || notes.is_some() {
+        let details = ProjectDetails {
+            title: title.unwrap_or(project.title),
+            target_duration_secs: target_duration_secs
+                .unwrap_or(project.target_duration_secs),
+            notes: notes.unwrap_or(project.notes),
+        }
+        .validate()?;
+        db::project::update(&db, project_id, &details)?;
+    }
+    if let Some(status) = status.filter(|status| *status != project.status) {
+        db::project::set_status(&db, project_id, status, &session.user)?;
+    }
+
+    Ok(Json(with_entries(&db, project_id)?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/projects/{project_id}",
+    tag = "projects",
+    operation_id = "delete_project",
+    params(("project_id" = i64, Path, description = "Id of the project")),
+    responses((status = 204)),
+)]
+pub async fn delete(
+    State(s): State<g::HttpState>,
+    Path(project_id): Path<i64>,
+) -> Result<StatusCode> {
+    let db = s.db.lock().await;
+    db::project::delete(&db, project_id)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Appends the clip to the project.
+#[utoipa::path(
+    post,
+    path = "/api/v1/projects/{project_id}/entries",
+    tag = "projects",
+    operation_id = "add_project_entry",
+    params(("project_id" = i64, Path, description = "Id of the project")),
+    request_body = AddEntry,
+    responses(
+        (status = 201, body = ProjectWithEntries),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody, description = "Already in project"),
+    ),
+)]
+pub async fn add_entry(
+    State(s): State<g::HttpState>,
+    Path(project_id): Path<i64>,
+    body: StdResult<Json<AddEntry>, JsonRejection>,
+) -> Result<(StatusCode, Json<ProjectWithEntries>)> {
+    let Json(AddEntry { clip_id, details }) = body?;
+
+    let db = s.db.lock().await;
+    db::project::insert_entry(&db, project_id, &clip_id, details)?;
+
+    Ok((StatusCode::CREATED, Json(with_entries(&db, project_id)?)))
+}
+
+/// Replaces the trims and notes, and moves the entry if a position is
+/// given.
+#[utoipa::path(
+    put,
+    path = "/api/v1/projects/{project_id}/entries/{entry_id}",
+    tag = "projects",
+    operation_id = "edit_project_entry",
+    params(("project_id" = i64, Path, description = "Id of the project"), ("entry_id" = i64, Path, description = "Id of the entry in the project")),
+    request_body = EditEntry,
+    responses(
+        (status = 200, body = ProjectWithEntries),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn edit_entry(
+    State(s): State<g::HttpState>,
+    Path((project_id, entry_id)): Path<(i64, i64)>,
+    body: StdResult<Json<EditEntry>, JsonRejection>,
+) -> Result<Json<ProjectWithEntries>> {
+    let Json(EditEntry { details, position }) = body?;
+
+    let db = s.db.lock().await;
+    db::project::update_entry(&db, project_id, entry_id, details)?;
+    if let Some(position) = position {
+        db::project::move_entry(&db, project_id, entry_id, position)?;
+    }
+
+    Ok(Json(with_entries(&db, project_id)?))
+}
+
+/// The entries after it move up.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/projects/{project_id}/entries/{entry_id}",
+    tag = "projects",
+    operation_id = "delete_project_entry",
+    params(("project_id" = i64, Path, description = "Id of the project"), ("entry_id" = i64, Path, description = "Id of the entry in the project")),
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    ),
+)]
+pub async fn delete_entry(
+    State(s): State<g::HttpState>,
+    Path((project_id, entry_id)): Path<(i64, i64)>,
+) -> Result<StatusCode> {
+    let db = s.db.lock().await;
+    db::project::delete_entry(&db, project_id, entry_id)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn with_entries(db: &DbConn, project_id: i64) -> Result<ProjectWithEntries> {
+    let project = db::project::select_by_id(db, project_id)?;
+    let entries = db::project::select_entries(db, project_id)?;
+    let credits = project::credits(&entries);
+
+    Ok(ProjectWithEntries {
+        credits_text: project::credits_text(&entries),
+        project,
+        entries,
+        credits,
+    })
+}
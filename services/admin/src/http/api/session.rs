@@ -0,0 +1,72 @@
+use axum::{
+    extract::rejection::JsonRejection,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use hyper::StatusCode;
+
+use crate::http::auth::{self, Login};
+use crate::models::user::Session;
+use crate::prelude::*;
+
+/// Logs in and sets the session cookie, which must be sent along with every
+/// other request.
+#[utoipa::path(
+    post,
+    path = "/api/v1/session",
+    tag = "session",
+    operation_id = "create_session",
+    request_body = Login,
+    responses(
+        (status = 201, body = Session),
+        (status = 401, body = ErrorBody),
+    ),
+)]
+pub async fn create(
+    State(s): State<g::HttpState>,
+    body: StdResult<Json<Login>, JsonRejection>,
+) -> Result<Response> {
+    let Json(credentials) = body?;
+    let session = auth::start_session(&s, credentials).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(header::SET_COOKIE, auth::session_cookie(&session))],
+        Json(session),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/session",
+    tag = "session",
+    operation_id = "show_session",
+    responses(
+        (status = 200, body = Session),
+        (status = 401, body = ErrorBody),
+    ),
+)]
+pub async fn show(session: Session) -> Json<Session> {
+    Json(session)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/session",
+    tag = "session",
+    operation_id = "delete_session",
+    responses((status = 204)),
+)]
+pub async fn delete(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Response> {
+    auth::end_session(&s, &session).await?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, auth::expired_session_cookie())],
+    )
+        .into_response())
+}
@@ -0,0 +1,74 @@
+use axum::extract::rejection::JsonRejection;
+
+use crate::http::settings;
+use crate::models::setting::{self, Change, Definition, Values};
+use crate::models::user::Session;
+use crate::prelude::*;
+
+/// How many of the latest changes are listed.
+const CHANGES_LIMIT: usize = 100;
+
+/// Values of all settings by their name, which is also what they are
+/// imported as.
+#[utoipa::path(
+    get,
+    path = "/api/v1/settings",
+    tag = "settings",
+    operation_id = "show_settings",
+    responses((status = 200, body = BTreeMap<String, Value>)),
+)]
+pub async fn show(State(s): State<g::HttpState>) -> Result<Json<Values>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::setting::select_all(&db)?))
+}
+
+/// Changes the given settings and answers with all of them.
+/// Nothing changes unless all of them are valid.
+/// Scheduled jobs pick up new cron expressions right away.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/settings",
+    tag = "settings",
+    operation_id = "edit_settings",
+    request_body = BTreeMap<String, Value>,
+    responses(
+        (status = 200, body = BTreeMap<String, Value>),
+        (status = 400, body = ErrorBody),
+    ),
+)]
+pub async fn edit(
+    State(s): State<g::HttpState>,
+    session: Session,
+    body: StdResult<Json<Values>, JsonRejection>,
+) -> Result<Json<Values>> {
+    let Json(values) = body?;
+    settings::apply(&s, &session, values).await?;
+
+    let db = s.db.lock().await;
+    Ok(Json(db::setting::select_all(&db)?))
+}
+
+/// What settings there are and what values they accept.
+#[utoipa::path(
+    get,
+    path = "/api/v1/settings/definitions",
+    tag = "settings",
+    responses((status = 200, body = [Definition])),
+)]
+pub async fn definitions() -> Json<&'static [&'static Definition]> {
+    Json(&setting::REGISTRY)
+}
+
+/// Who changed which setting, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/settings/changes",
+    tag = "settings",
+    responses((status = 200, body = [Change])),
+)]
+pub async fn changes(
+    State(s): State<g::HttpState>,
+) -> Result<Json<Vec<Change>>> {
+    let db = s.db.lock().await;
+    Ok(Json(db::setting::select_changes(&db, CHANGES_LIMIT)?))
+}
@@ -0,0 +1,284 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, OriginalUri},
+    headers::Cookie,
+    http::{header, request::Parts, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Form, TypedHeader,
+};
+use serde::Deserialize;
+use std::sync::LazyLock;
+use utoipa::ToSchema;
+use worker::constant_time_eq;
+
+use crate::models::user::{self, Role, Session};
+use crate::prelude::*;
+
+const SESSION_COOKIE: &str = "session";
+const SESSION_TTL_DAYS: i64 = 14;
+/// Name of the hidden input of every form which posts.
+const CSRF_FIELD: &str = "csrf-token";
+/// Scripts send the token in a header instead.
+const CSRF_HEADER: &str = "x-csrf-token";
+/// Verified against when the username doesn't exist.
+static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    user::hash_password(&user::new_token()).expect("Token is a valid password")
+});
+
+/// Lets through only requests with a valid session, which handlers then
+/// extract.
+/// Requests which change something must also carry the CSRF token of the
+/// session.
+pub async fn authenticate(
+    State(s): State<g::HttpState>,
+    OriginalUri(uri): OriginalUri,
+    cookie: Option<TypedHeader<Cookie>>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response> {
+    let session = match cookie.as_ref().and_then(|c| c.get(SESSION_COOKIE)) {
+        Some(token) => {
+            let db = s.db.lock().await;
+            db::user::select_session(&db, token)?
+        }
+        None => None,
+    };
+    let Some(session) = session else {
+        // nested routers only see the rest of the path
+        let is_page =
+            req.method() == Method::GET && !uri.path().starts_with("/api/");
+        return if is_page {
+            Ok(Redirect::to("/login").into_response())
+        } else {
+            Err(AppError::unauthorized("Log in first"))
+        };
+    };
+
+    let mut req = if is_safe(req.method()) {
+        req
+    } else {
+        verify_csrf_token(&session, req).await?
+    };
+    req.extensions_mut().insert(session);
+
+    Ok(next.run(req).await)
+}
+
+/// Rejects users whose role is below given one.
+/// Must run after [`authenticate`].
+pub async fn require_role(
+    State(role): State<Role>,
+    session: Session,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response> {
+    if session.user.role < role {
+        return Err(AppError::forbidden(format!(
+            "Only {} users can do this",
+            <&str>::from(role)
+        )));
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Session {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(|| AppError::unauthorized("Log in first"))
+    }
+}
+
+pub async fn login_page(
+    State(s): State<g::HttpState>,
+    cookie: Option<TypedHeader<Cookie>>,
+) -> Result<Response> {
+    if let Some(token) = cookie.as_ref().and_then(|c| c.get(SESSION_COOKIE)) {
+        let db = s.db.lock().await;
+        if db::user::select_session(&db, token)?.is_some() {
+            return Ok(Redirect::to("/").into_response());
+        }
+    }
+
+    Ok(s.views.login()?.into_response())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+}
+
+/// There's no CSRF token before logging in, the session cookie being
+/// `SameSite` keeps other sites from using it anyway.
+pub async fn login(
+    State(s): State<g::HttpState>,
+    Form(credentials): Form<Login>,
+) -> Result<impl IntoResponse> {
+    let session = start_session(&s, credentials).await?;
+
+    Ok((
+        [(header::SET_COOKIE, session_cookie(&session))],
+        Redirect::to("/"),
+    ))
+}
+
+pub async fn logout(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<impl IntoResponse> {
+    end_session(&s, &session).await?;
+
+    Ok((
+        [(header::SET_COOKIE, expired_session_cookie())],
+        Redirect::to("/login"),
+    ))
+}
+
+/// Verifies the credentials and stores a new session for the user.
+pub(super) async fn start_session(
+    s: &g::HttpState,
+    Login { username, password }: Login,
+) -> Result<Session> {
+    let found = {
+        let db = s.db.lock().await;
+        db::user::select_by_username(&db, &username)?
+    };
+
+    // hashing takes a while on purpose, also when there is no such user so
+    // that usernames cannot be told apart by how long the login takes
+    let user = tokio::task::spawn_blocking(move || match found {
+        Some((user, hash)) => {
+            user::verify_password(&password, &hash).then_some(user)
+        }
+        None => {
+            user::verify_password(&password, &DUMMY_PASSWORD_HASH);
+            None
+        }
+    })
+    .await
+    .map_err(AnyError::from)?
+    .ok_or_else(|| AppError::unauthorized("Wrong username or password"))?;
+
+    let session = Session {
+        token: user::new_token(),
+        csrf_token: user::new_token(),
+        user,
+        expires_at: chrono::Utc::now()
+            + chrono::Duration::days(SESSION_TTL_DAYS),
+    };
+    let db = s.db.lock().await;
+    db::user::insert_session(&db, &session)?;
+    info!("User {} logged in", session.user.username);
+
+    Ok(session)
+}
+
+pub(super) async fn end_session(
+    s: &g::HttpState,
+    session: &Session,
+) -> Result<()> {
+    let db = s.db.lock().await;
+    db::user::delete_session(&db, &session.token)?;
+    info!("User {} logged out", session.user.username);
+
+    Ok(())
+}
+
+/// Not readable by scripts and only sent along with top level navigation
+/// from other sites.
+pub(super) fn session_cookie(session: &Session) -> HeaderValue {
+    let max_age = session.expires_at - chrono::Utc::now();
+    HeaderValue::from_str(&format!(
+        "{SESSION_COOKIE}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        session.token,
+        max_age.num_seconds()
+    ))
+    .expect("Token is hex")
+}
+
+pub(super) fn expired_session_cookie() -> HeaderValue {
+    HeaderValue::from_str(&format!(
+        "{SESSION_COOKIE}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0"
+    ))
+    .expect("Cookie is ascii")
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Reads the token from the header, or from the form body which is then put
+/// back for the handler.
+async fn verify_csrf_token(
+    session: &Session,
+    req: Request<Body>,
+) -> Result<Request<Body>> {
+    if let Some(token) = req.headers().get(CSRF_HEADER) {
+        return if constant_time_eq(
+            token.as_bytes(),
+            session.csrf_token.as_bytes(),
+        ) {
+            Ok(req)
+        } else {
+            Err(AppError::forbidden("Invalid CSRF token"))
+        };
+    }
+
+    let is_form = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Err(AppError::forbidden(format!(
+            "Missing {CSRF_HEADER} header"
+        )));
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = hyper::body::to_bytes(body).await.map_err(AnyError::from)?;
+    let is_valid =
+        form_urlencoded_field(&bytes, CSRF_FIELD).is_some_and(|token| {
+            constant_time_eq(token.as_bytes(), session.csrf_token.as_bytes())
+        });
+    if !is_valid {
+        return Err(AppError::forbidden(
+            "Invalid CSRF token, reload the page and try again",
+        ));
+    }
+
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+/// Tokens are hex so they need no percent decoding.
+fn form_urlencoded_field<'a>(body: &'a [u8], name: &str) -> Option<&'a str> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_csrf_token_in_forms() {
+        let body = b"limit=10&csrf-token=abc123&title=a%26b";
+        assert_eq!(form_urlencoded_field(body, CSRF_FIELD), Some("abc123"));
+        assert_eq!(form_urlencoded_field(body, "title"), Some("a%26b"));
+        assert_eq!(form_urlencoded_field(b"limit=10", CSRF_FIELD), None);
+        assert_eq!(form_urlencoded_field(b"", CSRF_FIELD), None);
+    }
+}
@@ -1,11 +1,23 @@
 use axum::{
-    extract::{Path, Query},
-    response::{Html, Redirect},
+    extract::{Path, Query, RawQuery},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
+use itertools::Itertools;
 use serde::Deserialize;
+use serde_json::json;
 use std::sync::Arc;
+use tokio::sync::Mutex;
 
+use crate::job::history;
+use crate::models::clip::{Clip, ShowParams};
+use crate::models::clip_import::{ImportClips, ImportReport, ImportedClip};
+use crate::models::export::{ExportColumn, ExportParams, Exporter};
+use crate::models::job_run::Trigger;
+use crate::models::review::ReviewState;
+use crate::models::saved_search::NewSavedSearch;
+use crate::models::user::Session;
 use crate::prelude::*;
 
 #[derive(Deserialize, Debug)]
@@ -24,8 +36,23 @@ pub async fn trigger_fetch(
         recorded_at_least_hours_ago: at_least,
     }): Form<TriggerFetchClipsJob>,
 ) -> Result<Redirect> {
-    let at_most = at_most.unwrap_or_default();
-    let at_least = at_least.unwrap_or_default();
+    spawn_fetch(
+        &s,
+        game_id.clone(),
+        at_most.unwrap_or_default(),
+        at_least.unwrap_or_default(),
+    )?;
+
+    Ok(Redirect::to(&format!("/game/{game_id}")))
+}
+
+/// Zero hours means the game's fetch policy applies.
+pub(super) fn spawn_fetch(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+    at_most: usize,
+    at_least: usize,
+) -> Result<()> {
     if at_most != 0 && at_most <= at_least {
         return Err(AppError::bad_request(
             "Recorded 'at most' must be greater than to 'at least'",
@@ -52,20 +79,434 @@ pub async fn trigger_fetch(
                 Some(chrono::Duration::hours(at_least as i64))
             },
         },
-        game_id.clone(),
+        game_id,
+        Trigger::Manual,
     ));
 
+    Ok(())
+}
+
+pub async fn trigger_detect_moments(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+) -> Result<Redirect> {
+    spawn_detect_moments(&s, game_id.clone());
+
     Ok(Redirect::to(&format!("/game/{game_id}")))
 }
 
-pub async fn show(
+pub(super) fn spawn_detect_moments(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+) {
+    info!("Triggering detect moments job for game {game_id}");
+
+    let (db, workers) = (Arc::clone(&s.db), s.workers.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "detect_moments",
+        Trigger::Manual,
+        vec![game_id.to_string()],
+        json!({}),
+        |_| crate::job::detect_moments::once(db, workers, game_id),
+    ));
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TriggerMeasureLoudness {
+    limit: usize,
+}
+
+pub async fn trigger_measure_loudness(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    Form(TriggerMeasureLoudness { limit }): Form<TriggerMeasureLoudness>,
+) -> Result<Redirect> {
+    spawn_measure_loudness(&s, game_id.clone(), limit);
+
+    Ok(Redirect::to(&format!("/game/{game_id}")))
+}
+
+pub(super) fn spawn_measure_loudness(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) {
+    info!(
+        "Triggering measure loudness job for {limit} clips of game {game_id}"
+    );
+
+    let (db, workers) = (Arc::clone(&s.db), s.workers.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "measure_loudness",
+        Trigger::Manual,
+        vec![game_id.to_string()],
+        json!({ "limit": limit }),
+        move |_| {
+            crate::job::measure_loudness::once(db, workers, game_id, limit)
+        },
+    ));
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TriggerGeneratePreviews {
+    limit: usize,
+}
+
+pub async fn trigger_generate_previews(
     State(s): State<g::HttpState>,
     Path(game_id): Path<twitch::models::GameId>,
+    Form(TriggerGeneratePreviews { limit }): Form<TriggerGeneratePreviews>,
+) -> Result<Redirect> {
+    spawn_generate_previews(&s, game_id.clone(), limit);
+
+    Ok(Redirect::to(&format!("/game/{game_id}")))
+}
+
+pub(super) fn spawn_generate_previews(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) {
+    info!(
+        "Triggering generate previews job for {limit} clips of game {game_id}"
+    );
+
+    let (db, workers) = (Arc::clone(&s.db), s.workers.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "generate_previews",
+        Trigger::Manual,
+        vec![game_id.to_string()],
+        json!({ "limit": limit }),
+        move |_| {
+            crate::job::generate_previews::once(db, workers, game_id, limit)
+        },
+    ));
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Review {
+    state: ReviewState,
+}
+
+pub async fn review(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(clip_id): Path<String>,
+    headers: HeaderMap,
+    Form(Review { state }): Form<Review>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let clip = db::clip::select_by_id(&db, &clip_id)?;
+    db::review::transition(&db, &clip.id, state, &session.user)?;
+
+    Ok(super::media::back(
+        &headers,
+        &format!("/game/{}/clips", clip.game_id),
+    ))
+}
+
+/// Moves all clips which match the filters, on any page, which can become
+/// given state.
+pub async fn review_all(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(game_id): Path<twitch::models::GameId>,
     Query(query): Query<models::clip::ShowParams>,
+    RawQuery(raw_query): RawQuery,
+    Form(Review { state }): Form<Review>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let clip_ids = db::clip::select_ids(&db, &game_id, &query)?;
+    db::review::transition_all(&db, &clip_ids, state, &session.user)?;
+
+    Ok(Redirect::to(&format!(
+        "/game/{game_id}/clips?{}",
+        raw_query.unwrap_or_default()
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Tag {
+    tag: String,
+}
+
+pub async fn tag(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(clip_id): Path<String>,
+    headers: HeaderMap,
+    Form(Tag { tag }): Form<Tag>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let clip = db::clip::select_by_id(&db, &clip_id)?;
+    db::tag::tag(&db, &clip.id, &tag, &session.user)?;
+
+    Ok(super::media::back(
+        &headers,
+        &format!("/game/{}/clips", clip.game_id),
+    ))
+}
+
+pub async fn untag(
+    State(s): State<g::HttpState>,
+    Path((clip_id, tag)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let clip = db::clip::select_by_id(&db, &clip_id)?;
+    db::tag::untag(&db, &clip.id, &tag)?;
+
+    Ok(super::media::back(
+        &headers,
+        &format!("/game/{}/clips", clip.game_id),
+    ))
+}
+
+/// Saves the filters the clips page is showing, and shows it again.
+pub async fn save_search(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(game_id): Path<twitch::models::GameId>,
+    Form(search): Form<NewSavedSearch>,
+) -> Result<Redirect> {
+    let search = search.validate()?;
+
+    let db = s.db.lock().await;
+    db::saved_search::insert(&db, &search, &session.user)?;
+
+    Ok(Redirect::to(&format!(
+        "/game/{game_id}/clips?{}",
+        search.query
+    )))
+}
+
+pub async fn delete_search(
+    State(s): State<g::HttpState>,
+    Path(search_id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::saved_search::delete(&db, search_id)?;
+
+    Ok(super::media::back(&headers, "/"))
+}
+
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(game_id): Path<twitch::models::GameId>,
+    Query(query): Query<ShowParams>,
+    RawQuery(raw_query): RawQuery,
 ) -> Result<Html<String>> {
     let db = s.db.lock().await;
+    s.views.clips(
+        &session,
+        &db,
+        &game_id,
+        query,
+        raw_query.as_deref().unwrap_or_default(),
+    )
+}
+
+pub async fn import_page(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    s.views.clip_import(&session, None)
+}
+
+/// Shows what was imported rather than redirecting, the report is not
+/// stored.
+pub async fn import(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Form(ImportClips { clips }): Form<ImportClips>,
+) -> Result<Html<String>> {
+    let report = import_clips(&s, &session, &clips).await?;
+
+    s.views.clip_import(&session, Some(&report))
+}
+
+/// Twitch is asked for clips this many at a time.
+const IMPORT_BATCH_SIZE: usize = 100;
 
-    let (total_count, clips) = db::clip::list(&db, &game_id, &query)?;
+/// Stores given clips whatever their game, recording date or view count,
+/// for clips the fetch job missed.
+/// Games which are not tracked yet are added paused, so that only the
+/// imported clips of them are stored.
+/// Imported clips don't move where the fetch job carries on from.
+pub(super) async fn import_clips(
+    s: &g::HttpState,
+    session: &Session,
+    clips: &str,
+) -> Result<ImportReport> {
+    let clip_ids = models::clip_import::parse_clip_ids(clips)?;
+
+    let mut found = vec![];
+    for batch in clip_ids.chunks(IMPORT_BATCH_SIZE) {
+        found.extend(s.twitch.get_clips_by_ids(batch.to_vec()).await?);
+    }
+    let not_found = clip_ids
+        .into_iter()
+        .filter(|clip_id| !found.iter().any(|clip| &clip.id == clip_id))
+        .collect_vec();
+
+    let tracked_game_ids = {
+        let db = s.db.lock().await;
+        db::game::select_all(&db)?
+            .into_iter()
+            .map(|game| game.id)
+            .collect_vec()
+    };
+    // some clips aren't of any game
+    let untracked_game_ids = found
+        .iter()
+        .map(|clip| clip.game_id.as_str())
+        .filter(|game_id| !game_id.is_empty())
+        .unique()
+        .map(twitch::models::GameId::from)
+        .filter(|game_id| !tracked_game_ids.contains(game_id))
+        .collect_vec();
+    let mut untracked_games = vec![];
+    for game_id in untracked_game_ids {
+        match s.twitch.get_game(game_id.clone()).await? {
+            Some(game) => untracked_games.push(game),
+            None => warn!("Game {game_id} of imported clips not on Twitch"),
+        }
+    }
+
+    let mut db = s.db.lock().await;
+    let tx = db.transaction()?;
+    let mut added_games = vec![];
+    for game in &untracked_games {
+        db::game::insert(&tx, game)?;
+        db::game::set_is_paused(&tx, &game.id, true)?;
+        added_games.push(db::game::select_by_id(&tx, &game.id)?);
+    }
+    let inserted = db::clip::upsert_imported(&tx, &found, &session.user)?;
+    tx.commit()?;
+
+    info!(
+        "Imported {} clips, {} of them new, {} not found on Twitch",
+        found.len(),
+        inserted.len(),
+        not_found.len()
+    );
+
+    let (inserted, updated) = found
+        .iter()
+        .map(ImportedClip::from)
+        .partition(|clip| inserted.contains(&clip.id));
+    Ok(ImportReport {
+        inserted,
+        updated,
+        not_found,
+        added_games,
+    })
+}
+
+/// Clips are exported this many at a time, the database is locked only
+/// while a batch is read.
+const EXPORT_BATCH_SIZE: usize = 500;
+
+pub async fn export(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    Query(query): Query<ShowParams>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response> {
+    export_clips(&s, game_id, query, params).await
+}
+
+/// Streams all clips which match the filters, whatever the page size and
+/// offset.
+pub(super) async fn export_clips(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+    mut query: ShowParams,
+    params: ExportParams,
+) -> Result<Response> {
+    let columns = ExportColumn::parse_listed(&params.columns)?;
+    query.page_size = EXPORT_BATCH_SIZE;
+    query.page_offset = 0;
+    query.cursor = None;
+
+    // invalid filters fail the request rather than the download, clips are
+    // not counted as the export goes through all of them anyway
+    let first_batch = {
+        let db = s.db.lock().await;
+        db::clip::select_page(&db, &game_id, &query)?
+    };
+
+    let filename = format!("clips-{game_id}.{}", params.format.extension());
+    let (mut sender, body) = hyper::Body::channel();
+    let db = Arc::clone(&s.db);
+    let exporter = Exporter::new(params.format, columns);
+    tokio::spawn(async move {
+        if let Err(e) = stream_export(
+            &db,
+            &game_id,
+            query,
+            exporter,
+            first_batch,
+            &mut sender,
+        )
+        .await
+        {
+            error!("Cannot export clips of game {game_id}: {e}");
+            sender.abort();
+        }
+    });
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                params.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        axum::body::StreamBody::new(body),
+    )
+        .into_response())
+}
+
+async fn stream_export(
+    db: &Mutex<DbConn>,
+    game_id: &twitch::models::GameId,
+    mut query: ShowParams,
+    mut exporter: Exporter,
+    (mut clips, mut next_cursor): (Vec<Clip>, Option<String>),
+    sender: &mut hyper::body::Sender,
+) -> Result<()> {
+    let mut chunk = exporter.start()?;
+    loop {
+        chunk.extend(exporter.write(&clips)?);
+        let Some(cursor) = next_cursor else {
+            break;
+        };
+        sender
+            .send_data(std::mem::take(&mut chunk).into())
+            .await
+            .map_err(AnyError::from)?;
+
+        query.cursor = Some(cursor);
+        (clips, next_cursor) = {
+            let db = db.lock().await;
+            db::clip::select_page(&db, game_id, &query)?
+        };
+    }
+    chunk.extend(exporter.end());
+    sender
+        .send_data(chunk.into())
+        .await
+        .map_err(AnyError::from)?;
 
-    s.views.clips(&db, &game_id, total_count, clips, query)
+    Ok(())
 }
@@ -1,10 +1,21 @@
+use crate::models::user::{Role, Session};
 use crate::prelude::*;
 use axum::response::Redirect;
 
-pub async fn reset(State(s): State<g::HttpState>) -> Result<Redirect> {
+/// Everyone is logged out, only the admin who reset keeps their account so
+/// that they can log back in.
+pub async fn reset(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Redirect> {
     let mut db = s.db.lock().await;
+    let (_, password_hash) =
+        db::user::select_by_username(&db, &session.user.username)?
+            .ok_or_else(|| AppError::unauthorized("Log in first"))?;
+
     db::down(&mut db)?;
     db::up(&mut db)?;
+    db::user::insert(&db, &session.user.username, &password_hash, Role::Admin)?;
 
-    Ok(Redirect::to("/"))
+    Ok(Redirect::to("/login"))
 }
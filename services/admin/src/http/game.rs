@@ -1,13 +1,18 @@
 use axum::{
     extract::{Path, Query},
     response::{Html, Redirect},
+    Form,
 };
+use serde::Deserialize;
 use std::collections::HashMap;
 
+use crate::models::fetch_policy::FetchPolicy;
+use crate::models::user::Session;
 use crate::prelude::*;
 
 pub async fn search(
     State(s): State<g::HttpState>,
+    session: Session,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Html<String>> {
     let query = params
@@ -16,15 +21,16 @@ pub async fn search(
 
     let games = s.twitch.search_for_game(query).await?;
 
-    s.views.search_game(&games)
+    s.views.search_game(&session, &games)
 }
 
 pub async fn show(
     State(s): State<g::HttpState>,
+    session: Session,
     Path(game_id): Path<twitch::models::GameId>,
 ) -> Result<Html<String>> {
     let db = s.db.lock().await;
-    s.views.game(&db, &game_id)
+    s.views.game(&session, &db, &game_id)
 }
 
 pub async fn add(
@@ -71,6 +77,59 @@ pub async fn resume(
     Ok(Redirect::to(&format!("/game/{game_id}")))
 }
 
+/// Empty fields fall back to the global settings.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct SaveFetchPolicy {
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    fetch_every_hours: Option<u32>,
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    recorded_at_least_hours_ago: Option<u32>,
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    recorded_at_most_hours_ago: Option<u32>,
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    min_view_count: Option<u32>,
+    /// Comma separated
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    langs: Vec<String>,
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    max_pages_per_run: Option<u32>,
+}
+
+pub async fn save_fetch_policy(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    Form(form): Form<SaveFetchPolicy>,
+) -> Result<Redirect> {
+    let policy = FetchPolicy {
+        fetch_every_hours: form.fetch_every_hours,
+        recorded_at_least_hours_ago: form.recorded_at_least_hours_ago,
+        recorded_at_most_hours_ago: form.recorded_at_most_hours_ago,
+        min_view_count: form.min_view_count,
+        langs: form.langs,
+        max_pages_per_run: form.max_pages_per_run,
+    };
+    save_fetch_policy_(&s, &game_id, policy).await?;
+
+    Ok(Redirect::to(&format!("/game/{game_id}")))
+}
+
+/// Validates the policy of a game which exists.
+pub(super) async fn save_fetch_policy_(
+    s: &g::HttpState,
+    game_id: &twitch::models::GameId,
+    policy: FetchPolicy,
+) -> Result<FetchPolicy> {
+    let policy = policy.validate()?;
+
+    let db = s.db.lock().await;
+    db::game::select_by_id(&db, game_id)?;
+    db::fetch_policy::save(&db, game_id, &policy)?;
+    info!("Saved fetch policy of game {game_id}: {policy:?}");
+
+    Ok(policy)
+}
+
 async fn set_is_paused(
     s: &g::HttpState,
     game_id: twitch::models::GameId,
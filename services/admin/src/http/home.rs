@@ -1,7 +1,11 @@
+use crate::models::user::Session;
 use crate::prelude::*;
 use axum::response::Html;
 
-pub async fn page(State(s): State<g::HttpState>) -> Result<Html<String>> {
+pub async fn page(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
     let db = s.db.lock().await;
-    s.views.homepage(&db)
+    s.views.homepage(&session, &db)
 }
@@ -0,0 +1,18 @@
+use crate::models::user::Session;
+use crate::prelude::*;
+use axum::response::Html;
+
+/// How many of the latest runs are listed.
+const RUNS_LIMIT: usize = 100;
+
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let next_ticks = s.jobs.next_ticks().await?;
+
+    let db = s.db.lock().await;
+    let runs = db::job_run::select_latest(&db, RUNS_LIMIT)?;
+
+    s.views.jobs(&session, &runs, &next_ticks)
+}
@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::job::{collect_media_garbage, history};
+use crate::models::job_run::Trigger;
+use crate::models::preview::PreviewKind;
+use crate::models::user::Session;
+use crate::prelude::*;
+
+/// Artifacts are read from workers in pieces of this size, well below the
+/// 4 MB limit on gRPC messages.
+const ARTIFACT_CHUNK_BYTES: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ShowMedia {
+    /// Asks every worker what it would delete, which has them walk their
+    /// whole media store
+    #[serde(default)]
+    preview: bool,
+}
+
+/// Kept clips, and when asked for, previews what the next garbage
+/// collection would delete on each worker.
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Query(ShowMedia { preview }): Query<ShowMedia>,
+) -> Result<Html<String>> {
+    let previews = if preview {
+        Some(
+            collect_media_garbage::once(
+                Arc::clone(&s.db),
+                s.workers.clone(),
+                true,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    let db = s.db.lock().await;
+    s.views.media(&session, &db, previews.as_deref())
+}
+
+pub async fn trigger_gc(State(s): State<g::HttpState>) -> Result<Redirect> {
+    spawn_gc(&s);
+
+    Ok(Redirect::to("/media"))
+}
+
+pub(super) fn spawn_gc(s: &g::HttpState) {
+    info!("Triggering media garbage collection");
+
+    let (db, workers) = (Arc::clone(&s.db), s.workers.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "collect_media_garbage",
+        Trigger::Manual,
+        vec![],
+        json!({}),
+        |_| collect_media_garbage::once(db, workers, false),
+    ));
+}
+
+#[derive(Deserialize)]
+pub struct Artifact {
+    /// The artifact is only on the worker which created it
+    worker: SocketAddr,
+    key: String,
+}
+
+/// Streams an artifact such as a rendered short from the worker it's on.
+pub async fn download_artifact(
+    State(s): State<g::HttpState>,
+    Query(Artifact { worker, key }): Query<Artifact>,
+) -> Result<impl IntoResponse> {
+    stream_artifact(s.workers, worker, key).await
+}
+
+/// Streams a preview of the clip from the worker which generated it.
+pub async fn preview(
+    State(s): State<g::HttpState>,
+    Path((clip_id, kind)): Path<(String, PreviewKind)>,
+) -> Result<impl IntoResponse> {
+    let preview = db::preview::select_by_clip(&*s.db.lock().await, &clip_id)?;
+    let Some((worker, key)) = preview.as_ref().and_then(|preview| {
+        Some((preview.worker_addr, preview.key(kind)?.to_string()))
+    }) else {
+        return Err(AppError::not_found(format!(
+            "Clip {clip_id} has no {kind:?} preview"
+        )));
+    };
+
+    stream_artifact(s.workers, worker, key).await
+}
+
+async fn stream_artifact(
+    workers: WorkerPool,
+    worker: SocketAddr,
+    key: String,
+) -> Result<impl IntoResponse> {
+    let read = move |workers: WorkerPool, key: String, start: u64| async move {
+        workers
+            .call_on(worker, |mut worker| async move {
+                worker
+                    .read_artifact(worker::rpc::ReadArtifactRequest {
+                        key,
+                        start: Some(start),
+                        end: Some(start + ARTIFACT_CHUNK_BYTES),
+                    })
+                    .await
+            })
+            .await
+            .map(|resp| resp.into_inner().data)
+    };
+
+    // the first chunk is read before responding so that a missing artifact
+    // is an error page rather than an empty download
+    let first = read(workers.clone(), key.clone(), 0).await?;
+
+    let file_name = key.rsplit('/').next().unwrap_or(&key).to_string();
+    let content_type = match key.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("mp4") => "video/mp4",
+        Some("png") => "image/png",
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    };
+    // images open in the browser, e.g. a composed thumbnail, and can be
+    // saved from there, anything else is downloaded
+    let disposition = if content_type.starts_with("image/") {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    let (mut sender, body) = hyper::Body::channel();
+    tokio::spawn(async move {
+        let mut chunk = first;
+        let mut start = 0;
+        loop {
+            let len = chunk.len() as u64;
+            if len > 0 && sender.send_data(chunk.into()).await.is_err() {
+                // the user cancelled the download
+                return;
+            }
+            if len < ARTIFACT_CHUNK_BYTES {
+                return;
+            }
+
+            start += len;
+            chunk = match read(workers.clone(), key.clone(), start).await {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    warn!("Cannot read {key} from worker {worker}: {e}");
+                    sender.abort();
+                    return;
+                }
+            };
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("{disposition}; filename=\"{file_name}\""),
+            ),
+        ],
+        axum::body::boxed(body),
+    ))
+}
+
+pub async fn keep(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::retention::keep(&db, &clip_id)?;
+
+    Ok(back(&headers, "/media"))
+}
+
+pub async fn release(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::retention::release(&db, &clip_id)?;
+
+    Ok(back(&headers, "/media"))
+}
+
+/// Clips are kept and reviewed from the clips listing as well as other
+/// pages, and the listing has filters in its query we don't want to lose.
+pub(super) fn back(headers: &HeaderMap, fallback: &str) -> Redirect {
+    let referer = headers
+        .get(header::REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .unwrap_or(fallback);
+
+    Redirect::to(referer)
+}
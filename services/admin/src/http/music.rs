@@ -0,0 +1,189 @@
+use axum::{
+    extract::Path,
+    response::{Html, Redirect},
+    Form,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::job::history;
+use crate::models::job_run::Trigger;
+use crate::models::music::MusicAction;
+use crate::models::user::Session;
+use crate::prelude::*;
+use crate::worker_pool::Route;
+
+/// Every worker keeps its own copy of the reference tracks, with its own
+/// ids.
+/// They are always listed from the same worker so that the ids on the page
+/// stay the same.
+const TRACKS_ROUTE: Route = Route::Affinity("reference_tracks");
+
+pub async fn tracks(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let tracks = list_tracks(&s.workers).await?;
+
+    s.views.music(&session, &tracks)
+}
+
+#[derive(Deserialize)]
+pub struct AddTrack {
+    title: String,
+    source: String,
+}
+
+/// Adds the track to every worker, as any of them can be scanning clips.
+pub async fn add_track(
+    State(s): State<g::HttpState>,
+    Form(AddTrack { title, source }): Form<AddTrack>,
+) -> Result<Redirect> {
+    let results = s
+        .workers
+        .call_each(|mut worker| {
+            let request = worker::rpc::AddReferenceTrackRequest {
+                title: title.clone(),
+                source: source.clone(),
+            };
+            async move { worker.add_reference_track(request).await }
+        })
+        .await;
+    ensure_on_each_worker("add", results)?;
+
+    Ok(Redirect::to("/music"))
+}
+
+/// Deletes the track from every worker.
+/// Since the id is only valid on the worker tracks are listed from, the
+/// track is looked up on the others by its title and source.
+pub async fn delete_track(
+    State(s): State<g::HttpState>,
+    Path(track_id): Path<i64>,
+) -> Result<Redirect> {
+    let track = list_tracks(&s.workers)
+        .await?
+        .into_iter()
+        .find(|track| track.id == track_id)
+        .ok_or_else(|| AppError::bad_request("No such reference track"))?;
+
+    let results = s
+        .workers
+        .call_each(|mut worker| {
+            let track = track.clone();
+            async move {
+                let tracks =
+                    worker.list_reference_tracks(()).await?.into_inner().tracks;
+                let copies = tracks.into_iter().filter(|copy| {
+                    copy.title == track.title && copy.source == track.source
+                });
+                for copy in copies {
+                    worker
+                        .delete_reference_track(
+                            worker::rpc::DeleteReferenceTrackRequest {
+                                track_id: copy.id,
+                            },
+                        )
+                        .await?;
+                }
+
+                Ok(())
+            }
+        })
+        .await;
+    ensure_on_each_worker("delete", results)?;
+
+    Ok(Redirect::to("/music"))
+}
+
+async fn list_tracks(
+    workers: &WorkerPool,
+) -> Result<Vec<worker::rpc::ReferenceTrack>> {
+    let resp = workers
+        .call(TRACKS_ROUTE, |mut worker| async move {
+            worker.list_reference_tracks(()).await
+        })
+        .await?;
+
+    Ok(resp.into_inner().tracks)
+}
+
+/// Tracks which didn't make it to some workers are not retried, the error
+/// says which workers to check.
+fn ensure_on_each_worker<T>(
+    action: &str,
+    results: Vec<(SocketAddr, Result<T>)>,
+) -> Result<()> {
+    let failed: Vec<_> = results
+        .into_iter()
+        .filter_map(|(addr, res)| res.err().map(|e| format!("{addr}: {e}")))
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::internal(format!(
+            "Cannot {action} reference track on workers {}",
+            failed.join(", ")
+        )))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TriggerScan {
+    limit: usize,
+}
+
+pub async fn trigger_scan(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    Form(TriggerScan { limit }): Form<TriggerScan>,
+) -> Result<Redirect> {
+    spawn_scan(&s, game_id.clone(), limit);
+
+    Ok(Redirect::to(&format!("/game/{game_id}")))
+}
+
+pub(super) fn spawn_scan(
+    s: &g::HttpState,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) {
+    info!("Triggering scan music job for {limit} clips of game {game_id}");
+
+    let (db, workers) = (Arc::clone(&s.db), s.workers.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "scan_music",
+        Trigger::Manual,
+        vec![game_id.to_string()],
+        json!({ "limit": limit }),
+        move |_| crate::job::scan_music::once(db, workers, game_id, limit),
+    ));
+}
+
+pub async fn clip(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(clip_id): Path<String>,
+) -> Result<Html<String>> {
+    let db = s.db.lock().await;
+    s.views.clip_music(&session, &db, &clip_id)
+}
+
+#[derive(Deserialize)]
+pub struct SetAction {
+    action: MusicAction,
+}
+
+pub async fn set_action(
+    State(s): State<g::HttpState>,
+    Path((clip_id, match_id)): Path<(String, i64)>,
+    Form(SetAction { action }): Form<SetAction>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::music::set_action(&db, &clip_id, match_id, action)?;
+
+    Ok(Redirect::to(&format!("/clip/{clip_id}/music")))
+}
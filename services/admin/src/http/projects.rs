@@ -0,0 +1,340 @@
+use axum::{
+    extract::Path,
+    http::HeaderMap,
+    response::{Html, Redirect},
+    Form,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::job::history;
+use crate::models::job_run::Trigger;
+use crate::models::project::{EntryDetails, ProjectDetails, ProjectStatus};
+use crate::models::user::Session;
+use crate::prelude::*;
+
+pub async fn list(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let db = s.db.lock().await;
+    s.views.projects(&session, &db)
+}
+
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+) -> Result<Html<String>> {
+    let videos = match &s.conf.youtube {
+        Some(conf) => Some(list_videos(&conf.videos_dir).await),
+        None => None,
+    };
+
+    let db = s.db.lock().await;
+    s.views
+        .project(&session, &db, project_id, videos.as_deref())
+}
+
+/// Names of the files in the videos dir, a missing dir has none.
+async fn list_videos(videos_dir: &std::path::Path) -> Vec<String> {
+    let mut videos = vec![];
+    let Ok(mut entries) = tokio::fs::read_dir(videos_dir).await else {
+        return videos;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let is_file = entry
+            .file_type()
+            .await
+            .is_ok_and(|file_type| file_type.is_file());
+        if let (true, Some(name)) = (is_file, entry.file_name().to_str()) {
+            videos.push(name.to_string());
+        }
+    }
+    videos.sort();
+
+    videos
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EditProject {
+    title: String,
+    target_minutes: u32,
+    #[serde(default)]
+    notes: String,
+}
+
+impl From<EditProject> for ProjectDetails {
+    fn from(form: EditProject) -> Self {
+        Self {
+            title: form.title,
+            target_duration_secs: form.target_minutes.saturating_mul(60),
+            notes: form.notes,
+        }
+    }
+}
+
+pub async fn add(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Form(form): Form<EditProject>,
+) -> Result<Redirect> {
+    let details = ProjectDetails::from(form).validate()?;
+
+    let db = s.db.lock().await;
+    let project_id = db::project::insert(&db, &details, &session.user)?;
+    info!("{} created project {project_id}", session.user.username);
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+pub async fn edit(
+    State(s): State<g::HttpState>,
+    Path(project_id): Path<i64>,
+    Form(form): Form<EditProject>,
+) -> Result<Redirect> {
+    let details = ProjectDetails::from(form).validate()?;
+
+    let db = s.db.lock().await;
+    db::project::update(&db, project_id, &details)?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+#[derive(Deserialize)]
+pub struct SetStatus {
+    status: ProjectStatus,
+}
+
+pub async fn set_status(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+    Form(SetStatus { status }): Form<SetStatus>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::set_status(&db, project_id, status, &session.user)?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UploadProject {
+    video_file_name: String,
+}
+
+pub async fn upload(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+    Form(UploadProject { video_file_name }): Form<UploadProject>,
+) -> Result<Redirect> {
+    spawn_upload(&s, &session, project_id, &video_file_name).await?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+/// Checks what can be checked before the upload, which runs as a job that
+/// publishes the project once the video is on YouTube.
+///
+/// Only files right in the videos dir can be uploaded, and a project only
+/// once at a time.
+pub(super) async fn spawn_upload(
+    s: &g::HttpState,
+    session: &Session,
+    project_id: i64,
+    video_file_name: &str,
+) -> Result<()> {
+    let (Some(client), Some(conf)) = (&s.youtube, &s.conf.youtube) else {
+        return Err(AppError::bad_request(
+            "YouTube is not configured, see YOUTUBE_CLIENT_ID",
+        ));
+    };
+
+    let is_file_name = std::path::Path::new(video_file_name).file_name()
+        == Some(std::ffi::OsStr::new(video_file_name));
+    let video_path = conf.videos_dir.join(video_file_name);
+    let is_file = tokio::fs::metadata(&video_path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file());
+    if !is_file_name || !is_file {
+        return Err(AppError::bad_request(format!(
+            "No video '{video_file_name}' in {:?}",
+            conf.videos_dir
+        )));
+    }
+
+    {
+        let db = s.db.lock().await;
+        let project = db::project::select_by_id(&db, project_id)?;
+        if project.status != ProjectStatus::Ready {
+            return Err(AppError::bad_request(format!(
+                "Project {project_id} must be ready to be uploaded"
+            )));
+        }
+        if db::youtube::select_token(&db)?.is_none() {
+            return Err(AppError::bad_request(
+                "No YouTube channel is connected, connect it at /youtube",
+            ));
+        }
+        db::project::start_upload(&db, project_id, &session.user)?;
+    }
+
+    info!(
+        "{} triggered upload of project {project_id}",
+        session.user.username
+    );
+
+    let (db, client, user) =
+        (Arc::clone(&s.db), client.clone(), session.user.clone());
+    tokio::spawn(history::track(
+        Arc::clone(&db),
+        "upload_project",
+        Trigger::Manual,
+        vec![],
+        json!({ "project_id": project_id, "video": video_file_name }),
+        move |_| async move {
+            let uploaded = crate::job::upload_project::once(
+                Arc::clone(&db),
+                client,
+                project_id,
+                video_path,
+                user,
+            )
+            .await;
+            db::project::finish_upload(&*db.lock().await, project_id)?;
+
+            uploaded
+        },
+    ));
+
+    Ok(())
+}
+
+pub async fn delete(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(project_id): Path<i64>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::delete(&db, project_id)?;
+    info!("{} deleted project {project_id}", session.user.username);
+
+    Ok(Redirect::to("/projects"))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AddEntry {
+    clip_id: String,
+}
+
+pub async fn add_entry(
+    State(s): State<g::HttpState>,
+    Path(project_id): Path<i64>,
+    Form(AddEntry { clip_id }): Form<AddEntry>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::insert_entry(
+        &db,
+        project_id,
+        clip_id.trim(),
+        EntryDetails::default(),
+    )?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AddClip {
+    project_id: i64,
+}
+
+/// Adds the clip from the clips listing, which is where the user goes back
+/// to.
+pub async fn add_clip(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+    headers: HeaderMap,
+    Form(AddClip { project_id }): Form<AddClip>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let clip = db::clip::select_by_id(&db, &clip_id)?;
+    db::project::insert_entry(
+        &db,
+        project_id,
+        &clip.id,
+        EntryDetails::default(),
+    )?;
+
+    Ok(super::media::back(
+        &headers,
+        &format!("/game/{}/clips", clip.game_id),
+    ))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EditEntry {
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    trim_in_secs: Option<f64>,
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    trim_out_secs: Option<f64>,
+    #[serde(default)]
+    notes: String,
+}
+
+pub async fn edit_entry(
+    State(s): State<g::HttpState>,
+    Path((project_id, entry_id)): Path<(i64, i64)>,
+    Form(EditEntry {
+        trim_in_secs,
+        trim_out_secs,
+        notes,
+    }): Form<EditEntry>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::update_entry(
+        &db,
+        project_id,
+        entry_id,
+        EntryDetails {
+            trim_in_secs,
+            trim_out_secs,
+            notes,
+        },
+    )?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+#[derive(Deserialize)]
+pub struct MoveEntry {
+    /// Starts at 1 as shown on the page
+    to: usize,
+}
+
+pub async fn move_entry(
+    State(s): State<g::HttpState>,
+    Path((project_id, entry_id)): Path<(i64, i64)>,
+    Form(MoveEntry { to }): Form<MoveEntry>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::move_entry(&db, project_id, entry_id, to.saturating_sub(1))?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
+
+pub async fn delete_entry(
+    State(s): State<g::HttpState>,
+    Path((project_id, entry_id)): Path<(i64, i64)>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::project::delete_entry(&db, project_id, entry_id)?;
+
+    Ok(Redirect::to(&format!("/project/{project_id}")))
+}
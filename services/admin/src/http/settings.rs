@@ -1,43 +1,137 @@
 use axum::{
-    response::{Html, Redirect},
+    http::header,
+    response::{Html, IntoResponse, Redirect},
     Form,
 };
 use serde::Deserialize;
 
+use crate::models::setting::{Change, Definition, Values};
+use crate::models::user::{Session, User};
 use crate::prelude::*;
 
-pub async fn show(State(s): State<g::HttpState>) -> Result<Html<String>> {
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let next_ticks = s.jobs.next_ticks().await?;
+
     let db = s.db.lock().await;
-    s.views.settings(&db)
+    s.views.settings(&session, &db, &next_ticks)
 }
 
 #[derive(Deserialize)]
-#[serde(rename_all = "kebab-case")]
-pub struct EditSettings {
-    fetch_new_game_clips_cron: Option<String>,
-    recorded_at_least_hours_ago: Option<i64>,
+pub struct EditSetting {
+    name: String,
+    value: String,
 }
 
 pub async fn edit(
     State(s): State<g::HttpState>,
-    Form(settings): Form<EditSettings>,
+    session: Session,
+    Form(EditSetting { name, value }): Form<EditSetting>,
 ) -> Result<Redirect> {
-    let EditSettings {
-        fetch_new_game_clips_cron,
-        recorded_at_least_hours_ago,
-    } = settings;
+    let value = Definition::find(&name)?.parse(&value)?;
+    apply(&s, &session, Values::from([(name, value)])).await?;
+
+    Ok(Redirect::to("/settings"))
+}
 
+/// All settings as JSON, which can be imported elsewhere.
+pub async fn export(
+    State(s): State<g::HttpState>,
+) -> Result<impl IntoResponse> {
     let db = s.db.lock().await;
-    if let Some(cron) = fetch_new_game_clips_cron {
-        db::setting::update(&db, "fetch_new_game_clips_cron", &cron)?;
-    }
-    if let Some(hours) = recorded_at_least_hours_ago {
-        db::setting::update(
-            &db,
-            "recorded_at_least_hours_ago",
-            &hours.to_string(),
-        )?;
-    }
+    let values = db::setting::select_all(&db)?;
+    let json = serde_json::to_string_pretty(&values).map_err(AnyError::from)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"settings.json\"",
+            ),
+        ],
+        json,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ImportSettings {
+    /// As exported, settings which are left out are not changed
+    settings: String,
+}
+
+pub async fn import(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Form(ImportSettings { settings }): Form<ImportSettings>,
+) -> Result<Redirect> {
+    let values: Values = serde_json::from_str(&settings).map_err(|e| {
+        AppError::bad_request(format!("Cannot parse settings: {e}"))
+    })?;
+    apply(&s, &session, values).await?;
 
     Ok(Redirect::to("/settings"))
 }
+
+/// Stores the given settings and reschedules jobs whose cron expression
+/// changed.
+/// Nothing changes unless all settings are valid and all jobs could be
+/// rescheduled, jobs which were rescheduled before one failed go back to
+/// their old cron expressions.
+pub(super) async fn apply(
+    s: &g::HttpState,
+    session: &Session,
+    values: Values,
+) -> Result<()> {
+    let values = models::setting::validate_all(values)?;
+
+    // held until the changes are stored so that no other change comes in
+    // between, no transaction is open across the reschedule as it could be
+    // left open if the request is dropped while waiting for it
+    let db = s.db.lock().await;
+    let old_values: Values = db::setting::select_all(&db)?
+        .into_iter()
+        .filter(|(name, _)| values.contains_key(name))
+        .collect();
+
+    s.jobs.reschedule(&values).await.inspect_err(|_| {
+        // jobs rescheduled before the one which failed
+        restore_jobs(s, &old_values);
+    })?;
+    let changes = store(&db, values, &session.user)
+        .inspect_err(|_| restore_jobs(s, &old_values))?;
+
+    for change in changes {
+        info!(
+            "{} changed {} from '{}' to '{}'",
+            change.changed_by, change.name, change.old_value, change.new_value
+        );
+    }
+
+    Ok(())
+}
+
+fn store(
+    db: &DbConn,
+    values: Values,
+    changed_by: &User,
+) -> Result<Vec<Change>> {
+    let tx = db.unchecked_transaction()?;
+    let changes = db::setting::update_all(&tx, values, changed_by)?;
+    tx.commit()?;
+
+    Ok(changes)
+}
+
+/// Puts the jobs back on the cron expressions which are still stored.
+/// Spawned so that it finishes even if the request is dropped meanwhile.
+fn restore_jobs(s: &g::HttpState, old_values: &Values) {
+    let (jobs, old_values) = (s.jobs.clone(), old_values.clone());
+    tokio::spawn(async move {
+        if let Err(e) = jobs.reschedule(&old_values).await {
+            error!("Cannot restore jobs to their old schedules: {e}");
+        }
+    });
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::Path,
+    response::{Html, Redirect},
+    Form,
+};
+use serde::Deserialize;
+
+use crate::models::layout::{BroadcasterLayout, CropRect, LayoutKind};
+use crate::models::user::Session;
+use crate::models::{music, setting};
+use crate::prelude::*;
+
+pub async fn clip(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(clip_id): Path<String>,
+) -> Result<Html<String>> {
+    let db = s.db.lock().await;
+    s.views.clip_vertical(&session, &db, &clip_id)
+}
+
+#[derive(Deserialize)]
+pub struct Render {
+    /// The broadcaster's saved layout if not set
+    #[serde(default)]
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    layout: Option<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    start_secs: Option<f64>,
+    #[serde(default)]
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    end_secs: Option<f64>,
+}
+
+/// Renders the clip on a worker and sends the user to download the short.
+/// Rendering takes about as long as the clip plays, the request waits for
+/// it.
+///
+/// The audio is normalized by the loudness of the rendered fragment.
+/// Music the clip was marked to mute is silenced, music marked to exclude
+/// must be left out of the fragment.
+pub async fn render(
+    State(s): State<g::HttpState>,
+    Path(clip_id): Path<String>,
+    Form(Render {
+        layout,
+        start_secs,
+        end_secs,
+    }): Form<Render>,
+) -> Result<Redirect> {
+    let (clip, saved, target_lufs, music) = {
+        let db = s.db.lock().await;
+        let clip = db::clip::select_by_id(&db, &clip_id)?;
+        let saved =
+            db::layout::select_by_broadcaster(&db, &clip.broadcaster_id)?;
+        (
+            clip,
+            saved,
+            db::setting::get(&db, &setting::LOUDNESS_TARGET_LUFS)?,
+            db::music::select_matches(&db, &clip_id)?,
+        )
+    };
+    let mute_ranges = music::mute_ranges(
+        &music,
+        start_secs.unwrap_or(0.0),
+        end_secs.unwrap_or(clip.duration.as_secs_f64()),
+    )?;
+    let layout_kind = match layout {
+        Some(layout) => LayoutKind::try_from(layout.as_str())
+            .map_err(AppError::bad_request)?,
+        None => saved.layout,
+    };
+    let layout = saved.to_rpc(layout_kind);
+    info!("Rendering clip {clip_id} as vertical {layout_kind:?}");
+
+    let is_whole_clip = start_secs.is_none() && end_secs.is_none();
+    let game_id = clip.game_id.as_str().into();
+    let (addr, (loudness, rendered)) = s
+        .workers
+        .call_located(s.workers.route_for_game(&game_id), |mut worker| {
+            let (clip, layout) = (&clip, layout.clone());
+            let mute_ranges = mute_ranges
+                .iter()
+                .map(|&(start_secs, end_secs)| worker::rpc::TimeRange {
+                    start_secs,
+                    end_secs,
+                })
+                .collect();
+            async move {
+                worker
+                    .download_clip(worker::rpc::DownloadClipRequest {
+                        clip_id: clip.id.clone(),
+                        url: clip.url.clone(),
+                    })
+                    .await?;
+
+                // the whole clip is measured once, a fragment whenever it is
+                // rendered as it can be much quieter or louder than the clip
+                let loudness = match clip.loudness {
+                    Some(loudness) if is_whole_clip => loudness,
+                    _ => worker
+                        .measure_loudness(worker::rpc::MeasureLoudnessRequest {
+                            clip_id: clip.id.clone(),
+                            start_secs,
+                            end_secs,
+                        })
+                        .await?
+                        .into_inner()
+                        .into(),
+                };
+
+                let rendered = worker
+                    .render_vertical(worker::rpc::RenderVerticalRequest {
+                        clip_id: clip.id.clone(),
+                        start_secs,
+                        end_secs,
+                        layout: Some(layout),
+                        audio_gain_db: loudness
+                            .normalization_gain_db(target_lufs),
+                        mute_ranges,
+                    })
+                    .await?;
+
+                Ok((loudness, rendered))
+            }
+        })
+        .await?;
+    if is_whole_clip && clip.loudness.is_none() {
+        let db = s.db.lock().await;
+        db::loudness::insert(&db, &clip_id, &loudness)?;
+    }
+    let rendered = rendered.into_inner();
+    info!(
+        "Clip {clip_id} rendered to {} on worker {addr} ({} bytes)",
+        rendered.key, rendered.size_bytes
+    );
+
+    // keys are made of the clip id and hex digits, they need no escaping
+    Ok(Redirect::to(&format!(
+        "/media/artifact?worker={addr}&key={}",
+        rendered.key
+    )))
+}
+
+pub async fn layout(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(broadcaster_id): Path<String>,
+) -> Result<Html<String>> {
+    let db = s.db.lock().await;
+    s.views.broadcaster_layout(&session, &db, &broadcaster_id)
+}
+
+/// Rectangles are edited in percent of the frame.
+#[derive(Deserialize)]
+pub struct SaveLayout {
+    layout: LayoutKind,
+    facecam_x: f64,
+    facecam_y: f64,
+    facecam_width: f64,
+    facecam_height: f64,
+    gameplay_x: f64,
+    gameplay_y: f64,
+    gameplay_width: f64,
+    gameplay_height: f64,
+}
+
+pub async fn save_layout(
+    State(s): State<g::HttpState>,
+    Path(broadcaster_id): Path<String>,
+    Form(form): Form<SaveLayout>,
+) -> Result<Redirect> {
+    let percent = |x: f64, y: f64, width: f64, height: f64| CropRect {
+        x: x / 100.0,
+        y: y / 100.0,
+        width: width / 100.0,
+        height: height / 100.0,
+    };
+    let layout = BroadcasterLayout {
+        broadcaster_id,
+        layout: form.layout,
+        facecam: percent(
+            form.facecam_x,
+            form.facecam_y,
+            form.facecam_width,
+            form.facecam_height,
+        ),
+        gameplay: percent(
+            form.gameplay_x,
+            form.gameplay_y,
+            form.gameplay_width,
+            form.gameplay_height,
+        ),
+    };
+    for (name, rect) in
+        [("Facecam", &layout.facecam), ("Gameplay", &layout.gameplay)]
+    {
+        if !rect.is_inside_frame() {
+            return Err(AppError::bad_request(format!(
+                "{name} rectangle must be inside the frame"
+            )));
+        }
+    }
+
+    let db = s.db.lock().await;
+    db::layout::save(&db, &layout)?;
+    info!(
+        "Saved vertical layout of broadcaster {}",
+        layout.broadcaster_id
+    );
+
+    Ok(Redirect::to(&format!(
+        "/broadcaster/{}/layout",
+        layout.broadcaster_id
+    )))
+}
@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Path, Query},
+    response::{Html, Redirect},
+    Form,
+};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+use crate::models::user::Session;
+use crate::prelude::*;
+
+/// Every clip's broadcaster gets an avatar on the thumbnail, more don't
+/// fit.
+const MAX_CLIPS: usize = 6;
+/// Candidate frames the worker picks from each clip.
+const FRAMES_PER_CLIP: u32 = 4;
+
+#[derive(Deserialize)]
+pub struct Show {
+    #[serde(default)]
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    clips: Vec<String>,
+}
+
+/// Has the worker pick the best frames of the selected clips to choose the
+/// background of the thumbnail from.
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+    Path(game_id): Path<twitch::models::GameId>,
+    Query(Show { clips }): Query<Show>,
+) -> Result<Html<String>> {
+    let clips = select_clips(&s, &game_id, &clips).await?;
+
+    let (addr, frames) = s
+        .workers
+        .call_located(s.workers.route_for_game(&game_id), |mut worker| {
+            let clips = &clips;
+            async move {
+                let mut frames = Vec::with_capacity(clips.len());
+                for clip in clips {
+                    worker
+                        .download_clip(worker::rpc::DownloadClipRequest {
+                            clip_id: clip.id.clone(),
+                            url: clip.url.clone(),
+                        })
+                        .await?;
+
+                    let found = worker
+                        .find_thumbnail_frames(
+                            worker::rpc::FindThumbnailFramesRequest {
+                                clip_id: clip.id.clone(),
+                                count: FRAMES_PER_CLIP,
+                            },
+                        )
+                        .await?;
+                    frames.push(found.into_inner().frames);
+                }
+
+                Ok(frames)
+            }
+        })
+        .await?;
+
+    let db = s.db.lock().await;
+    s.views
+        .thumbnail(&session, &db, &game_id, addr, clips, frames)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Compose {
+    /// Frames are only on the worker which found them
+    worker: SocketAddr,
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    clips: Vec<String>,
+    /// Clip id and second of the frame separated by a slash
+    frame: String,
+    title: String,
+    /// Checkboxes are only sent when checked
+    with_box_art: Option<String>,
+    with_avatars: Option<String>,
+}
+
+/// Composites the thumbnail on the worker which has the clips and sends the
+/// user to it.
+pub async fn compose(
+    State(s): State<g::HttpState>,
+    Path(game_id): Path<twitch::models::GameId>,
+    Form(form): Form<Compose>,
+) -> Result<Redirect> {
+    let clips = select_clips(&s, &game_id, &form.clips).await?;
+    let (clip_id, at_secs) = form
+        .frame
+        .rsplit_once('/')
+        .and_then(|(clip_id, at_secs)| Some((clip_id, at_secs.parse().ok()?)))
+        .filter(|(clip_id, _)| clips.iter().any(|clip| clip.id == *clip_id))
+        .ok_or_else(|| {
+            AppError::bad_request("Pick a frame of one of the clips")
+        })?;
+
+    let box_art_url = if form.with_box_art.is_some() {
+        let db = s.db.lock().await;
+        Some(db::game::select_by_id(&db, &game_id)?.box_art_url)
+    } else {
+        None
+    };
+    let avatar_urls = if form.with_avatars.is_some() {
+        let broadcaster_ids = clips
+            .iter()
+            .map(|clip| clip.broadcaster_id.clone())
+            .unique()
+            .collect();
+        s.twitch
+            .get_broadcasters(broadcaster_ids)
+            .await?
+            .into_iter()
+            .filter_map(|broadcaster| broadcaster.profile_image_url)
+            .collect()
+    } else {
+        vec![]
+    };
+    info!(
+        "Composing thumbnail of game {game_id} from clip {clip_id} at \
+        {at_secs}s with {} avatars",
+        avatar_urls.len()
+    );
+
+    let composed = s
+        .workers
+        .call_on(form.worker, |mut worker| async move {
+            worker
+                .compose_thumbnail(worker::rpc::ComposeThumbnailRequest {
+                    clip_id: clip_id.to_string(),
+                    at_secs,
+                    title: form.title,
+                    box_art_url,
+                    avatar_urls,
+                })
+                .await
+        })
+        .await?
+        .into_inner();
+    info!(
+        "Thumbnail composed to {} on worker {} ({} bytes)",
+        composed.key, form.worker, composed.size_bytes
+    );
+
+    // keys are made of the clip id and hex digits, they need no escaping
+    Ok(Redirect::to(&format!(
+        "/media/artifact?worker={}&key={}",
+        form.worker, composed.key
+    )))
+}
+
+/// Clips of the game in the order they were selected.
+async fn select_clips(
+    s: &g::HttpState,
+    game_id: &twitch::models::GameId,
+    clip_ids: &[String],
+) -> Result<Vec<models::clip::Clip>> {
+    if clip_ids.is_empty() || clip_ids.len() > MAX_CLIPS {
+        return Err(AppError::bad_request(format!(
+            "Select between 1 and {MAX_CLIPS} clips"
+        )));
+    }
+
+    let db = s.db.lock().await;
+    clip_ids
+        .iter()
+        .map(|clip_id| {
+            let clip = db::clip::select_by_id(&db, clip_id)?;
+            if clip.game_id != game_id.as_str() {
+                return Err(AppError::bad_request(format!(
+                    "Clip {clip_id} is not of game {game_id}"
+                )));
+            }
+            Ok(clip)
+        })
+        .try_collect()
+}
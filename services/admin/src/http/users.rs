@@ -0,0 +1,116 @@
+use axum::{
+    extract::Path,
+    response::{Html, Redirect},
+    Form,
+};
+use serde::Deserialize;
+
+use crate::models::user::{self, Role, Session};
+use crate::prelude::*;
+
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let db = s.db.lock().await;
+    s.views.users(&session, &db)
+}
+
+#[derive(Deserialize)]
+pub struct AddUser {
+    username: String,
+    password: String,
+    role: Role,
+}
+
+pub async fn add(
+    State(s): State<g::HttpState>,
+    Form(AddUser {
+        username,
+        password,
+        role,
+    }): Form<AddUser>,
+) -> Result<Redirect> {
+    user::validate_username(&username)?;
+    let password_hash = hash_password(password).await?;
+
+    let db = s.db.lock().await;
+    db::user::insert(&db, &username, &password_hash, role)?;
+    info!("Added {} user {username}", <&str>::from(role));
+
+    Ok(Redirect::to("/users"))
+}
+
+#[derive(Deserialize)]
+pub struct SetRole {
+    role: Role,
+}
+
+pub async fn set_role(
+    State(s): State<g::HttpState>,
+    Path(user_id): Path<i64>,
+    Form(SetRole { role }): Form<SetRole>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let user = db::user::select_by_id(&db, user_id)?;
+    if role != Role::Admin {
+        ensure_not_last_admin(&db, &user)?;
+    }
+    db::user::set_role(&db, user_id, role)?;
+    info!("User {} is now {}", user.username, <&str>::from(role));
+
+    Ok(Redirect::to("/users"))
+}
+
+#[derive(Deserialize)]
+pub struct SetPassword {
+    password: String,
+}
+
+/// Logs the user out everywhere, including the admin who changes their own
+/// password.
+pub async fn set_password(
+    State(s): State<g::HttpState>,
+    Path(user_id): Path<i64>,
+    Form(SetPassword { password }): Form<SetPassword>,
+) -> Result<Redirect> {
+    let password_hash = hash_password(password).await?;
+
+    let db = s.db.lock().await;
+    let user = db::user::select_by_id(&db, user_id)?;
+    db::user::set_password_hash(&db, user_id, &password_hash)?;
+    info!("Changed password of user {}", user.username);
+
+    Ok(Redirect::to("/users"))
+}
+
+pub async fn delete(
+    State(s): State<g::HttpState>,
+    Path(user_id): Path<i64>,
+) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    let user = db::user::select_by_id(&db, user_id)?;
+    ensure_not_last_admin(&db, &user)?;
+    db::user::delete(&db, user_id)?;
+    info!("Deleted user {}", user.username);
+
+    Ok(Redirect::to("/users"))
+}
+
+/// Nobody could manage users anymore.
+fn ensure_not_last_admin(db: &DbConn, user: &models::user::User) -> Result<()> {
+    if user.role == Role::Admin && db::user::count_admins(db)? <= 1 {
+        return Err(AppError::bad_request(
+            "There must be at least one admin left",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Hashing takes a while on purpose.
+async fn hash_password(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || user::hash_password(&password))
+        .await
+        .map_err(AnyError::from)?
+}
@@ -0,0 +1,11 @@
+use crate::models::user::Session;
+use crate::prelude::*;
+use axum::response::Html;
+
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    s.views
+        .workers(&session, &s.workers.statuses(), s.workers.game_routing())
+}
@@ -0,0 +1,134 @@
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Redirect},
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::models::user::Session;
+use crate::prelude::*;
+
+/// Ties the OAuth callback to the browser which started connecting, so that
+/// nobody else can connect their channel by sending a link.
+const STATE_COOKIE: &str = "youtube_oauth_state";
+
+/// Connection to the channel, refreshing the access token if it expired to
+/// check that the channel is still connected.
+pub async fn show(
+    State(s): State<g::HttpState>,
+    session: Session,
+) -> Result<Html<String>> {
+    let Some(client) = &s.youtube else {
+        return s.views.youtube(&session, false, None, None);
+    };
+
+    let token = {
+        let db = s.db.lock().await;
+        db::youtube::select_token(&db)?
+    };
+    let (token, error) = match token {
+        Some(token) if token.needs_refresh(chrono::Utc::now()) => {
+            match client.refresh(&token).await {
+                Ok(token) => {
+                    let db = s.db.lock().await;
+                    db::youtube::save_token(&db, &token)?;
+                    (Some(token), None)
+                }
+                Err(e) => {
+                    warn!("Cannot refresh YouTube token: {e}");
+                    (Some(token), Some(e.to_string()))
+                }
+            }
+        }
+        token => (token, None),
+    };
+
+    s.views
+        .youtube(&session, true, token.as_ref(), error.as_deref())
+}
+
+/// Sends the user to Google to grant access to their channel.
+pub async fn connect(
+    State(s): State<g::HttpState>,
+) -> Result<impl IntoResponse> {
+    let (client, conf) = configured(&s)?;
+
+    let state = format!("{:032x}", rand::thread_rng().gen::<u128>());
+    let url = client.authorize_url(&conf.redirect_url, &state)?;
+    let cookie = format!(
+        "{STATE_COOKIE}={state}; Path=/youtube; HttpOnly; SameSite=Lax; \
+        Max-Age=600"
+    );
+
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(&url)))
+}
+
+#[derive(Deserialize)]
+pub struct Callback {
+    code: Option<String>,
+    state: Option<String>,
+    /// E.g. "access_denied" if the user didn't grant access
+    error: Option<String>,
+}
+
+pub async fn callback(
+    State(s): State<g::HttpState>,
+    headers: HeaderMap,
+    Query(Callback { code, state, error }): Query<Callback>,
+) -> Result<Redirect> {
+    let (client, conf) = configured(&s)?;
+
+    if let Some(error) = error {
+        return Err(AppError::bad_request(format!(
+            "Access to the channel was not granted: {error}"
+        )));
+    }
+
+    let expected_state = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|cookies| cookies.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| {
+            cookie.trim().strip_prefix(&format!("{STATE_COOKIE}="))
+        });
+    if state.is_none() || state.as_deref() != expected_state {
+        return Err(AppError::bad_request(
+            "Connecting the channel expired or was not started here, try again",
+        ));
+    }
+    let code = code
+        .ok_or_else(|| AppError::bad_request("Authorization code missing"))?;
+
+    let token = client.exchange_code(&code, &conf.redirect_url).await?;
+    if token.refresh_token.is_none() {
+        warn!("Google did not grant a refresh token, uploads stop in an hour");
+    }
+
+    let db = s.db.lock().await;
+    db::youtube::save_token(&db, &token)?;
+    info!("YouTube channel connected");
+
+    Ok(Redirect::to("/youtube"))
+}
+
+/// Forgets the token, access should also be revoked in the Google account.
+pub async fn disconnect(State(s): State<g::HttpState>) -> Result<Redirect> {
+    let db = s.db.lock().await;
+    db::youtube::delete_token(&db)?;
+    info!("YouTube channel disconnected");
+
+    Ok(Redirect::to("/youtube"))
+}
+
+fn configured(
+    s: &g::HttpState,
+) -> Result<(&youtube::Client, &crate::conf::YoutubeConf)> {
+    match (&s.youtube, &s.conf.youtube) {
+        (Some(client), Some(conf)) => Ok((client, conf)),
+        _ => Err(AppError::bad_request(
+            "YouTube is not configured, see YOUTUBE_CLIENT_ID",
+        )),
+    }
+}
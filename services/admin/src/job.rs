@@ -1,75 +1,299 @@
+/// Frees worker disk, scheduled and triggered manually
+pub mod collect_media_garbage;
+/// Groups clips of the same moment, triggered manually
+pub mod detect_moments;
 pub mod fetch_new_game_clips;
+/// Generates storyboards, waveforms and animations of clips, scheduled and
+/// triggered manually
+pub mod generate_previews;
+/// Records every run of a job in db, see `/jobs`
+pub mod history;
+/// Measures the loudness of clips, triggered manually
+pub mod measure_loudness;
+/// Checks clips audio for known tracks, triggered manually
+pub mod scan_music;
+/// Uploads ready projects to YouTube, triggered manually
+pub mod upload_project;
 
-use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde_json::json;
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio_cron_scheduler::{Job, JobScheduler};
 
+use crate::models::job_run::Trigger;
+use crate::models::setting::{self, Definition, Kind, Values};
 use crate::prelude::*;
 
+/// Handles to scheduled jobs, held for the lifetime of the app.
+///
+/// Jobs are rescheduled when their cron expression changes, which gives
+/// them a new id.
 #[derive(Clone)]
 pub struct Jobs {
     pub scheduler: JobScheduler,
-    pub fetch_new_game_clips: uuid::Uuid,
+    pub fetch_new_game_clips: Arc<Mutex<uuid::Uuid>>,
+    pub collect_media_garbage: Arc<Mutex<uuid::Uuid>>,
+    pub generate_previews: Arc<Mutex<uuid::Uuid>>,
+    db: DbLock,
+    tc: Arc<twitch::Client>,
+    workers: WorkerPool,
 }
 
 pub async fn schedule_all(
     db: DbLock,
     tc: Arc<twitch::Client>,
+    workers: WorkerPool,
 ) -> AnyResult<Jobs> {
-    let mut scheduler = JobScheduler::new().await?;
+    let scheduler = JobScheduler::new().await?;
 
-    let (fetch_new_game_clips_cron, recorded_at_least_ago) = {
+    let (
+        fetch_new_game_clips_cron,
+        collect_media_garbage_cron,
+        generate_previews_cron,
+    ) = {
         let db = db.lock().await;
-        let new_clips_cron = db::setting::fetch_new_game_clips_cron(&db)?;
-        let at_least_ago = db::setting::recorded_at_least_hours_ago(&db)?;
+        let abandoned = db::job_run::abandon_running(&db)?;
+        if abandoned > 0 {
+            warn!("{abandoned} job runs were interrupted by the last restart");
+        }
+        let abandoned = db::project::abandon_uploads(&db)?;
+        if abandoned > 0 {
+            warn!("{abandoned} uploads were interrupted by the last restart");
+        }
 
-        (new_clips_cron, Some(chrono::Duration::hours(at_least_ago)))
+        (
+            db::setting::get(&db, &setting::FETCH_NEW_GAME_CLIPS_CRON)?,
+            db::setting::get(&db, &setting::COLLECT_MEDIA_GARBAGE_CRON)?,
+            db::setting::get(&db, &setting::GENERATE_PREVIEWS_CRON)?,
+        )
     };
 
+    let collect_media_garbage = scheduler
+        .add(collect_media_garbage_job(
+            &collect_media_garbage_cron,
+            Arc::clone(&db),
+            workers.clone(),
+        )?)
+        .await?;
+    let generate_previews = scheduler
+        .add(generate_previews_job(
+            &generate_previews_cron,
+            Arc::clone(&db),
+            workers.clone(),
+        )?)
+        .await?;
     let fetch_new_game_clips = scheduler
-        .add(Job::new_async(
-            fetch_new_game_clips_cron.as_ref(),
-            move |_, _| {
-                debug!("Triggering fetch_new_game_clips");
-
-                let db = Arc::clone(&db);
-                let tc = Arc::clone(&tc);
-
-                Box::pin(async move {
-                    let res = fetch_new_game_clips::once_for_all(
-                        Arc::clone(&db),
-                        Arc::clone(&tc),
-                        fetch_new_game_clips::Conf {
-                            recorded_at_most_ago: None, // last clip
-                            recorded_at_least_ago,
-                        },
-                    )
-                    .await;
-                    if let Err(e) = res {
-                        error!("Cannot trigger fetching new game clips: {e}");
-                    }
-                })
-            },
+        .add(fetch_new_game_clips_job(
+            &fetch_new_game_clips_cron,
+            Arc::clone(&db),
+            Arc::clone(&tc),
         )?)
         .await?;
 
-    let next_tick = scheduler
-        .next_tick_for_job(fetch_new_game_clips)
-        .await?
-        .ok_or_else(|| {
-            anyhow!("Cannot find next tick for fetch_new_game_clips")
-        })?;
-    info!("Next tick for fetch_new_game_clips: {next_tick}");
+    let jobs = Jobs {
+        scheduler: scheduler.clone(),
+        fetch_new_game_clips: Arc::new(Mutex::new(fetch_new_game_clips)),
+        collect_media_garbage: Arc::new(Mutex::new(collect_media_garbage)),
+        generate_previews: Arc::new(Mutex::new(generate_previews)),
+        db,
+        tc,
+        workers,
+    };
+    for (job, next_tick) in jobs.next_ticks().await? {
+        match next_tick {
+            Some(next_tick) => info!("Next tick for {job}: {next_tick}"),
+            None => warn!("Job {job} will never run"),
+        }
+    }
 
-    let shed = scheduler.clone();
     tokio::spawn(async move {
-        if let Err(e) = shed.start().await {
+        if let Err(e) = scheduler.start().await {
             error!("Error in scheduler: {e}");
         }
     });
 
-    Ok(Jobs {
-        scheduler,
-        fetch_new_game_clips,
-    })
+    Ok(jobs)
+}
+
+impl Jobs {
+    /// When each scheduled job runs next, None if it's not scheduled.
+    pub async fn next_ticks(
+        &self,
+    ) -> AnyResult<Vec<(&'static str, Option<DateTime<Utc>>)>> {
+        let mut scheduler = self.scheduler.clone();
+        let fetch_new_game_clips = *self.fetch_new_game_clips.lock().await;
+        let collect_media_garbage = *self.collect_media_garbage.lock().await;
+        let generate_previews = *self.generate_previews.lock().await;
+
+        Ok(vec![
+            (
+                "fetch_new_game_clips",
+                scheduler.next_tick_for_job(fetch_new_game_clips).await?,
+            ),
+            (
+                "collect_media_garbage",
+                scheduler.next_tick_for_job(collect_media_garbage).await?,
+            ),
+            (
+                "generate_previews",
+                scheduler.next_tick_for_job(generate_previews).await?,
+            ),
+        ])
+    }
+
+    /// Replaces the scheduled jobs whose cron expression is given, so that
+    /// changed settings apply without a restart.
+    ///
+    /// The new job is scheduled before the old one is removed, hence if
+    /// scheduling fails the old one keeps running.
+    pub async fn reschedule(&self, values: &Values) -> Result<()> {
+        for (name, value) in values {
+            let Kind::Cron { job } = Definition::find(name)?.kind else {
+                continue;
+            };
+            let cron = value.to_string();
+
+            let (job_id, new_job) = match job {
+                "fetch_new_game_clips" => (
+                    &self.fetch_new_game_clips,
+                    fetch_new_game_clips_job(
+                        &cron,
+                        Arc::clone(&self.db),
+                        Arc::clone(&self.tc),
+                    )?,
+                ),
+                "collect_media_garbage" => (
+                    &self.collect_media_garbage,
+                    collect_media_garbage_job(
+                        &cron,
+                        Arc::clone(&self.db),
+                        self.workers.clone(),
+                    )?,
+                ),
+                "generate_previews" => (
+                    &self.generate_previews,
+                    generate_previews_job(
+                        &cron,
+                        Arc::clone(&self.db),
+                        self.workers.clone(),
+                    )?,
+                ),
+                _ => {
+                    return Err(AppError::internal(format!(
+                        "Setting {name} schedules unknown job {job}"
+                    )))
+                }
+            };
+            self.replace(job_id, new_job).await?;
+            info!("Rescheduled {job} to '{cron}'");
+        }
+
+        Ok(())
+    }
+
+    async fn replace(
+        &self,
+        job_id: &Mutex<uuid::Uuid>,
+        job: Job,
+    ) -> AnyResult<()> {
+        // held throughout so that concurrent edits don't leave a job behind
+        let mut job_id = job_id.lock().await;
+        let new_job_id = self.scheduler.add(job).await?;
+        self.scheduler.remove(&job_id).await?;
+        *job_id = new_job_id;
+
+        Ok(())
+    }
+}
+
+fn collect_media_garbage_job(
+    cron: &str,
+    db: DbLock,
+    workers: WorkerPool,
+) -> AnyResult<Job> {
+    Ok(Job::new_async(cron, move |_, _| {
+        debug!("Triggering collect_media_garbage");
+
+        let db = Arc::clone(&db);
+        let workers = workers.clone();
+
+        Box::pin(async move {
+            // failures are logged and recorded in the job run
+            history::track(
+                Arc::clone(&db),
+                "collect_media_garbage",
+                Trigger::Cron,
+                vec![],
+                json!({}),
+                |_| collect_media_garbage::once(db, workers, false),
+            )
+            .await
+            .ok();
+        })
+    })?)
+}
+
+fn generate_previews_job(
+    cron: &str,
+    db: DbLock,
+    workers: WorkerPool,
+) -> AnyResult<Job> {
+    Ok(Job::new_async(cron, move |_, _| {
+        debug!("Triggering generate_previews");
+
+        let db = Arc::clone(&db);
+        let workers = workers.clone();
+
+        Box::pin(async move {
+            // failures are logged and recorded in the job run
+            generate_previews::once_for_all(db, workers, Trigger::Cron)
+                .await
+                .ok();
+        })
+    })?)
+}
+
+/// Reads how old clips must be on every tick, so that changing it applies
+/// right away.
+fn fetch_new_game_clips_job(
+    cron: &str,
+    db: DbLock,
+    tc: Arc<twitch::Client>,
+) -> AnyResult<Job> {
+    Ok(Job::new_async(cron, move |_, _| {
+        debug!("Triggering fetch_new_game_clips");
+
+        let db = Arc::clone(&db);
+        let tc = Arc::clone(&tc);
+
+        Box::pin(async move {
+            let at_least_hours_ago = {
+                let db = db.lock().await;
+                db::setting::get(&db, &setting::RECORDED_AT_LEAST_HOURS_AGO)
+            };
+            let at_least_hours_ago = match at_least_hours_ago {
+                Ok(hours) => hours,
+                Err(e) => {
+                    error!("Cannot trigger fetching new game clips: {e}");
+                    return;
+                }
+            };
+
+            // failures are logged and recorded in the job run
+            fetch_new_game_clips::once_for_all(
+                db,
+                tc,
+                fetch_new_game_clips::Conf {
+                    recorded_at_most_ago: None, // last clip
+                    recorded_at_least_ago: Some(chrono::Duration::hours(
+                        at_least_hours_ago,
+                    )),
+                },
+                Trigger::Cron,
+            )
+            .await
+            .ok();
+        })
+    })?)
 }
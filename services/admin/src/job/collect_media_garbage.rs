@@ -0,0 +1,74 @@
+use itertools::Itertools;
+use std::net::SocketAddr;
+
+use crate::prelude::*;
+
+/// Has every worker delete media which is too old or doesn't fit its disk
+/// quota.
+/// Media of clips marked as kept is never deleted.
+///
+/// With dry run nothing is deleted and the responses are a preview.
+/// Otherwise previews whose media was evicted are forgotten, so that they
+/// are generated again.
+/// A worker which fails is logged and left out, unless all of them fail.
+pub async fn once(
+    db: DbLock,
+    workers: WorkerPool,
+    dry_run: bool,
+) -> Result<Vec<(SocketAddr, worker::rpc::CollectGarbageResponse)>> {
+    let keep_clip_ids = {
+        let db = db.lock().await;
+        db::retention::select_kept_clip_ids(&db)?
+    };
+
+    let mut collected = vec![];
+    let mut last_err = None;
+    let results = workers
+        .call_each(|mut worker| {
+            let keep_clip_ids = keep_clip_ids.clone();
+            async move {
+                worker
+                    .collect_garbage(worker::rpc::CollectGarbageRequest {
+                        dry_run,
+                        keep_clip_ids,
+                    })
+                    .await
+            }
+        })
+        .await;
+    for (addr, res) in results {
+        match res {
+            Ok(resp) => {
+                let resp = resp.into_inner();
+                if !dry_run {
+                    info!(
+                        "Worker {addr} freed {} bytes by deleting {} artifacts",
+                        resp.freed_bytes,
+                        resp.evicted.len()
+                    );
+                    let evicted_keys = resp
+                        .evicted
+                        .iter()
+                        .map(|artifact| artifact.key.clone())
+                        .collect_vec();
+                    let db = db.lock().await;
+                    let forgotten =
+                        db::preview::delete_evicted(&db, addr, &evicted_keys)?;
+                    if forgotten > 0 {
+                        info!("Forgot {forgotten} previews evicted on {addr}");
+                    }
+                }
+                collected.push((addr, resp));
+            }
+            Err(e) => {
+                warn!("Cannot collect media garbage on worker {addr}: {e}");
+                last_err = Some(e);
+            }
+        }
+    }
+
+    match last_err {
+        Some(e) if collected.is_empty() => Err(e),
+        _ => Ok(collected),
+    }
+}
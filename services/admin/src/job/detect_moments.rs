@@ -0,0 +1,82 @@
+use crate::models::moment::{self, MomentCandidate};
+use crate::prelude::*;
+
+/// Finds clips of the game recorded close to each other by the same
+/// broadcaster, has the worker download and hash frames of those that
+/// haven't been hashed yet and groups them into moments.
+///
+/// A clip which fails to download or hash is logged and left out.
+pub async fn once(
+    db: DbLock,
+    workers: WorkerPool,
+    game_id: twitch::models::GameId,
+) -> Result<()> {
+    let candidates = {
+        let db = db.lock().await;
+        db::moment::select_candidates(
+            &db,
+            &game_id,
+            moment::recorded_at_proximity(),
+        )?
+    };
+    info!(
+        "Detecting moments of game {game_id} among {} candidate clips",
+        candidates.len()
+    );
+
+    let mut hashed = Vec::with_capacity(candidates.len());
+    for mut candidate in candidates {
+        if candidate.frame_hashes.is_empty() {
+            match hash_frames(&workers, &game_id, &candidate).await {
+                Ok(hashes) => {
+                    let mut db = db.lock().await;
+                    db::moment::insert_frame_hashes(
+                        &mut db,
+                        &candidate.clip_id,
+                        &hashes,
+                    )?;
+                    candidate.frame_hashes = hashes;
+                }
+                Err(e) => {
+                    warn!("Cannot hash frames of {}: {e}", candidate.clip_id);
+                    continue;
+                }
+            }
+        }
+
+        hashed.push(candidate);
+    }
+
+    let moments = moment::group(hashed);
+    info!("Detected {} moments of game {game_id}", moments.len());
+
+    let mut db = db.lock().await;
+    db::moment::replace_for_game(&mut db, &game_id, &moments)?;
+
+    Ok(())
+}
+
+async fn hash_frames(
+    workers: &WorkerPool,
+    game_id: &twitch::models::GameId,
+    candidate: &MomentCandidate,
+) -> Result<Vec<u64>> {
+    let resp = workers
+        .call(workers.route_for_game(game_id), |mut worker| async move {
+            worker
+                .download_clip(worker::rpc::DownloadClipRequest {
+                    clip_id: candidate.clip_id.clone(),
+                    url: candidate.url.clone(),
+                })
+                .await?;
+
+            worker
+                .hash_clip_frames(worker::rpc::HashClipFramesRequest {
+                    clip_id: candidate.clip_id.clone(),
+                })
+                .await
+        })
+        .await?;
+
+    Ok(resp.into_inner().hashes)
+}
@@ -1,12 +1,17 @@
 use chrono::Utc;
+use itertools::Itertools;
 use rand::Rng;
 use std::{sync::Arc, time::Duration};
 use tokio::{
     sync::mpsc::{self, error::TryRecvError, Sender, UnboundedSender},
+    task::JoinHandle,
     time::{sleep, sleep_until, Instant},
 };
 use twitch::twitch_api2::types::Timestamp;
 
+use super::history::{self, Run};
+use crate::models::fetch_policy::FetchPolicy;
+use crate::models::job_run::Trigger;
 use crate::prelude::*;
 
 /// Good default will have some hours because that allows for views to grow on
@@ -22,12 +27,39 @@ pub struct Conf {
     pub recorded_at_least_ago: Option<chrono::Duration>,
 }
 
+impl Conf {
+    /// As recorded in the job run.
+    pub fn describe(&self) -> serde_json::Value {
+        serde_json::json!({
+            "recorded_at_most_hours_ago":
+                self.recorded_at_most_ago.map(|d| d.num_hours()),
+            "recorded_at_least_hours_ago":
+                self.recorded_at_least_ago.map(|d| d.num_hours()),
+        })
+    }
+}
+
+/// What is fetched of one game.
+struct Target {
+    game_id: twitch::models::GameId,
+    latest_clip_recorded_at: Option<chrono::DateTime<Utc>>,
+    /// Resolved from the job conf and the game's policy
+    recorded_at_most_ago: Option<chrono::Duration>,
+    /// Resolved from the job conf and the game's policy
+    recorded_at_least_ago: Option<chrono::Duration>,
+    /// Which fetched clips are stored and how many pages are fetched
+    policy: Arc<FetchPolicy>,
+}
+
 /// Like a breath-first search, we start with a seed where we have one element
 /// for each game and new elements are added to the end of the queue based on
 /// result of pagination.
 struct QueueElement {
     /// We can feed this type to `twitch::Client::get_clips_paginated`.
     request: twitch::models::GetClipsRequest,
+    /// Starts at 1 for the first page of each game.
+    page: u32,
+    policy: Arc<FetchPolicy>,
     /// If we get 409 from Twitch, we retry a few times.
     /// If this reaches 3, we give up.
     retry_count: u8,
@@ -41,95 +73,187 @@ struct QueueElement {
     fetch_clips: Sender<QueueElement>,
 }
 
+/// The time window given when triggering the job beats the game's policy.
 pub async fn once(
     db: DbLock,
     tc: Arc<twitch::Client>,
     conf: Conf,
     game_id: twitch::models::GameId,
+    trigger: Trigger,
 ) -> Result<()> {
-    let recorded_at = {
-        let db = db.lock().await;
-        db::game::select_latest_clip_recorded_at(&db, &game_id)?
-    };
+    history::track(
+        Arc::clone(&db),
+        "fetch_new_game_clips",
+        trigger,
+        vec![game_id.to_string()],
+        conf.describe(),
+        |run| async move {
+            let target = {
+                let db = db.lock().await;
+                let policy = db::fetch_policy::select_by_game(&db, &game_id)?;
+
+                Target {
+                    latest_clip_recorded_at:
+                        db::game::select_latest_clip_recorded_at(
+                            &db, &game_id,
+                        )?,
+                    recorded_at_most_ago: conf
+                        .recorded_at_most_ago
+                        .or(policy.recorded_at_most_ago()),
+                    recorded_at_least_ago: conf
+                        .recorded_at_least_ago
+                        .or(policy.recorded_at_least_ago()),
+                    policy: Arc::new(policy),
+                    game_id,
+                }
+            };
 
-    once_(db, tc, conf, vec![(game_id, recorded_at)]).await
+            once_(db, tc, vec![target], run).await
+        },
+    )
+    .await
 }
 
+/// Fetches clips of games which are not paused and are due as per their
+/// policy, which beats the conf.
 pub async fn once_for_all(
     db: DbLock,
     tc: Arc<twitch::Client>,
     conf: Conf,
+    trigger: Trigger,
 ) -> Result<()> {
-    let game_ids = {
-        let db = db.lock().await;
-        db::game::select_all_active_that_have_last_clip_older_than(
-            &db,
-            conf.recorded_at_least_ago.unwrap_or(
-                chrono::Duration::from_std(
-                    HOW_LONG_UNTIL_CLIP_HAS_REASONABLE_VIEWS,
-                )
-                .unwrap(),
-            ),
-        )?
-    };
-
-    once_(db, tc, conf, game_ids).await
+    history::track(
+        Arc::clone(&db),
+        "fetch_new_game_clips",
+        trigger,
+        vec![],
+        conf.describe(),
+        |run| async move {
+            let targets = {
+                let db = db.lock().await;
+                let targets = select_due_targets(&db, &conf)?;
+                run.set_game_ids(
+                    &db,
+                    &targets
+                        .iter()
+                        .map(|target| target.game_id.to_string())
+                        .collect_vec(),
+                )?;
+
+                targets
+            };
+
+            once_(db, tc, targets, run).await
+        },
+    )
+    .await
+}
+
+fn select_due_targets(db: &DbConn, conf: &Conf) -> Result<Vec<Target>> {
+    let now = chrono::Utc::now();
+    let mut targets = vec![];
+    for (game_id, latest_clip_recorded_at) in
+        db::game::select_all_active_with_latest_clip_recorded_at(db)?
+    {
+        let policy = db::fetch_policy::select_by_game(db, &game_id)?;
+        let recorded_at_least_ago = policy
+            .recorded_at_least_ago()
+            .or(conf.recorded_at_least_ago);
+
+        let is_due = if policy.fetch_every_hours.is_some() {
+            let last_fetched_at =
+                db::fetch_policy::select_last_fetched_at(db, &game_id)?;
+            policy.is_due(last_fetched_at, now)
+        } else {
+            // clips newer than this are not fetched yet, so if the game has
+            // any then the last run already fetched all there is
+            let fetch_until = now
+                - recorded_at_least_ago.unwrap_or(
+                    chrono::Duration::from_std(
+                        HOW_LONG_UNTIL_CLIP_HAS_REASONABLE_VIEWS,
+                    )
+                    .unwrap(),
+                );
+            latest_clip_recorded_at.is_none_or(|at| at <= fetch_until)
+        };
+
+        if is_due {
+            targets.push(Target {
+                game_id,
+                latest_clip_recorded_at,
+                recorded_at_most_ago: policy
+                    .recorded_at_most_ago()
+                    .or(conf.recorded_at_most_ago),
+                recorded_at_least_ago,
+                policy: Arc::new(policy),
+            });
+        } else {
+            debug!("Game {game_id} is not due to fetch clips yet");
+        }
+    }
+
+    Ok(targets)
 }
 
 async fn once_(
     db: DbLock,
     tc: Arc<twitch::Client>,
-    conf: Conf,
-    game_ids: Vec<(twitch::models::GameId, Option<chrono::DateTime<Utc>>)>,
+    targets: Vec<Target>,
+    run: Run,
 ) -> Result<()> {
-    if game_ids.is_empty() {
+    if targets.is_empty() {
         debug!("No games to fetch clips for, skipping job");
         return Ok(());
     } else {
-        info!("Fetching new clips with {conf:?} for games {game_ids:?}");
+        info!(
+            "Fetching new clips for games {:?}",
+            targets.iter().map(|target| &target.game_id).collect_vec()
+        );
     }
 
-    let store_clips = spawn_channel_to_store_clips(db);
+    let started_at = Utc::now();
+    let game_ids = targets
+        .iter()
+        .map(|target| target.game_id.clone())
+        .collect_vec();
+
+    let (store_clips, stored) =
+        spawn_channel_to_store_clips(Arc::clone(&db), run.clone());
     // there'll be (at most) 1 request per game as pagination requires a request
     // to be finished before new one can be sent
     let (fetch_clips, mut next_clip_request) =
-        mpsc::channel::<QueueElement>(game_ids.len());
-
-    for (game_id, latest_clip_recorded_at) in game_ids {
+        mpsc::channel::<QueueElement>(targets.len());
+
+    for Target {
+        game_id,
+        latest_clip_recorded_at,
+        recorded_at_most_ago,
+        recorded_at_least_ago,
+        policy,
+    } in targets
+    {
         if let Some(recorded_at) = latest_clip_recorded_at {
             debug!("Game {game_id} has latest clip recorded at {recorded_at}");
         } else {
             debug!("Game {game_id} has no clips yet");
         }
+        if !policy.is_default() {
+            debug!("Game {game_id} is fetched with {policy:?}");
+        }
 
-        let request = twitch::models::GetClipsRequest::builder()
-            .game_id(Some(game_id.into()))
-            .first(100)
-            .started_at(
-                conf.recorded_at_most_ago
-                    .map(|at| chrono::Utc::now() - at) // user precedence
-                    .or(latest_clip_recorded_at) // else use latest (if set)
-                    .or_else(|| {
-                        // otherwise default to 2 days ago for new games
-                        Some(chrono::Utc::now() - chrono::Duration::days(2))
-                    })
-                    .and_then(|t| Timestamp::new(t.to_rfc3339()).ok()),
-            )
-            .ended_at(
-                Some(
-                    chrono::Utc::now()
-                        - conf
-                            .recorded_at_least_ago
-                            .unwrap_or(chrono::Duration::days(1)),
-                )
-                .and_then(|t| Timestamp::new(t.to_rfc3339()).ok()),
-            )
-            .build();
+        let request = first_request(
+            &game_id,
+            latest_clip_recorded_at,
+            recorded_at_most_ago,
+            recorded_at_least_ago,
+        );
 
         let fetch_clips_to_send = fetch_clips.clone();
         fetch_clips
             .send(QueueElement {
                 request,
+                page: 1,
+                policy,
                 retry_count: 0,
                 fetch_clips: fetch_clips_to_send,
             })
@@ -163,7 +287,12 @@ async fn once_(
                 break 'outer;
             };
 
-            spawn_task_to_fetch_clip(Arc::clone(&tc), store_clips.clone(), el);
+            spawn_task_to_fetch_clip(
+                Arc::clone(&tc),
+                store_clips.clone(),
+                run.clone(),
+                el,
+            );
         }
 
         // if it was more than 1s, this immediately resolves
@@ -174,20 +303,69 @@ async fn once_(
     // main job task end
     //
 
+    // the run is over once the last clips are stored
+    drop(store_clips);
+    let is_stored = stored.await.map_err(AnyError::from)?;
+
+    // games are due as per their policy since they were last fetched in
+    // full, a game which failed is tried again on the next run
+    if is_stored {
+        let fetched = game_ids
+            .into_iter()
+            .filter(|game_id| !run.has_failed_game(game_id.as_str()))
+            .collect_vec();
+        let db = db.lock().await;
+        db::fetch_policy::set_last_fetched_at(&db, &fetched, started_at)?;
+    }
+
     Ok(())
 }
 
+/// Asks for clips recorded since the latest fetched clip of the game, unless
+/// a window is given.
+fn first_request(
+    game_id: &twitch::models::GameId,
+    latest_clip_recorded_at: Option<chrono::DateTime<Utc>>,
+    recorded_at_most_ago: Option<chrono::Duration>,
+    recorded_at_least_ago: Option<chrono::Duration>,
+) -> twitch::models::GetClipsRequest {
+    twitch::models::GetClipsRequest::builder()
+        .game_id(Some(game_id.clone().into()))
+        .first(100)
+        .started_at(
+            recorded_at_most_ago
+                .map(|at| chrono::Utc::now() - at) // user precedence
+                .or(latest_clip_recorded_at) // else use latest (if set)
+                .or_else(|| {
+                    // otherwise default to 2 days ago for new games
+                    Some(chrono::Utc::now() - chrono::Duration::days(2))
+                })
+                .and_then(|t| Timestamp::new(t.to_rfc3339()).ok()),
+        )
+        .ended_at(
+            Some(
+                chrono::Utc::now()
+                    - recorded_at_least_ago
+                        .unwrap_or(chrono::Duration::days(1)),
+            )
+            .and_then(|t| Timestamp::new(t.to_rfc3339()).ok()),
+        )
+        .build()
+}
+
 /// Send clips down this channel to get them persisted in db.
 ///
 /// This channel will keep running as long as the main job task scope lives.
-/// Then the handle to the sender is dropped.
+/// Then the handle to the sender is dropped and the returned task ends once
+/// the remaining clips are stored, telling whether all of them were.
 fn spawn_channel_to_store_clips(
     db: DbLock,
-) -> UnboundedSender<twitch::models::Clip> {
+    run: Run,
+) -> (UnboundedSender<twitch::models::Clip>, JoinHandle<bool>) {
     let (store_clips, mut next_clip) =
         mpsc::unbounded_channel::<twitch::models::Clip>();
 
-    tokio::spawn(async move {
+    let stored = tokio::spawn(async move {
         const BUFFER_CAP: usize = 128;
 
         let mut buffer = Vec::with_capacity(BUFFER_CAP);
@@ -195,13 +373,23 @@ fn spawn_channel_to_store_clips(
         let store =
             |db: &mut DbConn, buffer: &mut Vec<twitch::models::Clip>| {
                 let tx = db.transaction()?;
+                let mut is_stored = tx.prepare(
+                    "SELECT EXISTS(SELECT 1 FROM clips WHERE id = ?)",
+                )?;
                 let mut stmt = twitch::models::InsertClipStatement::new(&tx)?;
 
                 for clip in buffer.iter() {
+                    if is_stored.query_row([&clip.id], |row| row.get(0))? {
+                        run.count(|stats| stats.clips_updated += 1);
+                    } else {
+                        run.count(|stats| stats.clips_inserted += 1);
+                    }
                     stmt.execute(clip)?;
                 }
 
+                drop(is_stored);
                 drop(stmt);
+                run.save_progress(&tx)?;
                 tx.commit()?;
 
                 buffer.clear();
@@ -247,12 +435,15 @@ fn spawn_channel_to_store_clips(
             }
         };
 
-        if let Err(e) = res {
+        if let Err(e) = &res {
             error!("Failed to store {} clips in db: {e}", buffer.len());
+            run.error(format!("Cannot store clips: {e}"));
         }
+
+        res.is_ok()
     });
 
-    store_clips
+    (store_clips, stored)
 }
 
 /// Spawns a new task which will fetch clips for a given request.
@@ -260,14 +451,18 @@ fn spawn_channel_to_store_clips(
 /// If the request is paginated, it will spawn a new task for the next page.
 /// The difference between the new paginated request is only the cursor.
 ///
-/// Any fetched clips will be sent down the channel to store them in db.
+/// Any fetched clips the game's policy accepts will be sent down the channel
+/// to store them in db.
 fn spawn_task_to_fetch_clip(
     tc: Arc<twitch::Client>,
     channel_to_store_clips: UnboundedSender<twitch::models::Clip>,
+    run: Run,
     el: QueueElement,
 ) {
     let QueueElement {
         request,
+        page,
+        policy,
         retry_count,
         fetch_clips,
     } = el;
@@ -275,16 +470,44 @@ fn spawn_task_to_fetch_clip(
     tokio::spawn(async move {
         match tc.get_clips_paginated(request.clone()).await {
             Ok((clips, cursor)) => {
+                run.count(|stats| stats.pages_fetched += 1);
                 let clips_len = clips.len();
                 let game_id =
                     request.game_id.expect("Game ID is always present");
+                // Twitch returns the most viewed clips first, so no clip on
+                // the next pages has enough views either
+                let is_below_min_view_count =
+                    policy.min_view_count.is_some_and(|min| {
+                        clips
+                            .last()
+                            .is_some_and(|clip| clip.view_count < min as usize)
+                    });
 
                 for clip in clips {
+                    if !policy.accepts(&clip) {
+                        continue;
+                    }
                     if channel_to_store_clips.send(clip).is_err() {
                         error!("Failed to send clip to store, channel closed");
                     }
                 }
 
+                let cursor = cursor.filter(|_| {
+                    if policy.is_last_page(page) {
+                        debug!(
+                            "Fetched all {page} pages allowed for {game_id}"
+                        );
+                        false
+                    } else if is_below_min_view_count {
+                        debug!(
+                            "Fetched all clips with enough views for {game_id}"
+                        );
+                        false
+                    } else {
+                        true
+                    }
+                });
+
                 if let Some(next_request) = cursor {
                     debug!("Fetched {clips_len} more clips for {game_id}");
 
@@ -292,6 +515,8 @@ fn spawn_task_to_fetch_clip(
                         .clone()
                         .send(QueueElement {
                             request: next_request,
+                            page: page + 1,
+                            policy,
                             retry_count: 0,
                             fetch_clips,
                         })
@@ -310,12 +535,15 @@ fn spawn_task_to_fetch_clip(
                     let delay_ms = rand::thread_rng().gen_range(1_000..8_000);
 
                     warn!("Rate limited, retrying after {delay_ms}ms");
+                    run.count(|stats| stats.retries += 1);
                     sleep(Duration::from_millis(delay_ms)).await;
 
                     if fetch_clips
                         .clone()
                         .send(QueueElement {
                             request,
+                            page,
+                            policy,
                             retry_count: retry_count + 1,
                             fetch_clips,
                         })
@@ -325,18 +553,90 @@ fn spawn_task_to_fetch_clip(
                         error!("Main channel closed, cannot resend page");
                     }
                 } else {
-                    error!(
-                        "Rate limited and retry count exceeded (game {})",
-                        request.game_id.expect("Game ID is always present")
+                    let game_id =
+                        request.game_id.expect("Game ID is always present");
+                    let e = format!(
+                        "Rate limited and retry count exceeded (game {game_id})"
                     );
+                    error!("{e}");
+                    run.fail_game(game_id, e);
                 }
             }
             Err(e) => {
-                error!(
-                    "Failed to get clips for game {}: {e}",
-                    request.game_id.expect("Game ID is always present")
-                );
+                let game_id =
+                    request.game_id.expect("Game ID is always present");
+                let e = format!("Failed to get clips for game {game_id}: {e}");
+                error!("{e}");
+                run.fail_game(game_id, e);
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::{Role, User};
+
+    #[test]
+    fn it_fetches_on_from_the_latest_fetched_clip() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        db::game::insert(
+            &db,
+            &twitch::models::Game {
+                id: "55".into(),
+                name: "Game".to_string(),
+                box_art_url: String::new(),
+            },
+        )?;
+        db::game::set_is_paused(&db, &"55".into(), false)?;
+        let started_at = |db: &DbConn| -> Result<_> {
+            let targets = select_due_targets(db, &Conf::default())?;
+            assert_eq!(targets.len(), 1, "game 55 is due");
+            let target = &targets[0];
+
+            Ok(first_request(
+                &target.game_id,
+                target.latest_clip_recorded_at,
+                target.recorded_at_most_ago,
+                target.recorded_at_least_ago,
+            )
+            .started_at)
+        };
+        let before_import = started_at(&db)?;
+        assert!(before_import.is_some());
+
+        let mut fresh_clip =
+            db::clip::select_by_id(&db, "FlirtyTenuousCasetteHoneyBadger")
+                .map(|clip| twitch::models::Clip {
+                    id: "ImportedFreshClip".to_string(),
+                    broadcaster_id: clip.broadcaster_id,
+                    broadcaster_name: clip.broadcaster_name,
+                    creator_name: clip.creator_name,
+                    recorded_at: String::new(),
+                    duration: clip.duration,
+                    title: clip.title,
+                    url: clip.url,
+                    thumbnail_url: clip.thumbnail_url,
+                    view_count: clip.view_count,
+                    lang: clip.lang,
+                    game_id: clip.game_id,
+                })?;
+        fresh_clip.recorded_at = (Utc::now() - chrono::Duration::hours(1))
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        db::clip::upsert_imported(
+            &db,
+            &[fresh_clip],
+            &User {
+                id: 1,
+                username: "jane".to_string(),
+                role: Role::Editor,
+            },
+        )?;
+
+        assert_eq!(started_at(&db)?, before_import);
+
+        Ok(())
+    }
+}
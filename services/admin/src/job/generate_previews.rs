@@ -0,0 +1,133 @@
+use itertools::Itertools;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::history;
+use crate::models::job_run::Trigger;
+use crate::models::preview::ClipPreview;
+use crate::prelude::*;
+
+/// Clips of each game a scheduled run goes through, most are previewed by
+/// earlier runs already.
+const SCHEDULED_LIMIT: usize = 50;
+
+/// Has the worker download the most viewed clips of the game which have no
+/// previews yet and generate them.
+///
+/// A clip whose previews fail to generate is logged and left without them.
+pub async fn once(
+    db: DbLock,
+    workers: WorkerPool,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) -> Result<()> {
+    let clips = {
+        let db = db.lock().await;
+        db::preview::select_unpreviewed_clips(&db, &game_id, limit)?
+    };
+    info!(
+        "Generating previews of {} clips of game {game_id}",
+        clips.len()
+    );
+
+    for (clip_id, url, duration) in clips {
+        match generate(&workers, &game_id, &clip_id, &url, duration).await {
+            Ok(preview) => {
+                let db = db.lock().await;
+                db::preview::insert(&db, &preview)?;
+            }
+            Err(e) => {
+                warn!("Cannot generate previews of {clip_id}: {e}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Generates previews of the most viewed clips of every game which is not
+/// paused, a game which fails is recorded and the rest go on.
+pub async fn once_for_all(
+    db: DbLock,
+    workers: WorkerPool,
+    trigger: Trigger,
+) -> Result<()> {
+    history::track(
+        Arc::clone(&db),
+        "generate_previews",
+        trigger,
+        vec![],
+        json!({ "limit": SCHEDULED_LIMIT }),
+        |run| async move {
+            let game_ids = {
+                let db = db.lock().await;
+                let game_ids =
+                    db::game::select_all_active_with_latest_clip_recorded_at(
+                        &db,
+                    )?
+                    .into_iter()
+                    .map(|(game_id, _)| game_id)
+                    .collect_vec();
+                run.set_game_ids(
+                    &db,
+                    &game_ids
+                        .iter()
+                        .map(|game_id| game_id.to_string())
+                        .collect_vec(),
+                )?;
+
+                game_ids
+            };
+
+            for game_id in game_ids {
+                if let Err(e) = once(
+                    Arc::clone(&db),
+                    workers.clone(),
+                    game_id.clone(),
+                    SCHEDULED_LIMIT,
+                )
+                .await
+                {
+                    warn!("Cannot generate previews of game {game_id}: {e}");
+                    run.error(format!("Game {game_id}: {e}"));
+                }
+            }
+
+            Ok(())
+        },
+    )
+    .await
+}
+
+async fn generate(
+    workers: &WorkerPool,
+    game_id: &twitch::models::GameId,
+    clip_id: &str,
+    url: &str,
+    duration: Duration,
+) -> Result<ClipPreview> {
+    let (addr, resp) = workers
+        .call_located(
+            workers.route_for_game(game_id),
+            |mut worker| async move {
+                worker
+                    .download_clip(worker::rpc::DownloadClipRequest {
+                        clip_id: clip_id.to_string(),
+                        url: url.to_string(),
+                    })
+                    .await?;
+
+                worker
+                    .generate_previews(worker::rpc::GeneratePreviewsRequest {
+                        clip_id: clip_id.to_string(),
+                        // durations are stored in whole seconds
+                        duration_secs: duration.as_secs().max(1) as f64,
+                    })
+                    .await
+            },
+        )
+        .await?;
+
+    Ok(ClipPreview::from_rpc(clip_id, addr, resp.into_inner()))
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::models::job_run::{Stats, Trigger};
+use crate::prelude::*;
+
+/// Handle to the record of a running job, which the job counts what it did
+/// with.
+#[derive(Clone)]
+pub struct Run {
+    id: i64,
+    progress: Arc<Mutex<Progress>>,
+}
+
+#[derive(Default, Clone)]
+struct Progress {
+    stats: Stats,
+    errors: Vec<String>,
+    /// Games the job failed for, if it tells
+    failed_game_ids: Vec<String>,
+}
+
+impl Run {
+    pub fn count(&self, f: impl FnOnce(&mut Stats)) {
+        f(&mut self.lock().stats)
+    }
+
+    /// The run fails, but the job goes on.
+    pub fn error(&self, e: impl ToString) {
+        self.lock().errors.push(e.to_string());
+    }
+
+    /// The run fails for the game, but the job goes on with the rest.
+    pub fn fail_game(&self, game_id: impl ToString, e: impl ToString) {
+        let mut progress = self.lock();
+        progress.errors.push(e.to_string());
+        progress.failed_game_ids.push(game_id.to_string());
+    }
+
+    pub fn has_failed_game(&self, game_id: &str) -> bool {
+        self.lock().failed_game_ids.iter().any(|id| id == game_id)
+    }
+
+    pub fn set_game_ids(&self, db: &DbConn, game_ids: &[String]) -> Result<()> {
+        db::job_run::set_game_ids(db, self.id, game_ids)
+    }
+
+    /// So that the progress of running jobs can be seen.
+    pub fn save_progress(&self, db: &DbConn) -> Result<()> {
+        let Progress { stats, errors, .. } = self.lock().clone();
+        db::job_run::update_progress(db, self.id, &stats, &errors)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress
+            .lock()
+            .expect("Nothing panics while holding it")
+    }
+}
+
+/// Runs the job and keeps a record of it in db, see `/jobs`.
+///
+/// Nobody waits for jobs which were spawned, so an error the job returns is
+/// logged here besides being recorded.
+pub async fn track<T, F, Fut>(
+    db: DbLock,
+    job: &'static str,
+    trigger: Trigger,
+    game_ids: Vec<String>,
+    conf: Value,
+    f: F,
+) -> Result<T>
+where
+    F: FnOnce(Run) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let id = {
+        let db = db.lock().await;
+        db::job_run::insert(&db, job, trigger, &game_ids, &conf)?
+    };
+    let run = Run {
+        id,
+        progress: Default::default(),
+    };
+
+    let res = f(run.clone()).await;
+    if let Err(e) = &res {
+        error!("Job {job} failed: {e}");
+        run.error(e);
+    }
+
+    let Progress { stats, errors, .. } = run.lock().clone();
+    let db = db.lock().await;
+    if let Err(e) = db::job_run::finish(&db, id, &stats, &errors) {
+        error!("Cannot record the end of job {job}: {e}");
+    }
+
+    res
+}
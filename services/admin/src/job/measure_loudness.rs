@@ -0,0 +1,65 @@
+use crate::models::loudness::Loudness;
+use crate::prelude::*;
+
+/// Has the worker download the most viewed clips of the game whose loudness
+/// wasn't measured yet and measure it.
+///
+/// A clip which fails to download or measure is logged and left unmeasured.
+pub async fn once(
+    db: DbLock,
+    workers: WorkerPool,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) -> Result<()> {
+    let clips = {
+        let db = db.lock().await;
+        db::loudness::select_unmeasured_clips(&db, &game_id, limit)?
+    };
+    info!(
+        "Measuring loudness of {} clips of game {game_id}",
+        clips.len()
+    );
+
+    for (clip_id, url) in clips {
+        match measure(&workers, &game_id, &clip_id, &url).await {
+            Ok(loudness) => {
+                let db = db.lock().await;
+                db::loudness::insert(&db, &clip_id, &loudness)?;
+            }
+            Err(e) => {
+                warn!("Cannot measure loudness of {clip_id}: {e}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Downloads the clip to the worker if it isn't there yet.
+pub async fn measure(
+    workers: &WorkerPool,
+    game_id: &twitch::models::GameId,
+    clip_id: &str,
+    url: &str,
+) -> Result<Loudness> {
+    let resp = workers
+        .call(workers.route_for_game(game_id), |mut worker| async move {
+            worker
+                .download_clip(worker::rpc::DownloadClipRequest {
+                    clip_id: clip_id.to_string(),
+                    url: url.to_string(),
+                })
+                .await?;
+
+            worker
+                .measure_loudness(worker::rpc::MeasureLoudnessRequest {
+                    clip_id: clip_id.to_string(),
+                    start_secs: None,
+                    end_secs: None,
+                })
+                .await
+        })
+        .await?;
+
+    Ok(resp.into_inner().into())
+}
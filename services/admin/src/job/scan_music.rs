@@ -0,0 +1,65 @@
+use crate::prelude::*;
+
+/// Has the worker download the most viewed clips of the game which haven't
+/// been scanned yet and check their audio against the reference tracks.
+///
+/// A clip which fails to download or scan is logged and left unscanned.
+pub async fn once(
+    db: DbLock,
+    workers: WorkerPool,
+    game_id: twitch::models::GameId,
+    limit: usize,
+) -> Result<()> {
+    let clips = {
+        let db = db.lock().await;
+        db::music::select_unscanned_clips(&db, &game_id, limit)?
+    };
+    info!("Scanning music of {} clips of game {game_id}", clips.len());
+
+    for (clip_id, url) in clips {
+        match scan(&workers, &game_id, &clip_id, &url).await {
+            Ok(matches) => {
+                if !matches.is_empty() {
+                    info!(
+                        "Clip {clip_id} plays {} known tracks",
+                        matches.len()
+                    );
+                }
+
+                let mut db = db.lock().await;
+                db::music::insert_scan(&mut db, &clip_id, &matches)?;
+            }
+            Err(e) => {
+                warn!("Cannot scan music of {clip_id}: {e}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn scan(
+    workers: &WorkerPool,
+    game_id: &twitch::models::GameId,
+    clip_id: &str,
+    url: &str,
+) -> Result<Vec<worker::rpc::MusicMatch>> {
+    let resp = workers
+        .call(workers.route_for_game(game_id), |mut worker| async move {
+            worker
+                .download_clip(worker::rpc::DownloadClipRequest {
+                    clip_id: clip_id.to_string(),
+                    url: url.to_string(),
+                })
+                .await?;
+
+            worker
+                .scan_clip_music(worker::rpc::ScanClipMusicRequest {
+                    clip_id: clip_id.to_string(),
+                })
+                .await
+        })
+        .await?;
+
+    Ok(resp.into_inner().matches)
+}
@@ -0,0 +1,93 @@
+use itertools::Itertools;
+use std::path::PathBuf;
+
+use crate::models::project::ProjectStatus;
+use crate::models::user::User;
+use crate::prelude::*;
+
+/// Uploads the edited video of a ready project to the connected channel,
+/// with a title, chapters and credits generated from its entries, then
+/// publishes the project.
+///
+/// Fails if music which was marked to exclude plays in an entry as trimmed.
+/// The channel's token is stored again if it was refreshed, even if the
+/// upload fails.
+pub async fn once(
+    db: DbLock,
+    youtube: youtube::Client,
+    project_id: i64,
+    video_path: PathBuf,
+    user: User,
+) -> Result<()> {
+    let (project, entries, game_name, token) = {
+        let db = db.lock().await;
+        let project = db::project::select_by_id(&db, project_id)?;
+        let entries = db::project::select_entries(&db, project_id)?
+            .into_iter()
+            .map(|entry| {
+                let music = db::music::select_matches(&db, &entry.clip.id)?;
+                Ok((entry, music))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // compilations are mostly of one game, tagged with the most common
+        let game_name = entries
+            .iter()
+            .map(|(entry, _)| entry.clip.game_id.as_str())
+            .counts()
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .and_then(|(game_id, _)| {
+                db::game::select_by_id(&db, &game_id.into()).ok()
+            })
+            .map(|game| game.name);
+        let token = db::youtube::select_token(&db)?.ok_or_else(|| {
+            AppError::bad_request("No YouTube channel is connected")
+        })?;
+
+        (project, entries, game_name, token)
+    };
+    if project.status != ProjectStatus::Ready {
+        return Err(AppError::bad_request(format!(
+            "Project {project_id} must be ready to be uploaded"
+        )));
+    }
+    for (entry, music) in &entries {
+        models::music::mute_ranges(
+            music,
+            entry.trim_in_secs.unwrap_or(0.0),
+            entry
+                .trim_out_secs
+                .unwrap_or(entry.clip.duration.as_secs_f64()),
+        )?;
+    }
+
+    let entries = entries.into_iter().map(|(entry, _)| entry).collect_vec();
+    let metadata = youtube::metadata::generate(
+        &project.title,
+        game_name.as_deref(),
+        &models::project::compilation_clips(&entries),
+    );
+    info!(
+        "Uploading project {project_id} from {video_path:?} as '{}'",
+        metadata.title
+    );
+
+    let mut refreshed_token = token.clone();
+    let uploaded = youtube
+        .upload(&mut refreshed_token, &metadata, &video_path)
+        .await;
+
+    let db = db.lock().await;
+    if refreshed_token != token {
+        db::youtube::save_token(&db, &refreshed_token)?;
+    }
+    let video = uploaded?;
+    db::project::insert_upload(&db, project_id, &video.id, &user)?;
+    db::project::set_status(&db, project_id, ProjectStatus::Published, &user)?;
+    info!(
+        "Project {project_id} published as YouTube video {}",
+        video.id
+    );
+
+    Ok(())
+}
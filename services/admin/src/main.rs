@@ -1,3 +1,5 @@
+/// Commands run from the terminal instead of serving http
+mod cli;
 /// Global config structure loaded from .env file
 mod conf;
 /// Database schema, models and helpers
@@ -16,6 +18,8 @@ mod models;
 mod prelude;
 /// Http templates with handlebars
 mod views;
+/// Routes RPCs to workers which are up and least busy
+mod worker_pool;
 
 use crate::prelude::*;
 use crate::views::Views;
@@ -30,21 +34,30 @@ async fn main() -> AnyResult<()> {
     info!("web admin starting");
 
     let conf = Conf::from_env()?;
-    let worker = Arc::new(Mutex::new(conf.connect_lazy_worker_client().await?));
+    if cli::run(&conf)? {
+        return Ok(());
+    }
+
+    let workers = conf.connect_worker_pool()?;
+    workers.spawn_health_checks();
     let tc = Arc::new(conf.construct_twitch_client().await?);
     let mut db = db::open(conf.db_path())?;
     db::up(&mut db)?;
     let db = Arc::new(Mutex::new(db));
 
-    let jobs = job::schedule_all(Arc::clone(&db), Arc::clone(&tc)).await?;
+    let jobs =
+        job::schedule_all(Arc::clone(&db), Arc::clone(&tc), workers.clone())
+            .await?;
 
+    let youtube = conf.construct_youtube_client();
     let g = g::HttpState {
         conf: Arc::new(conf),
         db,
-        worker,
+        workers,
         views: Views::new()?,
         twitch: tc,
-        _jobs: jobs,
+        youtube,
+        jobs,
     };
 
     http::start(g).await?;
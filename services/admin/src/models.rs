@@ -1 +1,16 @@
 pub mod clip;
+pub mod clip_import;
+pub mod export;
+pub mod fetch_policy;
+pub mod job_run;
+pub mod layout;
+pub mod loudness;
+pub mod moment;
+pub mod music;
+pub mod preview;
+pub mod project;
+pub mod review;
+pub mod saved_search;
+pub mod setting;
+pub mod tag;
+pub mod user;
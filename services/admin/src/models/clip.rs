@@ -1,22 +1,34 @@
+use crate::models::loudness::Loudness;
+use crate::models::review::ReviewState;
 use crate::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Copy, Default)]
 #[serde(rename_all(deserialize = "kebab-case", serialize = "snake_case"))]
+#[schema(rename_all = "kebab-case")]
 pub enum ShowSortBy {
     RecordedAt,
     #[default]
     ViewCount,
+    /// Best matches of `search` first, by view count without a search
+    Relevance,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, IntoParams, Debug, Default)]
 #[serde(rename_all(deserialize = "kebab-case", serialize = "snake_case"))]
+#[into_params(parameter_in = Query, rename_all = "kebab-case")]
 pub struct ShowParams {
     #[serde(default = "default_page_size")]
     pub page_size: usize,
     #[serde(default)]
     pub page_offset: usize,
+    /// Where the page starts, the `next_cursor` of the page before. Unlike
+    /// `page-offset`, which it replaces, it stays fast deep into a listing.
+    #[serde(default)]
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    pub cursor: Option<String>,
     #[serde(default)]
     pub sort_direction_asc: bool,
     #[serde(default)]
@@ -25,10 +37,18 @@ pub struct ShowParams {
     #[serde(default)]
     #[serde(deserialize_with = "g::empty_string_is_none")]
     pub title_like: Option<String>,
+    /// Full-text search in titles and tags. Words are stemmed and all must
+    /// match, supports `"exact phrase"`, `prefix*`, `OR`, `NOT` and
+    /// `tags:sponsor-safe`.
+    #[serde(default)]
+    #[serde(deserialize_with = "g::empty_string_is_none")]
+    pub search: Option<String>,
     #[serde(default)]
     #[serde(deserialize_with = "g::csv_string_is_vec")]
+    #[param(value_type = Option<String>, example = "en,de")]
     pub langs: Vec<String>,
     #[serde(default = "default_sort_by")]
+    #[param(inline)]
     pub sort_by: ShowSortBy,
     pub view_count_max: Option<usize>,
     #[serde(default)]
@@ -39,19 +59,57 @@ pub struct ShowParams {
     #[serde(default)]
     #[serde(deserialize_with = "g::empty_string_is_none")]
     pub max_recorded_at: Option<String>,
+    /// Only clips recorded in the last this many days, unlike
+    /// `min-recorded-at` it stays relative to today in saved searches.
+    pub recorded_within_days: Option<u32>,
+    /// By default only the canonical clip of each moment is listed.
+    #[serde(default)]
+    pub show_duplicates: bool,
+    /// Lists all clips of given moment, including duplicates.
+    pub moment_id: Option<i64>,
+    /// Rejected clips and clips used in a video are left out unless asked
+    /// for.
+    #[serde(default)]
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    #[param(value_type = Option<String>, example = "new,shortlisted")]
+    pub review_states: Vec<String>,
+    /// Only clips which have all of the tags.
+    #[serde(default)]
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    #[param(value_type = Option<String>, example = "funny,sponsor-safe")]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Clip {
     pub broadcaster_id: String,
     pub broadcaster_name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub creator_name: String,
+    /// How many other clips captured the same moment if this is the
+    /// canonical clip of the moment
+    pub duplicate_count: usize,
+    #[schema(value_type = SerializedDuration)]
     pub duration: Duration,
     pub game_id: String,
+    /// Whether the worker generated a storyboard, waveform and animation
+    pub has_preview: bool,
     pub id: String,
+    /// Media of the clip is never garbage collected by the worker
+    pub is_kept: bool,
     pub lang: String,
+    /// None until the worker measures it
+    pub loudness: Option<Loudness>,
+    pub moment_id: Option<i64>,
+    /// How many parts of the clip play a known track
+    pub music_match_count: usize,
     pub recorded_at: chrono::DateTime<chrono::Utc>,
+    pub review_state: ReviewState,
+    /// None until someone reviews the clip
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Sorted by name
+    pub tags: Vec<String>,
     pub thumbnail_url: String,
     pub title: String,
     pub updated_at: chrono::DateTime<chrono::Utc>,
@@ -59,6 +117,28 @@ pub struct Clip {
     pub view_count: usize,
 }
 
+/// One page of the clips listing.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClipPage {
+    /// Of all clips which match the filters, not only of this page, but
+    /// counting stops past 10 000
+    pub total_count: usize,
+    /// False if more clips match than were counted
+    pub is_total_count_exact: bool,
+    pub clips: Vec<Clip>,
+    /// Pass as `cursor` to get the page after this one, None on the last
+    /// page
+    pub next_cursor: Option<String>,
+}
+
+/// How serde serializes a [`Duration`], only described for the API.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct SerializedDuration {
+    secs: u64,
+    nanos: u32,
+}
+
 fn default_page_size() -> usize {
     50
 }
@@ -71,6 +151,7 @@ impl From<ShowSortBy> for &'static str {
         match s {
             ShowSortBy::RecordedAt => "recorded_at",
             ShowSortBy::ViewCount => "view_count",
+            ShowSortBy::Relevance => "relevance",
         }
     }
 }
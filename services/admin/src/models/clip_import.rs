@@ -0,0 +1,208 @@
+use axum::http::Uri;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::game::Game;
+use crate::prelude::*;
+
+/// An import looks up at most this many clips on Twitch.
+pub const MAX_CLIPS: usize = 500;
+
+/// CSV columns which hold clip links or ids, compared case insensitively.
+const ID_COLUMNS: [&str; 6] =
+    ["id", "clip_id", "clip", "url", "clip_url", "watch_url"];
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct ImportClips {
+    /// Twitch clip links or ids separated by whitespace, commas or new lines,
+    /// or a CSV whose header row names the column with them, such as an
+    /// export of clips
+    #[schema(example = "https://clips.twitch.tv/AwkwardHelplessSalamander")]
+    pub clips: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, Default)]
+pub struct ImportReport {
+    /// Clips which were not stored before
+    pub inserted: Vec<ImportedClip>,
+    /// Clips which were stored before, their view counts are refreshed
+    pub updated: Vec<ImportedClip>,
+    /// Ids Twitch doesn't know a clip by
+    pub not_found: Vec<String>,
+    /// Games of imported clips which were not tracked, added paused so that
+    /// their clips aren't fetched
+    pub added_games: Vec<Game>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ImportedClip {
+    pub id: String,
+    pub title: String,
+    pub broadcaster_name: String,
+    #[schema(value_type = String)]
+    pub game_id: twitch::models::GameId,
+}
+
+impl From<&twitch::models::Clip> for ImportedClip {
+    fn from(clip: &twitch::models::Clip) -> Self {
+        Self {
+            id: clip.id.clone(),
+            title: clip.title.clone(),
+            broadcaster_name: clip.broadcaster_name.clone(),
+            game_id: clip.game_id.clone().into(),
+        }
+    }
+}
+
+/// Finds the ids of the clips which were pasted or uploaded, in the order
+/// given and without duplicates.
+/// Fails on anything which is neither a clip link nor an id.
+pub fn parse_clip_ids(clips: &str) -> Result<Vec<String>> {
+    let records: Vec<csv::StringRecord> = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(clips.as_bytes())
+        .records()
+        .try_collect()
+        .map_err(|e| AppError::bad_request(format!("Cannot parse CSV: {e}")))?;
+
+    let id_column = records.first().and_then(|header| {
+        header
+            .iter()
+            .position(|name| ID_COLUMNS.contains(&name.to_lowercase().as_str()))
+    });
+    let references = match id_column {
+        Some(column) => records[1..]
+            .iter()
+            .filter_map(|record| record.get(column))
+            .collect_vec(),
+        None => records
+            .iter()
+            .flat_map(|record| record.iter())
+            .flat_map(str::split_whitespace)
+            .collect_vec(),
+    };
+
+    let (clip_ids, invalid): (Vec<_>, Vec<_>) = references
+        .into_iter()
+        .filter(|reference| !reference.is_empty())
+        .partition_map(|reference| match parse_clip_id(reference) {
+            Some(clip_id) => itertools::Either::Left(clip_id),
+            None => itertools::Either::Right(format!("'{reference}'")),
+        });
+    if !invalid.is_empty() {
+        return Err(AppError::bad_request(format!(
+            "Neither Twitch clip links nor ids: {}",
+            invalid.join(", ")
+        )));
+    }
+
+    let clip_ids = clip_ids.into_iter().unique().collect_vec();
+    if clip_ids.is_empty() {
+        return Err(AppError::bad_request("No clips to import"));
+    }
+    if clip_ids.len() > MAX_CLIPS {
+        return Err(AppError::bad_request(format!(
+            "Cannot import {} clips at once, at most {MAX_CLIPS}",
+            clip_ids.len()
+        )));
+    }
+
+    Ok(clip_ids)
+}
+
+/// Accepts the links Twitch shares clips by, with or without the scheme:
+///
+/// - `https://clips.twitch.tv/{id}`
+/// - `https://clips.twitch.tv/embed?clip={id}`
+/// - `https://www.twitch.tv/{channel}/clip/{id}`
+/// - `https://m.twitch.tv/clip/{id}`
+///
+/// and the ids themselves.
+fn parse_clip_id(reference: &str) -> Option<String> {
+    let clip_id = if reference.contains("twitch.tv/") {
+        let uri: Uri = if reference.contains("://") {
+            reference.parse().ok()?
+        } else {
+            format!("https://{reference}").parse().ok()?
+        };
+        let segments = uri
+            .path()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect_vec();
+
+        match (uri.host()?, segments.as_slice()) {
+            ("clips.twitch.tv", ["embed"]) => uri
+                .query()?
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("clip="))?
+                .to_string(),
+            ("clips.twitch.tv", [clip_id]) => clip_id.to_string(),
+            (
+                "twitch.tv" | "www.twitch.tv" | "m.twitch.tv",
+                [_, "clip", clip_id] | ["clip", clip_id],
+            ) => clip_id.to_string(),
+            _ => return None,
+        }
+    } else {
+        reference.to_string()
+    };
+
+    let is_clip_id = clip_id.len() <= 100
+        && clip_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    is_clip_id.then_some(clip_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_clip_links_and_ids() -> Result<()> {
+        let clip_ids = parse_clip_ids(
+            "https://clips.twitch.tv/AwkwardSalamander-x_Y1
+            clips.twitch.tv/embed?parent=example.com&clip=SwiftRage
+            https://www.twitch.tv/davaeorn/clip/FunnyOtter?filter=clips,
+            https://m.twitch.tv/clip/SadPanda BigBird
+            AwkwardSalamander-x_Y1",
+        )?;
+        assert_eq!(
+            clip_ids,
+            [
+                "AwkwardSalamander-x_Y1",
+                "SwiftRage",
+                "FunnyOtter",
+                "SadPanda",
+                "BigBird"
+            ]
+        );
+
+        // an export of clips
+        let clip_ids = parse_clip_ids(
+            "title,watch_url,view_count\n\
+            \"Wow, look\",https://clips.twitch.tv/FunnyOtter,10\n\
+            Nope,https://clips.twitch.tv/SadPanda,3\n",
+        )?;
+        assert_eq!(clip_ids, ["FunnyOtter", "SadPanda"]);
+
+        for clips in [
+            "",
+            "https://www.twitch.tv/davaeorn",
+            "https://youtube.com/watch?v=FunnyOtter",
+            "FunnyOtter Sad.Panda",
+        ] {
+            assert!(parse_clip_ids(clips).is_err(), "{clips}");
+        }
+
+        let too_many = (0..=MAX_CLIPS).map(|i| format!("Clip{i}")).join(" ");
+        assert!(parse_clip_ids(&too_many).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::clip::Clip;
+use crate::prelude::*;
+
+/// How exported clips are written out.
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// One row per clip after a header row, for spreadsheets
+    #[default]
+    Csv,
+    /// An array of objects
+    Json,
+    /// One object per line
+    Ndjson,
+}
+
+/// What can be exported of a clip, including fields computed on export.
+#[derive(
+    Deserialize, Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportColumn {
+    Id,
+    Title,
+    BroadcasterId,
+    BroadcasterName,
+    CreatorName,
+    RecordedAt,
+    DurationSecs,
+    ViewCount,
+    /// Views since the clip was recorded, per hour
+    ViewsPerHour,
+    Lang,
+    GameId,
+    ReviewState,
+    /// Separated by commas
+    Tags,
+    /// Where the clip plays on Twitch
+    WatchUrl,
+    /// Of the video file
+    DownloadUrl,
+    ThumbnailUrl,
+}
+
+/// Which clips are exported is given by [`super::clip::ShowParams`], this
+/// is how.
+#[derive(Deserialize, IntoParams, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+#[into_params(parameter_in = Query, rename_all = "kebab-case")]
+pub struct ExportParams {
+    #[serde(default)]
+    #[param(inline)]
+    pub format: ExportFormat,
+    /// In this order, all of them if empty.
+    #[serde(default)]
+    #[serde(deserialize_with = "g::csv_string_is_vec")]
+    #[param(value_type = Option<String>, example = "title,watch_url")]
+    pub columns: Vec<String>,
+}
+
+/// Writes clips in given format a batch at a time, so that they can be
+/// streamed.
+pub struct Exporter {
+    format: ExportFormat,
+    columns: Vec<ExportColumn>,
+    /// Views per hour are as of when the export started
+    started_at: DateTime<Utc>,
+    /// No comma before the first object of a JSON array
+    is_first: bool,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl ExportColumn {
+    pub const ALL: [Self; 16] = [
+        Self::Id,
+        Self::Title,
+        Self::BroadcasterId,
+        Self::BroadcasterName,
+        Self::CreatorName,
+        Self::RecordedAt,
+        Self::DurationSecs,
+        Self::ViewCount,
+        Self::ViewsPerHour,
+        Self::Lang,
+        Self::GameId,
+        Self::ReviewState,
+        Self::Tags,
+        Self::WatchUrl,
+        Self::DownloadUrl,
+        Self::ThumbnailUrl,
+    ];
+
+    /// Parses columns as given in the export query, empty means all.
+    pub fn parse_listed(columns: &[String]) -> Result<Vec<Self>> {
+        if columns.is_empty() {
+            return Ok(Self::ALL.to_vec());
+        }
+
+        let columns: Vec<Self> = columns
+            .iter()
+            .map(|column| {
+                Self::try_from(column.replace('-', "_").as_str())
+                    .map_err(AppError::bad_request)
+            })
+            .try_collect()?;
+
+        Ok(columns.into_iter().unique().collect())
+    }
+
+    pub fn value(self, clip: &Clip, now: DateTime<Utc>) -> Value {
+        match self {
+            Self::Id => clip.id.clone().into(),
+            Self::Title => clip.title.clone().into(),
+            Self::BroadcasterId => clip.broadcaster_id.clone().into(),
+            Self::BroadcasterName => clip.broadcaster_name.clone().into(),
+            Self::CreatorName => clip.creator_name.clone().into(),
+            Self::RecordedAt => clip
+                .recorded_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+                .into(),
+            Self::DurationSecs => clip.duration.as_secs().into(),
+            Self::ViewCount => clip.view_count.into(),
+            Self::ViewsPerHour => {
+                // clips an hour old or younger count as an hour old
+                let hours = ((now - clip.recorded_at).num_seconds() as f64
+                    / 3600.0)
+                    .max(1.0);
+                ((clip.view_count as f64 / hours * 100.0).round() / 100.0)
+                    .into()
+            }
+            Self::Lang => clip.lang.clone().into(),
+            Self::GameId => clip.game_id.clone().into(),
+            Self::ReviewState => <&str>::from(clip.review_state).into(),
+            Self::Tags => clip.tags.join(",").into(),
+            Self::WatchUrl => {
+                format!("https://clips.twitch.tv/{}", clip.id).into()
+            }
+            Self::DownloadUrl => clip.url.clone().into(),
+            Self::ThumbnailUrl => clip.thumbnail_url.clone().into(),
+        }
+    }
+}
+
+impl Exporter {
+    pub fn new(format: ExportFormat, columns: Vec<ExportColumn>) -> Self {
+        Self {
+            format,
+            columns,
+            started_at: Utc::now(),
+            is_first: true,
+        }
+    }
+
+    /// The header row of a CSV or the opening bracket of a JSON array.
+    pub fn start(&self) -> Result<Vec<u8>> {
+        match self.format {
+            ExportFormat::Csv => {
+                let mut csv = csv::Writer::from_writer(vec![]);
+                csv.write_record(
+                    self.columns.iter().map(|column| <&str>::from(*column)),
+                )
+                .map_err(AnyError::from)?;
+                Ok(csv
+                    .into_inner()
+                    .map_err(|e| AnyError::msg(e.to_string()))?)
+            }
+            ExportFormat::Json => Ok(b"[".to_vec()),
+            ExportFormat::Ndjson => Ok(vec![]),
+        }
+    }
+
+    pub fn write(&mut self, clips: &[Clip]) -> Result<Vec<u8>> {
+        if let ExportFormat::Csv = self.format {
+            let mut csv = csv::Writer::from_writer(vec![]);
+            for clip in clips {
+                csv.write_record(self.columns.iter().map(|column| {
+                    match column.value(clip, self.started_at) {
+                        Value::String(value) => escape_formula(value),
+                        value => value.to_string(),
+                    }
+                }))
+                .map_err(AnyError::from)?;
+            }
+            return Ok(csv
+                .into_inner()
+                .map_err(|e| AnyError::msg(e.to_string()))?);
+        }
+
+        let mut out = String::new();
+        for clip in clips {
+            // built by hand as a JSON map would sort the columns
+            let object = self
+                .columns
+                .iter()
+                .map(|column| {
+                    format!(
+                        "{}:{}",
+                        Value::from(<&str>::from(*column)),
+                        column.value(clip, self.started_at)
+                    )
+                })
+                .join(",");
+            match self.format {
+                ExportFormat::Json if self.is_first => {
+                    out.push_str(&format!("\n{{{object}}}"))
+                }
+                ExportFormat::Json => out.push_str(&format!(",\n{{{object}}}")),
+                _ => out.push_str(&format!("{{{object}}}\n")),
+            }
+            self.is_first = false;
+        }
+
+        Ok(out.into_bytes())
+    }
+
+    /// The closing bracket of a JSON array.
+    pub fn end(&self) -> Vec<u8> {
+        match self.format {
+            ExportFormat::Json => b"\n]\n".to_vec(),
+            ExportFormat::Csv | ExportFormat::Ndjson => vec![],
+        }
+    }
+}
+
+/// Spreadsheets run cells which start like a formula, and titles are made
+/// up by whoever clipped, so such a cell is prefixed with a quote to be read
+/// as text.
+fn escape_formula(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value
+    }
+}
+
+impl From<ExportColumn> for &'static str {
+    fn from(column: ExportColumn) -> Self {
+        match column {
+            ExportColumn::Id => "id",
+            ExportColumn::Title => "title",
+            ExportColumn::BroadcasterId => "broadcaster_id",
+            ExportColumn::BroadcasterName => "broadcaster_name",
+            ExportColumn::CreatorName => "creator_name",
+            ExportColumn::RecordedAt => "recorded_at",
+            ExportColumn::DurationSecs => "duration_secs",
+            ExportColumn::ViewCount => "view_count",
+            ExportColumn::ViewsPerHour => "views_per_hour",
+            ExportColumn::Lang => "lang",
+            ExportColumn::GameId => "game_id",
+            ExportColumn::ReviewState => "review_state",
+            ExportColumn::Tags => "tags",
+            ExportColumn::WatchUrl => "watch_url",
+            ExportColumn::DownloadUrl => "download_url",
+            ExportColumn::ThumbnailUrl => "thumbnail_url",
+        }
+    }
+}
+
+impl TryFrom<&str> for ExportColumn {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|column| <&str>::from(*column) == s)
+            .ok_or_else(|| format!("Unknown export column '{s}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch::models::GameId;
+
+    use super::*;
+    use crate::models::clip::ShowParams;
+
+    #[test]
+    fn it_exports_clips() -> Result<()> {
+        let db = db::open(":memory:")?;
+        db.execute_batch(include_str!("../../../../tests/assets/clips.sql"))?;
+        let mut clips = db::clip::list(
+            &db,
+            &GameId::from("55"),
+            &ShowParams {
+                page_size: 2,
+                broadcaster_name: Some("Davaeorn".to_string()),
+                ..Default::default()
+            },
+        )?
+        .clips;
+        clips[1].title = "=HYPERLINK(\"https://example.com\")".to_string();
+        let columns = ExportColumn::parse_listed(&[
+            "id".to_string(),
+            "watch-url".to_string(),
+            "views_per_hour".to_string(),
+            "title".to_string(),
+        ])?;
+        let export = |format| -> Result<String> {
+            let mut exporter = Exporter::new(format, columns.clone());
+            let mut out = exporter.start()?;
+            out.extend(exporter.write(&clips[..1])?);
+            out.extend(exporter.write(&clips[1..])?);
+            out.extend(exporter.end());
+            Ok(String::from_utf8(out).map_err(AnyError::from)?)
+        };
+
+        let csv = export(ExportFormat::Csv)?;
+        let rows = csv.lines().collect_vec();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], "id,watch_url,views_per_hour,title");
+        assert!(rows[1].starts_with(&format!(
+            "{0},https://clips.twitch.tv/{0},",
+            clips[0].id
+        )));
+        assert!(
+            rows[2].ends_with(",\"'=HYPERLINK(\"\"https://example.com\"\")\""),
+            "{}",
+            rows[2]
+        );
+
+        let json: Vec<serde_json::Map<String, Value>> =
+            serde_json::from_str(&export(ExportFormat::Json)?)
+                .map_err(AnyError::from)?;
+        assert_eq!(json.len(), 2);
+        assert_eq!(json[1]["id"], clips[1].id);
+        assert!(json[1]["views_per_hour"].is_f64());
+        assert_eq!(json[1]["title"], clips[1].title, "only CSV is escaped");
+
+        let ndjson = export(ExportFormat::Ndjson)?;
+        let lines = ndjson.lines().collect_vec();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!("{{\"id\":\"{}\"", clips[0].id)));
+
+        assert!(ExportColumn::parse_listed(&["secret".to_string()]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::prelude::*;
+
+/// Fetch interval can be at most this, runs are forgotten after 30 days.
+const MAX_FETCH_EVERY_HOURS: u32 = 24 * 30;
+/// Twitch keeps clips forever but fetching more than a month back on every
+/// run would be wasteful.
+const MAX_RECORDED_HOURS_AGO: u32 = 24 * 30;
+
+/// How clips of a game are fetched, unset fields fall back to the global
+/// settings.
+#[derive(
+    Deserialize, Serialize, ToSchema, Debug, Clone, Default, PartialEq,
+)]
+#[serde(default)]
+pub struct FetchPolicy {
+    /// The scheduled job skips the game until this many hours passed since
+    /// its clips were last fetched.
+    /// The game cannot be fetched more often than the job runs.
+    pub fetch_every_hours: Option<u32>,
+    /// Only clips older than this are fetched
+    pub recorded_at_least_hours_ago: Option<u32>,
+    /// Only clips newer than this are fetched, otherwise the job continues
+    /// from the latest clip of the game
+    pub recorded_at_most_hours_ago: Option<u32>,
+    /// Clips with fewer views are not stored
+    pub min_view_count: Option<u32>,
+    /// Language codes as Twitch reports them, clips in other languages are
+    /// not stored.
+    /// Any language if empty.
+    pub langs: Vec<String>,
+    /// Each page has up to 100 clips, most viewed first
+    pub max_pages_per_run: Option<u32>,
+}
+
+impl FetchPolicy {
+    /// Languages are lowercased, duplicates are removed.
+    pub fn validate(mut self) -> Result<Self> {
+        let positive = [
+            ("fetch_every_hours", self.fetch_every_hours),
+            ("max_pages_per_run", self.max_pages_per_run),
+        ];
+        for (name, value) in positive {
+            if value == Some(0) {
+                return Err(AppError::bad_request(format!(
+                    "Policy {name} must be at least 1"
+                )));
+            }
+        }
+
+        if self.fetch_every_hours > Some(MAX_FETCH_EVERY_HOURS) {
+            return Err(AppError::bad_request(format!(
+                "Policy fetch_every_hours must be at most \
+                {MAX_FETCH_EVERY_HOURS}"
+            )));
+        }
+
+        let hours_ago = [
+            (
+                "recorded_at_least_hours_ago",
+                self.recorded_at_least_hours_ago,
+            ),
+            (
+                "recorded_at_most_hours_ago",
+                self.recorded_at_most_hours_ago,
+            ),
+        ];
+        for (name, value) in hours_ago {
+            if value > Some(MAX_RECORDED_HOURS_AGO) {
+                return Err(AppError::bad_request(format!(
+                    "Policy {name} must be at most {MAX_RECORDED_HOURS_AGO}"
+                )));
+            }
+        }
+
+        if let (Some(at_most), Some(at_least)) = (
+            self.recorded_at_most_hours_ago,
+            self.recorded_at_least_hours_ago,
+        ) {
+            if at_most <= at_least {
+                return Err(AppError::bad_request(
+                    "Recorded 'at most' must be greater than 'at least'",
+                ));
+            }
+        }
+
+        for lang in &mut self.langs {
+            *lang = lang.trim().to_lowercase();
+        }
+        self.langs.retain(|lang| !lang.is_empty());
+        self.langs.sort();
+        self.langs.dedup();
+
+        Ok(self)
+    }
+
+    pub fn recorded_at_least_ago(&self) -> Option<Duration> {
+        self.recorded_at_least_hours_ago
+            .map(|hours| Duration::hours(hours.into()))
+    }
+
+    pub fn recorded_at_most_ago(&self) -> Option<Duration> {
+        self.recorded_at_most_hours_ago
+            .map(|hours| Duration::hours(hours.into()))
+    }
+
+    /// Whether the scheduled job should fetch the game given when its clips
+    /// were last fetched.
+    pub fn is_due(
+        &self,
+        last_fetched_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        // the job records when it started, a few seconds after the cron
+        // fired, the next run must not miss the game because of that
+        let leeway = Duration::minutes(5);
+
+        match (self.fetch_every_hours, last_fetched_at) {
+            (Some(hours), Some(at)) => {
+                now - at >= Duration::hours(hours.into()) - leeway
+            }
+            _ => true,
+        }
+    }
+
+    pub fn accepts(&self, clip: &twitch::models::Clip) -> bool {
+        let min_view_count = self.min_view_count.unwrap_or_default();
+
+        clip.view_count >= min_view_count as usize
+            && (self.langs.is_empty()
+                || self.langs.contains(&clip.lang.to_lowercase()))
+    }
+
+    pub fn is_last_page(&self, page: u32) -> bool {
+        self.max_pages_per_run.is_some_and(|max| page >= max)
+    }
+
+    /// True if nothing is overridden.
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_validates_policy() -> Result<()> {
+        assert_eq!(FetchPolicy::default().validate()?, FetchPolicy::default());
+
+        let policy = FetchPolicy {
+            fetch_every_hours: Some(72),
+            recorded_at_least_hours_ago: Some(1),
+            recorded_at_most_hours_ago: Some(72),
+            langs: vec![" EN".to_string(), "en".to_string(), String::new()],
+            ..Default::default()
+        }
+        .validate()?;
+        assert_eq!(policy.langs, vec!["en".to_string()]);
+
+        for invalid in [
+            FetchPolicy {
+                fetch_every_hours: Some(0),
+                ..Default::default()
+            },
+            FetchPolicy {
+                max_pages_per_run: Some(0),
+                ..Default::default()
+            },
+            FetchPolicy {
+                recorded_at_least_hours_ago: Some(8),
+                recorded_at_most_hours_ago: Some(8),
+                ..Default::default()
+            },
+            FetchPolicy {
+                recorded_at_most_hours_ago: Some(MAX_RECORDED_HOURS_AGO + 1),
+                ..Default::default()
+            },
+        ] {
+            assert!(invalid.clone().validate().is_err(), "{invalid:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_is_due_once_interval_passed() {
+        let now = Utc::now();
+        let policy = FetchPolicy {
+            fetch_every_hours: Some(1),
+            ..Default::default()
+        };
+
+        assert!(policy.is_due(None, now));
+        assert!(policy.is_due(Some(now - Duration::minutes(59)), now));
+        assert!(!policy.is_due(Some(now - Duration::minutes(30)), now));
+        assert!(FetchPolicy::default().is_due(Some(now), now));
+    }
+}
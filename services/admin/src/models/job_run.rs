@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Scheduled, see `/settings` for when
+    Cron,
+    /// By a user from the dashboard or the API
+    Manual,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Running,
+    Succeeded,
+    /// Either the job returned an error or it ran into some on the way
+    Failed,
+}
+
+/// Only fetching clips counts these so far.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Pages of clips as returned by Twitch
+    pub pages_fetched: usize,
+    /// Clips which were not in db yet
+    pub clips_inserted: usize,
+    /// Clips which were in db, mostly to update their view count
+    pub clips_updated: usize,
+    /// Requests repeated because Twitch rate limited them
+    pub retries: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct JobRun {
+    pub id: i64,
+    /// Name of the job as it appears in the logs
+    pub job: String,
+    pub trigger: Trigger,
+    pub game_ids: Vec<String>,
+    /// How the job was configured, differs per job
+    pub conf: serde_json::Value,
+    pub status: Status,
+    pub started_at: DateTime<Utc>,
+    /// None while running
+    pub ended_at: Option<DateTime<Utc>>,
+    pub stats: Stats,
+    pub errors: Vec<String>,
+}
+
+impl From<Trigger> for &'static str {
+    fn from(trigger: Trigger) -> Self {
+        match trigger {
+            Trigger::Cron => "cron",
+            Trigger::Manual => "manual",
+        }
+    }
+}
+
+impl TryFrom<&str> for Trigger {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        match s {
+            "cron" => Ok(Self::Cron),
+            "manual" => Ok(Self::Manual),
+            _ => Err(format!("Unknown trigger '{s}'")),
+        }
+    }
+}
+
+impl From<Status> for &'static str {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Running => "running",
+            Status::Succeeded => "succeeded",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+impl TryFrom<&str> for Status {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        match s {
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("Unknown job run status '{s}'")),
+        }
+    }
+}
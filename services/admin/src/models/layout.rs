@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+/// How a landscape clip is arranged in a vertical short.
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutKind {
+    /// Crop the middle of the frame
+    #[default]
+    CenterCrop,
+    /// Whole frame in the middle over a blurred copy of it
+    BlurredFit,
+    /// Facecam on top, gameplay below
+    Split,
+}
+
+/// Part of the source frame, all values are fractions of its width or
+/// height.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Saved per broadcaster since each of them has the facecam elsewhere.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BroadcasterLayout {
+    pub broadcaster_id: String,
+    /// Used unless another layout is picked when rendering
+    pub layout: LayoutKind,
+    pub facecam: CropRect,
+    pub gameplay: CropRect,
+}
+
+impl BroadcasterLayout {
+    /// Until the broadcaster's layout is saved the rectangles assume a
+    /// 16:9 stream with the facecam in the top left corner, sized so that
+    /// neither is cut when filling its part of the short.
+    pub fn default_for(broadcaster_id: &str) -> Self {
+        Self {
+            broadcaster_id: broadcaster_id.to_string(),
+            layout: LayoutKind::default(),
+            facecam: CropRect {
+                x: 0.0,
+                y: 0.0,
+                width: 0.25,
+                height: 0.2634,
+            },
+            gameplay: CropRect {
+                x: 0.2627,
+                y: 0.0,
+                width: 0.4746,
+                height: 1.0,
+            },
+        }
+    }
+
+    /// Layout as the worker renders it, the broadcaster's rectangles are
+    /// used if given layout is split.
+    pub fn to_rpc(&self, layout: LayoutKind) -> worker::rpc::VerticalLayout {
+        use worker::rpc::vertical_layout::Layout;
+
+        let rect = |rect: &CropRect| worker::rpc::CropRect {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        };
+
+        worker::rpc::VerticalLayout {
+            layout: Some(match layout {
+                LayoutKind::CenterCrop => {
+                    Layout::CenterCrop(worker::rpc::CenterCropLayout {})
+                }
+                LayoutKind::BlurredFit => {
+                    Layout::BlurredFit(worker::rpc::BlurredFitLayout {})
+                }
+                LayoutKind::Split => Layout::Split(worker::rpc::SplitLayout {
+                    facecam: Some(rect(&self.facecam)),
+                    gameplay: Some(rect(&self.gameplay)),
+                }),
+            }),
+        }
+    }
+}
+
+impl CropRect {
+    /// Percentages which add up to 100 may stick out by a rounding error.
+    pub fn is_inside_frame(&self) -> bool {
+        let Self {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+
+        [x, y, width, height].iter().all(|v| v.is_finite())
+            && x >= 0.0
+            && y >= 0.0
+            && width > 0.0
+            && height > 0.0
+            && x + width <= 1.0 + 1e-6
+            && y + height <= 1.0 + 1e-6
+    }
+}
+
+impl From<LayoutKind> for &'static str {
+    fn from(l: LayoutKind) -> Self {
+        match l {
+            LayoutKind::CenterCrop => "center_crop",
+            LayoutKind::BlurredFit => "blurred_fit",
+            LayoutKind::Split => "split",
+        }
+    }
+}
+
+impl TryFrom<&str> for LayoutKind {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "center_crop" => Ok(Self::CenterCrop),
+            "blurred_fit" => Ok(Self::BlurredFit),
+            "split" => Ok(Self::Split),
+            _ => Err(format!("Unknown layout '{s}'")),
+        }
+    }
+}
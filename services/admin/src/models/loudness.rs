@@ -0,0 +1,123 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Normalization never amplifies peaks above this, as EBU R128 recommends
+/// for distribution.
+const MAX_TRUE_PEAK_DBTP: f64 = -1.0;
+
+/// EBU R128 loudness of a clip as measured by the worker.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct Loudness {
+    /// None if the clip is silent
+    pub integrated_lufs: Option<f64>,
+    /// None if the clip is silent
+    pub true_peak_dbtp: Option<f64>,
+}
+
+/// Why a clip stands out from the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoudnessFlag {
+    /// Peaks at full scale, which normalization cannot undo
+    Clipping,
+    Loud,
+    Quiet,
+}
+
+impl Loudness {
+    /// Gain which brings the clip to given loudness, lowered so that its
+    /// peaks stay below [`MAX_TRUE_PEAK_DBTP`].
+    /// None if the clip is silent.
+    pub fn normalization_gain_db(&self, target_lufs: f64) -> Option<f64> {
+        let gain = target_lufs - self.integrated_lufs?;
+        let peak_headroom = self
+            .true_peak_dbtp
+            .map_or(f64::INFINITY, |peak| MAX_TRUE_PEAK_DBTP - peak);
+
+        // rounded so that measurements which differ by noise render alike
+        Some((gain.min(peak_headroom) * 10.0).round() / 10.0)
+    }
+
+    /// Flags a clip which clips or is further from the target than the
+    /// tolerance.
+    pub fn flag(
+        &self,
+        target_lufs: f64,
+        tolerance_lu: f64,
+    ) -> Option<LoudnessFlag> {
+        if self.true_peak_dbtp.is_some_and(|peak| peak >= 0.0) {
+            return Some(LoudnessFlag::Clipping);
+        }
+
+        let lufs = self.integrated_lufs?;
+        if lufs > target_lufs + tolerance_lu {
+            Some(LoudnessFlag::Loud)
+        } else if lufs < target_lufs - tolerance_lu {
+            Some(LoudnessFlag::Quiet)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<worker::rpc::MeasureLoudnessResponse> for Loudness {
+    fn from(resp: worker::rpc::MeasureLoudnessResponse) -> Self {
+        Self {
+            integrated_lufs: resp.integrated_lufs,
+            true_peak_dbtp: resp.true_peak_dbtp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loudness(lufs: f64, peak: f64) -> Loudness {
+        Loudness {
+            integrated_lufs: Some(lufs),
+            true_peak_dbtp: Some(peak),
+        }
+    }
+
+    #[test]
+    fn it_normalizes_without_clipping() {
+        assert_eq!(
+            loudness(-20.0, -10.0).normalization_gain_db(-14.0),
+            Some(6.0)
+        );
+        assert_eq!(
+            loudness(-8.0, -0.5).normalization_gain_db(-14.0),
+            Some(-6.0)
+        );
+        // a quiet clip with a loud peak is only amplified up to the peak
+        assert_eq!(
+            loudness(-30.0, -3.0).normalization_gain_db(-14.0),
+            Some(2.0)
+        );
+
+        let silent = Loudness {
+            integrated_lufs: None,
+            true_peak_dbtp: None,
+        };
+        assert_eq!(silent.normalization_gain_db(-14.0), None);
+    }
+
+    #[test]
+    fn it_flags_outliers() {
+        assert_eq!(loudness(-14.0, -3.0).flag(-14.0, 6.0), None);
+        assert_eq!(loudness(-19.9, -3.0).flag(-14.0, 6.0), None);
+        assert_eq!(
+            loudness(-21.0, -3.0).flag(-14.0, 6.0),
+            Some(LoudnessFlag::Quiet)
+        );
+        assert_eq!(
+            loudness(-7.0, -1.0).flag(-14.0, 6.0),
+            Some(LoudnessFlag::Loud)
+        );
+        assert_eq!(
+            loudness(-14.0, 0.3).flag(-14.0, 6.0),
+            Some(LoudnessFlag::Clipping)
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use std::{collections::HashMap, time::Duration};
+
+/// Viewers who clip the same moment do so within a few minutes of each other.
+pub const RECORDED_AT_PROXIMITY: Duration = Duration::from_secs(3 * 60);
+/// Two frames are considered the same if their perceptual hashes differ in at
+/// most this many bits.
+pub const MAX_FRAME_HASH_DISTANCE: u32 = 10;
+/// At least this share of frames of the shorter clip must have a matching
+/// frame in the longer clip for the two clips to be the same moment.
+pub const MIN_MATCHING_FRAMES_RATIO: f64 = 0.5;
+
+/// A clip which was recorded close to another clip of the same broadcaster.
+#[derive(Debug, Clone)]
+pub struct MomentCandidate {
+    pub clip_id: String,
+    pub broadcaster_id: String,
+    pub recorded_at: DateTime<Utc>,
+    pub view_count: usize,
+    /// Where the worker can download the clip from
+    pub url: String,
+    /// One hash per second of the clip.
+    /// Empty if the clip has not been hashed yet.
+    pub frame_hashes: Vec<u64>,
+}
+
+/// Group of clips which captured the same moment of a stream.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Moment {
+    pub broadcaster_id: String,
+    /// The most viewed clip of the moment
+    pub canonical_clip_id: String,
+    /// Includes the canonical clip
+    pub clip_ids: Vec<String>,
+}
+
+pub fn recorded_at_proximity() -> chrono::Duration {
+    chrono::Duration::from_std(RECORDED_AT_PROXIMITY).unwrap()
+}
+
+/// Clips are the same moment if they were recorded by the same broadcaster
+/// close to each other and their sampled frames look alike.
+///
+/// Clips without frame hashes are never the same moment, proximity alone is
+/// not enough because a streamer can have several clip-worthy moments in a
+/// few minutes.
+pub fn is_same_moment(a: &MomentCandidate, b: &MomentCandidate) -> bool {
+    a.broadcaster_id == b.broadcaster_id
+        && (a.recorded_at - b.recorded_at).abs() <= recorded_at_proximity()
+        && frames_match(&a.frame_hashes, &b.frame_hashes)
+}
+
+/// Clips of the same moment overlap but rarely start at the same time, so we
+/// look for each frame of the shorter clip anywhere in the longer clip.
+pub fn frames_match(a: &[u64], b: &[u64]) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if shorter.is_empty() {
+        return false;
+    }
+
+    let matching = shorter
+        .iter()
+        .filter(|hash| {
+            longer.iter().any(|other| {
+                (*hash ^ other).count_ones() <= MAX_FRAME_HASH_DISTANCE
+            })
+        })
+        .count();
+
+    matching as f64 / shorter.len() as f64 >= MIN_MATCHING_FRAMES_RATIO
+}
+
+/// Groups candidates into moments.
+/// Candidates which aren't the same moment as any other candidate are not
+/// returned.
+///
+/// Being the same moment is transitive here, a chain of clips each
+/// overlapping with the next one forms a single moment.
+pub fn group(mut candidates: Vec<MomentCandidate>) -> Vec<Moment> {
+    candidates.sort_by(|a, b| {
+        (&a.broadcaster_id, a.recorded_at)
+            .cmp(&(&b.broadcaster_id, b.recorded_at))
+    });
+
+    // union-find over candidate indices
+    let mut parents = (0..candidates.len()).collect_vec();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    for (i, a) in candidates.iter().enumerate() {
+        // sorted by broadcaster and time, so we only need to look ahead until
+        // we're out of the proximity window
+        for (j, b) in candidates.iter().enumerate().skip(i + 1) {
+            if a.broadcaster_id != b.broadcaster_id
+                || b.recorded_at - a.recorded_at > recorded_at_proximity()
+            {
+                break;
+            }
+
+            if is_same_moment(a, b) {
+                let (ra, rb) = (root(&mut parents, i), root(&mut parents, j));
+                parents[ra] = rb;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<&MomentCandidate>> = HashMap::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        groups
+            .entry(root(&mut parents, i))
+            .or_default()
+            .push(candidate);
+    }
+
+    groups
+        .into_values()
+        .filter(|clips| clips.len() > 1)
+        .map(|clips| {
+            let canonical = clips
+                .iter()
+                .max_by(|a, b| {
+                    // most views, then earliest
+                    a.view_count
+                        .cmp(&b.view_count)
+                        .then(b.recorded_at.cmp(&a.recorded_at))
+                })
+                .expect("Group is not empty");
+
+            Moment {
+                broadcaster_id: canonical.broadcaster_id.clone(),
+                canonical_clip_id: canonical.clip_id.clone(),
+                clip_ids: clips.iter().map(|c| c.clip_id.clone()).collect(),
+            }
+        })
+        .sorted_by(|a, b| a.canonical_clip_id.cmp(&b.canonical_clip_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        clip_id: &str,
+        broadcaster_id: &str,
+        recorded_at: &str,
+        view_count: usize,
+        frame_hashes: Vec<u64>,
+    ) -> MomentCandidate {
+        MomentCandidate {
+            clip_id: clip_id.to_string(),
+            broadcaster_id: broadcaster_id.to_string(),
+            recorded_at: recorded_at.parse().unwrap(),
+            view_count,
+            url: String::new(),
+            frame_hashes,
+        }
+    }
+
+    #[test]
+    fn it_groups_clips_of_the_same_moment() {
+        let moments = group(vec![
+            candidate("a", "1", "2023-07-11T08:05:11Z", 10, vec![1, 2, 3]),
+            // shifted by a second and slightly different encoding
+            candidate(
+                "b",
+                "1",
+                "2023-07-11T08:06:30Z",
+                50,
+                vec![3, 2 | 1 << 40],
+            ),
+            // different broadcaster
+            candidate("c", "2", "2023-07-11T08:05:11Z", 90, vec![1, 2, 3]),
+            // too far in time
+            candidate("d", "1", "2023-07-11T08:20:00Z", 90, vec![1, 2, 3]),
+            // looks different
+            candidate("e", "1", "2023-07-11T08:05:15Z", 90, vec![u64::MAX]),
+        ]);
+
+        assert_eq!(
+            moments,
+            vec![Moment {
+                broadcaster_id: "1".to_string(),
+                canonical_clip_id: "b".to_string(),
+                clip_ids: vec!["a".to_string(), "b".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn it_chains_overlapping_clips() {
+        const X: u64 = 0;
+        const Y: u64 = u64::MAX;
+        const Z: u64 = 0x0000_0000_ffff_ffff;
+        const W: u64 = 0xffff_ffff_0000_0000;
+
+        let moments = group(vec![
+            candidate("a", "1", "2023-07-11T08:00:00Z", 10, vec![X, Y]),
+            candidate("b", "1", "2023-07-11T08:02:00Z", 20, vec![Y, Z]),
+            candidate("c", "1", "2023-07-11T08:04:00Z", 10, vec![Z, W]),
+        ]);
+
+        assert_eq!(moments.len(), 1);
+        assert_eq!(moments[0].canonical_clip_id, "b");
+        assert_eq!(moments[0].clip_ids.len(), 3);
+    }
+
+    #[test]
+    fn it_does_not_group_clips_without_hashes() {
+        let moments = group(vec![
+            candidate("a", "1", "2023-07-11T08:00:00Z", 10, vec![]),
+            candidate("b", "1", "2023-07-11T08:00:10Z", 20, vec![]),
+        ]);
+
+        assert!(moments.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// What to do with a part of a clip which plays a known track when the clip
+/// is exported.
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MusicAction {
+    /// Not decided yet
+    #[default]
+    None,
+    /// Silence the audio of the segment
+    Mute,
+    /// Cut the segment out
+    Exclude,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MusicMatch {
+    pub id: i64,
+    pub clip_id: String,
+    pub track_id: i64,
+    pub track_title: String,
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub score: usize,
+    pub action: MusicAction,
+}
+
+/// Parts of given fragment of the clip to mute when it's rendered.
+/// Fails if excluded music plays in the fragment, it must be cut out first.
+pub fn mute_ranges(
+    matches: &[MusicMatch],
+    start_secs: f64,
+    end_secs: f64,
+) -> Result<Vec<(f64, f64)>> {
+    let playing = matches
+        .iter()
+        .filter(|m| m.start_secs < end_secs && start_secs < m.end_secs);
+
+    let mut ranges = vec![];
+    for m in playing {
+        match m.action {
+            MusicAction::None => {}
+            MusicAction::Mute => ranges.push((m.start_secs, m.end_secs)),
+            MusicAction::Exclude => {
+                return Err(AppError::bad_request(format!(
+                    "'{}' plays from {:.1}s to {:.1}s of clip {}, which is \
+                    excluded, leave it out of the fragment",
+                    m.track_title, m.start_secs, m.end_secs, m.clip_id
+                )))
+            }
+        }
+    }
+
+    Ok(ranges)
+}
+
+impl From<MusicAction> for &'static str {
+    fn from(a: MusicAction) -> Self {
+        match a {
+            MusicAction::None => "none",
+            MusicAction::Mute => "mute",
+            MusicAction::Exclude => "exclude",
+        }
+    }
+}
+
+impl TryFrom<&str> for MusicAction {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        match s {
+            "none" => Ok(Self::None),
+            "mute" => Ok(Self::Mute),
+            "exclude" => Ok(Self::Exclude),
+            _ => Err(format!("Unknown music action '{s}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn music(
+        start_secs: f64,
+        end_secs: f64,
+        action: MusicAction,
+    ) -> MusicMatch {
+        MusicMatch {
+            id: 1,
+            clip_id: "Clip-1".to_string(),
+            track_id: 1,
+            track_title: "Song".to_string(),
+            start_secs,
+            end_secs,
+            score: 10,
+            action,
+        }
+    }
+
+    #[test]
+    fn it_mutes_and_excludes_music() {
+        let matches = [
+            music(2.0, 5.0, MusicAction::Mute),
+            music(8.0, 9.0, MusicAction::None),
+            music(20.0, 25.0, MusicAction::Exclude),
+        ];
+
+        assert_eq!(
+            mute_ranges(&matches, 0.0, 20.0).ok(),
+            Some(vec![(2.0, 5.0)])
+        );
+        assert_eq!(mute_ranges(&matches, 5.0, 20.0).ok(), Some(vec![]));
+        assert!(mute_ranges(&matches, 0.0, 30.0).is_err());
+        assert!(mute_ranges(&matches, 24.0, 26.0).is_err());
+    }
+}
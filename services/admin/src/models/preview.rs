@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+/// Images which let the user triage a clip without playing it.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewKind {
+    /// Grid of frames spread over the clip
+    Storyboard,
+    Waveform,
+    /// Looping few seconds of the middle of the clip
+    Animation,
+}
+
+/// Previews are artifacts on the worker which generated them.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClipPreview {
+    pub clip_id: String,
+    pub worker_addr: SocketAddr,
+    pub storyboard_key: String,
+    /// None if the clip has no audio
+    pub waveform_key: Option<String>,
+    pub animation_key: String,
+}
+
+impl ClipPreview {
+    pub fn from_rpc(
+        clip_id: &str,
+        worker_addr: SocketAddr,
+        resp: worker::rpc::GeneratePreviewsResponse,
+    ) -> Self {
+        Self {
+            clip_id: clip_id.to_string(),
+            worker_addr,
+            storyboard_key: resp.storyboard_key,
+            waveform_key: resp.waveform_key,
+            animation_key: resp.animation_key,
+        }
+    }
+
+    pub fn key(&self, kind: PreviewKind) -> Option<&str> {
+        match kind {
+            PreviewKind::Storyboard => Some(&self.storyboard_key),
+            PreviewKind::Waveform => self.waveform_key.as_deref(),
+            PreviewKind::Animation => Some(&self.animation_key),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::clip::Clip;
+use crate::prelude::*;
+
+/// Where a compilation is on its way to the channel.
+///
+/// draft -> editing -> ready -> published
+#[derive(
+    Deserialize, Serialize, ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectStatus {
+    /// Clips are being picked
+    #[default]
+    Draft,
+    /// Clips are being edited together
+    Editing,
+    /// Waiting to be uploaded
+    Ready,
+    /// Uploaded, the project can't be changed anymore
+    Published,
+}
+
+/// A compilation video such as a weekly best of.
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Project {
+    pub id: i64,
+    pub title: String,
+    /// How long the video should be
+    pub target_duration_secs: u32,
+    pub status: ProjectStatus,
+    pub notes: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// None until published
+    pub published_at: Option<DateTime<Utc>>,
+    /// Set once the video is uploaded to YouTube
+    pub youtube_video_id: Option<String>,
+    /// Of all entries as trimmed
+    pub total_duration_secs: f64,
+    pub entry_count: usize,
+}
+
+/// What editors set when creating or editing a project.
+#[derive(Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct ProjectDetails {
+    pub title: String,
+    /// How long the video should be
+    pub target_duration_secs: u32,
+    #[serde(default)]
+    pub notes: String,
+}
+
+/// What editors set when adding or editing an entry.
+#[derive(Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct EntryDetails {
+    /// Where in the clip the entry starts, the start of the clip if None
+    pub trim_in_secs: Option<f64>,
+    /// Where in the clip the entry ends, the end of the clip if None
+    pub trim_out_secs: Option<f64>,
+    pub notes: String,
+}
+
+/// A clip in a project.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Entry {
+    pub id: i64,
+    /// Starts at 0, entries play in this order
+    pub position: usize,
+    /// Where in the clip the entry starts, the start of the clip if None
+    pub trim_in_secs: Option<f64>,
+    /// Where in the clip the entry ends, the end of the clip if None
+    pub trim_out_secs: Option<f64>,
+    pub notes: String,
+    /// As trimmed
+    pub duration_secs: f64,
+    pub clip: Clip,
+}
+
+/// Broadcasters whose clips are in a project, to credit them in the video
+/// description.
+#[derive(Serialize, ToSchema, Debug, PartialEq, Eq)]
+pub struct Credit {
+    pub broadcaster_id: String,
+    pub broadcaster_name: String,
+    /// How many of the entries are theirs
+    pub clip_count: usize,
+}
+
+impl ProjectStatus {
+    pub const ALL: [Self; 4] =
+        [Self::Draft, Self::Editing, Self::Ready, Self::Published];
+
+    /// A project moves a step at a time and can go back until it's
+    /// published.
+    pub fn next_statuses(self) -> &'static [Self] {
+        match self {
+            Self::Draft => &[Self::Editing],
+            Self::Editing => &[Self::Ready, Self::Draft],
+            Self::Ready => &[Self::Published, Self::Editing],
+            Self::Published => &[],
+        }
+    }
+
+    pub fn can_become(self, status: Self) -> bool {
+        self.next_statuses().contains(&status)
+    }
+}
+
+impl Project {
+    /// Positive if the entries are longer than the target.
+    pub fn overrun_secs(&self) -> f64 {
+        self.total_duration_secs - f64::from(self.target_duration_secs)
+    }
+
+    pub fn ensure_is_editable(&self) -> Result<()> {
+        if self.status == ProjectStatus::Published {
+            return Err(AppError::bad_request(format!(
+                "Project {} is published and cannot be changed",
+                self.id
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectDetails {
+    /// Title is trimmed.
+    pub fn validate(mut self) -> Result<Self> {
+        self.title = self.title.trim().to_string();
+        if self.title.is_empty() {
+            return Err(AppError::bad_request("Project title must be set"));
+        }
+        if self.target_duration_secs == 0 {
+            return Err(AppError::bad_request(
+                "Project target duration must be at least 1s",
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+impl EntryDetails {
+    /// Trims must be within the clip and must leave something of it.
+    pub fn validate(self, clip: &Clip) -> Result<Self> {
+        let clip_secs = clip.duration.as_secs_f64();
+        let trim_in = self.trim_in_secs.unwrap_or(0.0);
+        let trim_out = self.trim_out_secs.unwrap_or(clip_secs);
+
+        if trim_in < 0.0 || trim_out > clip_secs {
+            return Err(AppError::bad_request(format!(
+                "Clip {} is {clip_secs}s long, trim within it",
+                clip.id
+            )));
+        }
+        if trim_in >= trim_out {
+            return Err(AppError::bad_request(
+                "Trim in must come before trim out",
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// Of the clip as trimmed.
+    pub fn duration_secs(&self, clip: &Clip) -> f64 {
+        self.trim_out_secs.unwrap_or(clip.duration.as_secs_f64())
+            - self.trim_in_secs.unwrap_or(0.0)
+    }
+}
+
+/// Entries as they play in the video, for its YouTube metadata.
+pub fn compilation_clips(
+    entries: &[Entry],
+) -> Vec<youtube::metadata::CompilationClip> {
+    entries
+        .iter()
+        .map(|entry| youtube::metadata::CompilationClip {
+            title: entry.clip.title.clone(),
+            broadcaster_name: entry.clip.broadcaster_name.clone(),
+            duration: std::time::Duration::from_secs_f64(entry.duration_secs),
+        })
+        .collect()
+}
+
+/// In the order the broadcasters first appear in the entries.
+pub fn credits(entries: &[Entry]) -> Vec<Credit> {
+    let counts = entries
+        .iter()
+        .map(|entry| &entry.clip.broadcaster_id)
+        .counts();
+
+    entries
+        .iter()
+        .unique_by(|entry| &entry.clip.broadcaster_id)
+        .map(|entry| Credit {
+            broadcaster_id: entry.clip.broadcaster_id.clone(),
+            broadcaster_name: entry.clip.broadcaster_name.clone(),
+            clip_count: counts[&entry.clip.broadcaster_id],
+        })
+        .collect()
+}
+
+/// One line per broadcaster, ready to paste into the video description, as
+/// the upload credits them.
+pub fn credits_text(entries: &[Entry]) -> String {
+    youtube::metadata::credits(&compilation_clips(entries)).join("\n")
+}
+
+impl From<ProjectStatus> for &'static str {
+    fn from(status: ProjectStatus) -> Self {
+        match status {
+            ProjectStatus::Draft => "draft",
+            ProjectStatus::Editing => "editing",
+            ProjectStatus::Ready => "ready",
+            ProjectStatus::Published => "published",
+        }
+    }
+}
+
+impl TryFrom<&str> for ProjectStatus {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|status| <&str>::from(*status) == s)
+            .ok_or_else(|| format!("Unknown project status '{s}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_moves_projects_to_published() {
+        use ProjectStatus::*;
+
+        assert!(Draft.can_become(Editing));
+        assert!(!Draft.can_become(Published));
+        assert!(Editing.can_become(Draft));
+        assert!(Ready.can_become(Published));
+        assert!(Published.next_statuses().is_empty());
+
+        for status in ProjectStatus::ALL {
+            assert_eq!(
+                ProjectStatus::try_from(<&str>::from(status)),
+                Ok(status)
+            );
+        }
+    }
+
+    #[test]
+    fn it_validates_details() -> Result<()> {
+        let details = ProjectDetails {
+            title: " Best of week 42 ".to_string(),
+            target_duration_secs: 600,
+            notes: String::new(),
+        }
+        .validate()?;
+        assert_eq!(details.title, "Best of week 42");
+
+        assert!(ProjectDetails {
+            title: " ".to_string(),
+            ..details.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(ProjectDetails {
+            target_duration_secs: 0,
+            ..details
+        }
+        .validate()
+        .is_err());
+
+        Ok(())
+    }
+}
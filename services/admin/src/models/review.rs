@@ -0,0 +1,148 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::prelude::*;
+
+/// Where a clip is in curation, clips nobody reviewed yet are new.
+///
+/// new -> shortlisted or rejected -> downloaded -> used in video
+#[derive(
+    Deserialize, Serialize, ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewState {
+    #[default]
+    New,
+    /// Picked as a candidate for a video
+    Shortlisted,
+    /// Not to be listed again unless asked for
+    Rejected,
+    /// Downloaded for editing
+    Downloaded,
+    /// Made it into a video, it's never listed again unless asked for
+    UsedInVideo,
+}
+
+/// Who moved a clip from which state to which.
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub clip_id: String,
+    pub from_state: ReviewState,
+    pub to_state: ReviewState,
+    pub reviewed_by: String,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+impl ReviewState {
+    pub const ALL: [Self; 5] = [
+        Self::New,
+        Self::Shortlisted,
+        Self::Rejected,
+        Self::Downloaded,
+        Self::UsedInVideo,
+    ];
+
+    /// Listed when browsing clips unless other states are asked for, so
+    /// that a decision about a clip doesn't need to be made twice.
+    pub const LISTED_BY_DEFAULT: [Self; 3] =
+        [Self::New, Self::Shortlisted, Self::Downloaded];
+
+    /// A decision can be taken back until the clip is used in a video.
+    pub fn next_states(self) -> &'static [Self] {
+        match self {
+            Self::New => &[Self::Shortlisted, Self::Rejected],
+            Self::Shortlisted => &[Self::Downloaded, Self::Rejected, Self::New],
+            Self::Rejected => &[Self::Shortlisted, Self::New],
+            Self::Downloaded => {
+                &[Self::UsedInVideo, Self::Shortlisted, Self::Rejected]
+            }
+            Self::UsedInVideo => &[],
+        }
+    }
+
+    pub fn can_become(self, state: Self) -> bool {
+        self.next_states().contains(&state)
+    }
+
+    /// Parses states as given in the clips query, empty means the default.
+    pub fn parse_listed(states: &[String]) -> Result<Vec<Self>> {
+        if states.is_empty() {
+            return Ok(Self::LISTED_BY_DEFAULT.to_vec());
+        }
+
+        states
+            .iter()
+            .map(|state| {
+                Self::try_from(state.replace('-', "_").as_str())
+                    .map_err(AppError::bad_request)
+            })
+            .collect()
+    }
+}
+
+impl From<ReviewState> for &'static str {
+    fn from(state: ReviewState) -> Self {
+        match state {
+            ReviewState::New => "new",
+            ReviewState::Shortlisted => "shortlisted",
+            ReviewState::Rejected => "rejected",
+            ReviewState::Downloaded => "downloaded",
+            ReviewState::UsedInVideo => "used_in_video",
+        }
+    }
+}
+
+impl TryFrom<&str> for ReviewState {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|state| <&str>::from(*state) == s)
+            .ok_or_else(|| format!("Unknown review state '{s}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_moves_clips_through_review() {
+        use ReviewState::*;
+
+        assert!(New.can_become(Shortlisted));
+        assert!(New.can_become(Rejected));
+        assert!(!New.can_become(Downloaded));
+        assert!(!New.can_become(New));
+        assert!(Shortlisted.can_become(Downloaded));
+        assert!(Downloaded.can_become(UsedInVideo));
+        assert!(Rejected.can_become(Shortlisted));
+        assert!(UsedInVideo.next_states().is_empty());
+
+        for state in ReviewState::ALL {
+            assert_eq!(ReviewState::try_from(<&str>::from(state)), Ok(state));
+        }
+    }
+
+    #[test]
+    fn it_parses_listed_states() -> Result<()> {
+        use ReviewState::*;
+
+        assert_eq!(
+            ReviewState::parse_listed(&[])?,
+            ReviewState::LISTED_BY_DEFAULT
+        );
+        assert_eq!(
+            ReviewState::parse_listed(&[
+                "rejected".to_string(),
+                "used-in-video".to_string()
+            ])?,
+            vec![Rejected, UsedInVideo]
+        );
+        assert!(ReviewState::parse_listed(&["maybe".to_string()]).is_err());
+
+        Ok(())
+    }
+}
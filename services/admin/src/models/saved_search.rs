@@ -0,0 +1,108 @@
+use axum::{extract::Query, http::Uri};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::clip::ShowParams;
+use crate::models::review::ReviewState;
+use crate::prelude::*;
+
+const MAX_QUERY_LEN: usize = 2000;
+
+/// Filters of the clips page saved under a name, they apply to clips of any
+/// game.
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    /// Query string of the clips page, e.g. `langs=en&view-count-min=5000`
+    pub query: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct NewSavedSearch {
+    pub name: String,
+    /// Query string of the clips page, the page offset and cursor are left
+    /// out so that the search starts at the first page
+    pub query: String,
+}
+
+impl NewSavedSearch {
+    /// Fails if the query is not one the clips page understands.
+    pub fn validate(self) -> Result<Self> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::bad_request("Search name must be set"));
+        }
+
+        let query = self
+            .query
+            .trim()
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                !key.is_empty()
+                    && !value.is_empty()
+                    && key != "page-offset"
+                    && key != "cursor"
+            })
+            .join("&");
+        if query.len() > MAX_QUERY_LEN {
+            return Err(AppError::bad_request(format!(
+                "Search query must have at most {MAX_QUERY_LEN} characters"
+            )));
+        }
+
+        let uri: Uri = format!("/?{query}")
+            .parse()
+            .map_err(|_| AppError::bad_request("Search query is not valid"))?;
+        let Query(params) = Query::<ShowParams>::try_from_uri(&uri)
+            .map_err(|e| AppError::bad_request(e.body_text()))?;
+        ReviewState::parse_listed(&params.review_states)?;
+        for tag in &params.tags {
+            models::tag::normalize_name(tag)?;
+        }
+
+        Ok(Self { name, query })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_validates_queries() -> Result<()> {
+        let search = NewSavedSearch {
+            name: " EN popular ".to_string(),
+            query: "?langs=en&view-count-min=5000&page-offset=100\
+                &recorded-within-days=7&title-like=&cursor=view_count,9,a"
+                .to_string(),
+        }
+        .validate()?;
+        assert_eq!(search.name, "EN popular");
+        assert_eq!(
+            search.query,
+            "langs=en&view-count-min=5000&recorded-within-days=7"
+        );
+
+        for query in ["view-count-min=many", "review-states=maybe", "tags=a,b!"]
+        {
+            assert!(
+                NewSavedSearch {
+                    name: "invalid".to_string(),
+                    query: query.to_string(),
+                }
+                .validate()
+                .is_err(),
+                "{query}"
+            );
+        }
+
+        Ok(())
+    }
+}
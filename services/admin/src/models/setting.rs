@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use crate::prelude::*;
+
+pub static FETCH_NEW_GAME_CLIPS_CRON: Setting<String> =
+    Setting::new(Definition {
+        name: "fetch_new_game_clips_cron",
+        kind: Kind::Cron {
+            job: "fetch_new_game_clips",
+        },
+        default: Value::Text(Cow::Borrowed("0 0 * * * * *")),
+        description:
+            "How often to fetch new clips of games which are not paused.",
+        is_live: true,
+    });
+
+pub static RECORDED_AT_LEAST_HOURS_AGO: Setting<i64> =
+    Setting::new(Definition {
+        name: "recorded_at_least_hours_ago",
+        kind: Kind::Integer {
+            min: 0,
+            max: 24 * 7,
+        },
+        default: Value::Integer(8),
+        description: "Only clips which are at least this many hours old are \
+        fetched, by then they have presumably accumulated some views. \
+        We use clip views as a metric to rank clips.",
+        is_live: true,
+    });
+
+pub static COLLECT_MEDIA_GARBAGE_CRON: Setting<String> =
+    Setting::new(Definition {
+        name: "collect_media_garbage_cron",
+        kind: Kind::Cron {
+            job: "collect_media_garbage",
+        },
+        default: Value::Text(Cow::Borrowed("0 0 4 * * * *")),
+        description: "How often the workers delete media which are too old or \
+        don't fit their disk.",
+        is_live: true,
+    });
+
+pub static GENERATE_PREVIEWS_CRON: Setting<String> = Setting::new(Definition {
+    name: "generate_previews_cron",
+    kind: Kind::Cron {
+        job: "generate_previews",
+    },
+    default: Value::Text(Cow::Borrowed("0 30 * * * * *")),
+    description: "How often previews are generated for the most viewed \
+        clips of games which are not paused, including clips whose previews \
+        were garbage collected.",
+    is_live: true,
+});
+
+pub static LOUDNESS_TARGET_LUFS: Setting<f64> = Setting::new(Definition {
+    name: "loudness_target_lufs",
+    kind: Kind::Float {
+        min: -70.0,
+        max: 0.0,
+    },
+    default: Value::Float(-14.0),
+    description: "Shorts are normalized to this loudness when rendered, \
+        unless that would push their true peak above -1 dBTP. \
+        Most platforms play videos at around -14 LUFS.",
+    is_live: true,
+});
+
+pub static LOUDNESS_TOLERANCE_LU: Setting<f64> = Setting::new(Definition {
+    name: "loudness_tolerance_lu",
+    kind: Kind::Float {
+        min: 0.0,
+        max: 70.0,
+    },
+    default: Value::Float(6.0),
+    description: "Clips louder or quieter than the target by more than this \
+        are flagged when browsing clips, normalizing them changes how they \
+        sound the most.",
+    is_live: true,
+});
+
+/// Every setting there is, in the order they are listed in.
+pub static REGISTRY: [&Definition; 6] = [
+    &FETCH_NEW_GAME_CLIPS_CRON.definition,
+    &RECORDED_AT_LEAST_HOURS_AGO.definition,
+    &COLLECT_MEDIA_GARBAGE_CRON.definition,
+    &GENERATE_PREVIEWS_CRON.definition,
+    &LOUDNESS_TARGET_LUFS.definition,
+    &LOUDNESS_TOLERANCE_LU.definition,
+];
+
+/// Values of settings by their name, this is also what they are exported
+/// and imported as.
+pub type Values = BTreeMap<String, Value>;
+
+/// A setting whose value is read as `T`.
+pub struct Setting<T> {
+    pub definition: Definition,
+    _type: PhantomData<fn() -> T>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Definition {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub kind: Kind,
+    pub default: Value,
+    pub description: &'static str,
+    /// False if the admin must be restarted for a change to apply
+    pub is_live: bool,
+}
+
+/// What values a setting accepts.
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Kind {
+    /// Cron expression with seconds and an optional year, which schedules
+    /// given job
+    Cron { job: &'static str },
+    /// Whole number within the bounds, inclusive
+    Integer { min: i64, max: i64 },
+    /// Number within the bounds, inclusive
+    Float { min: f64, max: f64 },
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    #[schema(value_type = String)]
+    Text(Cow<'static, str>),
+}
+
+/// Who changed a setting from what to what.
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub old_value: String,
+    pub new_value: String,
+    pub changed_by: String,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// How a value of a setting is read.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl<T> Setting<T> {
+    const fn new(definition: Definition) -> Self {
+        Self {
+            definition,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T: FromValue> Setting<T> {
+    /// The value must have been validated against the definition.
+    pub fn read(&self, value: &Value) -> Result<T> {
+        T::from_value(value).ok_or_else(|| {
+            AppError::internal(format!(
+                "Setting {} has unexpected value {value}",
+                self.definition.name
+            ))
+        })
+    }
+}
+
+impl Definition {
+    pub fn find(name: &str) -> Result<&'static Definition> {
+        REGISTRY
+            .iter()
+            .find(|definition| definition.name == name)
+            .copied()
+            .ok_or_else(|| {
+                AppError::bad_request(format!("Unknown setting {name}"))
+            })
+    }
+
+    /// Parses the value as stored in db or sent by a form, without
+    /// validating it.
+    pub fn parse(&self, text: &str) -> Result<Value> {
+        let text = text.trim();
+        let value = match self.kind {
+            Kind::Cron { .. } => Value::Text(Cow::Owned(text.to_string())),
+            Kind::Integer { .. } => {
+                Value::Integer(text.parse().map_err(|_| self.invalid(text))?)
+            }
+            Kind::Float { .. } => {
+                Value::Float(text.parse().map_err(|_| self.invalid(text))?)
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Returns the value as it's stored, whole numbers are accepted where
+    /// any number is.
+    pub fn validate(&self, value: Value) -> Result<Value> {
+        match (self.kind, value) {
+            (Kind::Cron { .. }, Value::Text(cron)) => {
+                // the scheduler would accept a cron expression which never
+                // fires, but a job which never runs is surely a mistake
+                let schedule =
+                    cron::Schedule::from_str(&cron).map_err(|e| {
+                        AppError::bad_request(format!(
+                            "Cannot parse {}: {e}",
+                            self.name
+                        ))
+                    })?;
+                if schedule.upcoming(chrono::Utc).next().is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "Cron expression {} never fires",
+                        self.name
+                    )));
+                }
+
+                Ok(Value::Text(cron))
+            }
+            (Kind::Integer { min, max }, Value::Integer(n)) => {
+                if (min..=max).contains(&n) {
+                    Ok(Value::Integer(n))
+                } else {
+                    Err(self.out_of_bounds(min, max))
+                }
+            }
+            (Kind::Float { .. }, Value::Integer(n)) => {
+                self.validate(Value::Float(n as f64))
+            }
+            (Kind::Float { min, max }, Value::Float(n)) => {
+                if (min..=max).contains(&n) {
+                    Ok(Value::Float(n))
+                } else {
+                    Err(self.out_of_bounds(min, max))
+                }
+            }
+            (_, value) => Err(self.invalid(value)),
+        }
+    }
+
+    fn invalid(&self, value: impl std::fmt::Display) -> AppError {
+        let expected = match self.kind {
+            Kind::Cron { .. } => "a cron expression",
+            Kind::Integer { .. } => "a whole number",
+            Kind::Float { .. } => "a number",
+        };
+
+        AppError::bad_request(format!(
+            "Setting {} must be {expected}, got {value}",
+            self.name
+        ))
+    }
+
+    fn out_of_bounds(
+        &self,
+        min: impl std::fmt::Display,
+        max: impl std::fmt::Display,
+    ) -> AppError {
+        AppError::bad_request(format!(
+            "Setting {} must be between {min} and {max}",
+            self.name
+        ))
+    }
+}
+
+/// Validates all values before any of them can be stored.
+pub fn validate_all(values: Values) -> Result<Values> {
+    values
+        .into_iter()
+        .map(|(name, value)| {
+            let value = Definition::find(&name)?.validate(value)?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(n) => n.fmt(f),
+            Self::Float(n) => n.fmt(f),
+            Self::Text(s) => s.fmt(f),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Text(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
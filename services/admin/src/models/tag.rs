@@ -0,0 +1,58 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::prelude::*;
+
+const MAX_NAME_LEN: usize = 32;
+
+/// A label editors put on clips.
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// How many clips have it
+    pub clip_count: usize,
+}
+
+/// Tags are lowercase letters, digits and dashes, whitespace becomes a dash
+/// so that "Sponsor safe" is the same tag as "sponsor-safe".
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(AppError::bad_request(format!(
+            "Tag must have 1 to {MAX_NAME_LEN} characters"
+        )));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(AppError::bad_request(format!(
+            "Tag '{name}' must only have letters, digits and dashes"
+        )));
+    }
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_normalizes_names() -> Result<()> {
+        assert_eq!(normalize_name("fail")?, "fail");
+        assert_eq!(normalize_name(" Sponsor  safe ")?, "sponsor-safe");
+        assert_eq!(normalize_name("top-10")?, "top-10");
+        assert!(normalize_name("  ").is_err());
+        assert!(normalize_name("lol,fail").is_err());
+        assert!(normalize_name("ünïcode").is_err());
+        assert!(normalize_name(&"a".repeat(33)).is_err());
+
+        Ok(())
+    }
+}
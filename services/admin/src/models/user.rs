@@ -0,0 +1,158 @@
+use argon2::password_hash::{
+    PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::prelude::*;
+
+/// Each role can do everything the roles before it can.
+#[derive(
+    Deserialize,
+    Serialize,
+    ToSchema,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Browses pages without changing anything
+    Viewer,
+    /// Fetches clips, runs jobs and renders media
+    Editor,
+    /// Deletes data, changes settings and manages users
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, PartialEq, Eq)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+/// A logged in browser.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Session {
+    /// Only ever sent in the cookie
+    #[serde(skip)]
+    pub token: String,
+    /// Sent along with every request which changes something so that other
+    /// sites cannot make the browser change things
+    pub csrf_token: String,
+    pub user: User,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    validate_password(password)?;
+
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| AppError::internal(e.to_string()))?;
+
+    Ok(hash.to_string())
+}
+
+/// False also if the hash is malformed.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+pub fn validate_username(username: &str) -> Result<()> {
+    if !username.is_empty()
+        && username.len() <= 64
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(())
+    } else {
+        Err(AppError::bad_request(
+            "Username must be 1 to 64 letters, digits, dashes or underscores",
+        ))
+    }
+}
+
+fn validate_password(password: &str) -> Result<()> {
+    if password.chars().count() >= 12 {
+        Ok(())
+    } else {
+        Err(AppError::bad_request(
+            "Password must be at least 12 characters long",
+        ))
+    }
+}
+
+/// Random hex for session and CSRF tokens.
+pub fn new_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl From<Role> for &'static str {
+    fn from(r: Role) -> Self {
+        match r {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(s: &str) -> StdResult<Self, Self::Error> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "editor" => Ok(Self::Editor),
+            "admin" => Ok(Self::Admin),
+            _ => Err(format!("Unknown role '{s}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_verifies_hashed_passwords() -> Result<()> {
+        let hash = hash_password("correct horse battery")?;
+        assert!(hash.starts_with("$argon2id$"));
+        assert_ne!(hash, hash_password("correct horse battery")?, "salted");
+
+        assert!(verify_password("correct horse battery", &hash));
+        assert!(!verify_password("correct horse battery!", &hash));
+        assert!(!verify_password("correct horse battery", "not a hash"));
+
+        assert!(hash_password("short").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_orders_roles_by_privilege() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Admin);
+
+        assert!(validate_username("jane_doe-2").is_ok());
+        assert!(validate_username("").is_err());
+        assert!(validate_username("jane doe").is_err());
+    }
+}
@@ -8,6 +8,7 @@ pub(crate) use crate::db;
 pub(crate) use crate::error::AppError;
 pub(crate) use crate::g;
 pub(crate) use crate::models;
+pub(crate) use crate::worker_pool::WorkerPool;
 
 pub(crate) type DbConn = rusqlite::Connection;
 pub(crate) type DbLock = std::sync::Arc<tokio::sync::Mutex<DbConn>>;
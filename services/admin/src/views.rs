@@ -1,8 +1,17 @@
-use crate::prelude::*;
+use crate::models::clip_import::{ImportReport, MAX_CLIPS};
+use crate::models::export::ExportColumn;
+use crate::models::job_run::JobRun;
+use crate::models::project::ProjectStatus;
+use crate::models::review::ReviewState;
+use crate::models::setting;
+use crate::models::user::Session;
+use crate::{prelude::*, worker_pool};
 use axum::response::Html;
+use chrono::{DateTime, Utc};
 use handlebars::Handlebars;
+use itertools::Itertools;
 use serde_json::json;
-use std::sync::Arc;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 use twitch::models::GameId;
 
 #[derive(Clone)]
@@ -19,11 +28,17 @@ impl Views {
 
         // contains the head imports
         h.register_template_string("base", include_str!("views/base.hbs"))?;
+        // hidden input of every form which posts, see http::auth
+        h.register_partial("csrf", include_str!("views/csrf.hbs"))?;
         h.register_template_string(
             "base_xl",
             include_str!("views/base_xl.hbs"),
         )?;
 
+        h.register_template_string("login", include_str!("views/login.hbs"))?;
+
+        h.register_template_string("users", include_str!("views/users.hbs"))?;
+
         h.register_template_string(
             "homepage",
             include_str!("views/homepage.hbs"),
@@ -43,94 +58,687 @@ impl Views {
 
         h.register_template_string("clips", include_str!("views/clips.hbs"))?;
 
+        h.register_template_string(
+            "clip_import",
+            include_str!("views/clip_import.hbs"),
+        )?;
+
+        h.register_template_string(
+            "thumbnail",
+            include_str!("views/thumbnail.hbs"),
+        )?;
+
+        h.register_template_string("music", include_str!("views/music.hbs"))?;
+
+        h.register_template_string("media", include_str!("views/media.hbs"))?;
+
+        h.register_template_string(
+            "workers",
+            include_str!("views/workers.hbs"),
+        )?;
+
+        h.register_template_string("jobs", include_str!("views/jobs.hbs"))?;
+
+        h.register_template_string(
+            "projects",
+            include_str!("views/projects.hbs"),
+        )?;
+
+        h.register_template_string(
+            "project",
+            include_str!("views/project.hbs"),
+        )?;
+
+        h.register_template_string(
+            "youtube",
+            include_str!("views/youtube.hbs"),
+        )?;
+
+        h.register_template_string(
+            "clip_music",
+            include_str!("views/clip_music.hbs"),
+        )?;
+
+        h.register_template_string(
+            "clip_vertical",
+            include_str!("views/clip_vertical.hbs"),
+        )?;
+
+        h.register_template_string(
+            "broadcaster_layout",
+            include_str!("views/broadcaster_layout.hbs"),
+        )?;
+
         Ok(Self {
             handlebars: Arc::new(h),
         })
     }
 
-    /// Pull all necessary data to render homepage from db.
-    pub fn homepage(&self, db: &DbConn) -> Result<Html<String>> {
-        let games: Vec<_> = db::game::select_all(db)?;
+    /// Every page shows who's logged in, and their forms carry the CSRF
+    /// token of the session.
+    fn render(
+        &self,
+        name: &str,
+        session: &Session,
+        mut data: serde_json::Value,
+    ) -> Result<Html<String>> {
+        data["session"] = json!(session);
+
+        self.handlebars
+            .render(name, &data)
+            .map(Html)
+            .map_err(From::from)
+    }
 
+    pub fn login(&self) -> Result<Html<String>> {
         self.handlebars
-            .render("homepage", &json!({ "parent": "base", "games": games }))
+            .render("login", &json!({ "parent": "base" }))
             .map(Html)
             .map_err(From::from)
     }
 
+    pub fn users(
+        &self,
+        session: &Session,
+        db: &DbConn,
+    ) -> Result<Html<String>> {
+        let users = db::user::select_all(db)?;
+
+        self.render(
+            "users",
+            session,
+            json!({ "parent": "base", "users": users }),
+        )
+    }
+
+    /// Pull all necessary data to render homepage from db.
+    pub fn homepage(
+        &self,
+        session: &Session,
+        db: &DbConn,
+    ) -> Result<Html<String>> {
+        let games: Vec<_> = db::game::select_all(db)?;
+
+        self.render(
+            "homepage",
+            session,
+            json!({ "parent": "base", "games": games }),
+        )
+    }
+
     pub fn search_game(
         &self,
+        session: &Session,
         games: &[twitch::models::Game],
     ) -> Result<Html<String>> {
-        self.handlebars
-            .render(
-                "search_game",
-                &json!({
-                    "parent": "base",
-                    "games": games
-                }),
-            )
-            .map(Html)
-            .map_err(From::from)
+        self.render(
+            "search_game",
+            session,
+            json!({
+                "parent": "base",
+                "games": games
+            }),
+        )
     }
 
     /// Pull all necessary data to render game info from db.
-    pub fn game(&self, db: &DbConn, game: &GameId) -> Result<Html<String>> {
+    pub fn game(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        game: &GameId,
+    ) -> Result<Html<String>> {
+        let fetch_policy = db::fetch_policy::select_by_game(db, game)?;
         let game = db::game::select_by_id(db, game)?;
 
-        self.handlebars
-            .render("game", &json!({ "parent": "base", "game": game }))
-            .map(Html)
-            .map_err(From::from)
+        self.render(
+            "game",
+            session,
+            json!({
+                "parent": "base",
+                "game": game,
+                "fetch_policy": fetch_policy,
+                "langs": fetch_policy.langs.join(","),
+            }),
+        )
     }
 
     /// View global settings.
-    pub fn settings(&self, db: &DbConn) -> Result<Html<String>> {
-        let fetch_new_game_clips_cron =
-            db::setting::fetch_new_game_clips_cron(db)?;
-        let recorded_at_least_hours_ago =
-            db::setting::recorded_at_least_hours_ago(db)?;
+    /// Every setting in the registry, along with when the jobs they
+    /// schedule run next and who changed them lately.
+    pub fn settings(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        next_ticks: &[(&str, Option<DateTime<Utc>>)],
+    ) -> Result<Html<String>> {
+        const CHANGES_LIMIT: usize = 20;
 
-        self.handlebars
-            .render(
-                "settings",
-                &json!(
-                    {
-                        "parent": "base",
-                        "settings": {
-                            "fetch_new_game_clips_cron": fetch_new_game_clips_cron,
-                            "recorded_at_least_hours_ago": recorded_at_least_hours_ago,
-                        }
-                    }
-                ),
-            )
-            .map(Html)
-            .map_err(From::from)
+        let values = db::setting::select_all(db)?;
+        let next_ticks: HashMap<_, _> = next_ticks.iter().copied().collect();
+        let settings = setting::REGISTRY
+            .iter()
+            .map(|definition| {
+                let next_tick = match definition.kind {
+                    setting::Kind::Cron { job } => next_ticks.get(job).copied(),
+                    _ => None,
+                };
+
+                json!({
+                    "definition": definition,
+                    "value": values.get(definition.name),
+                    "next_tick": next_tick,
+                })
+            })
+            .collect_vec();
+        let changes = db::setting::select_changes(db, CHANGES_LIMIT)?;
+
+        self.render(
+            "settings",
+            session,
+            json!(
+                {
+                    "parent": "base",
+                    "settings": settings,
+                    "changes": changes,
+                }
+            ),
+        )
     }
 
     pub fn clips(
         &self,
+        session: &Session,
         db: &DbConn,
         game_id: &GameId,
-        total_count: usize,
-        clips: Vec<models::clip::Clip>,
         query: models::clip::ShowParams,
+        raw_query: &str,
     ) -> Result<Html<String>> {
         let game = db::game::select_by_id(db, game_id)?;
+        let page = db::clip::list(db, game_id, &query)?;
+        let target_lufs = db::setting::get(db, &setting::LOUDNESS_TARGET_LUFS)?;
+        let tolerance_lu =
+            db::setting::get(db, &setting::LOUDNESS_TOLERANCE_LU)?;
+        let clips: Vec<_> = page
+            .clips
+            .into_iter()
+            .map(|clip| ListedClip {
+                loudness_flag: clip
+                    .loudness
+                    .and_then(|l| l.flag(target_lufs, tolerance_lu)),
+                next_review_states: clip.review_state.next_states(),
+                clip,
+            })
+            .collect();
+        let listed_review_states =
+            ReviewState::parse_listed(&query.review_states)?;
+        let review_states = ReviewState::ALL
+            .iter()
+            .map(|state| {
+                json!({
+                    "state": state,
+                    "is_listed": listed_review_states.contains(state),
+                })
+            })
+            .collect_vec();
+        // which clips can be added to
+        let projects = db::project::select_all_unpublished(db)?;
+        let tags = db::tag::select_all(db)?
+            .into_iter()
+            .map(|tag| {
+                json!({
+                    "is_listed": query.tags.contains(&tag.name),
+                    "tag": tag,
+                })
+            })
+            .collect_vec();
+        let saved_searches = db::saved_search::select_all(db)?;
 
-        self.handlebars
-            .render(
-                "clips",
-                &json!({
-                    "parent": "base_xl",
-                    "game": game,
-                    "total_count": total_count,
-                    "query": query,
-                    "clips": clips,
-                }),
-            )
-            .map(Html)
-            .map_err(From::from)
+        self.render(
+            "clips",
+            session,
+            json!({
+                "parent": "base_xl",
+                "game": game,
+                "total_count": page.total_count,
+                "is_total_count_exact": page.is_total_count_exact,
+                "query": query,
+                "clips": clips,
+                "review_states": review_states,
+                "projects": projects,
+                "tags": tags,
+                "saved_searches": saved_searches,
+                "raw_query": raw_query,
+                "export_columns": ExportColumn::ALL
+                    .iter()
+                    .map(|column| <&str>::from(*column))
+                    .collect_vec(),
+                "listed_review_states": listed_review_states
+                    .iter()
+                    .map(|state| <&str>::from(*state))
+                    .join(","),
+            }),
+        )
+    }
+
+    /// The import form, along with what the last import did if there was one.
+    pub fn clip_import(
+        &self,
+        session: &Session,
+        report: Option<&ImportReport>,
+    ) -> Result<Html<String>> {
+        self.render(
+            "clip_import",
+            session,
+            json!({
+                "parent": "base",
+                "report": report,
+                "max_clips": MAX_CLIPS,
+            }),
+        )
+    }
+
+    /// Candidate frames of each clip are artifacts on given worker.
+    pub fn thumbnail(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        game_id: &GameId,
+        worker: SocketAddr,
+        clips: Vec<models::clip::Clip>,
+        frames: Vec<Vec<worker::rpc::ThumbnailFrame>>,
+    ) -> Result<Html<String>> {
+        let game = db::game::select_by_id(db, game_id)?;
+        let clip_ids = clips.iter().map(|clip| clip.id.as_str()).join(",");
+        let default_title = clips
+            .first()
+            .map(|clip| clip.title.clone())
+            .unwrap_or_default();
+        let clips: Vec<_> = clips
+            .into_iter()
+            .zip(frames)
+            .map(|(clip, frames)| {
+                let frames: Vec<_> = frames
+                    .into_iter()
+                    .map(|frame| {
+                        json!({
+                            "at_secs": frame.at_secs,
+                            "score": frame.score.round(),
+                            "key": frame.key,
+                        })
+                    })
+                    .collect();
+                json!({ "clip": clip, "frames": frames })
+            })
+            .collect();
+
+        self.render(
+            "thumbnail",
+            session,
+            json!({
+                "parent": "base_xl",
+                "game": game,
+                "worker": worker,
+                "clip_ids": clip_ids,
+                "default_title": default_title,
+                "clips": clips,
+            }),
+        )
+    }
+
+    /// Reference tracks are stored in the worker.
+    pub fn music(
+        &self,
+        session: &Session,
+        tracks: &[worker::rpc::ReferenceTrack],
+    ) -> Result<Html<String>> {
+        let tracks: Vec<_> = tracks
+            .iter()
+            .map(|track| {
+                json!({
+                    "id": track.id,
+                    "title": track.title,
+                    "source": track.source,
+                    "duration_secs": track.duration_secs.round(),
+                    "fingerprint_count": track.fingerprint_count,
+                })
+            })
+            .collect();
+
+        self.render(
+            "music",
+            session,
+            json!({ "parent": "base", "tracks": tracks }),
+        )
+    }
+
+    /// Worker media which the next garbage collection would delete on each
+    /// worker, and clips whose media is kept regardless.
+    pub fn media(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        previews: Option<&[(SocketAddr, worker::rpc::CollectGarbageResponse)]>,
+    ) -> Result<Html<String>> {
+        let kept_clips = db::retention::select_kept_clips(db)?;
+        let workers: Vec<_> = previews
+            .unwrap_or_default()
+            .iter()
+            .map(|(addr, preview)| {
+                let evicted: Vec<_> = preview
+                    .evicted
+                    .iter()
+                    .map(|artifact| {
+                        json!({
+                            "key": artifact.key,
+                            "clip_id": artifact.clip_id,
+                            "size": human_bytes(artifact.size_bytes),
+                            "reason": artifact.reason,
+                        })
+                    })
+                    .collect();
+
+                json!({
+                    "addr": addr,
+                    "evicted": evicted,
+                    "freed": human_bytes(preview.freed_bytes),
+                    "remaining": human_bytes(preview.remaining_bytes),
+                    "max_total": preview.max_total_bytes.map(human_bytes),
+                    "max_age_days": preview
+                        .max_age_secs
+                        .map(|secs| secs / (24 * 60 * 60)),
+                })
+            })
+            .collect();
+        let freed = previews
+            .unwrap_or_default()
+            .iter()
+            .map(|(_, p)| p.freed_bytes)
+            .sum();
+
+        self.render(
+            "media",
+            session,
+            json!({
+                "parent": "base",
+                "is_previewed": previews.is_some(),
+                "workers": workers,
+                "freed": human_bytes(freed),
+                "kept_clips": kept_clips,
+            }),
+        )
+    }
+
+    /// Health and load of the workers as of the last health check.
+    pub fn workers(
+        &self,
+        session: &Session,
+        statuses: &[worker_pool::WorkerStatus],
+        game_routing: worker_pool::GameRouting,
+    ) -> Result<Html<String>> {
+        self.render(
+            "workers",
+            session,
+            json!({
+                "parent": "base",
+                "workers": statuses,
+                "routes_by_game":
+                    game_routing == worker_pool::GameRouting::Affinity,
+            }),
+        )
+    }
+
+    /// Latest job runs, newest first.
+    pub fn jobs(
+        &self,
+        session: &Session,
+        runs: &[JobRun],
+        next_ticks: &[(&str, Option<DateTime<Utc>>)],
+    ) -> Result<Html<String>> {
+        self.render(
+            "jobs",
+            session,
+            json!({
+                "parent": "base_xl",
+                "runs": runs,
+                "next_ticks": next_ticks
+                    .iter()
+                    .map(|(job, at)| json!({ "job": job, "at": at }))
+                    .collect_vec(),
+            }),
+        )
+    }
+
+    /// Whether a YouTube channel is connected and whether it still works.
+    pub fn youtube(
+        &self,
+        session: &Session,
+        is_configured: bool,
+        token: Option<&youtube::OAuthToken>,
+        error: Option<&str>,
+    ) -> Result<Html<String>> {
+        self.render(
+            "youtube",
+            session,
+            json!({
+                "parent": "base",
+                "is_configured": is_configured,
+                "is_connected": token.is_some(),
+                "expires_at": token.map(|t| t.expires_at),
+                "can_refresh": token
+                    .is_some_and(|t| t.refresh_token.is_some()),
+                "error": error,
+            }),
+        )
+    }
+
+    pub fn projects(
+        &self,
+        session: &Session,
+        db: &DbConn,
+    ) -> Result<Html<String>> {
+        let projects = db::project::select_all(db)?
+            .into_iter()
+            .map(|project| {
+                json!({
+                    "total": minutes_seconds(project.total_duration_secs),
+                    "target": minutes_seconds(
+                        project.target_duration_secs.into()
+                    ),
+                    "is_over_target": project.overrun_secs() > 0.0,
+                    "project": project,
+                })
+            })
+            .collect_vec();
+
+        self.render(
+            "projects",
+            session,
+            json!({ "parent": "base", "projects": projects }),
+        )
+    }
+
+    /// Entries in the order they play with where each ends in the video,
+    /// and the credits for the description.
+    /// Videos are the files which can be uploaded, None if uploading isn't
+    /// configured.
+    pub fn project(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        project_id: i64,
+        videos: Option<&[String]>,
+    ) -> Result<Html<String>> {
+        let project = db::project::select_by_id(db, project_id)?;
+        let entries = db::project::select_entries(db, project_id)?;
+        let credits = models::project::credits(&entries);
+        let is_uploading = db::project::is_uploading(db, project_id)?;
+
+        let mut ends_at = 0.0;
+        let listed_entries = entries
+            .iter()
+            .map(|entry| {
+                ends_at += entry.duration_secs;
+                json!({
+                    "entry": entry,
+                    "duration": minutes_seconds(entry.duration_secs),
+                    "ends_at": minutes_seconds(ends_at),
+                    "is_over_target": ends_at
+                        > f64::from(project.target_duration_secs),
+                })
+            })
+            .collect_vec();
+        let overrun = project.overrun_secs();
+
+        self.render(
+            "project",
+            session,
+            json!({
+                "parent": "base",
+                "is_editable": project.ensure_is_editable().is_ok(),
+                "is_uploadable": project.status == ProjectStatus::Ready
+                    && videos.is_some()
+                    && !is_uploading,
+                "is_uploading": is_uploading,
+                "videos": videos,
+                "next_statuses": project.status.next_statuses(),
+                "target_minutes": project.target_duration_secs / 60,
+                "total": minutes_seconds(project.total_duration_secs),
+                "target": minutes_seconds(project.target_duration_secs.into()),
+                "over_by": (overrun > 0.0).then(|| minutes_seconds(overrun)),
+                "left": (overrun <= 0.0).then(|| minutes_seconds(-overrun)),
+                "project": project,
+                "entries": listed_entries,
+                "credits": credits,
+                "credits_text": models::project::credits_text(&entries),
+            }),
+        )
+    }
+
+    /// Pull a clip and parts of it which play known tracks from db.
+    pub fn clip_music(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        clip_id: &str,
+    ) -> Result<Html<String>> {
+        let clip = db::clip::select_by_id(db, clip_id)?;
+        let game = db::game::select_by_id(db, &clip.game_id.as_str().into())?;
+        let matches = db::music::select_matches(db, clip_id)?;
+
+        self.render(
+            "clip_music",
+            session,
+            json!({
+                "parent": "base",
+                "game": game,
+                "clip": clip,
+                "matches": matches,
+            }),
+        )
+    }
+
+    /// Pull a clip and the layout of its broadcaster from db.
+    pub fn clip_vertical(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        clip_id: &str,
+    ) -> Result<Html<String>> {
+        let clip = db::clip::select_by_id(db, clip_id)?;
+        let game = db::game::select_by_id(db, &clip.game_id.as_str().into())?;
+        let layout =
+            db::layout::select_by_broadcaster(db, &clip.broadcaster_id)?;
+        let target_lufs = db::setting::get(db, &setting::LOUDNESS_TARGET_LUFS)?;
+        let gain_db = clip
+            .loudness
+            .and_then(|l| l.normalization_gain_db(target_lufs));
+
+        self.render(
+            "clip_vertical",
+            session,
+            json!({
+                "parent": "base",
+                "game": game,
+                "clip": clip,
+                "layout": layout,
+                "target_lufs": target_lufs,
+                "gain_db": gain_db,
+                "is_silent": clip.loudness.is_some() && gain_db.is_none(),
+            }),
+        )
+    }
+
+    /// Pull the broadcaster's layout and their latest clip to preview it
+    /// on from db.
+    pub fn broadcaster_layout(
+        &self,
+        session: &Session,
+        db: &DbConn,
+        broadcaster_id: &str,
+    ) -> Result<Html<String>> {
+        let layout = db::layout::select_by_broadcaster(db, broadcaster_id)?;
+        let clip = db::clip::select_latest_by_broadcaster(db, broadcaster_id)?;
+        let percent = |rect: &crate::models::layout::CropRect| {
+            json!({
+                "x": percent(rect.x),
+                "y": percent(rect.y),
+                "width": percent(rect.width),
+                "height": percent(rect.height),
+            })
+        };
+
+        self.render(
+            "broadcaster_layout",
+            session,
+            json!({
+                "parent": "base",
+                "broadcaster_id": broadcaster_id,
+                "clip": clip,
+                "layout": layout.layout,
+                "facecam": percent(&layout.facecam),
+                "gameplay": percent(&layout.gameplay),
+            }),
+        )
+    }
+}
+
+/// A clip in the list with how it stands out.
+#[derive(serde::Serialize)]
+struct ListedClip {
+    #[serde(flatten)]
+    clip: models::clip::Clip,
+    loudness_flag: Option<models::loudness::LoudnessFlag>,
+    /// What reviewers can move the clip to
+    next_review_states: &'static [ReviewState],
+}
+
+/// Fraction as percent rounded to two decimals, e.g. 0.2634 is 26.34
+fn percent(fraction: f64) -> f64 {
+    (fraction * 10_000.0).round() / 100.0
+}
+
+/// Rounded to whole seconds, e.g. 605.4 is 10:05
+fn minutes_seconds(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// E.g. 1.5 GB
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
     }
 }
 
@@ -0,0 +1,475 @@
+//! Jobs are spread over all workers in `WORKER_ADDRS`.
+//!
+//! Workers are polled for how busy they are and whether they are up at all.
+//! An RPC which cannot reach its worker is retried on the next one, so a
+//! worker going down only slows jobs down.
+
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{future::Future, net::SocketAddr, time::Duration};
+use twitch::models::GameId;
+
+/// How often workers are asked for their load.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// A worker which doesn't answer the health check in time is down.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cheap to clone, all clones share the workers and their health.
+#[derive(Clone)]
+pub struct WorkerPool {
+    workers: Arc<[PooledWorker]>,
+    game_routing: GameRouting,
+}
+
+/// How jobs on clips of a game pick their worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameRouting {
+    /// Each game sticks to one worker, which reuses the clips it downloaded
+    /// for earlier jobs.
+    Affinity,
+    /// Clips of a game are spread over all workers, which gets through a
+    /// large game faster at the cost of downloading clips again.
+    LeastLoaded,
+}
+
+/// How to pick a worker for an RPC.
+#[derive(Debug, Clone, Copy)]
+pub enum Route<'a> {
+    /// The least busy worker
+    LeastLoaded,
+    /// The same worker for the same key while it's up, so that for example
+    /// clips of a game downloaded by one job are reused by the next.
+    Affinity(&'a str),
+}
+
+/// Snapshot of a worker for the workers page.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStatus {
+    pub addr: SocketAddr,
+    /// Workers are assumed up until the first health check
+    pub is_up: bool,
+    /// As reported by the worker in the last health check
+    pub jobs_in_flight: u64,
+    /// RPCs of this admin waiting for the worker right now
+    pub admin_in_flight: u64,
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Why the worker is down
+    pub error: Option<String>,
+}
+
+struct PooledWorker {
+    addr: SocketAddr,
+    client: worker::Client,
+    admin_in_flight: AtomicU64,
+    health: Mutex<Health>,
+}
+
+struct Health {
+    is_up: bool,
+    jobs_in_flight: u64,
+    checked_at: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl WorkerPool {
+    /// Must be given at least one worker.
+    pub fn new(
+        workers: Vec<(SocketAddr, worker::Client)>,
+        game_routing: GameRouting,
+    ) -> Self {
+        assert!(!workers.is_empty(), "Worker pool needs at least one worker");
+
+        Self {
+            workers: workers
+                .into_iter()
+                .map(|(addr, client)| PooledWorker {
+                    addr,
+                    client,
+                    admin_in_flight: AtomicU64::new(0),
+                    health: Mutex::new(Health {
+                        is_up: true,
+                        jobs_in_flight: 0,
+                        checked_at: None,
+                        error: None,
+                    }),
+                })
+                .collect(),
+            game_routing,
+        }
+    }
+
+    pub fn game_routing(&self) -> GameRouting {
+        self.game_routing
+    }
+
+    /// Route of RPCs on clips of the game.
+    pub fn route_for_game<'a>(&self, game_id: &'a GameId) -> Route<'a> {
+        match self.game_routing {
+            GameRouting::Affinity => Route::Affinity(game_id.as_str()),
+            GameRouting::LeastLoaded => Route::LeastLoaded,
+        }
+    }
+
+    /// Polls the workers until the process exits.
+    pub fn spawn_health_checks(&self) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                pool.check_health().await;
+            }
+        });
+    }
+
+    /// Asks every worker for its load, marking those which don't answer as
+    /// down.
+    pub async fn check_health(&self) {
+        for w in self.workers.iter() {
+            let res = tokio::time::timeout(
+                HEALTH_CHECK_TIMEOUT,
+                w.client.clone().get_load(()),
+            )
+            .await;
+
+            match res {
+                Ok(Ok(resp)) => {
+                    let mut health = w.health();
+                    if !health.is_up {
+                        info!("Worker {} is up again", w.addr);
+                    }
+                    health.is_up = true;
+                    health.jobs_in_flight = resp.into_inner().jobs_in_flight;
+                    health.checked_at = Some(Utc::now());
+                    health.error = None;
+                }
+                Ok(Err(status)) => w.mark_down(status.message()),
+                Err(_) => w.mark_down("Health check timed out"),
+            }
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<WorkerStatus> {
+        self.workers
+            .iter()
+            .map(|w| {
+                let health = w.health();
+                WorkerStatus {
+                    addr: w.addr,
+                    is_up: health.is_up,
+                    jobs_in_flight: health.jobs_in_flight,
+                    admin_in_flight: w.admin_in_flight.load(Ordering::Relaxed),
+                    checked_at: health.checked_at,
+                    error: health.error.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Runs the RPCs on a worker picked by the route.
+    ///
+    /// If the worker cannot be reached, it's marked down and the RPCs are
+    /// run again on the next worker.
+    /// Therefore the RPCs must be safe to repeat, which downloading a clip
+    /// and deriving files from it are.
+    /// RPCs which depend on each other, such as downloading a clip and then
+    /// hashing its frames, must run in the same call to land on the same
+    /// worker.
+    pub async fn call<T, F, Fut>(&self, route: Route<'_>, rpc: F) -> Result<T>
+    where
+        F: FnMut(worker::Client) -> Fut,
+        Fut: Future<Output = StdResult<T, tonic::Status>>,
+    {
+        self.call_located(route, rpc).await.map(|(_, res)| res)
+    }
+
+    /// Like [`Self::call`], also returning which worker ran the RPCs, for
+    /// when the RPCs leave behind files on the worker to be read later
+    /// with [`Self::call_on`].
+    pub async fn call_located<T, F, Fut>(
+        &self,
+        route: Route<'_>,
+        mut rpc: F,
+    ) -> Result<(SocketAddr, T)>
+    where
+        F: FnMut(worker::Client) -> Fut,
+        Fut: Future<Output = StdResult<T, tonic::Status>>,
+    {
+        let mut last_status = None;
+        for w in self.candidates(route) {
+            let res = {
+                let _in_flight = w.start_rpc();
+                rpc(w.client.clone()).await
+            };
+
+            match res {
+                Ok(res) => return Ok((w.addr, res)),
+                Err(status) if is_unreachable(&status) => {
+                    warn!(
+                        "Worker {} is unreachable, trying another one: {}",
+                        w.addr,
+                        status.message()
+                    );
+                    w.mark_down(status.message());
+                    last_status = Some(status);
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
+
+        Err(last_status.expect("Pool is never empty").into())
+    }
+
+    /// Runs the RPCs on given worker of the pool, without failing over to
+    /// another one.
+    pub async fn call_on<T, Fut>(
+        &self,
+        addr: SocketAddr,
+        rpc: impl FnOnce(worker::Client) -> Fut,
+    ) -> Result<T>
+    where
+        Fut: Future<Output = StdResult<T, tonic::Status>>,
+    {
+        let w =
+            self.workers
+                .iter()
+                .find(|w| w.addr == addr)
+                .ok_or_else(|| {
+                    AppError::bad_request(format!(
+                        "No worker {addr} in the pool"
+                    ))
+                })?;
+
+        let res = {
+            let _in_flight = w.start_rpc();
+            rpc(w.client.clone()).await
+        };
+        if let Err(status) = &res {
+            if is_unreachable(status) {
+                w.mark_down(status.message());
+            }
+        }
+
+        res.map_err(AppError::from)
+    }
+
+    /// Runs the RPCs on every worker, whether it's up or not, for things
+    /// each worker keeps on its own such as its media and reference tracks.
+    pub async fn call_each<T, F, Fut>(
+        &self,
+        mut rpc: F,
+    ) -> Vec<(SocketAddr, Result<T>)>
+    where
+        F: FnMut(worker::Client) -> Fut,
+        Fut: Future<Output = StdResult<T, tonic::Status>>,
+    {
+        let mut results = Vec::with_capacity(self.workers.len());
+        for w in self.workers.iter() {
+            let res = {
+                let _in_flight = w.start_rpc();
+                rpc(w.client.clone()).await
+            };
+
+            if let Err(status) = &res {
+                if is_unreachable(status) {
+                    w.mark_down(status.message());
+                }
+            }
+            results.push((w.addr, res.map_err(AppError::from)));
+        }
+
+        results
+    }
+
+    /// Workers in the order they should be tried.
+    ///
+    /// Workers which are down go last rather than not at all, they might
+    /// have come back since the last health check.
+    fn candidates(&self, route: Route<'_>) -> Vec<&PooledWorker> {
+        let mut candidates: Vec<_> = self.workers.iter().collect();
+        match route {
+            Route::LeastLoaded => {
+                candidates.sort_by_key(|w| {
+                    let is_up = w.health().is_up;
+                    (!is_up, w.load())
+                });
+            }
+            Route::Affinity(key) => {
+                // rendezvous hashing, a worker going down only moves the
+                // keys which were routed to it
+                candidates.sort_by_key(|w| {
+                    let is_up = w.health().is_up;
+                    (!is_up, std::cmp::Reverse(rendezvous_weight(key, w.addr)))
+                });
+            }
+        }
+
+        candidates
+    }
+}
+
+impl PooledWorker {
+    fn health(&self) -> std::sync::MutexGuard<'_, Health> {
+        self.health.lock().expect("Worker health lock poisoned")
+    }
+
+    /// Jobs the worker reported plus those this admin sent since, whichever
+    /// is larger.
+    fn load(&self) -> u64 {
+        self.health()
+            .jobs_in_flight
+            .max(self.admin_in_flight.load(Ordering::Relaxed))
+    }
+
+    fn mark_down(&self, error: &str) {
+        let mut health = self.health();
+        if health.is_up {
+            warn!("Worker {} is down: {error}", self.addr);
+        }
+        health.is_up = false;
+        health.checked_at = Some(Utc::now());
+        health.error = Some(error.to_string());
+    }
+
+    /// Counted until the returned guard is dropped, which also happens if
+    /// the caller gives up on the RPC.
+    fn start_rpc(&self) -> impl Drop + '_ {
+        struct InFlight<'a>(&'a AtomicU64);
+        impl Drop for InFlight<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        self.admin_in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.admin_in_flight)
+    }
+}
+
+/// 64bit FNV-1a over the key and the worker's address.
+///
+/// Keys must go to the same worker after the admin is rebuilt, or they lose
+/// the media downloaded there, so this doesn't use `DefaultHasher` which may
+/// change between Rust versions.
+fn rendezvous_weight(key: &str, addr: SocketAddr) -> u64 {
+    let addr = addr.to_string();
+    let bytes = [
+        &(key.len() as u64).to_le_bytes()[..],
+        key.as_bytes(),
+        addr.as_bytes(),
+    ];
+
+    bytes.into_iter().flatten().fold(
+        0xcbf2_9ce4_8422_2325,
+        |hash: u64, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        },
+    )
+}
+
+/// The worker is down or not reachable over the network, as opposed to
+/// failing the RPC itself.
+fn is_unreachable(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unavailable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(ports: &[u16]) -> WorkerPool {
+        WorkerPool::new(
+            ports
+                .iter()
+                .map(|port| {
+                    let addr = SocketAddr::from(([127, 0, 0, 1], *port));
+                    let channel =
+                        tonic::transport::Endpoint::new(format!("http://{addr}"))
+                            .unwrap()
+                            .connect_lazy();
+                    let client =
+                        worker::rpc::worker_client::WorkerClient::with_interceptor(
+                            channel,
+                            worker::BearerToken::new(None).unwrap(),
+                        );
+                    (addr, client)
+                })
+                .collect(),
+            GameRouting::Affinity,
+        )
+    }
+
+    fn ports(candidates: Vec<&PooledWorker>) -> Vec<u16> {
+        candidates.iter().map(|w| w.addr.port()).collect()
+    }
+
+    #[tokio::test]
+    async fn it_routes_to_least_loaded_worker_which_is_up() {
+        let pool = pool(&[1, 2, 3]);
+        pool.workers[0].health().jobs_in_flight = 5;
+        pool.workers[1].health().jobs_in_flight = 1;
+        pool.workers[2].health().jobs_in_flight = 3;
+
+        assert_eq!(ports(pool.candidates(Route::LeastLoaded)), vec![2, 3, 1]);
+
+        let _rpcs: Vec<_> =
+            (0..4).map(|_| pool.workers[1].start_rpc()).collect();
+        assert_eq!(ports(pool.candidates(Route::LeastLoaded)), vec![3, 2, 1]);
+
+        pool.workers[2].mark_down("Connection refused");
+        assert_eq!(ports(pool.candidates(Route::LeastLoaded)), vec![2, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn it_routes_same_key_to_same_worker() {
+        let pool = pool(&[1, 2, 3]);
+        let game_id = GameId::from("game");
+        let route = pool.route_for_game(&game_id);
+
+        // the same across builds, so that media downloaded for a game is
+        // found after an upgrade
+        let first = ports(pool.candidates(route));
+        assert_eq!(first, vec![1, 3, 2]);
+        assert_eq!(ports(pool.candidates(route)), first);
+
+        // spreads different keys over the workers
+        let firsts: std::collections::HashSet<_> = (0..30)
+            .map(|i| ports(pool.candidates(Route::Affinity(&i.to_string())))[0])
+            .collect();
+        assert_eq!(firsts.len(), 3);
+
+        // the rest keep their order when the preferred worker goes down
+        let preferred = pool
+            .workers
+            .iter()
+            .find(|w| w.addr.port() == first[0])
+            .unwrap();
+        preferred.mark_down("Connection refused");
+        assert_eq!(
+            ports(pool.candidates(route)),
+            vec![first[1], first[2], first[0]]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_over_when_worker_is_unreachable() {
+        // nothing listens on these ports
+        let pool = pool(&[1, 2]);
+
+        let mut attempts = 0;
+        let res = pool
+            .call(Route::LeastLoaded, |mut client| {
+                attempts += 1;
+                async move { client.get_load(()).await }
+            })
+            .await;
+
+        assert!(res.is_err());
+        assert_eq!(attempts, 2);
+        assert!(pool.statuses().iter().all(|w| !w.is_up));
+        assert!(pool.statuses().iter().all(|w| w.admin_in_flight == 0));
+    }
+}
@@ -0,0 +1,23 @@
+-- known tracks, such as copyrighted music, which clips are checked against
+CREATE TABLE IF NOT EXISTS reference_tracks (
+    id INTEGER PRIMARY KEY,
+    -- human readable name, e.g. "artist - song"
+    title TEXT NOT NULL,
+    -- where was the audio ingested from, url or path on the worker host
+    source TEXT NOT NULL,
+    -- length of the ingested audio
+    duration_secs REAL NOT NULL,
+    -- when was the track added to the db
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+-- spectral peak hashes of reference tracks
+CREATE TABLE IF NOT EXISTS reference_fingerprints (
+    hash INTEGER NOT NULL,
+    track_id INTEGER NOT NULL,
+    -- window of the anchor peak in the track
+    frame INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS reference_fingerprints_hash
+ON reference_fingerprints (hash);
@@ -0,0 +1,17 @@
+-- files kept in the media store, such as downloaded clips
+CREATE TABLE IF NOT EXISTS artifacts (
+    -- where the file is in the media store, e.g. "clips/SomeClipId.mp4"
+    key TEXT PRIMARY KEY,
+    -- what the file is, e.g. "clip"
+    kind TEXT NOT NULL,
+    -- the clip the file was downloaded as or derived from, if any
+    clip_id TEXT,
+    size_bytes INTEGER NOT NULL,
+    -- when was the file put to the store
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    -- when was the file last read by any RPC
+    last_accessed_at TEXT NOT NULL
+        DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS artifacts_clip_id ON artifacts (clip_id);
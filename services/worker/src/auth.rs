@@ -0,0 +1,235 @@
+//! Who can make the worker download arbitrary urls.
+//!
+//! Clients present a shared secret as a bearer token, and with TLS
+//! configured they can also be required to present a certificate signed by
+//! a known CA.
+
+use crate::prelude::*;
+use anyhow::{bail, Context};
+use std::{path::PathBuf, sync::Arc};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tonic::{service::Interceptor, Request, Status};
+
+pub struct TlsConf {
+    /// PEM certificate chain the worker presents to clients
+    pub cert_path: PathBuf,
+    /// PEM private key of the certificate
+    pub key_path: PathBuf,
+    /// PEM CA certificate which client certificates must be signed by.
+    /// If not set, clients are not asked for a certificate.
+    pub client_ca_path: Option<PathBuf>,
+}
+
+impl TlsConf {
+    /// TLS is optional but all paths must make sense together.
+    pub fn from_paths(
+        cert_path: Option<String>,
+        key_path: Option<String>,
+        client_ca_path: Option<String>,
+    ) -> AnyResult<Option<Self>> {
+        match (cert_path, key_path, client_ca_path) {
+            (None, None, None) => Ok(None),
+            (Some(cert_path), Some(key_path), client_ca_path) => {
+                Ok(Some(Self {
+                    cert_path: cert_path.into(),
+                    key_path: key_path.into(),
+                    client_ca_path: client_ca_path.map(Into::into),
+                }))
+            }
+            (None, None, Some(_)) => bail!(
+                "RPC_TLS_CLIENT_CA requires RPC_TLS_CERT and RPC_TLS_KEY, \
+                client certificates can only be checked over TLS"
+            ),
+            _ => bail!("RPC_TLS_CERT and RPC_TLS_KEY must be set together"),
+        }
+    }
+
+    /// Fails if any of the files cannot be read.
+    /// Whether they are valid PEM is found out when the server is built with
+    /// the config, so the worker doesn't start with bad ones either.
+    pub fn server_config(&self) -> AnyResult<ServerTlsConfig> {
+        let read = |path: &PathBuf, what: &str| {
+            std::fs::read(path)
+                .with_context(|| format!("Cannot read {what} from {path:?}"))
+        };
+
+        let identity = Identity::from_pem(
+            read(&self.cert_path, "RPC_TLS_CERT")?,
+            read(&self.key_path, "RPC_TLS_KEY")?,
+        );
+        let mut config = ServerTlsConfig::new().identity(identity);
+        if let Some(path) = &self.client_ca_path {
+            config = config.client_ca_root(Certificate::from_pem(read(
+                path,
+                "RPC_TLS_CLIENT_CA",
+            )?));
+        }
+
+        Ok(config)
+    }
+}
+
+/// Rejects RPCs without the expected bearer token, lets everything through
+/// if there is none.
+#[derive(Clone)]
+pub struct RequireToken {
+    /// The whole "Bearer <token>" header value
+    expected: Option<Arc<[u8]>>,
+}
+
+impl RequireToken {
+    pub fn new(token: Option<&str>) -> Self {
+        Self {
+            expected: token
+                .map(|token| format!("Bearer {token}").into_bytes().into()),
+        }
+    }
+}
+
+impl Interceptor for RequireToken {
+    fn call(&mut self, request: Request<()>) -> StdResult<Request<()>, Status> {
+        let Some(expected) = &self.expected else {
+            return Ok(request);
+        };
+
+        let is_valid =
+            request
+                .metadata()
+                .get("authorization")
+                .is_some_and(|given| {
+                    worker::constant_time_eq(given.as_bytes(), expected)
+                });
+        if !is_valid {
+            return Err(Status::unauthenticated(
+                "Missing or invalid bearer token, see RPC_TOKEN",
+            ));
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa};
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::transport::{Channel, ClientTlsConfig, Server};
+    use tonic_health::pb::{health_client::HealthClient, HealthCheckRequest};
+
+    #[test]
+    fn it_requires_tls_paths_together() {
+        let s = |s: &str| Some(s.to_string());
+
+        assert!(TlsConf::from_paths(None, None, None).unwrap().is_none());
+        assert!(TlsConf::from_paths(s("c"), s("k"), None).unwrap().is_some());
+        assert!(TlsConf::from_paths(s("c"), s("k"), s("ca"))
+            .unwrap()
+            .is_some());
+        assert!(TlsConf::from_paths(s("c"), None, None).is_err());
+        assert!(TlsConf::from_paths(None, s("k"), s("ca")).is_err());
+        assert!(TlsConf::from_paths(None, None, s("ca")).is_err());
+    }
+
+    #[test]
+    fn it_checks_token() {
+        let mut check = RequireToken::new(Some("secret"));
+        let request = |token: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(token) = token {
+                request
+                    .metadata_mut()
+                    .insert("authorization", token.parse().unwrap());
+            }
+            request
+        };
+
+        assert!(check.call(request(Some("Bearer secret"))).is_ok());
+        assert!(check.call(request(Some("Bearer secre"))).is_err());
+        assert!(check.call(request(Some("Bearer secreT"))).is_err());
+        assert!(check.call(request(None)).is_err());
+
+        let mut no_check = RequireToken::new(None);
+        assert!(no_check.call(request(None)).is_ok());
+    }
+
+    /// Serves the health service behind mutual TLS and a token, the same way
+    /// the worker service is served, and checks who gets through.
+    #[tokio::test]
+    async fn it_authenticates_over_mutual_tls() -> AnyResult<()> {
+        let mut ca_params = CertificateParams::new(vec![]);
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = rcgen::Certificate::from_params(ca_params)?;
+        let server_cert =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let client_cert =
+            rcgen::generate_simple_self_signed(vec!["admin".to_string()])?;
+
+        let dir = tempfile::tempdir()?;
+        let write = |name: &str, contents: String| -> AnyResult<String> {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents)?;
+            Ok(path.to_string_lossy().into_owned())
+        };
+        let tls = TlsConf::from_paths(
+            Some(write(
+                "server.pem",
+                server_cert.serialize_pem_with_signer(&ca)?,
+            )?),
+            Some(write(
+                "server.key",
+                server_cert.serialize_private_key_pem(),
+            )?),
+            Some(write("ca.pem", ca.serialize_pem()?)?),
+        )?
+        .expect("TLS is configured");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        drop(listener);
+
+        let (_, health_service) = tonic_health::server::health_reporter();
+        let server = Server::builder()
+            .tls_config(tls.server_config()?)?
+            .add_service(InterceptedService::new(
+                health_service,
+                RequireToken::new(Some("secret")),
+            ))
+            .serve(addr);
+        tokio::spawn(server);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let ca_pem = ca.serialize_pem()?;
+        let client_pem = client_cert.serialize_pem_with_signer(&ca)?;
+        let client_key = client_cert.serialize_private_key_pem();
+        let check = |identity: bool, token: Option<&'static str>| {
+            let mut tls = ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(&ca_pem))
+                .domain_name("localhost");
+            if identity {
+                tls =
+                    tls.identity(Identity::from_pem(&client_pem, &client_key));
+            }
+            async move {
+                let channel = Channel::from_shared(format!("https://{addr}"))?
+                    .tls_config(tls)?
+                    .connect()
+                    .await?;
+                let mut client = HealthClient::with_interceptor(
+                    channel,
+                    worker::BearerToken::new(token)?,
+                );
+                client.check(HealthCheckRequest::default()).await?;
+
+                AnyResult::<()>::Ok(())
+            }
+        };
+
+        check(true, Some("secret")).await?;
+        assert!(check(true, Some("wrong")).await.is_err());
+        assert!(check(true, None).await.is_err());
+        assert!(check(false, Some("secret")).await.is_err());
+
+        Ok(())
+    }
+}
@@ -1,10 +1,45 @@
-use crate::prelude::*;
-use anyhow::Context;
-use std::{env, net::SocketAddr};
+use crate::{auth::TlsConf, gc, prelude::*};
+use anyhow::{bail, Context};
+use std::{env, net::SocketAddr, path::PathBuf};
 
 pub struct Conf {
     /// For example 0.0.0.0:8080
     pub rpc_addr: SocketAddr,
+    /// Prometheus metrics are served over HTTP on this address if set.
+    pub metrics_addr: Option<SocketAddr>,
+    /// RPC clients must send this as a bearer token if set.
+    pub rpc_token: Option<String>,
+    /// RPC is served over TLS if set.
+    pub rpc_tls: Option<TlsConf>,
+    pub sqlite_db_path: PathBuf,
+    /// Scratch space for files being downloaded or rendered.
+    /// Also where the local media store keeps its files.
+    pub media_dir: PathBuf,
+    pub media_store: MediaStoreConf,
+    /// Limits of the media store enforced by garbage collection.
+    pub gc_policy: gc::Policy,
+    /// Defaults to "ffmpeg" which is then looked up in PATH.
+    pub ffmpeg_bin: String,
+    /// TrueType font of thumbnail titles, ffmpeg's default font if not set.
+    pub thumbnail_font: Option<PathBuf>,
+}
+
+/// Where downloaded clips and files derived from them are stored.
+pub enum MediaStoreConf {
+    /// In the media dir
+    Local,
+    S3(S3Conf),
+}
+
+pub struct S3Conf {
+    pub bucket: String,
+    /// For example "eu-central-1"
+    pub region: String,
+    /// Set for S3-compatible storage other than AWS, such as MinIO.
+    /// For example http://localhost:9000
+    pub endpoint: Option<String>,
+    pub access_key: String,
+    pub secret_key: String,
 }
 
 impl Conf {
@@ -14,8 +49,118 @@ impl Conf {
         let rpc_addr = env::var("RPC_ADDR").context("RPC_ADDR")?;
         debug!("RPC_ADDR: {rpc_addr}");
 
+        let metrics_addr = env::var("METRICS_ADDR").ok();
+        debug!("METRICS_ADDR: {metrics_addr:?}");
+
+        let rpc_token = env::var("RPC_TOKEN").ok();
+        debug!(
+            "RPC_TOKEN: {}",
+            if rpc_token.is_some() { "***" } else { "-" }
+        );
+        if rpc_token.as_deref() == Some("") {
+            bail!("RPC_TOKEN must not be empty, unset it to disable it");
+        }
+
+        let rpc_tls_cert = env::var("RPC_TLS_CERT").ok();
+        debug!("RPC_TLS_CERT: {rpc_tls_cert:?}");
+        let rpc_tls_key = env::var("RPC_TLS_KEY").ok();
+        debug!("RPC_TLS_KEY: {rpc_tls_key:?}");
+        let rpc_tls_client_ca = env::var("RPC_TLS_CLIENT_CA").ok();
+        debug!("RPC_TLS_CLIENT_CA: {rpc_tls_client_ca:?}");
+        let rpc_tls =
+            TlsConf::from_paths(rpc_tls_cert, rpc_tls_key, rpc_tls_client_ca)?;
+
+        let requires_client_cert = rpc_tls
+            .as_ref()
+            .is_some_and(|tls| tls.client_ca_path.is_some());
+        if rpc_token.is_none() && !requires_client_cert {
+            warn!(
+                "Neither RPC_TOKEN nor RPC_TLS_CLIENT_CA is set, \
+                anyone who can reach {rpc_addr} can use the worker"
+            );
+        }
+
+        let sqlite_db_path =
+            env::var("SQLITE_DB_PATH").context("SQLITE_DB_PATH")?;
+        debug!("SQLITE_DB_PATH: {sqlite_db_path}");
+
+        let media_dir = env::var("MEDIA_DIR").context("MEDIA_DIR")?;
+        debug!("MEDIA_DIR: {media_dir}");
+
+        let media_store =
+            env::var("MEDIA_STORE").unwrap_or_else(|_| "local".to_string());
+        debug!("MEDIA_STORE: {media_store}");
+        let media_store = match media_store.as_str() {
+            "local" => MediaStoreConf::Local,
+            "s3" => MediaStoreConf::S3(S3Conf::from_env()?),
+            other => {
+                bail!("MEDIA_STORE must be 'local' or 's3', got '{other}'")
+            }
+        };
+
+        let max_total_bytes = env::var("MEDIA_MAX_BYTES").ok();
+        debug!("MEDIA_MAX_BYTES: {max_total_bytes:?}");
+        let max_age_days = env::var("MEDIA_MAX_AGE_DAYS").ok();
+        debug!("MEDIA_MAX_AGE_DAYS: {max_age_days:?}");
+        let gc_policy = gc::Policy {
+            max_total_bytes: max_total_bytes
+                .map(|bytes| bytes.parse())
+                .transpose()
+                .context("MEDIA_MAX_BYTES")?,
+            max_age: max_age_days
+                .map(|days| days.parse().map(chrono::Duration::days))
+                .transpose()
+                .context("MEDIA_MAX_AGE_DAYS")?,
+        };
+
+        let ffmpeg_bin =
+            env::var("FFMPEG_BIN").unwrap_or_else(|_| "ffmpeg".to_string());
+        debug!("FFMPEG_BIN: {ffmpeg_bin}");
+
+        let thumbnail_font = env::var("THUMBNAIL_FONT").ok();
+        debug!("THUMBNAIL_FONT: {thumbnail_font:?}");
+
         Ok(Self {
             rpc_addr: rpc_addr.parse()?,
+            metrics_addr: metrics_addr
+                .map(|addr| addr.parse())
+                .transpose()
+                .context("METRICS_ADDR")?,
+            rpc_token,
+            rpc_tls,
+            sqlite_db_path: sqlite_db_path.into(),
+            media_dir: media_dir.into(),
+            media_store,
+            gc_policy,
+            ffmpeg_bin,
+            thumbnail_font: thumbnail_font.map(Into::into),
+        })
+    }
+}
+
+impl S3Conf {
+    fn from_env() -> AnyResult<Self> {
+        let bucket = env::var("S3_BUCKET").context("S3_BUCKET")?;
+        debug!("S3_BUCKET: {bucket}");
+
+        let region =
+            env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        debug!("S3_REGION: {region}");
+
+        let endpoint = env::var("S3_ENDPOINT").ok();
+        debug!("S3_ENDPOINT: {endpoint:?}");
+
+        let access_key =
+            env::var("S3_ACCESS_KEY_ID").context("S3_ACCESS_KEY_ID")?;
+        let secret_key =
+            env::var("S3_SECRET_ACCESS_KEY").context("S3_SECRET_ACCESS_KEY")?;
+
+        Ok(Self {
+            bucket,
+            region,
+            endpoint,
+            access_key,
+            secret_key,
         })
     }
 }
@@ -0,0 +1,34 @@
+/// Files in the media store
+pub mod artifact;
+/// Known tracks to check clip audio against
+pub mod reference_track;
+
+use crate::prelude::*;
+use rusqlite_migration::{Migrations, M};
+use std::ffi::OsStr;
+
+/// Opens and runs migrations.
+pub fn open(path: impl AsRef<OsStr>) -> AnyResult<DbConn> {
+    let mut db = DbConn::open(path.as_ref())?;
+    rusqlite::vtab::array::load_module(&db)?;
+    up(&mut db)?;
+
+    Ok(db)
+}
+
+pub fn up(db: &mut DbConn) -> AnyResult<()> {
+    info!("Running db UP migrations");
+
+    migrations().to_latest(db)?;
+
+    Ok(())
+}
+
+fn migrations() -> Migrations<'static> {
+    Migrations::new(vec![
+        M::up(include_str!("../migrations/0001.up.sql"))
+            .down(include_str!("../migrations/0001.down.sql")),
+        M::up(include_str!("../migrations/0002.up.sql"))
+            .down(include_str!("../migrations/0002.down.sql")),
+    ])
+}
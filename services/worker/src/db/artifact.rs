@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{named_params, OptionalExtension, Row};
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// Clip as downloaded from Twitch
+    Clip,
+    /// Video rendered from a clip, such as a vertical short
+    Render,
+    /// Still frame of a clip, a candidate for a thumbnail
+    Frame,
+    /// Image composited from a frame of a clip
+    Thumbnail,
+    /// Storyboard, waveform or animation to triage a clip by
+    Preview,
+}
+
+/// File in the media store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// See [`crate::storage::MediaStore`]
+    pub key: String,
+    pub kind: ArtifactKind,
+    pub clip_id: Option<String>,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    pub last_accessed_at: DateTime<Utc>,
+}
+
+/// Replaces any artifact previously stored under the same key.
+pub fn insert(
+    db: &DbConn,
+    key: &str,
+    kind: ArtifactKind,
+    clip_id: Option<&str>,
+    size_bytes: u64,
+) -> Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO artifacts (key, kind, clip_id, size_bytes)
+        VALUES (:key, :kind, :clip_id, :size_bytes)",
+        named_params! {
+            ":key": key,
+            ":kind": <&str>::from(kind),
+            ":clip_id": clip_id,
+            ":size_bytes": size_bytes,
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn select_by_key(db: &DbConn, key: &str) -> Result<Option<Artifact>> {
+    db.query_row(
+        "SELECT * FROM artifacts WHERE key = :key",
+        named_params! { ":key": key },
+        from_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+/// Artifacts whose keys start with given prefix, sorted by key.
+pub fn select_by_prefix(db: &DbConn, prefix: &str) -> Result<Vec<Artifact>> {
+    db.prepare(
+        "SELECT * FROM artifacts
+        WHERE substr(key, 1, length(:prefix)) = :prefix
+        ORDER BY key ASC",
+    )?
+    .query_map(named_params! { ":prefix": prefix }, from_row)?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+/// Marks the artifact as just used.
+pub fn touch(db: &DbConn, key: &str) -> Result<()> {
+    db.execute(
+        "UPDATE artifacts
+        SET last_accessed_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+        WHERE key = :key",
+        named_params! { ":key": key },
+    )?;
+
+    Ok(())
+}
+
+pub fn delete(db: &DbConn, key: &str) -> Result<()> {
+    db.execute(
+        "DELETE FROM artifacts WHERE key = :key",
+        named_params! { ":key": key },
+    )?;
+
+    Ok(())
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Artifact> {
+    let kind: String = row.get("kind")?;
+    Ok(Artifact {
+        key: row.get("key")?,
+        kind: ArtifactKind::try_from(kind.as_str()).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                e.into(),
+            )
+        })?,
+        clip_id: row.get("clip_id")?,
+        size_bytes: row.get("size_bytes")?,
+        created_at: row.get("created_at")?,
+        last_accessed_at: row.get("last_accessed_at")?,
+    })
+}
+
+impl From<ArtifactKind> for &'static str {
+    fn from(kind: ArtifactKind) -> Self {
+        match kind {
+            ArtifactKind::Clip => "clip",
+            ArtifactKind::Render => "render",
+            ArtifactKind::Frame => "frame",
+            ArtifactKind::Thumbnail => "thumbnail",
+            ArtifactKind::Preview => "preview",
+        }
+    }
+}
+
+impl TryFrom<&str> for ArtifactKind {
+    type Error = AnyError;
+
+    fn try_from(kind: &str) -> AnyResult<Self> {
+        match kind {
+            "clip" => Ok(Self::Clip),
+            "render" => Ok(Self::Render),
+            "frame" => Ok(Self::Frame),
+            "thumbnail" => Ok(Self::Thumbnail),
+            "preview" => Ok(Self::Preview),
+            other => Err(anyhow::anyhow!("Unknown artifact kind '{other}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_records_artifacts() -> Result<()> {
+        let db = db::open(":memory:")?;
+
+        insert(&db, "clips/a.mp4", ArtifactKind::Clip, Some("a"), 10)?;
+        insert(&db, "clips/b.mp4", ArtifactKind::Clip, Some("b"), 20)?;
+        insert(&db, "clips/a.mp4", ArtifactKind::Clip, Some("a"), 30)?;
+        touch(&db, "clips/a.mp4")?;
+
+        let a = select_by_key(&db, "clips/a.mp4")?.expect("Inserted");
+        assert_eq!(a.kind, ArtifactKind::Clip);
+        assert_eq!(a.clip_id.as_deref(), Some("a"));
+        assert_eq!(a.size_bytes, 30);
+
+        assert_eq!(select_by_prefix(&db, "clips/")?.len(), 2);
+        assert_eq!(select_by_prefix(&db, "clips/b")?.len(), 1);
+        assert!(select_by_prefix(&db, "thumbnails/")?.is_empty());
+
+        delete(&db, "clips/a.mp4")?;
+        assert_eq!(select_by_key(&db, "clips/a.mp4")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use itertools::Itertools;
+use rusqlite::named_params;
+use std::{collections::HashMap, rc::Rc};
+
+use crate::fingerprint::Fingerprint;
+use crate::prelude::*;
+
+#[derive(Debug)]
+pub struct ReferenceTrack {
+    pub id: i64,
+    pub title: String,
+    pub source: String,
+    pub duration_secs: f64,
+    pub fingerprint_count: usize,
+}
+
+pub fn insert(
+    db: &mut DbConn,
+    title: &str,
+    source: &str,
+    duration_secs: f64,
+    fingerprints: &[Fingerprint],
+) -> Result<i64> {
+    let tx = db.transaction()?;
+    tx.execute(
+        "INSERT INTO reference_tracks (title, source, duration_secs)
+        VALUES (:title, :source, :duration_secs)",
+        named_params! {
+            ":title": title,
+            ":source": source,
+            ":duration_secs": duration_secs,
+        },
+    )?;
+    let track_id = tx.last_insert_rowid();
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO reference_fingerprints (hash, track_id, frame)
+            VALUES (:hash, :track_id, :frame)",
+        )?;
+        for fp in fingerprints {
+            stmt.execute(named_params! {
+                ":hash": fp.hash,
+                ":track_id": track_id,
+                ":frame": fp.frame,
+            })?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(track_id)
+}
+
+pub fn delete(db: &mut DbConn, track_id: i64) -> Result<()> {
+    let tx = db.transaction()?;
+    tx.execute(
+        "DELETE FROM reference_fingerprints WHERE track_id = :track_id",
+        named_params! { ":track_id": track_id },
+    )?;
+    let deleted = tx.execute(
+        "DELETE FROM reference_tracks WHERE id = :track_id",
+        named_params! { ":track_id": track_id },
+    )?;
+    tx.commit()?;
+
+    if deleted == 0 {
+        return Err(AppError::not_found(format!(
+            "Reference track {track_id} does not exist"
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn select_all(db: &DbConn) -> Result<Vec<ReferenceTrack>> {
+    db.prepare(
+        "SELECT
+            id,
+            title,
+            source,
+            duration_secs,
+            (
+                SELECT COUNT(*) FROM reference_fingerprints
+                WHERE track_id = reference_tracks.id
+            ) AS fingerprint_count
+        FROM reference_tracks
+        ORDER BY title ASC",
+    )?
+    .query_map((), |row| {
+        Ok(ReferenceTrack {
+            id: row.get("id")?,
+            title: row.get("title")?,
+            source: row.get("source")?,
+            duration_secs: row.get("duration_secs")?,
+            fingerprint_count: row.get("fingerprint_count")?,
+        })
+    })?
+    .map(|res| res.map_err(AppError::from))
+    .collect()
+}
+
+pub fn select_titles(db: &DbConn) -> Result<HashMap<i64, String>> {
+    db.prepare("SELECT id, title FROM reference_tracks")?
+        .query_map((), |row| Ok((row.get("id")?, row.get("title")?)))?
+        .map(|res| res.map_err(AppError::from))
+        .collect()
+}
+
+/// Returns hash => (track id, frame) of all reference fingerprints which
+/// have any of the given hashes.
+pub fn select_fingerprints_by_hashes(
+    db: &DbConn,
+    hashes: impl IntoIterator<Item = u32>,
+) -> Result<HashMap<u32, Vec<(i64, u32)>>> {
+    // array feature of sqlite
+    let hashes = Rc::new(
+        hashes
+            .into_iter()
+            .unique()
+            .map(|hash| rusqlite::types::Value::from(hash as i64))
+            .collect_vec(),
+    );
+
+    let mut references: HashMap<u32, Vec<(i64, u32)>> = HashMap::new();
+    let mut stmt = db.prepare(
+        "SELECT hash, track_id, frame FROM reference_fingerprints
+        WHERE hash IN rarray(:hashes)",
+    )?;
+    let mut rows = stmt.query(named_params! { ":hashes": hashes })?;
+    while let Some(row) = rows.next()? {
+        references
+            .entry(row.get("hash")?)
+            .or_default()
+            .push((row.get("track_id")?, row.get("frame")?));
+    }
+
+    Ok(references)
+}
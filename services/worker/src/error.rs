@@ -60,6 +60,24 @@ impl From<AnyError> for AppError {
     }
 }
 
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            message: err.to_string().into(),
+            kind: AppErrorKind::Other,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self {
+            message: err.to_string().into(),
+            kind: AppErrorKind::Other,
+        }
+    }
+}
+
 impl From<AppError> for tonic::Status {
     fn from(err: AppError) -> Self {
         let code = match err.kind {
@@ -78,3 +96,5 @@ impl std::fmt::Display for AppError {
         f.write_str(&self.message)
     }
 }
+
+impl std::error::Error for AppError {}
@@ -0,0 +1,302 @@
+//! Spectral peak hashing of audio.
+//!
+//! The spectrogram of the audio is reduced to a constellation of its
+//! strongest peaks.
+//! Pairs of nearby peaks are hashed by their frequencies and the time between
+//! them, which survives noise, compression and voices talking over the music.
+//! A clip matches a reference track if many of its hashes are found in the
+//! track at the same time offset.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::collections::HashMap;
+
+/// Audio must be decoded to mono at this rate before fingerprinting.
+/// Music carries most of its identifiable energy below 5kHz.
+pub const SAMPLE_RATE: u32 = 11_025;
+/// Samples per FFT window, ~93ms
+const WINDOW_SIZE: usize = 1024;
+/// Samples between starts of two consecutive windows, ~46ms
+const HOP_SIZE: usize = 512;
+/// Spectrum bins are split into roughly logarithmic bands and the strongest
+/// bin of each band is a peak candidate.
+const BANDS: [(usize, usize); 6] =
+    [(1, 10), (10, 20), (20, 40), (40, 80), (80, 160), (160, 512)];
+/// Peaks quieter than this are considered silence.
+const MIN_PEAK_MAGNITUDE: f32 = 0.5;
+/// Each peak is paired with this many following peaks.
+const FAN_OUT: usize = 5;
+/// Paired peaks are at most this many frames apart, ~3s
+const TARGET_ZONE_FRAMES: u32 = 64;
+/// How many hashes of a clip must be found in a track at the same time
+/// offset for the clip to be considered containing the track.
+pub const MIN_ALIGNED_HASHES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub hash: u32,
+    /// Window index of the anchor peak, see [`frame_to_secs`]
+    pub frame: u32,
+}
+
+/// Part of a clip which contains part of a reference track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub track_id: i64,
+    pub start_secs: f64,
+    pub end_secs: f64,
+    /// How many hashes aligned, the higher the more certain the match
+    pub score: usize,
+}
+
+pub fn frame_to_secs(frame: u32) -> f64 {
+    frame as f64 * HOP_SIZE as f64 / SAMPLE_RATE as f64
+}
+
+/// Expects mono samples at [`SAMPLE_RATE`].
+pub fn fingerprint(samples: &[f32]) -> Vec<Fingerprint> {
+    let peaks = spectral_peaks(samples);
+
+    let mut fingerprints = Vec::with_capacity(peaks.len() * FAN_OUT);
+    for (i, &(anchor_frame, anchor_bin)) in peaks.iter().enumerate() {
+        // a sustained tone is a peak in the same bin over several frames,
+        // pairing it with itself would produce the same hash for any track
+        // which happens to contain that tone
+        let targets = peaks[i + 1..]
+            .iter()
+            .skip_while(|(frame, _)| *frame == anchor_frame)
+            .take_while(|(frame, _)| frame - anchor_frame <= TARGET_ZONE_FRAMES)
+            .filter(|(_, bin)| bin.abs_diff(anchor_bin) > 1)
+            .take(FAN_OUT);
+
+        for &(target_frame, target_bin) in targets {
+            // 9 bits per bin as there are 512 of them, 14 bits for delta
+            let hash = (anchor_bin as u32) << 23
+                | (target_bin as u32) << 14
+                | (target_frame - anchor_frame);
+            fingerprints.push(Fingerprint {
+                hash,
+                frame: anchor_frame,
+            });
+        }
+    }
+
+    fingerprints
+}
+
+/// Reference fingerprints are looked up by the hashes of the clip and given
+/// as a map of hash to list of (track id, frame).
+///
+/// For each track we count how many hashes appear at each offset between the
+/// clip and the track.
+/// Offsets with enough hashes are where the clip plays the track.
+pub fn find_matches(
+    clip: &[Fingerprint],
+    references: &HashMap<u32, Vec<(i64, u32)>>,
+) -> Vec<Match> {
+    // (track id, reference frame - clip frame) => clip frames
+    let mut votes: HashMap<(i64, i64), Vec<u32>> = HashMap::new();
+    for fp in clip {
+        for &(track_id, frame) in references.get(&fp.hash).into_iter().flatten()
+        {
+            votes
+                .entry((track_id, frame as i64 - fp.frame as i64))
+                .or_default()
+                .push(fp.frame);
+        }
+    }
+
+    // the clip can start at any sample, so the same peak can land in
+    // neighbouring windows and the offset jitters by one
+    let votes_around = |(track_id, delta): (i64, i64)| {
+        (delta - 1..=delta + 1)
+            .filter_map(|d| votes.get(&(track_id, d)))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    let mut matches: Vec<Match> = vec![];
+    for &(track_id, delta) in votes.keys() {
+        let frames = votes_around((track_id, delta));
+        if frames.len() < MIN_ALIGNED_HASHES {
+            continue;
+        }
+
+        // only the strongest offset of its neighbourhood is reported
+        let is_local_max = [delta - 1, delta + 1].into_iter().all(|d| {
+            let other = votes_around((track_id, d)).len();
+            other < frames.len() || (other == frames.len() && d > delta)
+        });
+        if !is_local_max {
+            continue;
+        }
+
+        let first = *frames.iter().min().expect("Not empty");
+        let last = *frames.iter().max().expect("Not empty");
+        matches.push(Match {
+            track_id,
+            start_secs: frame_to_secs(first),
+            end_secs: frame_to_secs(last)
+                + WINDOW_SIZE as f64 / SAMPLE_RATE as f64,
+            score: frames.len(),
+        });
+    }
+
+    merge_overlapping(matches)
+}
+
+/// A track can match at several offsets, for example if its chorus repeats.
+/// Those that overlap in the clip are reported as one.
+fn merge_overlapping(mut matches: Vec<Match>) -> Vec<Match> {
+    matches.sort_by(|a, b| {
+        (a.track_id, a.start_secs)
+            .partial_cmp(&(b.track_id, b.start_secs))
+            .expect("Not NaN")
+    });
+
+    let mut merged: Vec<Match> = Vec::with_capacity(matches.len());
+    for m in matches {
+        match merged.last_mut() {
+            Some(last)
+                if last.track_id == m.track_id
+                    && m.start_secs <= last.end_secs =>
+            {
+                last.end_secs = last.end_secs.max(m.end_secs);
+                last.score += m.score;
+            }
+            _ => merged.push(m),
+        }
+    }
+
+    merged
+}
+
+/// Returns (frame, bin) of the strongest bins in each window sorted by frame.
+fn spectral_peaks(samples: &[f32]) -> Vec<(u32, u16)> {
+    if samples.len() < WINDOW_SIZE {
+        return vec![];
+    }
+
+    let fft = FftPlanner::new().plan_fft_forward(WINDOW_SIZE);
+    let hann: Vec<f32> = (0..WINDOW_SIZE)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * i as f32
+                / (WINDOW_SIZE - 1) as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+
+    let mut peaks = vec![];
+    let mut buffer = vec![Complex::new(0.0, 0.0); WINDOW_SIZE];
+    let windows = (0..=samples.len() - WINDOW_SIZE).step_by(HOP_SIZE);
+    for (frame, start) in windows.enumerate() {
+        for ((b, s), w) in buffer
+            .iter_mut()
+            .zip(&samples[start..start + WINDOW_SIZE])
+            .zip(&hann)
+        {
+            *b = Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let band_peaks: Vec<(usize, f32)> = BANDS
+            .iter()
+            .map(|&(lo, hi)| {
+                buffer[lo..hi]
+                    .iter()
+                    .map(|c| c.norm())
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(i, magnitude)| (lo + i, magnitude))
+                    .expect("Bands are not empty")
+            })
+            .collect();
+        let mean = band_peaks.iter().map(|(_, m)| m).sum::<f32>()
+            / band_peaks.len() as f32;
+
+        peaks.extend(
+            band_peaks
+                .into_iter()
+                .filter(|&(_, m)| m > mean && m > MIN_PEAK_MAGNITUDE)
+                .map(|(bin, _)| (frame as u32, bin as u16)),
+        );
+    }
+
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each note is a chord of two pseudo-random tones lasting a quarter of
+    /// a second.
+    /// Notes fade in and out, otherwise every note boundary is a click which
+    /// looks the same in all melodies.
+    fn melody(seed: u64, notes: usize) -> Vec<f32> {
+        let mut state = seed;
+        let mut next_freq = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            200.0 + (state >> 33) as f32 % 2800.0
+        };
+
+        let note_len = SAMPLE_RATE as usize / 4;
+        (0..notes)
+            .flat_map(|_| {
+                let (f1, f2) = (next_freq(), next_freq());
+                (0..note_len).map(move |i| {
+                    use std::f32::consts::PI;
+
+                    let t = i as f32 / SAMPLE_RATE as f32;
+                    let envelope = (PI * i as f32 / note_len as f32).sin();
+                    envelope
+                        * (0.3 * (2.0 * PI * f1 * t).sin()
+                            + 0.2 * (2.0 * PI * f2 * t).sin())
+                })
+            })
+            .collect()
+    }
+
+    fn index(
+        track_id: i64,
+        fps: &[Fingerprint],
+    ) -> HashMap<u32, Vec<(i64, u32)>> {
+        let mut index: HashMap<u32, Vec<(i64, u32)>> = HashMap::new();
+        for fp in fps {
+            index.entry(fp.hash).or_default().push((track_id, fp.frame));
+        }
+        index
+    }
+
+    #[test]
+    fn it_finds_track_in_clip() {
+        let track = melody(1, 40);
+        let references = index(7, &fingerprint(&track));
+
+        // 3s of unrelated audio, then 5s of the track from its 2nd second,
+        // then unrelated audio again
+        let sr = SAMPLE_RATE as usize;
+        let mut clip = melody(2, 12);
+        clip.extend(&track[2 * sr + 100..7 * sr + 100]);
+        clip.extend(melody(3, 8));
+
+        let matches = find_matches(&fingerprint(&clip), &references);
+        assert_eq!(matches.len(), 1, "{matches:?}");
+        assert_eq!(matches[0].track_id, 7);
+        assert!((matches[0].start_secs - 3.0).abs() < 0.5, "{matches:?}");
+        assert!((matches[0].end_secs - 8.0).abs() < 0.5, "{matches:?}");
+    }
+
+    #[test]
+    fn it_does_not_find_unrelated_track() {
+        let references = index(7, &fingerprint(&melody(1, 40)));
+
+        let matches = find_matches(&fingerprint(&melody(4, 40)), &references);
+        assert!(matches.is_empty(), "{matches:?}");
+    }
+
+    #[test]
+    fn it_ignores_silence() {
+        assert!(fingerprint(&vec![0.0; SAMPLE_RATE as usize * 2]).is_empty());
+    }
+}
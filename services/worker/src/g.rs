@@ -1,7 +1,10 @@
-use crate::prelude::*;
+use crate::{metrics::Metrics, prelude::*, storage::MediaStore};
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub conf: Arc<Conf>,
+    pub db: DbLock,
+    pub metrics: Arc<Metrics>,
+    pub store: Arc<dyn MediaStore>,
 }
@@ -0,0 +1,196 @@
+//! Decides which artifacts to delete from the media store so that it does
+//! not grow forever.
+
+use crate::db::artifact::Artifact;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+/// Limits on the media store, both optional.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    /// Least recently used artifacts are deleted until the store fits.
+    pub max_total_bytes: Option<u64>,
+    /// Artifacts created longer ago than this are deleted.
+    pub max_age: Option<chrono::Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionReason {
+    /// Older than [`Policy::max_age`]
+    Age,
+    /// Evicted to fit [`Policy::max_total_bytes`]
+    Quota,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    pub key: String,
+    pub clip_id: Option<String>,
+    pub size_bytes: u64,
+    pub reason: EvictionReason,
+}
+
+/// Artifacts of kept clips are never evicted, even if that means the store
+/// stays over quota.
+///
+/// Expired artifacts go first, then the least recently used ones until the
+/// store fits the quota.
+pub fn plan(
+    artifacts: &[Artifact],
+    keep_clip_ids: &HashSet<String>,
+    policy: &Policy,
+    now: DateTime<Utc>,
+) -> Vec<Eviction> {
+    let is_kept = |artifact: &Artifact| {
+        artifact
+            .clip_id
+            .as_ref()
+            .is_some_and(|clip_id| keep_clip_ids.contains(clip_id))
+    };
+    let evict = |artifact: &Artifact, reason| Eviction {
+        key: artifact.key.clone(),
+        clip_id: artifact.clip_id.clone(),
+        size_bytes: artifact.size_bytes,
+        reason,
+    };
+
+    let mut evictions = vec![];
+    let mut remaining = vec![];
+    for artifact in artifacts {
+        let is_expired = policy
+            .max_age
+            .is_some_and(|max_age| now - artifact.created_at > max_age);
+
+        if is_expired && !is_kept(artifact) {
+            evictions.push(evict(artifact, EvictionReason::Age));
+        } else {
+            remaining.push(artifact);
+        }
+    }
+
+    if let Some(max_total_bytes) = policy.max_total_bytes {
+        let mut total_bytes: u64 = remaining.iter().map(|a| a.size_bytes).sum();
+
+        remaining.sort_by_key(|a| (a.last_accessed_at, &a.key));
+        for artifact in remaining {
+            if total_bytes <= max_total_bytes {
+                break;
+            }
+            if is_kept(artifact) {
+                continue;
+            }
+
+            total_bytes -= artifact.size_bytes;
+            evictions.push(evict(artifact, EvictionReason::Quota));
+        }
+    }
+
+    evictions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::artifact::ArtifactKind;
+
+    fn artifact(
+        clip_id: &str,
+        size_bytes: u64,
+        created_at: &str,
+        last_accessed_at: &str,
+    ) -> Artifact {
+        Artifact {
+            key: format!("clips/{clip_id}.mp4"),
+            kind: ArtifactKind::Clip,
+            clip_id: Some(clip_id.to_string()),
+            size_bytes,
+            created_at: created_at.parse().unwrap(),
+            last_accessed_at: last_accessed_at.parse().unwrap(),
+        }
+    }
+
+    fn keys(evictions: &[Eviction]) -> Vec<(&str, EvictionReason)> {
+        evictions
+            .iter()
+            .map(|e| (e.clip_id.as_deref().unwrap(), e.reason))
+            .collect()
+    }
+
+    #[test]
+    fn it_evicts_least_recently_used_over_quota() {
+        let artifacts = vec![
+            artifact("a", 40, "2023-07-01T00:00:00Z", "2023-07-05T00:00:00Z"),
+            artifact("b", 40, "2023-07-02T00:00:00Z", "2023-07-02T00:00:00Z"),
+            artifact("c", 40, "2023-07-03T00:00:00Z", "2023-07-03T00:00:00Z"),
+            artifact("d", 40, "2023-07-04T00:00:00Z", "2023-07-04T00:00:00Z"),
+        ];
+        let policy = Policy {
+            max_total_bytes: Some(100),
+            max_age: None,
+        };
+
+        let evictions = plan(
+            &artifacts,
+            &HashSet::new(),
+            &policy,
+            "2023-07-10T00:00:00Z".parse().unwrap(),
+        );
+        assert_eq!(
+            keys(&evictions),
+            vec![("b", EvictionReason::Quota), ("c", EvictionReason::Quota)]
+        );
+
+        // "b" is kept, so "c" and "d" have to go instead
+        let keep = HashSet::from(["b".to_string()]);
+        let evictions = plan(
+            &artifacts,
+            &keep,
+            &policy,
+            "2023-07-10T00:00:00Z".parse().unwrap(),
+        );
+        assert_eq!(
+            keys(&evictions),
+            vec![("c", EvictionReason::Quota), ("d", EvictionReason::Quota)]
+        );
+    }
+
+    #[test]
+    fn it_evicts_expired_first() {
+        let artifacts = vec![
+            artifact("a", 40, "2023-07-01T00:00:00Z", "2023-07-09T00:00:00Z"),
+            artifact("b", 40, "2023-07-08T00:00:00Z", "2023-07-08T00:00:00Z"),
+            artifact("c", 40, "2023-07-01T00:00:00Z", "2023-07-01T00:00:00Z"),
+        ];
+        let keep = HashSet::from(["c".to_string()]);
+        let policy = Policy {
+            max_total_bytes: Some(80),
+            max_age: Some(chrono::Duration::days(7)),
+        };
+
+        let evictions = plan(
+            &artifacts,
+            &keep,
+            &policy,
+            "2023-07-10T00:00:00Z".parse().unwrap(),
+        );
+        assert_eq!(keys(&evictions), vec![("a", EvictionReason::Age)]);
+    }
+
+    #[test]
+    fn it_keeps_everything_without_limits() {
+        let artifacts = vec![artifact(
+            "a",
+            u64::MAX / 2,
+            "2000-01-01T00:00:00Z",
+            "2000-01-01T00:00:00Z",
+        )];
+
+        let evictions = plan(
+            &artifacts,
+            &HashSet::new(),
+            &Policy::default(),
+            "2023-07-10T00:00:00Z".parse().unwrap(),
+        );
+        assert!(evictions.is_empty());
+    }
+}
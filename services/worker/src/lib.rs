@@ -1,5 +1,45 @@
+use tonic::metadata::{errors::InvalidMetadataValue, Ascii, MetadataValue};
+use tonic::{service::interceptor::InterceptedService, transport::Channel};
+
 pub mod rpc {
     tonic::include_proto!("worker");
 }
 
-pub type Client = rpc::worker_client::WorkerClient<tonic::transport::Channel>;
+pub type Client =
+    rpc::worker_client::WorkerClient<InterceptedService<Channel, BearerToken>>;
+
+/// Comparison whose duration does not depend on how many leading bytes
+/// match, so a secret cannot be guessed byte by byte.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Attaches the shared secret which the worker requires, if any, to every
+/// RPC.
+#[derive(Clone)]
+pub struct BearerToken(Option<MetadataValue<Ascii>>);
+
+impl BearerToken {
+    pub fn new(token: Option<&str>) -> Result<Self, InvalidMetadataValue> {
+        token
+            .map(|token| format!("Bearer {token}").parse())
+            .transpose()
+            .map(Self)
+    }
+}
+
+impl tonic::service::Interceptor for BearerToken {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(token) = &self.0 {
+            request
+                .metadata_mut()
+                .insert("authorization", token.clone());
+        }
+
+        Ok(request)
+    }
+}
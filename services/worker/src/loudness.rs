@@ -0,0 +1,266 @@
+//! Loudness as defined by [ITU-R BS.1770-4][bs1770] and used by
+//! [EBU R128][r128].
+//!
+//! [bs1770]: https://www.itu.int/rec/R-REC-BS.1770
+//! [r128]: https://tech.ebu.ch/publications/r128
+
+/// Audio must be decoded at this rate, the K-weighting filter coefficients
+/// are given for it.
+pub const SAMPLE_RATE: u32 = 48_000;
+/// Audio must be decoded as interleaved stereo.
+/// Both channels weigh the same.
+pub const CHANNELS: usize = 2;
+
+/// Loudness is measured over 400ms blocks...
+const BLOCK_SAMPLES: usize = SAMPLE_RATE as usize * 4 / 10;
+/// ...which overlap by 75%.
+const STEP_SAMPLES: usize = BLOCK_SAMPLES / 4;
+/// Blocks quieter than this are silence and don't count.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this much quieter than the loudness of the blocks above the
+/// absolute gate don't count either.
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// Samples are interpolated this many times to find peaks between them.
+const OVERSAMPLING: usize = 4;
+/// Taps of the interpolation filter on each side of the interpolated point.
+const INTERPOLATION_HALF_TAPS: isize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// None if the audio is silent or shorter than a block
+    pub integrated_lufs: Option<f64>,
+    /// Highest peak including those between samples, None if the audio is
+    /// silent
+    pub true_peak_dbtp: Option<f64>,
+}
+
+/// Measures interleaved stereo samples at [`SAMPLE_RATE`].
+pub fn measure(samples: &[f32]) -> Loudness {
+    Loudness {
+        integrated_lufs: integrated_lufs(samples),
+        true_peak_dbtp: true_peak_dbtp(samples),
+    }
+}
+
+fn integrated_lufs(samples: &[f32]) -> Option<f64> {
+    // mean square of the K-weighted signal per 100ms step, summed over
+    // channels
+    let mut steps = vec![0.0; samples.len() / CHANNELS / STEP_SAMPLES];
+    for channel in 0..CHANNELS {
+        let mut filter = KWeighting::default();
+        let channel_samples = samples.iter().skip(channel).step_by(CHANNELS);
+        for (i, sample) in channel_samples.enumerate() {
+            let filtered = filter.process(f64::from(*sample));
+            if let Some(step) = steps.get_mut(i / STEP_SAMPLES) {
+                *step += filtered * filtered;
+            }
+        }
+    }
+
+    let steps_per_block = BLOCK_SAMPLES / STEP_SAMPLES;
+    let block_powers: Vec<f64> = steps
+        .windows(steps_per_block)
+        .map(|block| block.iter().sum::<f64>() / BLOCK_SAMPLES as f64)
+        .filter(|power| lufs(*power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if block_powers.is_empty() {
+        return None;
+    }
+
+    let relative_gate =
+        lufs(mean(&block_powers).expect("Not empty")) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = block_powers
+        .into_iter()
+        .filter(|power| lufs(*power) > relative_gate)
+        .collect();
+
+    mean(&gated).map(lufs)
+}
+
+fn true_peak_dbtp(samples: &[f32]) -> Option<f64> {
+    let taps = interpolation_taps();
+
+    let mut peak: f64 = 0.0;
+    for channel in 0..CHANNELS {
+        let channel_samples: Vec<f64> = samples
+            .iter()
+            .skip(channel)
+            .step_by(CHANNELS)
+            .map(|s| f64::from(*s))
+            .collect();
+        let at = |i: isize| {
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| channel_samples.get(i))
+                .copied()
+                .unwrap_or(0.0)
+        };
+
+        for n in 0..channel_samples.len() as isize {
+            peak = peak.max(at(n).abs());
+            for phase_taps in &taps[1..] {
+                let interpolated: f64 = phase_taps
+                    .iter()
+                    .zip(-INTERPOLATION_HALF_TAPS + 1..)
+                    .map(|(tap, offset)| tap * at(n + offset))
+                    .sum();
+                peak = peak.max(interpolated.abs());
+            }
+        }
+    }
+
+    (peak > 0.0).then(|| 20.0 * peak.log10())
+}
+
+/// Hann windowed sinc, one set of taps per phase between two samples.
+/// Each set is normalized so that a constant signal stays constant.
+fn interpolation_taps() -> Vec<Vec<f64>> {
+    let half = INTERPOLATION_HALF_TAPS as f64;
+    (0..OVERSAMPLING)
+        .map(|phase| {
+            let fraction = phase as f64 / OVERSAMPLING as f64;
+            let taps: Vec<f64> = (-INTERPOLATION_HALF_TAPS + 1
+                ..=INTERPOLATION_HALF_TAPS)
+                .map(|offset| {
+                    let t = offset as f64 - fraction;
+                    let window =
+                        0.5 * (1.0 + (std::f64::consts::PI * t / half).cos());
+                    sinc(t) * window
+                })
+                .collect();
+            let sum: f64 = taps.iter().sum();
+            taps.into_iter().map(|tap| tap / sum).collect()
+        })
+        .collect()
+}
+
+fn sinc(t: f64) -> f64 {
+    if t == 0.0 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * t;
+        x.sin() / x
+    }
+}
+
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty())
+        .then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// High shelf modelling the acoustic effect of the head followed by a high
+/// pass, with the coefficients the standard gives for 48kHz.
+#[derive(Default)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn process(&mut self, sample: f64) -> f64 {
+        let shelved = self.shelf.process(
+            sample,
+            [1.53512485958697, -2.69169618940638, 1.19839281085285],
+            [-1.69065929318241, 0.73248077421585],
+        );
+        self.high_pass.process(
+            shelved,
+            [1.0, -2.0, 1.0],
+            [-1.99004745483398, 0.99007225036621],
+        )
+    }
+}
+
+/// Direct form I state.
+#[derive(Default)]
+struct Biquad {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64, b: [f64; 3], a: [f64; 2]) -> f64 {
+        let y = b[0] * x + b[1] * self.x1 + b[2] * self.x2
+            - a[0] * self.y1
+            - a[1] * self.y2;
+        (self.x2, self.x1) = (self.x1, x);
+        (self.y2, self.y1) = (self.y1, y);
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleaved stereo sine with the same phase in both channels.
+    fn sine(freq: f64, amplitude: f64, phase: f64, secs: f64) -> Vec<f32> {
+        let len = (secs * SAMPLE_RATE as f64) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let v = amplitude
+                    * (2.0 * std::f64::consts::PI * freq * t + phase).sin();
+                v as f32
+            })
+            .flat_map(|v| [v; CHANNELS])
+            .collect()
+    }
+
+    #[test]
+    fn it_measures_sine_loudness() {
+        // the standard's reference, a 0dBFS 997Hz sine in one channel is
+        // -3.01 LUFS, hence 0 LUFS in two
+        let loudness = measure(&sine(997.0, 1.0, 0.0, 5.0));
+        let lufs = loudness.integrated_lufs.unwrap();
+        assert!((lufs - 0.0).abs() < 0.1, "{lufs}");
+
+        let loudness = measure(&sine(997.0, 0.1, 0.0, 5.0));
+        let lufs = loudness.integrated_lufs.unwrap();
+        assert!((lufs + 20.0).abs() < 0.1, "{lufs}");
+        let peak = loudness.true_peak_dbtp.unwrap();
+        assert!((peak + 20.0).abs() < 0.1, "{peak}");
+    }
+
+    #[test]
+    fn it_gates_silence() {
+        let mut samples = sine(997.0, 0.1, 0.0, 5.0);
+        samples.extend(vec![0.0; 10 * SAMPLE_RATE as usize * CHANNELS]);
+        // only the few blocks which overlap both the sine and the silence
+        // pull it down
+        let lufs = measure(&samples).integrated_lufs.unwrap();
+        assert!((lufs + 20.0).abs() < 0.2, "{lufs}");
+
+        let silence = vec![0.0; 5 * SAMPLE_RATE as usize * CHANNELS];
+        assert_eq!(
+            measure(&silence),
+            Loudness {
+                integrated_lufs: None,
+                true_peak_dbtp: None,
+            }
+        );
+        assert_eq!(measure(&sine(997.0, 0.1, 0.0, 0.2)).integrated_lufs, None);
+    }
+
+    #[test]
+    fn it_finds_peaks_between_samples() {
+        // sampled at 45° every quarter period, no sample is above 0.707
+        let samples = sine(
+            SAMPLE_RATE as f64 / 4.0,
+            1.0,
+            std::f64::consts::FRAC_PI_4,
+            1.0,
+        );
+        let sample_peak = samples.iter().fold(0f32, |p, s| p.max(s.abs()));
+        assert!(sample_peak < 0.71);
+
+        let peak = measure(&samples).true_peak_dbtp.unwrap();
+        assert!(peak > -0.5, "{peak}");
+    }
+}
@@ -1,16 +1,46 @@
+/// Authentication of RPC clients
+mod auth;
 mod conf;
+/// Database schema and helpers
+mod db;
 mod error;
+/// Audio fingerprinting to detect copyrighted music
+mod fingerprint;
 mod g;
+/// Garbage collection of the media store
+mod gc;
+/// EBU R128 loudness measurement
+mod loudness;
+/// Downloaded files and ffmpeg invocations
+mod media;
+/// Prometheus metrics endpoint
+mod metrics;
+/// Perceptual hashing of video frames
+mod phash;
 mod prelude;
+/// Storyboards, waveforms and animations to triage clips by
+mod preview;
+/// Layouts for short-form video
+mod render;
 mod service;
+/// Where downloaded clips and derived files are kept
+mod storage;
+/// Thumbnails composited from frames of clips
+mod thumbnail;
 
-use crate::prelude::*;
+use crate::{metrics::Metrics, prelude::*};
 use rpc::worker_server::WorkerServer;
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tonic::transport::Server;
 
 mod rpc {
     tonic::include_proto!("worker");
+
+    /// Served by the reflection service so that grpcurl and similar tools
+    /// can call the worker without the proto files.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("worker_descriptor");
 }
 
 struct RpcWorker {
@@ -25,16 +55,54 @@ async fn main() -> AnyResult<()> {
     info!("worker starting");
 
     let conf = Conf::from_env()?;
+    let db = Arc::new(Mutex::new(db::open(&conf.sqlite_db_path)?));
+    let store = storage::open(&conf)?;
 
     let g = AppState {
         conf: Arc::new(conf),
+        db,
+        metrics: Arc::new(Metrics::new()?),
+        store,
     };
 
+    if let Some(addr) = g.conf.metrics_addr {
+        let g = g.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(g, addr).await {
+                error!("Metrics server failed: {e}");
+            }
+        });
+    }
+
+    let (mut health_reporter, health_service) =
+        tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<WorkerServer<RpcWorker>>()
+        .await;
+
+    let reflection_service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(rpc::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(
+            tonic_health::pb::FILE_DESCRIPTOR_SET,
+        )
+        .build()?;
+
+    let mut builder = Server::builder();
+    if let Some(tls) = &g.conf.rpc_tls {
+        builder = builder.tls_config(tls.server_config()?)?;
+    }
+
     let addr = g.conf.rpc_addr;
+    let require_token = auth::RequireToken::new(g.conf.rpc_token.as_deref());
     let server = RpcWorker { g };
 
-    Server::builder()
-        .add_service(WorkerServer::new(server))
+    // health and reflection stay open so that load balancers and tooling
+    // do not need the token
+    info!("Serving RPC on {addr}");
+    builder
+        .add_service(health_service)
+        .add_service(reflection_service)
+        .add_service(WorkerServer::with_interceptor(server, require_token))
         .serve(addr)
         .await?;
 
@@ -0,0 +1,419 @@
+use crate::{prelude::*, render};
+use anyhow::Context;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+/// Clip ids are assigned by Twitch and only contain alphanumeric characters
+/// and dashes.
+/// Anything else is rejected before it becomes part of a media key.
+pub fn clip_key(clip_id: &str) -> Result<String> {
+    let is_valid = !clip_id.is_empty()
+        && clip_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        return Err(AppError::bad_request(format!(
+            "Invalid clip id '{clip_id}'"
+        )));
+    }
+
+    Ok(format!("clips/{clip_id}.mp4"))
+}
+
+/// 64bit FNV-1a over what a media key is derived from.
+///
+/// Keys outlive the worker binary, the hash must not change between builds
+/// or Rust versions like `DefaultHasher` may, so values are written as
+/// little endian bytes and strings are prefixed with their length.
+pub struct KeyHasher(u64);
+
+impl KeyHasher {
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, v: u64) {
+        self.write(&v.to_le_bytes());
+    }
+
+    /// Floats are never NaN where keys are derived, their bits are written.
+    pub fn write_f64(&mut self, v: f64) {
+        self.write_u64(v.to_bits());
+    }
+
+    /// Tells `None` apart from any value.
+    pub fn write_opt_f64(&mut self, v: Option<f64>) {
+        match v {
+            Some(v) => {
+                self.write(&[1]);
+                self.write_f64(v);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_u64(s.len() as u64);
+        self.write(s.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Reference tracks are read from http(s) urls only.
+/// ffmpeg would read anything else too, such as local files or other
+/// protocols like `concat:` or `subfile:`, and anyone who can add tracks is
+/// not to read the worker's files.
+pub fn validate_reference_track_url(source: &str) -> Result<()> {
+    let is_http = reqwest::Url::parse(source)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !is_http {
+        return Err(AppError::bad_request(format!(
+            "Reference track source '{source}' must be an http(s) url"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Unique path in the media dir for a file which is yet to be put to the
+/// media store.
+pub async fn scratch_path(conf: &Conf, extension: &str) -> Result<PathBuf> {
+    let dir = conf.media_dir.join("tmp");
+    fs::create_dir_all(&dir)
+        .await
+        .context("Cannot create scratch dir")?;
+
+    Ok(dir.join(format!("{:016x}.{extension}", rand::random::<u64>())))
+}
+
+/// Streams the file at given url to given path.
+/// Returns the size of the file in bytes.
+pub async fn download(url: &str, path: &Path) -> Result<u64> {
+    let mut resp = reqwest::get(url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| {
+            AppError::bad_request(format!("Cannot download {url}: {e}"))
+        })?;
+
+    let mut file = fs::File::create(path).await?;
+    let mut written = 0;
+    while let Some(chunk) = resp.chunk().await.context("Cannot read body")? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    Ok(written)
+}
+
+/// Total size of files in given dir and its subdirs.
+/// A dir which does not exist yet is empty.
+pub async fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+/// Decodes the audio of anything ffmpeg can read, local file or url, into
+/// mono 32bit float samples at given rate.
+pub async fn decode_mono_audio(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    sample_rate: u32,
+) -> Result<Vec<f32>> {
+    decode_audio(conf, input, &render::Fragment::default(), 1, sample_rate)
+        .await
+}
+
+/// Like [`decode_mono_audio`] for given fragment of the input with given
+/// number of channels, the samples of which are interleaved.
+pub async fn decode_audio(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    fragment: &render::Fragment,
+    channels: usize,
+    sample_rate: u32,
+) -> Result<Vec<f32>> {
+    let input = input.as_ref();
+    let mut command = Command::new(&conf.ffmpeg_bin);
+    command.args(["-v", "error"]);
+    if let Some(start_secs) = fragment.start_secs {
+        command.args(["-ss", &start_secs.to_string()]);
+    }
+    if let Some(end_secs) = fragment.end_secs {
+        command.args(["-to", &end_secs.to_string()]);
+    }
+    let output = command
+        .arg("-i")
+        .arg(input)
+        .args(["-vn", "-ac", &channels.to_string()])
+        .args(["-ar", &sample_rate.to_string()])
+        .args(["-f", "f32le", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+/// Decodes one frame for every second of the video, scales it down to
+/// given dimensions and returns it as raw 8bit grayscale pixels.
+/// Input is anything ffmpeg can read, local file or url.
+pub async fn gray_frame_per_second(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    width: usize,
+    height: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let input = input.as_ref();
+    let output = Command::new(&conf.ffmpeg_bin)
+        .args(["-v", "error", "-i"])
+        .arg(input)
+        .args([
+            "-vf",
+            &format!("fps=1,scale={width}:{height},format=gray"),
+            "-f",
+            "rawvideo",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(width * height)
+        .map(<[u8]>::to_vec)
+        .collect())
+}
+
+/// Re-encodes given fragment of the video with given filter graph, which
+/// must label its output `[v]`, into an mp4 at given path.
+/// Audio is copied over as AAC if the input has any, through given audio
+/// filter.
+pub async fn render_video(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    output: &Path,
+    fragment: &render::Fragment,
+    filter_graph: &str,
+    audio_filter: Option<&str>,
+) -> Result<()> {
+    let input = input.as_ref();
+    let mut command = Command::new(&conf.ffmpeg_bin);
+    command.args(["-v", "error", "-y"]);
+    if let Some(start_secs) = fragment.start_secs {
+        command.args(["-ss", &start_secs.to_string()]);
+    }
+    if let Some(end_secs) = fragment.end_secs {
+        command.args(["-to", &end_secs.to_string()]);
+    }
+    let output = command
+        .arg("-i")
+        .arg(input)
+        .args(["-filter_complex", filter_graph])
+        .args(["-map", "[v]", "-map", "0:a?"])
+        .args(
+            audio_filter
+                .map(|filter| ["-af", filter])
+                .into_iter()
+                .flatten(),
+        )
+        .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "20"])
+        .args(["-pix_fmt", "yuv420p", "-c:a", "aac", "-b:a", "160k"])
+        // so that players can start before the whole file is downloaded
+        .args(["-movflags", "+faststart"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Decodes the frame at given time of the video into an image at given
+/// path, whose extension picks the format.
+pub async fn extract_frame(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    at_secs: f64,
+    output: &Path,
+) -> Result<()> {
+    let input = input.as_ref();
+    let output = Command::new(&conf.ffmpeg_bin)
+        .args(["-v", "error", "-y", "-ss", &at_secs.to_string(), "-i"])
+        .arg(input)
+        .args(["-frames:v", "1", "-q:v", "2"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Composites the frame at given time of the video with given images into
+/// an image at given path.
+/// The filter graph gets the video as the first input followed by the
+/// images and must label its output `[v]`.
+pub async fn composite_image(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    at_secs: f64,
+    images: &[&str],
+    filter_graph: &str,
+    output: &Path,
+) -> Result<()> {
+    let input = input.as_ref();
+    let mut command = Command::new(&conf.ffmpeg_bin);
+    command
+        .args(["-v", "error", "-y", "-ss", &at_secs.to_string(), "-i"])
+        .arg(input);
+    for image in images {
+        command.args(["-i", image]);
+    }
+    let output = command
+        .args(["-filter_complex", filter_graph])
+        .args(["-map", "[v]", "-frames:v", "1"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Runs given filter graph, which must label its output `[v]`, over given
+/// fragment of the video and writes it to given path, whose extension picks
+/// the format unless the output args say otherwise.
+pub async fn filter_to_file(
+    conf: &Conf,
+    input: impl AsRef<OsStr>,
+    fragment: &render::Fragment,
+    filter_graph: &str,
+    output_args: &[&str],
+    output: &Path,
+) -> Result<()> {
+    let input = input.as_ref();
+    let mut command = Command::new(&conf.ffmpeg_bin);
+    command.args(["-v", "error", "-y"]);
+    if let Some(start_secs) = fragment.start_secs {
+        command.args(["-ss", &start_secs.to_string()]);
+    }
+    if let Some(end_secs) = fragment.end_secs {
+        command.args(["-to", &end_secs.to_string()]);
+    }
+    let output = command
+        .arg("-i")
+        .arg(input)
+        .args(["-filter_complex", filter_graph, "-map", "[v]"])
+        .args(output_args)
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Cannot run ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(AppError::internal(format!(
+            "ffmpeg failed on {input:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_reference_tracks_from_http_only() {
+        assert!(validate_reference_track_url("https://a/song.mp3").is_ok());
+        assert!(validate_reference_track_url("http://a/song.mp3").is_ok());
+        for source in [
+            "/etc/passwd",
+            "file:///etc/passwd",
+            "concat:/etc/passwd|/etc/hosts",
+            "subfile,,start,0,end,0,,:/etc/passwd",
+            "ftp://a/song.mp3",
+            "",
+        ] {
+            assert!(validate_reference_track_url(source).is_err(), "{source}");
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Prometheus metrics of the worker, scraped over plain HTTP from
+//! `METRICS_ADDR`.
+
+use crate::{media, prelude::*};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use std::{
+    convert::Infallible, future::Future, net::SocketAddr, time::Instant,
+};
+use tonic::Status;
+
+/// Media jobs take from milliseconds (hashing a cached clip's frames) to
+/// minutes (fingerprinting a long reference track).
+const RPC_DURATION_BUCKETS: [f64; 11] =
+    [0.01, 0.05, 0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+pub struct Metrics {
+    registry: Registry,
+    rpc_requests: IntCounterVec,
+    rpc_duration: HistogramVec,
+    jobs_in_flight: IntGauge,
+    pub bytes_downloaded: IntCounter,
+    pub gc_freed_bytes: IntCounter,
+    media_dir_bytes: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> AnyResult<Self> {
+        let registry = Registry::new_custom(Some("worker".to_string()), None)?;
+
+        let rpc_requests = IntCounterVec::new(
+            Opts::new("rpc_requests_total", "Finished RPCs by method and code"),
+            &["method", "code"],
+        )?;
+        let rpc_duration = HistogramVec::new(
+            HistogramOpts::new("rpc_duration_seconds", "RPC latency by method")
+                .buckets(RPC_DURATION_BUCKETS.to_vec()),
+            &["method"],
+        )?;
+        let jobs_in_flight =
+            IntGauge::new("jobs_in_flight", "RPCs currently being handled")?;
+        let bytes_downloaded = IntCounter::new(
+            "bytes_downloaded_total",
+            "Bytes of clips downloaded, cached clips are not counted",
+        )?;
+        let gc_freed_bytes = IntCounter::new(
+            "gc_freed_bytes_total",
+            "Bytes of artifacts deleted by garbage collection",
+        )?;
+        let media_dir_bytes = IntGauge::new(
+            "media_dir_bytes",
+            "Size of all files in the media dir at the time of scraping",
+        )?;
+
+        registry.register(Box::new(rpc_requests.clone()))?;
+        registry.register(Box::new(rpc_duration.clone()))?;
+        registry.register(Box::new(jobs_in_flight.clone()))?;
+        registry.register(Box::new(bytes_downloaded.clone()))?;
+        registry.register(Box::new(gc_freed_bytes.clone()))?;
+        registry.register(Box::new(media_dir_bytes.clone()))?;
+
+        Ok(Self {
+            registry,
+            rpc_requests,
+            rpc_duration,
+            jobs_in_flight,
+            bytes_downloaded,
+            gc_freed_bytes,
+            media_dir_bytes,
+        })
+    }
+
+    /// Counts the RPC by its outcome and observes how long it took.
+    pub async fn track<T>(
+        &self,
+        method: &'static str,
+        rpc: impl Future<Output = StdResult<T, Status>>,
+    ) -> StdResult<T, Status> {
+        // decremented on drop as the client can cancel the RPC mid-flight
+        struct InFlight<'a>(&'a IntGauge);
+        impl Drop for InFlight<'_> {
+            fn drop(&mut self) {
+                self.0.dec();
+            }
+        }
+
+        self.jobs_in_flight.inc();
+        let _in_flight = InFlight(&self.jobs_in_flight);
+        let started_at = Instant::now();
+
+        let res = rpc.await;
+
+        self.rpc_duration
+            .with_label_values(&[method])
+            .observe(started_at.elapsed().as_secs_f64());
+        let code = match &res {
+            Ok(_) => tonic::Code::Ok,
+            Err(status) => status.code(),
+        };
+        self.rpc_requests
+            .with_label_values(&[method, &format!("{code:?}")])
+            .inc();
+
+        res
+    }
+
+    /// RPCs currently being handled.
+    pub fn jobs_in_flight(&self) -> u64 {
+        self.jobs_in_flight.get().max(0) as u64
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub async fn encode(&self, conf: &Conf) -> AnyResult<String> {
+        self.media_dir_bytes
+            .set(media::dir_size(&conf.media_dir).await? as i64);
+
+        let mut buf = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+
+        Ok(String::from_utf8(buf)?)
+    }
+}
+
+/// Serves `GET /metrics` until the process exits.
+pub async fn serve(g: AppState, addr: SocketAddr) -> AnyResult<()> {
+    let make_svc = make_service_fn(move |_| {
+        let g = g.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let g = g.clone();
+                async move { Ok::<_, Infallible>(respond(&g, req).await) }
+            }))
+        }
+    });
+
+    info!("Serving metrics on {addr}");
+    hyper::Server::try_bind(&addr)?.serve(make_svc).await?;
+
+    Ok(())
+}
+
+async fn respond(g: &AppState, req: Request<Body>) -> Response<Body> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return status(StatusCode::NOT_FOUND, "Not found");
+    }
+
+    match g.metrics.encode(&g.conf).await {
+        Ok(body) => Response::builder()
+            .header(
+                hyper::header::CONTENT_TYPE,
+                TextEncoder::new().format_type(),
+            )
+            .body(Body::from(body))
+            .expect("Valid response"),
+        Err(e) => {
+            error!("Cannot encode metrics: {e}");
+            status(StatusCode::INTERNAL_SERVER_ERROR, "Cannot encode metrics")
+        }
+    }
+}
+
+fn status(code: StatusCode, body: &'static str) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::from(body))
+        .expect("Valid response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_tracks_rpcs() -> AnyResult<()> {
+        let metrics = Metrics::new()?;
+
+        metrics.track("DownloadClip", async { Ok(()) }).await?;
+        metrics
+            .track("DownloadClip", async {
+                Err::<(), _>(Status::not_found("Clip not found"))
+            })
+            .await
+            .unwrap_err();
+        metrics.bytes_downloaded.inc_by(42);
+
+        let mut buf = vec![];
+        TextEncoder::new().encode(&metrics.registry.gather(), &mut buf)?;
+        let text = String::from_utf8(buf)?;
+
+        assert!(text.contains(
+            r#"worker_rpc_requests_total{code="Ok",method="DownloadClip"} 1"#
+        ));
+        assert!(text.contains(
+            r#"worker_rpc_requests_total{code="NotFound",method="DownloadClip"} 1"#
+        ));
+        assert!(text.contains(
+            r#"worker_rpc_duration_seconds_count{method="DownloadClip"} 2"#
+        ));
+        assert!(text.contains("worker_jobs_in_flight 0"));
+        assert!(text.contains("worker_bytes_downloaded_total 42"));
+
+        Ok(())
+    }
+}
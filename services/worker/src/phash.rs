@@ -0,0 +1,47 @@
+/// Frames must be scaled to this width before hashing.
+/// It's one more than the number of bits per row because each bit compares
+/// two neighbouring pixels.
+pub const FRAME_WIDTH: usize = 9;
+/// Frames must be scaled to this height before hashing.
+pub const FRAME_HEIGHT: usize = 8;
+
+/// Difference hash of a grayscale frame of [`FRAME_WIDTH`] x
+/// [`FRAME_HEIGHT`] pixels.
+///
+/// A bit is set if a pixel is brighter than its right neighbour.
+/// Similar frames have hashes with small hamming distance, which survives
+/// re-encoding, scaling and small shifts in brightness.
+pub fn dhash(frame: &[u8]) -> u64 {
+    debug_assert_eq!(frame.len(), FRAME_WIDTH * FRAME_HEIGHT);
+
+    frame
+        .chunks_exact(FRAME_WIDTH)
+        .flat_map(|row| row.windows(2))
+        .fold(0, |hash, pair| (hash << 1) | u64::from(pair[0] > pair[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+        (0..FRAME_HEIGHT)
+            .flat_map(|y| (0..FRAME_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn it_hashes_gradients() {
+        assert_eq!(dhash(&frame(|x, _| x as u8 * 10)), 0);
+        assert_eq!(dhash(&frame(|x, _| 255 - x as u8 * 10)), u64::MAX);
+    }
+
+    #[test]
+    fn it_is_stable_under_brightness_shift() {
+        let original = frame(|x, y| ((x * 31 + y * 17) % 200) as u8);
+        let brighter = frame(|x, y| ((x * 31 + y * 17) % 200) as u8 + 40);
+
+        assert_eq!(dhash(&original), dhash(&brighter));
+    }
+}
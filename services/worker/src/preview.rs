@@ -0,0 +1,137 @@
+use crate::{media, prelude::*, render::Fragment};
+
+/// Frames of the storyboard, laid out in a grid.
+const STORYBOARD_COLUMNS: u32 = 5;
+const STORYBOARD_ROWS: u32 = 2;
+const STORYBOARD_FRAME_WIDTH: u32 = 192;
+const STORYBOARD_FRAME_HEIGHT: u32 = 108;
+/// As wide as the storyboard.
+const WAVEFORM_WIDTH: u32 = STORYBOARD_COLUMNS * STORYBOARD_FRAME_WIDTH;
+const WAVEFORM_HEIGHT: u32 = 120;
+/// The animation loops this much of the middle of the clip.
+const ANIMATION_SECS: f64 = 4.0;
+const ANIMATION_FPS: u32 = 8;
+const ANIMATION_WIDTH: u32 = 320;
+/// Twitch clips are at most a minute long, longer durations are mistakes.
+const MAX_DURATION_SECS: f64 = 60.0 * 60.0;
+
+/// Keys of the previews of a clip, which are regenerated only if missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keys {
+    pub storyboard: String,
+    pub waveform: String,
+    pub animation: String,
+}
+
+impl Keys {
+    pub fn new(clip_id: &str) -> Result<Self> {
+        // validates the clip id
+        media::clip_key(clip_id)?;
+
+        Ok(Self {
+            storyboard: format!("previews/{clip_id}/storyboard.jpg"),
+            waveform: format!("previews/{clip_id}/waveform.png"),
+            animation: format!("previews/{clip_id}/animation.webp"),
+        })
+    }
+}
+
+pub fn validate_duration(duration_secs: f64) -> Result<()> {
+    if duration_secs.is_finite()
+        && duration_secs > 0.0
+        && duration_secs <= MAX_DURATION_SECS
+    {
+        Ok(())
+    } else {
+        Err(AppError::bad_request(format!(
+            "Duration must be between 0 and {MAX_DURATION_SECS} seconds"
+        )))
+    }
+}
+
+/// Frames spread evenly over the clip, tiled into a single image.
+pub fn storyboard_filter_graph(duration_secs: f64) -> String {
+    let frames = STORYBOARD_COLUMNS * STORYBOARD_ROWS;
+    // sampled slightly more often so that rounding never leaves a tile
+    // empty, the tile filter drops what doesn't fit
+    let fps = f64::from(frames) / duration_secs * 1.05;
+
+    format!(
+        "[0:v]fps={fps:.6},\
+        scale={STORYBOARD_FRAME_WIDTH}:{STORYBOARD_FRAME_HEIGHT}:\
+        force_original_aspect_ratio=decrease,\
+        pad={STORYBOARD_FRAME_WIDTH}:{STORYBOARD_FRAME_HEIGHT}:\
+        (ow-iw)/2:(oh-ih)/2,\
+        tile={STORYBOARD_COLUMNS}x{STORYBOARD_ROWS}[v]"
+    )
+}
+
+pub fn waveform_filter_graph() -> String {
+    format!(
+        "[0:a]aformat=channel_layouts=mono,\
+        showwavespic=s={WAVEFORM_WIDTH}x{WAVEFORM_HEIGHT}:colors=0x4a90d9[v]"
+    )
+}
+
+pub fn animation_filter_graph() -> String {
+    format!("[0:v]fps={ANIMATION_FPS},scale={ANIMATION_WIDTH}:-2[v]")
+}
+
+/// The middle of the clip, or all of it if it's short.
+pub fn animation_fragment(duration_secs: f64) -> Fragment {
+    let start_secs = ((duration_secs - ANIMATION_SECS) / 2.0).max(0.0);
+
+    Fragment {
+        start_secs: Some(start_secs),
+        end_secs: Some((start_secs + ANIMATION_SECS).min(duration_secs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keys_previews_by_clip() -> Result<()> {
+        let keys = Keys::new("AbcDef")?;
+        assert_eq!(keys.storyboard, "previews/AbcDef/storyboard.jpg");
+        assert_eq!(keys.waveform, "previews/AbcDef/waveform.png");
+        assert_eq!(keys.animation, "previews/AbcDef/animation.webp");
+        assert!(Keys::new("../etc").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_builds_filter_graphs() {
+        assert_eq!(
+            storyboard_filter_graph(30.0),
+            "[0:v]fps=0.350000,scale=192:108:force_original_aspect_ratio=\
+            decrease,pad=192:108:(ow-iw)/2:(oh-ih)/2,tile=5x2[v]"
+        );
+        assert!(waveform_filter_graph().contains("showwavespic=s=960x120"));
+        assert_eq!(animation_filter_graph(), "[0:v]fps=8,scale=320:-2[v]");
+
+        assert!(validate_duration(30.0).is_ok());
+        assert!(validate_duration(0.0).is_err());
+        assert!(validate_duration(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn it_animates_the_middle_of_clips() {
+        assert_eq!(
+            animation_fragment(30.0),
+            Fragment {
+                start_secs: Some(13.0),
+                end_secs: Some(17.0),
+            }
+        );
+        assert_eq!(
+            animation_fragment(2.5),
+            Fragment {
+                start_secs: Some(0.0),
+                end_secs: Some(2.5),
+            }
+        );
+    }
+}
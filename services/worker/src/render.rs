@@ -0,0 +1,460 @@
+use crate::{media, prelude::*, rpc};
+
+/// Full HD in portrait, as Shorts and TikTok play it.
+pub const VERTICAL_WIDTH: u32 = 1080;
+pub const VERTICAL_HEIGHT: u32 = 1920;
+/// The facecam gets the top third of the split layout, the gameplay the
+/// rest.
+const FACECAM_HEIGHT: u32 = 640;
+/// Rectangles may stick out of the frame by a rounding error.
+const RECT_EPSILON: f64 = 1e-6;
+/// Anything louder is a mistake which would destroy the audio.
+const MAX_AUDIO_GAIN_DB: f64 = 60.0;
+
+/// How a landscape stream is arranged in a portrait video.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerticalLayout {
+    CenterCrop,
+    BlurredFit,
+    Split {
+        facecam: CropRect,
+        gameplay: CropRect,
+    },
+}
+
+/// Part of the source frame, all values are fractions of its width or
+/// height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Part of a clip to render, the whole clip by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Fragment {
+    pub start_secs: Option<f64>,
+    pub end_secs: Option<f64>,
+}
+
+impl TryFrom<Option<rpc::VerticalLayout>> for VerticalLayout {
+    type Error = AppError;
+
+    fn try_from(layout: Option<rpc::VerticalLayout>) -> Result<Self> {
+        use rpc::vertical_layout::Layout;
+
+        match layout.and_then(|layout| layout.layout) {
+            Some(Layout::CenterCrop(_)) => Ok(Self::CenterCrop),
+            Some(Layout::BlurredFit(_)) => Ok(Self::BlurredFit),
+            Some(Layout::Split(rpc::SplitLayout { facecam, gameplay })) => {
+                Ok(Self::Split {
+                    facecam: CropRect::try_from_rpc("Facecam", facecam)?,
+                    gameplay: CropRect::try_from_rpc("Gameplay", gameplay)?,
+                })
+            }
+            None => Err(AppError::bad_request("Layout must be set")),
+        }
+    }
+}
+
+impl CropRect {
+    fn try_from_rpc(name: &str, rect: Option<rpc::CropRect>) -> Result<Self> {
+        let rpc::CropRect {
+            x,
+            y,
+            width,
+            height,
+        } = rect.ok_or_else(|| {
+            AppError::bad_request(format!("{name} rectangle must be set"))
+        })?;
+
+        let is_valid = [x, y, width, height].iter().all(|v| v.is_finite())
+            && x >= 0.0
+            && y >= 0.0
+            && width > 0.0
+            && height > 0.0
+            && x + width <= 1.0 + RECT_EPSILON
+            && y + height <= 1.0 + RECT_EPSILON;
+        if !is_valid {
+            return Err(AppError::bad_request(format!(
+                "{name} rectangle must be inside the frame, \
+                got {width}x{height} at {x},{y}"
+            )));
+        }
+
+        Ok(Self {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Crops the rectangle and scales it to fill given size, cutting off
+    /// whatever doesn't match the aspect ratio.
+    fn fill(&self, width: u32, height: u32) -> String {
+        let Self {
+            x,
+            y,
+            width: w,
+            height: h,
+        } = self;
+        format!(
+            "crop=w=iw*{w:.4}:h=ih*{h:.4}:x=iw*{x:.4}:y=ih*{y:.4},\
+            scale={width}:{height}:force_original_aspect_ratio=increase,\
+            crop={width}:{height},setsar=1"
+        )
+    }
+}
+
+impl Fragment {
+    pub fn new(start_secs: Option<f64>, end_secs: Option<f64>) -> Result<Self> {
+        let is_valid = start_secs.is_none_or(|s| s.is_finite() && s >= 0.0)
+            && end_secs.is_none_or(|e| e.is_finite() && e > 0.0)
+            && match (start_secs, end_secs) {
+                (Some(start), Some(end)) => start < end,
+                _ => true,
+            };
+        if !is_valid {
+            return Err(AppError::bad_request(format!(
+                "Invalid fragment from {start_secs:?} to {end_secs:?}"
+            )));
+        }
+
+        Ok(Self {
+            start_secs,
+            end_secs,
+        })
+    }
+}
+
+/// Parts of the clip whose audio is silenced, in seconds from the start of
+/// the clip.
+pub fn mute_ranges(ranges: Vec<rpc::TimeRange>) -> Result<Vec<(f64, f64)>> {
+    ranges
+        .into_iter()
+        .map(
+            |rpc::TimeRange {
+                 start_secs,
+                 end_secs,
+             }| {
+                let is_valid = start_secs.is_finite()
+                    && end_secs.is_finite()
+                    && start_secs >= 0.0
+                    && start_secs < end_secs;
+                if !is_valid {
+                    return Err(AppError::bad_request(format!(
+                        "Invalid mute range from {start_secs} to {end_secs}"
+                    )));
+                }
+
+                Ok((start_secs, end_secs))
+            },
+        )
+        .collect()
+}
+
+/// Filter chain for ffmpeg's `-af`, if the audio is changed at all.
+///
+/// The fragment is seeked to, so the rendered audio starts at its start and
+/// the mute ranges are moved to match.
+pub fn audio_filter(
+    fragment: &Fragment,
+    audio_gain_db: Option<f64>,
+    mute_ranges: &[(f64, f64)],
+) -> Option<String> {
+    let offset = fragment.start_secs.unwrap_or(0.0);
+    let filters: Vec<_> = audio_gain_db
+        .map(|gain| format!("volume={gain:.2}dB"))
+        .into_iter()
+        .chain(mute_ranges.iter().map(|(start, end)| {
+            format!(
+                "volume=0:enable='between(t,{:.3},{:.3})'",
+                start - offset,
+                end - offset
+            )
+        }))
+        .collect();
+
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+/// Gain to normalize the loudness of the audio with, if any.
+pub fn audio_gain(gain_db: Option<f64>) -> Result<Option<f64>> {
+    match gain_db {
+        Some(gain) if !gain.is_finite() || gain.abs() > MAX_AUDIO_GAIN_DB => {
+            Err(AppError::bad_request(format!(
+                "Audio gain must be within {MAX_AUDIO_GAIN_DB}dB, got {gain}dB"
+            )))
+        }
+        gain_db => Ok(gain_db),
+    }
+}
+
+/// Filter graph for ffmpeg's `-filter_complex` which turns the first video
+/// stream into a [`VERTICAL_WIDTH`] x [`VERTICAL_HEIGHT`] stream labelled
+/// `[v]`.
+pub fn vertical_filter_graph(layout: &VerticalLayout) -> String {
+    let (w, h) = (VERTICAL_WIDTH, VERTICAL_HEIGHT);
+    match layout {
+        VerticalLayout::CenterCrop => format!(
+            "[0:v]crop='min(iw,ih*9/16)':'min(ih,iw*16/9)',\
+            scale={w}:{h},setsar=1[v]"
+        ),
+        // the background is blurred at a quarter of the size, blurring
+        // full HD is several times slower and looks the same
+        VerticalLayout::BlurredFit => format!(
+            "[0:v]split[bg_in][fg_in];\
+            [bg_in]scale={}:{}:force_original_aspect_ratio=increase,\
+            crop={}:{},boxblur=10:2,scale={w}:{h}[bg];\
+            [fg_in]scale={w}:{h}:force_original_aspect_ratio=decrease:\
+            force_divisible_by=2[fg];\
+            [bg][fg]overlay=(W-w)/2:(H-h)/2,setsar=1[v]",
+            w / 4,
+            h / 4,
+            w / 4,
+            h / 4,
+        ),
+        VerticalLayout::Split { facecam, gameplay } => format!(
+            "[0:v]split[cam_in][game_in];\
+            [cam_in]{}[cam];\
+            [game_in]{}[game];\
+            [cam][game]vstack[v]",
+            facecam.fill(w, FACECAM_HEIGHT),
+            gameplay.fill(w, h - FACECAM_HEIGHT),
+        ),
+    }
+}
+
+/// Where the render is stored.
+/// The same clip, fragment, layout and audio always map to the same key so
+/// that the render can be reused.
+pub fn vertical_key(
+    clip_id: &str,
+    fragment: &Fragment,
+    layout: &VerticalLayout,
+    audio_gain_db: Option<f64>,
+    mute_ranges: &[(f64, f64)],
+) -> Result<String> {
+    // validates the clip id
+    media::clip_key(clip_id)?;
+
+    let mut hasher = media::KeyHasher::new();
+    for v in [fragment.start_secs, fragment.end_secs, audio_gain_db] {
+        hasher.write_opt_f64(v);
+    }
+    hasher.write_u64(mute_ranges.len() as u64);
+    for &(start, end) in mute_ranges {
+        hasher.write_f64(start);
+        hasher.write_f64(end);
+    }
+    match layout {
+        VerticalLayout::CenterCrop => hasher.write_str("center_crop"),
+        VerticalLayout::BlurredFit => hasher.write_str("blurred_fit"),
+        VerticalLayout::Split { facecam, gameplay } => {
+            hasher.write_str("split");
+            for rect in [facecam, gameplay] {
+                for v in [rect.x, rect.y, rect.width, rect.height] {
+                    hasher.write_f64(v);
+                }
+            }
+        }
+    }
+
+    Ok(format!(
+        "renders/{clip_id}/vertical-{:016x}.mp4",
+        hasher.finish()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> rpc::CropRect {
+        rpc::CropRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn split(
+        facecam: rpc::CropRect,
+        gameplay: rpc::CropRect,
+    ) -> Option<rpc::VerticalLayout> {
+        Some(rpc::VerticalLayout {
+            layout: Some(rpc::vertical_layout::Layout::Split(
+                rpc::SplitLayout {
+                    facecam: Some(facecam),
+                    gameplay: Some(gameplay),
+                },
+            )),
+        })
+    }
+
+    #[test]
+    fn it_validates_layouts() -> Result<()> {
+        let layout = VerticalLayout::try_from(split(
+            rect(0.75, 0.0, 0.25, 0.25),
+            rect(0.0, 0.0, 1.0, 1.0),
+        ))?;
+        assert_eq!(
+            layout,
+            VerticalLayout::Split {
+                facecam: CropRect {
+                    x: 0.75,
+                    y: 0.0,
+                    width: 0.25,
+                    height: 0.25,
+                },
+                gameplay: CropRect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 1.0,
+                    height: 1.0,
+                },
+            }
+        );
+
+        for facecam in [
+            rect(0.8, 0.0, 0.25, 0.25),
+            rect(-0.1, 0.0, 0.25, 0.25),
+            rect(0.0, 0.0, 0.0, 0.25),
+            rect(0.0, 0.0, f64::NAN, 0.25),
+        ] {
+            let layout = split(facecam, rect(0.0, 0.0, 1.0, 1.0));
+            assert!(VerticalLayout::try_from(layout).is_err());
+        }
+        assert!(VerticalLayout::try_from(None).is_err());
+
+        assert!(Fragment::new(Some(5.0), Some(15.0)).is_ok());
+        assert!(Fragment::new(None, None).is_ok());
+        assert!(Fragment::new(Some(15.0), Some(5.0)).is_err());
+        assert!(Fragment::new(Some(-1.0), None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_builds_filter_graphs() {
+        let graph = vertical_filter_graph(&VerticalLayout::CenterCrop);
+        assert_eq!(
+            graph,
+            "[0:v]crop='min(iw,ih*9/16)':'min(ih,iw*16/9)',\
+            scale=1080:1920,setsar=1[v]"
+        );
+
+        let graph = vertical_filter_graph(&VerticalLayout::BlurredFit);
+        assert!(graph.starts_with("[0:v]split[bg_in][fg_in];"));
+        assert!(graph.ends_with("overlay=(W-w)/2:(H-h)/2,setsar=1[v]"));
+
+        let graph = vertical_filter_graph(&VerticalLayout::Split {
+            facecam: CropRect {
+                x: 0.75,
+                y: 0.05,
+                width: 0.2,
+                height: 0.3,
+            },
+            gameplay: CropRect {
+                x: 0.1,
+                y: 0.0,
+                width: 0.8,
+                height: 1.0,
+            },
+        });
+        assert_eq!(
+            graph,
+            "[0:v]split[cam_in][game_in];\
+            [cam_in]crop=w=iw*0.2000:h=ih*0.3000:x=iw*0.7500:y=ih*0.0500,\
+            scale=1080:640:force_original_aspect_ratio=increase,\
+            crop=1080:640,setsar=1[cam];\
+            [game_in]crop=w=iw*0.8000:h=ih*1.0000:x=iw*0.1000:y=ih*0.0000,\
+            scale=1080:1280:force_original_aspect_ratio=increase,\
+            crop=1080:1280,setsar=1[game];\
+            [cam][game]vstack[v]"
+        );
+    }
+
+    #[test]
+    fn it_mutes_ranges_of_the_fragment() -> Result<()> {
+        let ranges = mute_ranges(vec![rpc::TimeRange {
+            start_secs: 4.0,
+            end_secs: 9.5,
+        }])?;
+        assert_eq!(ranges, vec![(4.0, 9.5)]);
+        for (start_secs, end_secs) in [(5.0, 5.0), (-1.0, 2.0), (0.0, f64::NAN)]
+        {
+            let range = rpc::TimeRange {
+                start_secs,
+                end_secs,
+            };
+            assert!(mute_ranges(vec![range]).is_err());
+        }
+
+        assert_eq!(audio_filter(&Fragment::default(), None, &[]), None);
+        assert_eq!(
+            audio_filter(&Fragment::default(), Some(-3.5), &ranges),
+            Some(
+                "volume=-3.50dB,volume=0:enable='between(t,4.000,9.500)'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            audio_filter(&Fragment::new(Some(2.0), None)?, None, &ranges),
+            Some("volume=0:enable='between(t,2.000,7.500)'".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keys_renders_by_fragment_layout_and_gain() -> Result<()> {
+        let whole = Fragment::default();
+        let part = Fragment::new(Some(2.0), Some(12.0))?;
+        let key = |clip_id, fragment, layout, gain| {
+            vertical_key(clip_id, fragment, layout, gain, &[])
+        };
+
+        // keys must stay the same across builds for renders to be reused
+        let center = key("Clip-1", &whole, &VerticalLayout::CenterCrop, None)?;
+        assert_eq!(center, "renders/Clip-1/vertical-2619f815a9c235b0.mp4");
+
+        assert_eq!(
+            center,
+            key("Clip-1", &whole, &VerticalLayout::CenterCrop, None)?
+        );
+        assert_ne!(
+            center,
+            key("Clip-1", &part, &VerticalLayout::CenterCrop, None)?
+        );
+        assert_ne!(
+            center,
+            key("Clip-1", &whole, &VerticalLayout::BlurredFit, None)?
+        );
+        assert_ne!(
+            center,
+            key("Clip-1", &whole, &VerticalLayout::CenterCrop, Some(-3.5))?
+        );
+        assert!(key("../x", &whole, &VerticalLayout::BlurredFit, None).is_err());
+        assert_ne!(
+            center,
+            vertical_key(
+                "Clip-1",
+                &whole,
+                &VerticalLayout::CenterCrop,
+                None,
+                &[(1.0, 3.0)]
+            )?
+        );
+
+        assert_eq!(audio_gain(Some(-3.5))?, Some(-3.5));
+        assert!(audio_gain(Some(f64::INFINITY)).is_err());
+        assert!(audio_gain(Some(100.0)).is_err());
+
+        Ok(())
+    }
+}
@@ -1,17 +1,938 @@
-use crate::{prelude::*, rpc, RpcWorker};
+use crate::{
+    fingerprint, gc, loudness, media, phash, prelude::*, preview, render, rpc,
+    thumbnail, RpcWorker,
+};
+use anyhow::Context;
+use db::artifact::ArtifactKind;
 use rpc::worker_server::Worker;
+use std::{collections::HashSet, path::Path, time::Duration};
 use tonic::{Request, Response, Status};
 
+/// Long enough for ffmpeg to read through any clip.
+const MEDIA_URL_TTL: Duration = Duration::from_secs(60 * 60);
+
 #[tonic::async_trait]
 impl Worker for RpcWorker {
     async fn download_clip(
         &self,
-        _request: Request<rpc::DownloadClipRequest>,
+        request: Request<rpc::DownloadClipRequest>,
     ) -> StdResult<Response<()>, Status> {
-        debug!("Download clip");
+        self.g
+            .metrics
+            .track("DownloadClip", async {
+                let rpc::DownloadClipRequest { clip_id, url } =
+                    request.into_inner();
+                debug!("Download clip {clip_id} from {url}");
+
+                // clips never change on Twitch, so an earlier download is
+                // reused
+                let key = media::clip_key(&clip_id)?;
+                if db::artifact::select_by_key(&*self.g.db.lock().await, &key)?
+                    .is_some()
+                {
+                    debug!("Clip {clip_id} already downloaded");
+                    return Ok(Response::new(()));
+                }
+
+                let scratch = media::scratch_path(&self.g.conf, "mp4").await?;
+                let stored = async {
+                    let bytes = media::download(&url, &scratch).await?;
+                    self.g.metrics.bytes_downloaded.inc_by(bytes);
+                    self.g.store.put(&key, &scratch).await
+                }
+                .await;
+                let bytes = match stored {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        tokio::fs::remove_file(&scratch).await.ok();
+                        return Err(e.into());
+                    }
+                };
+
+                db::artifact::insert(
+                    &*self.g.db.lock().await,
+                    &key,
+                    ArtifactKind::Clip,
+                    Some(&clip_id),
+                    bytes,
+                )?;
+                info!("Clip {clip_id} downloaded to {key} ({bytes} bytes)");
+
+                Ok(Response::new(()))
+            })
+            .await
+    }
+
+    async fn hash_clip_frames(
+        &self,
+        request: Request<rpc::HashClipFramesRequest>,
+    ) -> StdResult<Response<rpc::HashClipFramesResponse>, Status> {
+        self.g
+            .metrics
+            .track("HashClipFrames", async {
+                let rpc::HashClipFramesRequest { clip_id } =
+                    request.into_inner();
+                debug!("Hash frames of clip {clip_id}");
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let frames = media::gray_frame_per_second(
+                    &self.g.conf,
+                    &input,
+                    phash::FRAME_WIDTH,
+                    phash::FRAME_HEIGHT,
+                )
+                .await?;
+
+                Ok(Response::new(rpc::HashClipFramesResponse {
+                    hashes: frames
+                        .iter()
+                        .map(|frame| phash::dhash(frame))
+                        .collect(),
+                }))
+            })
+            .await
+    }
+
+    async fn add_reference_track(
+        &self,
+        request: Request<rpc::AddReferenceTrackRequest>,
+    ) -> StdResult<Response<rpc::AddReferenceTrackResponse>, Status> {
+        self.g
+            .metrics
+            .track("AddReferenceTrack", async {
+                let rpc::AddReferenceTrackRequest { title, source } =
+                    request.into_inner();
+                debug!("Add reference track '{title}' from {source}");
+
+                if title.trim().is_empty() {
+                    let e = AppError::bad_request("Title must not be empty");
+                    return Err(e.into());
+                }
+                media::validate_reference_track_url(&source)?;
+
+                let samples = media::decode_mono_audio(
+                    &self.g.conf,
+                    &source,
+                    fingerprint::SAMPLE_RATE,
+                )
+                .await?;
+                let duration_secs =
+                    samples.len() as f64 / fingerprint::SAMPLE_RATE as f64;
+                let fingerprints = fingerprint_blocking(samples).await?;
 
-        // TODO
+                let track_id = {
+                    let mut db = self.g.db.lock().await;
+                    db::reference_track::insert(
+                        &mut db,
+                        &title,
+                        &source,
+                        duration_secs,
+                        &fingerprints,
+                    )?
+                };
+                info!(
+                    "Reference track {track_id} '{title}' added with {} \
+                    fingerprints",
+                    fingerprints.len()
+                );
 
-        Ok(Response::new(()))
+                Ok(Response::new(rpc::AddReferenceTrackResponse { track_id }))
+            })
+            .await
     }
+
+    async fn list_reference_tracks(
+        &self,
+        _request: Request<()>,
+    ) -> StdResult<Response<rpc::ListReferenceTracksResponse>, Status> {
+        self.g
+            .metrics
+            .track("ListReferenceTracks", async {
+                let db = self.g.db.lock().await;
+                let tracks = db::reference_track::select_all(&db)?
+                    .into_iter()
+                    .map(|track| rpc::ReferenceTrack {
+                        id: track.id,
+                        title: track.title,
+                        source: track.source,
+                        duration_secs: track.duration_secs,
+                        fingerprint_count: track.fingerprint_count as u64,
+                    })
+                    .collect();
+
+                Ok(Response::new(rpc::ListReferenceTracksResponse { tracks }))
+            })
+            .await
+    }
+
+    async fn delete_reference_track(
+        &self,
+        request: Request<rpc::DeleteReferenceTrackRequest>,
+    ) -> StdResult<Response<()>, Status> {
+        self.g
+            .metrics
+            .track("DeleteReferenceTrack", async {
+                let rpc::DeleteReferenceTrackRequest { track_id } =
+                    request.into_inner();
+                debug!("Delete reference track {track_id}");
+
+                let mut db = self.g.db.lock().await;
+                db::reference_track::delete(&mut db, track_id)?;
+
+                Ok(Response::new(()))
+            })
+            .await
+    }
+
+    async fn scan_clip_music(
+        &self,
+        request: Request<rpc::ScanClipMusicRequest>,
+    ) -> StdResult<Response<rpc::ScanClipMusicResponse>, Status> {
+        self.g
+            .metrics
+            .track("ScanClipMusic", async {
+                let rpc::ScanClipMusicRequest { clip_id } =
+                    request.into_inner();
+                debug!("Scan music of clip {clip_id}");
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let samples = media::decode_mono_audio(
+                    &self.g.conf,
+                    &input,
+                    fingerprint::SAMPLE_RATE,
+                )
+                .await?;
+                let fingerprints = fingerprint_blocking(samples).await?;
+
+                let (references, titles) = {
+                    let db = self.g.db.lock().await;
+                    (
+                        db::reference_track::select_fingerprints_by_hashes(
+                            &db,
+                            fingerprints.iter().map(|fp| fp.hash),
+                        )?,
+                        db::reference_track::select_titles(&db)?,
+                    )
+                };
+
+                let matches =
+                    fingerprint::find_matches(&fingerprints, &references)
+                        .into_iter()
+                        .map(|m| rpc::MusicMatch {
+                            track_id: m.track_id,
+                            track_title: titles
+                                .get(&m.track_id)
+                                .cloned()
+                                .unwrap_or_default(),
+                            start_secs: m.start_secs,
+                            end_secs: m.end_secs,
+                            score: m.score as u64,
+                        })
+                        .collect::<Vec<_>>();
+                info!(
+                    "Clip {clip_id} matches {} reference tracks",
+                    matches.len()
+                );
+
+                Ok(Response::new(rpc::ScanClipMusicResponse { matches }))
+            })
+            .await
+    }
+
+    async fn list_artifacts(
+        &self,
+        request: Request<rpc::ListArtifactsRequest>,
+    ) -> StdResult<Response<rpc::ListArtifactsResponse>, Status> {
+        self.g
+            .metrics
+            .track("ListArtifacts", async {
+                let rpc::ListArtifactsRequest {
+                    prefix,
+                    include_untracked,
+                } = request.into_inner();
+
+                let mut artifacts: Vec<_> = {
+                    let db = self.g.db.lock().await;
+                    db::artifact::select_by_prefix(&db, &prefix)?
+                }
+                .into_iter()
+                .map(|artifact| rpc::Artifact {
+                    key: artifact.key,
+                    kind: <&str>::from(artifact.kind).to_string(),
+                    clip_id: artifact.clip_id,
+                    size_bytes: artifact.size_bytes,
+                    created_at: artifact.created_at.to_rfc3339(),
+                    last_accessed_at: artifact.last_accessed_at.to_rfc3339(),
+                })
+                .collect();
+
+                if include_untracked {
+                    let tracked: HashSet<_> =
+                        artifacts.iter().map(|a| a.key.clone()).collect();
+                    let untracked = self
+                        .g
+                        .store
+                        .list(&prefix)
+                        .await?
+                        .into_iter()
+                        .filter(|object| !tracked.contains(&object.key))
+                        .map(|object| rpc::Artifact {
+                            key: object.key,
+                            kind: "untracked".to_string(),
+                            clip_id: None,
+                            size_bytes: object.size_bytes,
+                            created_at: String::new(),
+                            last_accessed_at: String::new(),
+                        });
+                    artifacts.extend(untracked);
+                    artifacts.sort_by(|a, b| a.key.cmp(&b.key));
+                }
+
+                Ok(Response::new(rpc::ListArtifactsResponse { artifacts }))
+            })
+            .await
+    }
+
+    async fn presign_artifact(
+        &self,
+        request: Request<rpc::PresignArtifactRequest>,
+    ) -> StdResult<Response<rpc::PresignArtifactResponse>, Status> {
+        self.g
+            .metrics
+            .track("PresignArtifact", async {
+                let rpc::PresignArtifactRequest {
+                    key,
+                    expires_in_secs,
+                } = request.into_inner();
+                debug!("Presign artifact {key} for {expires_in_secs}s");
+
+                if db::artifact::select_by_key(&*self.g.db.lock().await, &key)?
+                    .is_none()
+                {
+                    return Err(AppError::not_found(format!(
+                        "No artifact under '{key}'"
+                    ))
+                    .into());
+                }
+
+                let url = self
+                    .g
+                    .store
+                    .presigned_url(&key, Duration::from_secs(expires_in_secs))
+                    .await?;
+
+                Ok(Response::new(rpc::PresignArtifactResponse { url }))
+            })
+            .await
+    }
+
+    async fn read_artifact(
+        &self,
+        request: Request<rpc::ReadArtifactRequest>,
+    ) -> StdResult<Response<rpc::ReadArtifactResponse>, Status> {
+        self.g
+            .metrics
+            .track("ReadArtifact", async {
+                let rpc::ReadArtifactRequest { key, start, end } =
+                    request.into_inner();
+                debug!("Read artifact {key} from {start:?} to {end:?}");
+
+                {
+                    let db = self.g.db.lock().await;
+                    if db::artifact::select_by_key(&db, &key)?.is_none() {
+                        return Err(AppError::not_found(format!(
+                            "No artifact under '{key}'"
+                        ))
+                        .into());
+                    }
+                    db::artifact::touch(&db, &key)?;
+                }
+
+                let data = match (start, end) {
+                    (None, None) => self.g.store.get(&key).await?,
+                    (start, end) => {
+                        let range = start.unwrap_or(0)..end.unwrap_or(u64::MAX);
+                        self.g.store.get_range(&key, range).await?
+                    }
+                };
+
+                Ok(Response::new(rpc::ReadArtifactResponse { data }))
+            })
+            .await
+    }
+
+    async fn delete_artifact(
+        &self,
+        request: Request<rpc::DeleteArtifactRequest>,
+    ) -> StdResult<Response<()>, Status> {
+        self.g
+            .metrics
+            .track("DeleteArtifact", async {
+                let rpc::DeleteArtifactRequest { key } = request.into_inner();
+                debug!("Delete artifact {key}");
+
+                // untracked files can be deleted too, hence no db lookup
+                self.g.store.delete(&key).await?;
+                db::artifact::delete(&*self.g.db.lock().await, &key)?;
+
+                Ok(Response::new(()))
+            })
+            .await
+    }
+
+    async fn collect_garbage(
+        &self,
+        request: Request<rpc::CollectGarbageRequest>,
+    ) -> StdResult<Response<rpc::CollectGarbageResponse>, Status> {
+        self.g
+            .metrics
+            .track("CollectGarbage", async {
+                let rpc::CollectGarbageRequest {
+                    dry_run,
+                    keep_clip_ids,
+                } = request.into_inner();
+                let policy = &self.g.conf.gc_policy;
+                debug!(
+                    "Collect garbage (dry run: {dry_run}, {} kept clips) \
+                    with {policy:?}",
+                    keep_clip_ids.len()
+                );
+
+                let artifacts = {
+                    let db = self.g.db.lock().await;
+                    db::artifact::select_by_prefix(&db, "")?
+                };
+                let total_bytes: u64 =
+                    artifacts.iter().map(|a| a.size_bytes).sum();
+                let planned = gc::plan(
+                    &artifacts,
+                    &keep_clip_ids.into_iter().collect(),
+                    policy,
+                    chrono::Utc::now(),
+                );
+
+                let mut evicted = Vec::with_capacity(planned.len());
+                for eviction in planned {
+                    if !dry_run {
+                        // one file failing to delete should not stop the
+                        // rest from being freed
+                        if let Err(e) = self.g.store.delete(&eviction.key).await
+                        {
+                            warn!("Cannot delete {}: {e}", eviction.key);
+                            continue;
+                        }
+                        db::artifact::delete(
+                            &*self.g.db.lock().await,
+                            &eviction.key,
+                        )?;
+                        self.g
+                            .metrics
+                            .gc_freed_bytes
+                            .inc_by(eviction.size_bytes);
+                    }
+
+                    evicted.push(rpc::EvictedArtifact {
+                        key: eviction.key,
+                        clip_id: eviction.clip_id,
+                        size_bytes: eviction.size_bytes,
+                        reason: match eviction.reason {
+                            gc::EvictionReason::Age => "age",
+                            gc::EvictionReason::Quota => "quota",
+                        }
+                        .to_string(),
+                    });
+                }
+
+                let freed_bytes = evicted.iter().map(|e| e.size_bytes).sum();
+                info!(
+                    "Garbage collection {} {} artifacts ({freed_bytes} bytes)",
+                    if dry_run { "would free" } else { "freed" },
+                    evicted.len()
+                );
+
+                Ok(Response::new(rpc::CollectGarbageResponse {
+                    evicted,
+                    freed_bytes,
+                    remaining_bytes: total_bytes - freed_bytes,
+                    max_total_bytes: policy.max_total_bytes,
+                    max_age_secs: policy
+                        .max_age
+                        .map(|max_age| max_age.num_seconds() as u64),
+                }))
+            })
+            .await
+    }
+
+    /// Not tracked in metrics, the admin polls it and it would only count
+    /// itself as a job in flight.
+    async fn get_load(
+        &self,
+        _: Request<()>,
+    ) -> StdResult<Response<rpc::GetLoadResponse>, Status> {
+        Ok(Response::new(rpc::GetLoadResponse {
+            jobs_in_flight: self.g.metrics.jobs_in_flight(),
+        }))
+    }
+
+    async fn render_vertical(
+        &self,
+        request: Request<rpc::RenderVerticalRequest>,
+    ) -> StdResult<Response<rpc::RenderVerticalResponse>, Status> {
+        self.g
+            .metrics
+            .track("RenderVertical", async {
+                let rpc::RenderVerticalRequest {
+                    clip_id,
+                    start_secs,
+                    end_secs,
+                    layout,
+                    audio_gain_db,
+                    mute_ranges,
+                } = request.into_inner();
+                let layout = render::VerticalLayout::try_from(layout)?;
+                let fragment = render::Fragment::new(start_secs, end_secs)?;
+                let audio_gain_db = render::audio_gain(audio_gain_db)?;
+                let mute_ranges = render::mute_ranges(mute_ranges)?;
+                debug!(
+                    "Render clip {clip_id} from {start_secs:?} to \
+                    {end_secs:?} as vertical {layout:?} with audio gain \
+                    {audio_gain_db:?}dB, muted {mute_ranges:?}"
+                );
+
+                let key = render::vertical_key(
+                    &clip_id,
+                    &fragment,
+                    &layout,
+                    audio_gain_db,
+                    &mute_ranges,
+                )?;
+                {
+                    let db = self.g.db.lock().await;
+                    if let Some(artifact) =
+                        db::artifact::select_by_key(&db, &key)?
+                    {
+                        debug!("Clip {clip_id} already rendered to {key}");
+                        db::artifact::touch(&db, &key)?;
+                        return Ok(Response::new(
+                            rpc::RenderVerticalResponse {
+                                key,
+                                size_bytes: artifact.size_bytes,
+                            },
+                        ));
+                    }
+                }
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let scratch = media::scratch_path(&self.g.conf, "mp4").await?;
+                let rendered = media::render_video(
+                    &self.g.conf,
+                    &input,
+                    &scratch,
+                    &fragment,
+                    &render::vertical_filter_graph(&layout),
+                    render::audio_filter(
+                        &fragment,
+                        audio_gain_db,
+                        &mute_ranges,
+                    )
+                    .as_deref(),
+                )
+                .await;
+                let size_bytes = self
+                    .store_scratch(
+                        rendered,
+                        &scratch,
+                        &key,
+                        ArtifactKind::Render,
+                        &clip_id,
+                    )
+                    .await?;
+                info!("Clip {clip_id} rendered to {key} ({size_bytes} bytes)");
+
+                Ok(Response::new(rpc::RenderVerticalResponse {
+                    key,
+                    size_bytes,
+                }))
+            })
+            .await
+    }
+
+    async fn measure_loudness(
+        &self,
+        request: Request<rpc::MeasureLoudnessRequest>,
+    ) -> StdResult<Response<rpc::MeasureLoudnessResponse>, Status> {
+        self.g
+            .metrics
+            .track("MeasureLoudness", async {
+                let rpc::MeasureLoudnessRequest {
+                    clip_id,
+                    start_secs,
+                    end_secs,
+                } = request.into_inner();
+                let fragment = render::Fragment::new(start_secs, end_secs)?;
+                debug!(
+                    "Measure loudness of clip {clip_id} from {start_secs:?} \
+                    to {end_secs:?}"
+                );
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let samples = media::decode_audio(
+                    &self.g.conf,
+                    &input,
+                    &fragment,
+                    loudness::CHANNELS,
+                    loudness::SAMPLE_RATE,
+                )
+                .await?;
+                let loudness::Loudness {
+                    integrated_lufs,
+                    true_peak_dbtp,
+                } = tokio::task::spawn_blocking(move || {
+                    loudness::measure(&samples)
+                })
+                .await
+                .map_err(|e| {
+                    AppError::internal(format!(
+                        "Measuring loudness failed: {e}"
+                    ))
+                })?;
+                info!(
+                    "Clip {clip_id} is {integrated_lufs:?} LUFS with true \
+                    peak {true_peak_dbtp:?} dBTP"
+                );
+
+                Ok(Response::new(rpc::MeasureLoudnessResponse {
+                    integrated_lufs,
+                    true_peak_dbtp,
+                }))
+            })
+            .await
+    }
+
+    async fn find_thumbnail_frames(
+        &self,
+        request: Request<rpc::FindThumbnailFramesRequest>,
+    ) -> StdResult<Response<rpc::FindThumbnailFramesResponse>, Status> {
+        self.g
+            .metrics
+            .track("FindThumbnailFrames", async {
+                let rpc::FindThumbnailFramesRequest { clip_id, count } =
+                    request.into_inner();
+                let count = count as usize;
+                if count == 0 || count > thumbnail::MAX_FRAMES {
+                    return Err(AppError::bad_request(format!(
+                        "Count must be between 1 and {}",
+                        thumbnail::MAX_FRAMES
+                    ))
+                    .into());
+                }
+                debug!("Find {count} thumbnail frames of clip {clip_id}");
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let frames = media::gray_frame_per_second(
+                    &self.g.conf,
+                    &input,
+                    thumbnail::SCORED_FRAME_WIDTH,
+                    thumbnail::SCORED_FRAME_HEIGHT,
+                )
+                .await?;
+                let scores: Vec<f64> = tokio::task::spawn_blocking(move || {
+                    frames.iter().map(|frame| thumbnail::score(frame)).collect()
+                })
+                .await
+                .map_err(|e| {
+                    AppError::internal(format!("Scoring frames failed: {e}"))
+                })?;
+
+                let mut found = vec![];
+                for i in thumbnail::best_frames(&scores, count) {
+                    // frames are sampled at the start of each second
+                    let at_secs = i as f64;
+                    let key = thumbnail::frame_key(&clip_id, at_secs)?;
+                    if self.touch_artifact(&key).await?.is_none() {
+                        let scratch =
+                            media::scratch_path(&self.g.conf, "jpg").await?;
+                        let extracted = media::extract_frame(
+                            &self.g.conf,
+                            &input,
+                            at_secs,
+                            &scratch,
+                        )
+                        .await;
+                        self.store_scratch(
+                            extracted,
+                            &scratch,
+                            &key,
+                            ArtifactKind::Frame,
+                            &clip_id,
+                        )
+                        .await?;
+                    }
+
+                    found.push(rpc::ThumbnailFrame {
+                        at_secs,
+                        score: scores[i],
+                        key,
+                    });
+                }
+                info!(
+                    "Found {} thumbnail frames of clip {clip_id}",
+                    found.len()
+                );
+
+                Ok(Response::new(rpc::FindThumbnailFramesResponse {
+                    frames: found,
+                }))
+            })
+            .await
+    }
+
+    async fn compose_thumbnail(
+        &self,
+        request: Request<rpc::ComposeThumbnailRequest>,
+    ) -> StdResult<Response<rpc::ComposeThumbnailResponse>, Status> {
+        self.g
+            .metrics
+            .track("ComposeThumbnail", async {
+                let rpc::ComposeThumbnailRequest {
+                    clip_id,
+                    at_secs,
+                    title,
+                    box_art_url,
+                    avatar_urls,
+                } = request.into_inner();
+                let template = thumbnail::Template::new(
+                    at_secs,
+                    title,
+                    box_art_url,
+                    avatar_urls,
+                )?;
+                debug!("Compose thumbnail of clip {clip_id}: {template:?}");
+
+                let key = thumbnail::thumbnail_key(&clip_id, &template)?;
+                {
+                    let db = self.g.db.lock().await;
+                    if let Some(artifact) =
+                        db::artifact::select_by_key(&db, &key)?
+                    {
+                        debug!("Thumbnail {key} already composed");
+                        db::artifact::touch(&db, &key)?;
+                        return Ok(Response::new(
+                            rpc::ComposeThumbnailResponse {
+                                key,
+                                size_bytes: artifact.size_bytes,
+                            },
+                        ));
+                    }
+                }
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let title_path =
+                    media::scratch_path(&self.g.conf, "txt").await?;
+                let scratch = media::scratch_path(&self.g.conf, "png").await?;
+                let composed = async {
+                    tokio::fs::write(
+                        &title_path,
+                        thumbnail::wrap_title(&template.title),
+                    )
+                    .await
+                    .context("Cannot write thumbnail title")?;
+                    media::composite_image(
+                        &self.g.conf,
+                        &input,
+                        template.at_secs,
+                        &template.overlays(),
+                        &thumbnail::filter_graph(
+                            &template,
+                            &title_path,
+                            self.g.conf.thumbnail_font.as_deref(),
+                        )?,
+                        &scratch,
+                    )
+                    .await
+                }
+                .await;
+                tokio::fs::remove_file(&title_path).await.ok();
+                let size_bytes = self
+                    .store_scratch(
+                        composed,
+                        &scratch,
+                        &key,
+                        ArtifactKind::Thumbnail,
+                        &clip_id,
+                    )
+                    .await?;
+                info!("Thumbnail of clip {clip_id} composed to {key}");
+
+                Ok(Response::new(rpc::ComposeThumbnailResponse {
+                    key,
+                    size_bytes,
+                }))
+            })
+            .await
+    }
+
+    async fn generate_previews(
+        &self,
+        request: Request<rpc::GeneratePreviewsRequest>,
+    ) -> StdResult<Response<rpc::GeneratePreviewsResponse>, Status> {
+        self.g
+            .metrics
+            .track("GeneratePreviews", async {
+                let rpc::GeneratePreviewsRequest {
+                    clip_id,
+                    duration_secs,
+                } = request.into_inner();
+                preview::validate_duration(duration_secs)?;
+                let keys = preview::Keys::new(&clip_id)?;
+                debug!("Generate previews of clip {clip_id}");
+
+                let input = self.downloaded_clip_url(&clip_id).await?;
+                let whole_clip = render::Fragment::default();
+                let previews = [
+                    (
+                        &keys.storyboard,
+                        "jpg",
+                        whole_clip,
+                        preview::storyboard_filter_graph(duration_secs),
+                        &["-frames:v", "1", "-q:v", "3"][..],
+                    ),
+                    (
+                        &keys.waveform,
+                        "png",
+                        whole_clip,
+                        preview::waveform_filter_graph(),
+                        &["-frames:v", "1"][..],
+                    ),
+                    (
+                        &keys.animation,
+                        "webp",
+                        preview::animation_fragment(duration_secs),
+                        preview::animation_filter_graph(),
+                        &["-c:v", "libwebp", "-loop", "0", "-quality", "50"][..],
+                    ),
+                ];
+
+                let mut has_waveform = true;
+                for (key, extension, fragment, filter_graph, args) in previews {
+                    if self.touch_artifact(key).await?.is_some() {
+                        continue;
+                    }
+
+                    let scratch =
+                        media::scratch_path(&self.g.conf, extension).await?;
+                    let generated = media::filter_to_file(
+                        &self.g.conf,
+                        &input,
+                        &fragment,
+                        &filter_graph,
+                        args,
+                        &scratch,
+                    )
+                    .await;
+                    let stored = self
+                        .store_scratch(
+                            generated,
+                            &scratch,
+                            key,
+                            ArtifactKind::Preview,
+                            &clip_id,
+                        )
+                        .await;
+                    match stored {
+                        Ok(_) => {}
+                        // ffmpeg fails to map audio of clips which have none
+                        Err(e) if *key == keys.waveform => {
+                            warn!("No waveform of clip {clip_id}: {e}");
+                            has_waveform = false;
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                info!("Generated previews of clip {clip_id}");
+
+                Ok(Response::new(rpc::GeneratePreviewsResponse {
+                    storyboard_key: keys.storyboard,
+                    waveform_key: has_waveform.then_some(keys.waveform),
+                    animation_key: keys.animation,
+                }))
+            })
+            .await
+    }
+}
+
+impl RpcWorker {
+    /// Counts as accessing the artifact if it's stored.
+    async fn touch_artifact(
+        &self,
+        key: &str,
+    ) -> Result<Option<db::artifact::Artifact>> {
+        let db = self.g.db.lock().await;
+        let artifact = db::artifact::select_by_key(&db, key)?;
+        if artifact.is_some() {
+            db::artifact::touch(&db, key)?;
+        }
+
+        Ok(artifact)
+    }
+
+    /// Moves a file produced in the scratch space to the media store as an
+    /// artifact of the clip.
+    /// The file is deleted if it cannot be stored or failed to be produced.
+    async fn store_scratch(
+        &self,
+        produced: Result<()>,
+        scratch: &Path,
+        key: &str,
+        kind: ArtifactKind,
+        clip_id: &str,
+    ) -> Result<u64> {
+        let stored = match produced {
+            Ok(()) => self.g.store.put(key, scratch).await,
+            Err(e) => Err(e),
+        };
+        let size_bytes = match stored {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tokio::fs::remove_file(scratch).await.ok();
+                return Err(e);
+            }
+        };
+
+        db::artifact::insert(
+            &*self.g.db.lock().await,
+            key,
+            kind,
+            Some(clip_id),
+            size_bytes,
+        )?;
+
+        Ok(size_bytes)
+    }
+
+    /// Url ffmpeg can read the downloaded clip from.
+    /// Reading the clip counts as accessing it.
+    async fn downloaded_clip_url(&self, clip_id: &str) -> Result<String> {
+        let key = media::clip_key(clip_id)?;
+        {
+            let db = self.g.db.lock().await;
+            if db::artifact::select_by_key(&db, &key)?.is_none() {
+                return Err(AppError::not_found(format!(
+                    "Clip {clip_id} has not been downloaded"
+                )));
+            }
+            db::artifact::touch(&db, &key)?;
+        }
+
+        self.g.store.presigned_url(&key, MEDIA_URL_TTL).await
+    }
+}
+
+/// FFT over minutes of audio would block the runtime.
+async fn fingerprint_blocking(
+    samples: Vec<f32>,
+) -> Result<Vec<fingerprint::Fingerprint>> {
+    tokio::task::spawn_blocking(move || fingerprint::fingerprint(&samples))
+        .await
+        .map_err(|e| AppError::internal(format!("Fingerprinting failed: {e}")))
 }
@@ -0,0 +1,95 @@
+/// Media store on the worker's disk
+pub mod local;
+/// Media store in an S3-compatible bucket
+pub mod s3;
+
+use crate::{conf::MediaStoreConf, prelude::*};
+use std::{ops::Range, path::Path, sync::Arc, time::Duration};
+
+/// Object in the media store as reported by [`MediaStore::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredObject {
+    pub key: String,
+    pub size_bytes: u64,
+}
+
+/// Where downloaded clips and files derived from them are kept.
+///
+/// Keys are relative paths separated by `/` such as `clips/SomeClipId.mp4`,
+/// see [`validate_key`].
+#[tonic::async_trait]
+pub trait MediaStore: Send + Sync {
+    /// Moves a local file to the store under given key, replacing whatever
+    /// was stored there.
+    /// The local file is gone afterwards.
+    /// Returns the size of the stored object in bytes.
+    async fn put(&self, key: &str, file: &Path) -> Result<u64>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Bytes of the object within given range, the end is exclusive.
+    /// The range is cut short if the object is smaller.
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>>;
+
+    /// Deleting a key which does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Objects whose keys start with given prefix, sorted by key.
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredObject>>;
+
+    /// Url from which the object can be read without any credentials until
+    /// it expires.
+    /// ffmpeg reads stored media through it.
+    async fn presigned_url(
+        &self,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String>;
+}
+
+/// Opens the store selected by the config.
+pub fn open(conf: &Conf) -> AnyResult<Arc<dyn MediaStore>> {
+    Ok(match &conf.media_store {
+        MediaStoreConf::Local => {
+            Arc::new(local::LocalStore::new(conf.media_dir.join("store")))
+        }
+        MediaStoreConf::S3(s3_conf) => Arc::new(s3::S3Store::new(s3_conf)?),
+    })
+}
+
+/// Keys become paths on disk and in buckets, so anything that could escape
+/// the store root is rejected.
+pub fn validate_key(key: &str) -> Result<()> {
+    let is_valid = !key.is_empty()
+        && key.split('/').all(|part| {
+            !part.is_empty()
+                && part != "."
+                && part != ".."
+                && part.chars().all(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+                })
+        });
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(AppError::bad_request(format!("Invalid media key '{key}'")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_validates_keys() {
+        assert!(validate_key("clips/SuaveHonestWeasel-JKanStyle.mp4").is_ok());
+        assert!(validate_key("a").is_ok());
+
+        assert!(validate_key("").is_err());
+        assert!(validate_key("/clips/a.mp4").is_err());
+        assert!(validate_key("clips//a.mp4").is_err());
+        assert!(validate_key("clips/../db.sqlite3").is_err());
+        assert!(validate_key("clips/a b.mp4").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use super::{validate_key, MediaStore, StoredObject};
+use crate::prelude::*;
+use std::io::{ErrorKind, SeekFrom};
+use std::{ops::Range, path::Path, path::PathBuf, time::Duration};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Keys are paths relative to the root dir.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[tonic::async_trait]
+impl MediaStore for LocalStore {
+    async fn put(&self, key: &str, file: &Path) -> Result<u64> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        // rename is atomic but does not work across file systems
+        if fs::rename(file, &path).await.is_err() {
+            fs::copy(file, &path).await?;
+            fs::remove_file(file).await?;
+        }
+
+        Ok(fs::metadata(&path).await?.len())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        fs::read(&path).await.map_err(|e| not_found_or(key, e))
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        let mut file = fs::File::open(&path)
+            .await
+            .map_err(|e| not_found_or(key, e))?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+
+        let mut buf = vec![];
+        file.take(range.end.saturating_sub(range.start))
+            .read_to_end(&mut buf)
+            .await?;
+
+        Ok(buf)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredObject>> {
+        let mut objects = vec![];
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+
+                let Some(key) = entry
+                    .path()
+                    .strip_prefix(&self.root)
+                    .ok()
+                    .and_then(|key| key.to_str())
+                    .map(|key| key.replace(std::path::MAIN_SEPARATOR, "/"))
+                else {
+                    continue;
+                };
+                if key.starts_with(prefix) {
+                    objects.push(StoredObject {
+                        key,
+                        size_bytes: metadata.len(),
+                    });
+                }
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    /// Files on disk need no signing, this is just the absolute path.
+    async fn presigned_url(
+        &self,
+        key: &str,
+        _expires_in: Duration,
+    ) -> Result<String> {
+        let path = self.path(key)?;
+        if !fs::try_exists(&path).await? {
+            return Err(AppError::not_found(format!("No media under '{key}'")));
+        }
+
+        Ok(std::path::absolute(&path)?.to_string_lossy().into_owned())
+    }
+}
+
+fn not_found_or(key: &str, err: std::io::Error) -> AppError {
+    if err.kind() == ErrorKind::NotFound {
+        AppError::not_found(format!("No media under '{key}'"))
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_stores_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = LocalStore::new(dir.path().join("store"));
+
+        let file = dir.path().join("scratch.mp4");
+        fs::write(&file, b"0123456789").await?;
+        assert_eq!(store.put("clips/a.mp4", &file).await?, 10);
+        assert!(!fs::try_exists(&file).await?);
+
+        fs::write(&file, b"xyz").await?;
+        store.put("thumbnails/a.jpg", &file).await?;
+
+        assert_eq!(store.get("clips/a.mp4").await?, b"0123456789");
+        assert_eq!(store.get_range("clips/a.mp4", 2..5).await?, b"234");
+        assert_eq!(store.get_range("clips/a.mp4", 8..20).await?, b"89");
+        assert_eq!(
+            store.list("clips/").await?,
+            vec![StoredObject {
+                key: "clips/a.mp4".to_string(),
+                size_bytes: 10,
+            }]
+        );
+        assert_eq!(store.list("").await?.len(), 2);
+        assert!(store
+            .presigned_url("clips/a.mp4", Duration::from_secs(60))
+            .await?
+            .ends_with("a.mp4"));
+
+        store.delete("clips/a.mp4").await?;
+        store.delete("clips/a.mp4").await?;
+        assert!(store.get("clips/a.mp4").await.is_err());
+        assert!(store
+            .presigned_url("clips/a.mp4", Duration::from_secs(60))
+            .await
+            .is_err());
+        assert_eq!(store.list("clips/").await?, vec![]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use super::{validate_key, MediaStore, StoredObject};
+use crate::conf::S3Conf;
+use crate::prelude::*;
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+use std::{ops::Range, path::Path, time::Duration};
+use tokio::fs;
+
+/// Presigned urls are valid for at most a week.
+const MAX_PRESIGN_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Keys are object keys in the bucket.
+pub struct S3Store {
+    bucket: Bucket,
+}
+
+impl S3Store {
+    pub fn new(conf: &S3Conf) -> AnyResult<Self> {
+        let region = match &conf.endpoint {
+            Some(endpoint) => Region::Custom {
+                region: conf.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => conf.region.parse()?,
+        };
+        let credentials = Credentials::new(
+            Some(&conf.access_key),
+            Some(&conf.secret_key),
+            None,
+            None,
+            None,
+        )?;
+
+        let bucket = Bucket::new(&conf.bucket, region, credentials)?;
+        // self-hosted S3 such as MinIO rarely has a DNS entry per bucket
+        let bucket = if conf.endpoint.is_some() {
+            bucket.with_path_style()
+        } else {
+            bucket
+        };
+
+        Ok(Self { bucket })
+    }
+}
+
+#[tonic::async_trait]
+impl MediaStore for S3Store {
+    async fn put(&self, key: &str, file: &Path) -> Result<u64> {
+        validate_key(key)?;
+        let size = fs::metadata(file).await?.len();
+
+        let mut reader = fs::File::open(file).await?;
+        let status = self
+            .bucket
+            .put_object_stream(&mut reader, key)
+            .await
+            .map_err(s3_error)?;
+        ensure_success(key, status)?;
+
+        fs::remove_file(file).await?;
+
+        Ok(size)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        validate_key(key)?;
+        let resp = self.bucket.get_object(key).await.map_err(s3_error)?;
+        ensure_success(key, resp.status_code())?;
+
+        Ok(resp.to_vec())
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>> {
+        validate_key(key)?;
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // S3 ranges are inclusive
+        let resp = self
+            .bucket
+            .get_object_range(key, range.start, Some(range.end - 1))
+            .await
+            .map_err(s3_error)?;
+        // the range starts past the end of the object
+        if resp.status_code() == 416 {
+            return Ok(vec![]);
+        }
+        ensure_success(key, resp.status_code())?;
+
+        Ok(resp.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        validate_key(key)?;
+        let resp = self.bucket.delete_object(key).await.map_err(s3_error)?;
+        if resp.status_code() == 404 {
+            return Ok(());
+        }
+
+        ensure_success(key, resp.status_code())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredObject>> {
+        let pages = self
+            .bucket
+            .list(prefix.to_string(), None)
+            .await
+            .map_err(s3_error)?;
+
+        let mut objects: Vec<_> = pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| StoredObject {
+                key: object.key,
+                size_bytes: object.size,
+            })
+            .collect();
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Ok(objects)
+    }
+
+    async fn presigned_url(
+        &self,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String> {
+        validate_key(key)?;
+        let expiry_secs = expires_in.as_secs().clamp(1, MAX_PRESIGN_SECS);
+
+        self.bucket
+            .presign_get(key, expiry_secs as u32, None)
+            .map_err(s3_error)
+    }
+}
+
+fn ensure_success(key: &str, status: u16) -> Result<()> {
+    match status {
+        200..=299 => Ok(()),
+        404 => Err(AppError::not_found(format!("No media under '{key}'"))),
+        _ => Err(AppError::internal(format!(
+            "S3 responded with {status} for '{key}'"
+        ))),
+    }
+}
+
+fn s3_error(err: S3Error) -> AppError {
+    AppError::internal(format!("S3 request failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs against a local MinIO, for example
+    ///
+    /// ```bash
+    /// docker run -p 9000:9000 minio/minio server /data
+    /// mc mb local/worker-test
+    /// S3_TEST_ENDPOINT=http://localhost:9000 \
+    ///     cargo test -p worker -- --ignored it_stores_objects_in_s3
+    /// ```
+    ///
+    /// Credentials default to those of a fresh MinIO.
+    #[tokio::test]
+    #[ignore]
+    async fn it_stores_objects_in_s3() -> AnyResult<()> {
+        let env = |name, default: &str| {
+            std::env::var(name).unwrap_or_else(|_| default.to_string())
+        };
+        let store = S3Store::new(&S3Conf {
+            bucket: env("S3_TEST_BUCKET", "worker-test"),
+            region: "us-east-1".to_string(),
+            endpoint: Some(std::env::var("S3_TEST_ENDPOINT")?),
+            access_key: env("S3_TEST_ACCESS_KEY", "minioadmin"),
+            secret_key: env("S3_TEST_SECRET_KEY", "minioadmin"),
+        })?;
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("scratch.mp4");
+        fs::write(&file, b"0123456789").await?;
+        assert_eq!(store.put("test/a.mp4", &file).await?, 10);
+        assert!(!fs::try_exists(&file).await?);
+
+        assert_eq!(store.get("test/a.mp4").await?, b"0123456789");
+        assert_eq!(store.get_range("test/a.mp4", 2..5).await?, b"234");
+        assert_eq!(
+            store.list("test/").await?,
+            vec![StoredObject {
+                key: "test/a.mp4".to_string(),
+                size_bytes: 10,
+            }]
+        );
+
+        let url = store
+            .presigned_url("test/a.mp4", Duration::from_secs(60))
+            .await?;
+        let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+        assert_eq!(&body[..], b"0123456789");
+
+        store.delete("test/a.mp4").await?;
+        store.delete("test/a.mp4").await?;
+        assert!(store.get("test/a.mp4").await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+use crate::{media, prelude::*};
+use std::path::Path;
+
+/// Frames are scored at this size, enough to tell detail from blur while
+/// keeping decoding cheap.
+pub const SCORED_FRAME_WIDTH: usize = 160;
+pub const SCORED_FRAME_HEIGHT: usize = 90;
+/// YouTube's recommended thumbnail size.
+pub const WIDTH: u32 = 1280;
+pub const HEIGHT: u32 = 720;
+/// Asking for more frames than this is a mistake, they'd be hardly
+/// different.
+pub const MAX_FRAMES: usize = 20;
+/// Candidate frames are at least this far apart so that they differ.
+const MIN_SECS_BETWEEN_FRAMES: usize = 3;
+/// More avatars don't fit next to the box art.
+const MAX_AVATARS: usize = 6;
+/// At the font size below, about this many characters fit on a line.
+const TITLE_LINE_CHARS: usize = 24;
+const TITLE_MAX_LINES: usize = 3;
+const TITLE_FONT_SIZE: u32 = 84;
+const AVATAR_SIZE: u32 = 112;
+const BOX_ART_HEIGHT: u32 = 280;
+/// Distance of everything from the edges of the thumbnail.
+const MARGIN: u32 = 40;
+
+/// Everything a thumbnail is composited from besides the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub at_secs: f64,
+    pub title: String,
+    pub box_art_url: Option<String>,
+    pub avatar_urls: Vec<String>,
+}
+
+impl Template {
+    pub fn new(
+        at_secs: f64,
+        title: String,
+        box_art_url: Option<String>,
+        avatar_urls: Vec<String>,
+    ) -> Result<Self> {
+        if !at_secs.is_finite() || at_secs < 0.0 {
+            return Err(AppError::bad_request(
+                "Frame must be at a non-negative time",
+            ));
+        }
+        if avatar_urls.len() > MAX_AVATARS {
+            return Err(AppError::bad_request(format!(
+                "At most {MAX_AVATARS} avatars fit on a thumbnail"
+            )));
+        }
+
+        Ok(Self {
+            at_secs,
+            title,
+            box_art_url: box_art_url.filter(|url| !url.is_empty()),
+            avatar_urls,
+        })
+    }
+
+    /// Images overlaid on the frame, in the order the filter graph expects
+    /// them as inputs after the frame.
+    pub fn overlays(&self) -> Vec<&str> {
+        self.box_art_url
+            .iter()
+            .chain(&self.avatar_urls)
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Sharpness times contrast of a grayscale frame of [`SCORED_FRAME_WIDTH`]
+/// x [`SCORED_FRAME_HEIGHT`] pixels.
+///
+/// Sharpness is the standard deviation of the laplacian, which is low for
+/// frames blurred by motion or a transition.
+/// Contrast is the standard deviation of the pixels, which is low for dark
+/// or washed out frames.
+pub fn score(frame: &[u8]) -> f64 {
+    debug_assert_eq!(frame.len(), SCORED_FRAME_WIDTH * SCORED_FRAME_HEIGHT);
+
+    let at = |x: usize, y: usize| f64::from(frame[y * SCORED_FRAME_WIDTH + x]);
+    let laplacians: Vec<f64> = (1..SCORED_FRAME_HEIGHT - 1)
+        .flat_map(|y| (1..SCORED_FRAME_WIDTH - 1).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)
+                - 4.0 * at(x, y)
+        })
+        .collect();
+    let pixels: Vec<f64> = frame.iter().map(|p| f64::from(*p)).collect();
+
+    std_dev(&laplacians) * std_dev(&pixels) / 255.0
+}
+
+/// Indexes of the best scored frames, best first, at least
+/// [`MIN_SECS_BETWEEN_FRAMES`] apart given one frame per second.
+pub fn best_frames(scores: &[f64], count: usize) -> Vec<usize> {
+    let mut by_score: Vec<usize> = (0..scores.len()).collect();
+    by_score.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+
+    let mut best: Vec<usize> = Vec::with_capacity(count);
+    for i in by_score {
+        if best.len() == count {
+            break;
+        }
+        if best
+            .iter()
+            .all(|b| b.abs_diff(i) >= MIN_SECS_BETWEEN_FRAMES)
+        {
+            best.push(i);
+        }
+    }
+
+    best
+}
+
+/// Breaks the title into lines at spaces.
+/// Whatever doesn't fit on [`TITLE_MAX_LINES`] lines is cut off with an
+/// ellipsis.
+pub fn wrap_title(title: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    for word in title.split_whitespace() {
+        match lines.last_mut() {
+            Some(line)
+                if line.chars().count() + 1 + word.chars().count()
+                    <= TITLE_LINE_CHARS =>
+            {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+
+    if lines.len() > TITLE_MAX_LINES {
+        lines.truncate(TITLE_MAX_LINES);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Filter graph which expects the frame as the first input followed by
+/// the template's overlays, and labels the thumbnail `[v]`.
+///
+/// The title is read from a file so that it needs no escaping, paths must
+/// not contain quotes.
+pub fn filter_graph(
+    template: &Template,
+    title_path: &Path,
+    font_path: Option<&Path>,
+) -> Result<String> {
+    let mut graph = format!(
+        "[0:v]scale={WIDTH}:{HEIGHT}:force_original_aspect_ratio=increase,\
+        crop={WIDTH}:{HEIGHT},eq=contrast=1.1:saturation=1.3,\
+        drawbox=x=0:y=ih*0.55:w=iw:h=ih*0.45:color=black@0.5:t=fill[l0];"
+    );
+    let mut input = 1;
+    let mut layer = 0;
+    let mut overlay = |graph: &mut String, filter: String, position: String| {
+        graph.push_str(&format!(
+            "[{input}:v]{filter}[o{input}];\
+            [l{layer}][o{input}]overlay={position}[l{}];",
+            layer + 1
+        ));
+        input += 1;
+        layer += 1;
+    };
+
+    if template.box_art_url.is_some() {
+        overlay(
+            &mut graph,
+            format!("scale=-1:{BOX_ART_HEIGHT}"),
+            format!("W-w-{MARGIN}:{MARGIN}"),
+        );
+    }
+    for i in 0..template.avatar_urls.len() as u32 {
+        // a white border sets avatars apart from the frame
+        overlay(
+            &mut graph,
+            format!(
+                "scale={AVATAR_SIZE}:{AVATAR_SIZE},pad=iw+8:ih+8:4:4:white"
+            ),
+            format!("{}:{MARGIN}", MARGIN + i * (AVATAR_SIZE + 24)),
+        );
+    }
+
+    let font = match font_path {
+        Some(path) => format!("fontfile={}:", quoted(path)?),
+        None => String::new(),
+    };
+    graph.push_str(&format!(
+        "[l{layer}]drawtext={font}textfile={}:fontsize={TITLE_FONT_SIZE}:\
+        fontcolor=white:borderw=6:bordercolor=black:line_spacing=12:\
+        x={MARGIN}:y=h-th-{MARGIN}[v]",
+        quoted(title_path)?
+    ));
+
+    Ok(graph)
+}
+
+/// Frames are keyed by the millisecond they were taken at.
+pub fn frame_key(clip_id: &str, at_secs: f64) -> Result<String> {
+    // validates the clip id
+    media::clip_key(clip_id)?;
+
+    Ok(format!(
+        "thumbnails/{clip_id}/frame-{}.jpg",
+        (at_secs * 1000.0).round() as u64
+    ))
+}
+
+pub fn thumbnail_key(clip_id: &str, template: &Template) -> Result<String> {
+    // validates the clip id
+    media::clip_key(clip_id)?;
+
+    let mut hasher = media::KeyHasher::new();
+    hasher.write_f64(template.at_secs);
+    hasher.write_str(&template.title);
+    match &template.box_art_url {
+        Some(url) => {
+            hasher.write(&[1]);
+            hasher.write_str(url);
+        }
+        None => hasher.write(&[0]),
+    }
+    hasher.write_u64(template.avatar_urls.len() as u64);
+    for url in &template.avatar_urls {
+        hasher.write_str(url);
+    }
+
+    Ok(format!("thumbnails/{clip_id}/{:016x}.png", hasher.finish()))
+}
+
+fn quoted(path: &Path) -> Result<String> {
+    let path = path.to_string_lossy();
+    if path.contains(['\'', '\\']) {
+        return Err(AppError::internal(format!(
+            "Path {path} cannot be passed to ffmpeg filters"
+        )));
+    }
+
+    Ok(format!("'{path}'"))
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+        (0..SCORED_FRAME_HEIGHT)
+            .flat_map(|y| (0..SCORED_FRAME_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn it_scores_sharp_contrasted_frames_higher() {
+        let flat = frame(|_, _| 128);
+        let gradient = frame(|x, _| (x * 255 / SCORED_FRAME_WIDTH) as u8);
+        let checkerboard =
+            frame(|x, y| if (x / 4 + y / 4) % 2 == 0 { 20 } else { 230 });
+        let dim_checkerboard =
+            frame(|x, y| if (x / 4 + y / 4) % 2 == 0 { 100 } else { 140 });
+
+        assert_eq!(score(&flat), 0.0);
+        assert!(score(&gradient) < score(&dim_checkerboard));
+        assert!(score(&dim_checkerboard) < score(&checkerboard));
+    }
+
+    #[test]
+    fn it_picks_best_frames_apart() {
+        let scores = [1.0, 9.0, 8.0, 2.0, 7.0, 3.0, 0.5, 6.0];
+
+        assert_eq!(best_frames(&scores, 3), vec![1, 4, 7]);
+        assert_eq!(best_frames(&scores, 10), vec![1, 4, 7]);
+        assert_eq!(best_frames(&scores, 1), vec![1]);
+        assert!(best_frames(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn it_wraps_titles() {
+        assert_eq!(wrap_title("  short  title "), "short title");
+        assert_eq!(
+            wrap_title("the most insane clutch you will ever see in ranked"),
+            "the most insane clutch\nyou will ever see in\nranked"
+        );
+        assert_eq!(
+            wrap_title(
+                "this title goes on and on and on and on and on and on and \
+                on and on and on and on"
+            ),
+            "this title goes on and\non and on and on and on\nand on and on and on and…"
+        );
+    }
+
+    #[test]
+    fn it_builds_filter_graphs() -> Result<()> {
+        let template = Template::new(
+            4.0,
+            "title".to_string(),
+            Some("https://box.art/55.jpg".to_string()),
+            vec!["https://a/1.png".to_string(), "https://a/2.png".to_string()],
+        )?;
+        assert_eq!(
+            template.overlays(),
+            vec![
+                "https://box.art/55.jpg",
+                "https://a/1.png",
+                "https://a/2.png"
+            ]
+        );
+
+        let graph = filter_graph(
+            &template,
+            Path::new("/media/tmp/title.txt"),
+            Some(Path::new("/fonts/Bold.ttf")),
+        )?;
+        assert!(graph.starts_with("[0:v]scale=1280:720"));
+        assert!(graph.contains("[1:v]scale=-1:280[o1];[l0][o1]overlay="));
+        assert!(graph.contains("[2:v]scale=112:112"));
+        assert!(graph.contains("[l2][o3]overlay=176:40[l3];"));
+        assert!(graph.contains(
+            "[l3]drawtext=fontfile='/fonts/Bold.ttf':\
+            textfile='/media/tmp/title.txt':"
+        ));
+        assert!(graph.ends_with("[v]"));
+
+        let bare = Template::new(0.0, "title".to_string(), None, vec![])?;
+        assert!(bare.overlays().is_empty());
+        let graph =
+            filter_graph(&bare, Path::new("/media/tmp/title.txt"), None)?;
+        assert!(graph.contains("[l0]drawtext=textfile="));
+
+        assert!(
+            filter_graph(&bare, Path::new("/it's/title.txt"), None).is_err()
+        );
+        assert!(Template::new(-1.0, String::new(), None, vec![]).is_err());
+        assert!(Template::new(
+            0.0,
+            String::new(),
+            None,
+            vec![String::new(); MAX_AVATARS + 1]
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keys_thumbnails_by_template() -> Result<()> {
+        assert_eq!(
+            frame_key("AbcDef", 12.0)?,
+            "thumbnails/AbcDef/frame-12000.jpg"
+        );
+        assert!(frame_key("../etc", 12.0).is_err());
+
+        let template = Template::new(
+            12.0,
+            "title".to_string(),
+            None,
+            vec!["https://a/1.png".to_string()],
+        )?;
+        let key = thumbnail_key("AbcDef", &template)?;
+        assert_eq!(key, "thumbnails/AbcDef/e114cac9bac7541f.png");
+        assert_eq!(key, thumbnail_key("AbcDef", &template.clone())?);
+
+        let retitled = Template {
+            title: "other".to_string(),
+            ..template
+        };
+        assert_ne!(key, thumbnail_key("AbcDef", &retitled)?);
+
+        Ok(())
+    }
+}